@@ -0,0 +1,115 @@
+// Counts the allocations made to build the payloads of a handler, once with a fresh vector for
+// every payload and once with the buffers of a pool. The payloads are kept in flight for a while,
+// like they are until numaflow has read them. Run with `cargo bench --bench buffer`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::VecDeque;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use bytes::{BufMut, Bytes};
+use numaflow::buffer::BufferPool;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// payloads waiting to be read by numaflow
+const IN_FLIGHT: usize = 16;
+// bytes of payloads built per run
+const VOLUME: usize = 1 << 30;
+
+#[derive(serde::Serialize)]
+struct Record<'a> {
+    id: usize,
+    body: &'a str,
+}
+
+fn run(size: usize, build: impl Fn(usize, &[u8]) -> Bytes) -> (usize, Duration) {
+    let chunk = vec![b'x'; size];
+    let mut in_flight = VecDeque::with_capacity(IN_FLIGHT);
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for i in 0..VOLUME / size {
+        if in_flight.len() == IN_FLIGHT {
+            black_box(in_flight.pop_front());
+        }
+        in_flight.push_back(build(i, &chunk));
+    }
+    let elapsed = start.elapsed();
+    (ALLOCATIONS.load(Ordering::Relaxed) - allocations, elapsed)
+}
+
+fn record(id: usize, chunk: &[u8]) -> Record<'_> {
+    Record {
+        id,
+        body: std::str::from_utf8(chunk).expect("payload is ascii"),
+    }
+}
+
+fn main() {
+    println!(
+        "{} MiB of payloads, {} in flight\n\n{:>8} {:>6} {:>14} {:>14} {:>12} {:>12}",
+        VOLUME >> 20,
+        IN_FLIGHT,
+        "payload",
+        "codec",
+        "allocs (vec)",
+        "allocs (pool)",
+        "vec",
+        "pool"
+    );
+    for size in [1 << 10, 64 << 10, 1 << 20] {
+        // room for the JSON around the body
+        let pool = BufferPool::new(size + 64);
+
+        let raw = run(size, |_, chunk| Bytes::from(chunk.to_vec()));
+        let pooled = run(size, |_, chunk| {
+            let mut buffer = pool.get();
+            buffer.put_slice(chunk);
+            buffer.freeze()
+        });
+        report(size, "raw", raw, pooled);
+
+        let raw = run(size, |i, chunk| {
+            Bytes::from(serde_json::to_vec(&record(i, chunk)).expect("record is serializable"))
+        });
+        let pooled = run(size, |i, chunk| {
+            pool.json(&record(i, chunk))
+                .expect("record is serializable")
+        });
+        report(size, "json", raw, pooled);
+    }
+}
+
+fn report(size: usize, codec: &str, raw: (usize, Duration), pooled: (usize, Duration)) {
+    println!(
+        "{:>7}K {:>6} {:>14} {:>14} {:>12.2?} {:>12.2?}",
+        size >> 10,
+        codec,
+        raw.0,
+        pooled.0,
+        raw.1,
+        pooled.1
+    );
+}
@@ -0,0 +1,61 @@
+// Times the decoding of JSON payloads into typed records, once with serde_json and once with
+// `numaflow::json::from_slice` on simd-json, including the copy of the payload simd-json parses
+// in place. Run with `cargo bench --bench json --features simd-json`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Event {
+    id: u64,
+    user: String,
+    action: String,
+    score: f64,
+    tags: Vec<String>,
+}
+
+// bytes of payloads decoded per run
+const VOLUME: usize = 256 << 20;
+
+fn payload(events: usize) -> Vec<u8> {
+    let events: Vec<Event> = (0..events as u64)
+        .map(|id| Event {
+            id,
+            user: format!("user-{}", id % 1000),
+            action: ["view", "click", "purchase"][id as usize % 3].to_string(),
+            score: id as f64 / 7.0,
+            tags: vec!["numaflow".to_string(), format!("partition-{}", id % 16)],
+        })
+        .collect();
+    serde_json::to_vec(&events).expect("events are serializable")
+}
+
+fn run(payload: &[u8], decode: impl Fn(&[u8]) -> Vec<Event>) -> Duration {
+    let start = Instant::now();
+    for _ in 0..VOLUME / payload.len() {
+        black_box(decode(black_box(payload)));
+    }
+    start.elapsed()
+}
+
+fn main() {
+    println!(
+        "{} MiB of payloads\n\n{:>8} {:>12} {:>12}",
+        VOLUME >> 20,
+        "payload",
+        "serde_json",
+        "simd-json"
+    );
+    for events in [1, 100, 10_000] {
+        let payload = payload(events);
+        let serde = run(&payload, |p| {
+            serde_json::from_slice(p).expect("payload is valid")
+        });
+        let simd = run(&payload, |p| {
+            numaflow::json::from_slice(p).expect("payload is valid")
+        });
+        println!("{:>7}B {:>12.2?} {:>12.2?}", payload.len(), serde, simd);
+    }
+}
@@ -0,0 +1,108 @@
+// Counts the allocations made to route the elements of a reduce stream to the tasks of their keys
+// and windows, once with the keys joined into a task name for every element and copied for every
+// window, and once with the keys interned. Run with `cargo bench --bench keys`. Interning saves
+// the copies of the keys which repeat, with barely any repeats, e.g., a hundred thousand keys
+// seen twice each, the lookup in the interner costs more time than it saves.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[allow(dead_code)]
+#[path = "../src/keys.rs"]
+mod keys;
+
+use keys::{Interned, KeyInterner};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const ELEMENTS: usize = 200_000;
+// sliding windows fan every element out to several windows
+const WINDOWS: usize = 3;
+
+// the element as it is decoded off the wire, the decoding allocations are paid either way
+fn decode(i: usize, cardinality: usize) -> (Vec<String>, HashMap<String, String>) {
+    let key = i % cardinality;
+    let keys = vec![format!("tenant-{}", key % 97), format!("user-{}", key)];
+    let headers = HashMap::from([("traceparent".to_string(), "00-01-02-01".to_string())]);
+    (keys, headers)
+}
+
+fn joined(cardinality: usize) {
+    let mut tasks: HashMap<(usize, String), Vec<String>> = HashMap::new();
+    for i in 0..ELEMENTS {
+        let (keys, headers) = decode(i, cardinality);
+        let key_name = keys.join(":");
+        for window in 0..WINDOWS {
+            let task_name = (window, key_name.clone());
+            tasks.entry(task_name).or_insert_with(|| keys.clone());
+            // the copy of the element sent to the task of the window
+            black_box((keys.clone(), headers.clone()));
+        }
+    }
+    black_box(tasks);
+}
+
+fn interned(cardinality: usize) {
+    let mut interner = KeyInterner::default();
+    let mut tasks: HashMap<(usize, Interned), Vec<String>> = HashMap::new();
+    for i in 0..ELEMENTS {
+        let (keys, headers) = decode(i, cardinality);
+        let keys = interner.intern(keys);
+        let headers = Arc::new(headers);
+        for window in 0..WINDOWS {
+            let task_name = (window, keys.clone());
+            tasks.entry(task_name).or_insert_with(|| keys.to_vec());
+            black_box((keys.clone(), Arc::clone(&headers)));
+        }
+    }
+    black_box(tasks);
+}
+
+fn measure(run: impl Fn(usize), cardinality: usize) -> (usize, Duration) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    run(cardinality);
+    let elapsed = start.elapsed();
+    (ALLOCATIONS.load(Ordering::Relaxed) - allocations, elapsed)
+}
+
+fn main() {
+    println!(
+        "{} elements, {} windows per element\n\n{:>11} {:>18} {:>18} {:>12} {:>12}",
+        ELEMENTS,
+        WINDOWS,
+        "cardinality",
+        "allocs (joined)",
+        "allocs (interned)",
+        "joined",
+        "interned"
+    );
+    for cardinality in [10, 1_000, 100_000] {
+        let (joined_allocations, joined_elapsed) = measure(joined, cardinality);
+        let (interned_allocations, interned_elapsed) = measure(interned, cardinality);
+        println!(
+            "{:>11} {:>18} {:>18} {:>12.2?} {:>12.2?}",
+            cardinality, joined_allocations, interned_allocations, joined_elapsed, interned_elapsed
+        );
+    }
+}
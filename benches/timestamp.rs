@@ -0,0 +1,79 @@
+// Times the conversion of the watermarks and the window times of a reduce stream, once through
+// `TimeZone::timestamp_opt` like the windows were converted before, once through the fast path of
+// the normalized timestamps and once through the per stream cache. The watermark moves every
+// hundred elements and every element belongs to three sliding windows. Run with
+// `cargo bench --bench timestamp`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, TimeZone, Utc};
+use prost_types::Timestamp;
+
+#[allow(dead_code)]
+#[path = "../src/timestamp.rs"]
+mod timestamp;
+
+use timestamp::{utc_from_timestamp, TimestampCache};
+
+const ELEMENTS: i64 = 10_000_000;
+// elements between two moves of the watermark
+const WATERMARK_PERIOD: i64 = 100;
+// windows an element belongs to
+const WINDOWS: i64 = 3;
+// elements between two windows
+const WINDOW_PERIOD: i64 = 1_000;
+
+// the watermark and the start and end times of the windows of an element, in milliseconds
+fn times(element: i64) -> impl Iterator<Item = Timestamp> {
+    let epoch_millis = 1_700_000_000_000;
+    let watermark = epoch_millis + element / WATERMARK_PERIOD;
+    let first_window = element / WINDOW_PERIOD;
+    let windows = (first_window..first_window + WINDOWS).flat_map(move |window| {
+        [window, window + WINDOWS].map(|w| epoch_millis + w * WINDOW_PERIOD)
+    });
+    std::iter::once(watermark)
+        .chain(windows)
+        .map(|millis| Timestamp {
+            seconds: millis.div_euclid(1_000),
+            nanos: (millis.rem_euclid(1_000) * 1_000_000) as i32,
+        })
+}
+
+fn run(mut convert: impl FnMut(Timestamp) -> DateTime<Utc>) -> Duration {
+    let start = Instant::now();
+    for element in 0..ELEMENTS {
+        for t in times(element) {
+            black_box(convert(black_box(t)));
+        }
+    }
+    start.elapsed()
+}
+
+fn main() {
+    // the time to build the timestamps alone
+    let baseline = run(|_| DateTime::<Utc>::MIN_UTC);
+    let timestamp_opt = run(|t| {
+        Utc.timestamp_opt(t.seconds, t.nanos as u32)
+            .single()
+            .expect("time is in range")
+    });
+    let fast_path = run(|t| utc_from_timestamp(Some(t)));
+    let mut cache = TimestampCache::default();
+    let cached = run(|t| cache.convert(Some(&t)));
+
+    println!(
+        "{} elements, {} timestamps each\n\n{:>14} {:>12}",
+        ELEMENTS,
+        1 + 2 * WINDOWS,
+        "conversion",
+        "time"
+    );
+    for (name, elapsed) in [
+        ("timestamp_opt", timestamp_opt),
+        ("fast path", fast_path),
+        ("cached", cached),
+    ] {
+        println!("{:>14} {:>12.2?}", name, elapsed.saturating_sub(baseline));
+    }
+}
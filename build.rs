@@ -1,9 +1,33 @@
+use std::env;
+
+const PROTOS: &[&str] = &[
+    "proto/accumulator.proto",
+    "proto/batchmap.proto",
+    "proto/map.proto",
+    "proto/mapstream.proto",
+    "proto/reduce.proto",
+    "proto/sideinput.proto",
+    "proto/sink.proto",
+    "proto/source.proto",
+    "proto/sourcetransform.proto",
+];
+
+/// The `function.v1` protocol of the older numaflow releases, see `src/legacy.rs`.
+const LEGACY_PROTO: &str = "proto/udf.proto";
+
 fn main() {
+    let mut config = prost_build::Config::new();
+    // the payloads are handed over to the handlers without being copied
+    config.bytes(["."]);
+
+    let mut protos = PROTOS.to_vec();
+    // the protocol of the older numaflow releases is served next to the current one on demand
+    if env::var_os("CARGO_FEATURE_LEGACY_PROTOCOL").is_some() {
+        protos.push(LEGACY_PROTO);
+    }
+
     tonic_build::configure()
         .build_server(true)
-        .compile(
-            &["proto/map.proto", "proto/reduce.proto", "proto/sink.proto"],
-            &["proto"],
-        )
-        .unwrap_or_else(|e| panic!("failed to compile the proto, {:?}", e))
+        .compile_with_config(config, &protos, &["proto"])
+        .unwrap_or_else(|e| panic!("failed to compile the proto, {:?}", e));
 }
@@ -85,7 +85,8 @@ pub(crate) mod tickgen {
                         value: payload.data.value,
                         time: ts,
                     })
-                    .unwrap_or(vec![]),
+                    .unwrap_or(vec![])
+                    .into(),
                     tags: vec![],
                 }]
             } else {
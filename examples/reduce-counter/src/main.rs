@@ -51,11 +51,7 @@ mod counter {
                 md.start_time(),
                 md.end_time()
             );
-            vec![Message {
-                keys: keys.clone(),
-                value: counter.to_string().into_bytes(),
-                tags: vec![],
-            }]
+            vec![Message::new(keys.clone(), counter.to_string().into_bytes(), vec![])]
         }
     }
 }
@@ -38,17 +38,14 @@ mod log_sink {
                     Ok(v) => {
                         println!("{}", v);
                         // record the response
-                        Response {
-                            id: datum.id().to_string(),
-                            success: true,
-                            err: "".to_string(),
-                        }
+                        Response::ok(datum.id().to_string())
+                    }
+                    Err(e) => {
+                        // there is no point in retrying as it is not going to help, hence the
+                        // message is acknowledged.
+                        eprintln!("Invalid UTF-8 sequence: {}", e);
+                        Response::ok(datum.id().to_string())
                     }
-                    Err(e) => Response {
-                        id: datum.id().to_string(),
-                        success: true, // there is no point setting success to false as retrying is not going to help
-                        err: format!("Invalid UTF-8 sequence: {}", e),
-                    },
                 };
 
                 // return the responses
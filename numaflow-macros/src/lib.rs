@@ -0,0 +1,167 @@
+//! Procedural macros of the [numaflow](https://docs.rs/numaflow) SDK, they are re-exported by the
+//! `numaflow` crate with the `macros` feature and should be used from there.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Item};
+
+/// Generates the `main` function serving the annotated reduce handler with the default settings,
+/// like `#[tokio::main]` does for the runtime.
+///
+/// The annotated item is either an async fn, which is served via
+/// `numaflow::reduce::Server::from_fn`, or a struct implementing `numaflow::reduce::Reducer` and
+/// [`Default`], which is served via `numaflow::reduce::Server::new`. Use the server directly to
+/// change the settings.
+///
+/// # Example
+///
+/// ```no_run
+/// use numaflow::reduce::{Datum, IntervalWindow, Message};
+/// use tokio::sync::mpsc::Receiver;
+///
+/// #[numaflow::reducer]
+/// async fn counter(
+///     keys: Vec<String>,
+///     mut input: Receiver<Box<dyn Datum + Send + Sync>>,
+///     _md: IntervalWindow,
+/// ) -> Vec<Message> {
+///     let mut counter = 0;
+///     while input.recv().await.is_some() {
+///         counter += 1;
+///     }
+///     vec![Message::new(keys, counter.to_string(), vec![])]
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn reducer(args: TokenStream, item: TokenStream) -> TokenStream {
+    if !args.is_empty() {
+        return syn::Error::new(Span::call_site(), "#[reducer] does not take any arguments")
+            .to_compile_error()
+            .into();
+    }
+
+    let item = parse_macro_input!(item as Item);
+    let server = match &item {
+        Item::Fn(function) => {
+            if function.sig.asyncness.is_none() {
+                return syn::Error::new(function.sig.fn_token.span(), "the reducer must be async")
+                    .to_compile_error()
+                    .into();
+            }
+            let ident = &function.sig.ident;
+            quote!(::numaflow::reduce::Server::from_fn(#ident))
+        }
+        Item::Struct(strukt) => {
+            if !strukt.generics.params.is_empty() {
+                return syn::Error::new(strukt.generics.span(), "the reducer must not be generic")
+                    .to_compile_error()
+                    .into();
+            }
+            let ident = &strukt.ident;
+            quote!(::numaflow::reduce::Server::new(
+                <#ident as ::core::default::Default>::default()
+            ))
+        }
+        other => {
+            return syn::Error::new(other.span(), "#[reducer] expects an async fn or a struct")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    quote! {
+        #item
+
+        fn main() -> ::core::result::Result<
+            (),
+            ::std::boxed::Box<dyn ::std::error::Error + ::core::marker::Send + ::core::marker::Sync>,
+        > {
+            ::numaflow::__private::tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()?
+                .block_on(#server.start())
+        }
+    }
+    .into()
+}
+
+/// Implements `numaflow::typedkeys::TypedKeys` for a struct of named fields, one key per field in
+/// the order of the fields. The fields are parsed with `FromStr` and written back with `Display`.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::typedkeys::TypedKeys;
+///
+/// #[derive(numaflow::TypedKeys, Debug, PartialEq)]
+/// struct Keys {
+///     region: String,
+///     device: u32,
+/// }
+///
+/// assert_eq!(Keys::NAMES, ["region", "device"]);
+///
+/// let keys = vec!["eu-west".to_string(), "42".to_string()];
+/// let typed = Keys::from_keys(&keys).unwrap();
+/// assert_eq!(typed, Keys { region: "eu-west".to_string(), device: 42 });
+/// assert_eq!(typed.into_keys(), keys);
+/// assert!(Keys::from_keys(&["eu-west".to_string()]).is_err());
+/// ```
+#[proc_macro_derive(TypedKeys)]
+pub fn typed_keys(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    if !input.generics.params.is_empty() {
+        return syn::Error::new(input.generics.span(), "the keys must not be generic")
+            .to_compile_error()
+            .into();
+    }
+    let fields = match &input.data {
+        Data::Struct(strukt) => match &strukt.fields {
+            Fields::Named(fields) => &fields.named,
+            other => {
+                return syn::Error::new(other.span(), "the keys must have named fields")
+                    .to_compile_error()
+                    .into();
+            }
+        },
+        _ => {
+            return syn::Error::new(Span::call_site(), "#[derive(TypedKeys)] expects a struct")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    let ident = &input.ident;
+    let fields: Vec<_> = fields
+        .iter()
+        .filter_map(|field| field.ident.as_ref())
+        .collect();
+    let names: Vec<String> = fields
+        .iter()
+        .map(|field| field.unraw().to_string())
+        .collect();
+    let indexes = 0..fields.len();
+
+    quote! {
+        impl ::numaflow::typedkeys::TypedKeys for #ident {
+            const NAMES: &'static [&'static str] = &[#(#names),*];
+
+            fn from_keys(
+                keys: &[::std::string::String],
+            ) -> ::core::result::Result<Self, ::numaflow::typedkeys::KeyError> {
+                <Self as ::numaflow::typedkeys::TypedKeys>::check_len(keys)?;
+                ::core::result::Result::Ok(Self {
+                    #(#fields: ::numaflow::typedkeys::parse(keys, #indexes, #names)?,)*
+                })
+            }
+
+            fn into_keys(self) -> ::std::vec::Vec<::std::string::String> {
+                ::std::vec![#(::std::string::ToString::to_string(&self.#fields)),*]
+            }
+        }
+    }
+    .into()
+}
@@ -0,0 +1,587 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{async_trait, Request, Response, Status, Streaming};
+use tracing::Instrument;
+
+use crate::accumulator::accumulatorer::accumulator_request::window_operation::Event;
+use crate::accumulator::accumulatorer::{
+    accumulator_server, AccumulatorRequest, AccumulatorResponse, KeyedWindow, Payload,
+    ReadyResponse,
+};
+use crate::error::{Error, ErrorKind};
+use crate::headers::Headers;
+use crate::keys::{self, Keys};
+use crate::shared::HandlerSettings;
+use crate::timestamp::TimestampCache;
+use crate::{metrics, shared, tasks};
+
+mod accumulatorer {
+    tonic::include_proto!("accumulator.v1");
+}
+
+/// The gRPC messages, client and server of the accumulator protocol, generated from its proto.
+pub mod proto {
+    pub use super::accumulatorer::*;
+}
+
+/// Default unix domain socket file of the accumulator server, see [`Server::with_socket_file`]. It is
+/// relocated by the [`SOCKET_ENV`](crate::shared::SOCKET_ENV) and
+/// [`RUN_DIR_ENV`](crate::shared::RUN_DIR_ENV) environment variables.
+pub const DEFAULT_SOCK_ADDR: &str = "/var/run/numaflow/accumulator.sock";
+
+/// Version of the accumulator protocol, i.e., of the `accumulator.v1` proto package.
+pub const PROTOCOL_VERSION: &str = "v1";
+
+struct AccumulatorService<T> {
+    handler: Arc<T>,
+    // buffer size of the channels between the gRPC streams and the user's handle
+    channel_size: usize,
+    settings: HandlerSettings,
+}
+
+/// Accumulator trait for implementing the accumulator handler.
+#[async_trait]
+pub trait Accumulator {
+    /// accumulate is invoked once per set of keys with an unbounded stream of [`Datum`] for those
+    /// keys, there are no window boundaries. Results can be written to the `output` at any time,
+    /// the input is closed once numaflow closes the keys, and
+    /// [`is_output_alive`](crate::shared::OutputAlive::is_output_alive) on the `output` returns
+    /// false once the client is gone. Unlike [`reduce`](crate::reduce::Reducer::reduce)
+    /// the results keep the event time and the watermark of the input they were derived from,
+    /// which is what makes use cases like stream joins and reordering possible. An OPEN of keys
+    /// which are already open, e.g., resent by numaflow after a transient failure, is handed to
+    /// the running invocation rather than starting another one, and the element the keys were
+    /// opened with is dropped if it comes again. More about
+    /// accumulators can be read
+    /// [here](https://numaflow.numaproj.io/user-guide/user-defined-functions/reduce/windowing/accumulator/).
+    ///
+    /// # Example
+    ///
+    /// Following is an example of an accumulator which drops the elements arriving out of order.
+    ///
+    /// ```no_run
+    /// use numaflow::accumulator::{self, Datum, Message};
+    /// use tokio::sync::mpsc::{Receiver, Sender};
+    ///
+    /// struct InOrder {}
+    ///
+    /// #[tonic::async_trait]
+    /// impl accumulator::Accumulator for InOrder {
+    ///     async fn accumulate<T>(&self, mut input: Receiver<T>, output: Sender<Message>)
+    ///     where
+    ///         T: Datum + Send + Sync + 'static,
+    ///     {
+    ///         let mut latest = None;
+    ///         while let Some(datum) = input.recv().await {
+    ///             if latest.is_some_and(|latest| datum.event_time() < latest) {
+    ///                 continue;
+    ///             }
+    ///             latest = Some(datum.event_time());
+    ///             if output.send(Message::from_datum(&datum)).await.is_err() {
+    ///                 return;
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     accumulator::Server::new(InOrder {}).start().await
+    /// }
+    /// ```
+    async fn accumulate<T: Datum + Send + Sync + 'static>(
+        &self,
+        input: mpsc::Receiver<T>,
+        output: mpsc::Sender<Message>,
+    );
+}
+
+/// Message is a result written to the output of [`Accumulator::accumulate`].
+pub struct Message {
+    /// Keys are a collection of strings which will be passed on to the next vertex as is. It can
+    /// be an empty collection.
+    pub keys: Vec<String>,
+    /// Value is the value passed to the next vertex.
+    pub value: Bytes,
+    /// Tags are used for [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/).
+    pub tags: Vec<String>,
+    /// event_time is the event time of the input the message is derived from.
+    pub event_time: DateTime<Utc>,
+    /// watermark is the watermark of the input the message is derived from.
+    pub watermark: DateTime<Utc>,
+    /// id is the ID of the input the message is derived from.
+    pub id: String,
+    /// headers are the headers of the input the message is derived from.
+    pub headers: HashMap<String, String>,
+}
+
+impl Message {
+    /// Creates a message which is a copy of the input [`Datum`], the value can then be replaced.
+    pub fn from_datum<T: Datum + ?Sized>(datum: &T) -> Self {
+        Self {
+            keys: datum.keys().clone(),
+            value: datum.value().clone(),
+            tags: vec![],
+            event_time: datum.event_time(),
+            watermark: datum.watermark(),
+            id: datum.id().to_string(),
+            headers: datum.headers().clone().into(),
+        }
+    }
+}
+
+/// Datum trait represents an incoming element into the [`Accumulator::accumulate`].
+pub trait Datum {
+    /// keys are the keys in the (key, value) terminology of map/reduce paradigm.
+    fn keys(&self) -> &Vec<String>;
+    /// value is the value in (key, value) terminology of map/reduce paradigm.
+    fn value(&self) -> &Bytes;
+    /// [watermark](https://numaflow.numaproj.io/core-concepts/watermarks/) represented by time is a guarantee that we will not see an element older than this
+    /// time.
+    fn watermark(&self) -> DateTime<Utc>;
+    /// event_time is the time of the element as seen at source or aligned after a reduce operation.
+    fn event_time(&self) -> DateTime<Utc>;
+    /// headers are the user defined headers set by the upstream vertices.
+    fn headers(&self) -> &Headers;
+    /// ID is the unique ID of the element.
+    fn id(&self) -> &str;
+}
+
+/// Owned copy of the AccumulatorRequest payload from Datum.
+struct OwnedAccumulatorRequest {
+    keys: Vec<String>,
+    value: Bytes,
+    watermark: DateTime<Utc>,
+    eventtime: DateTime<Utc>,
+    headers: Headers,
+    id: String,
+}
+
+impl OwnedAccumulatorRequest {
+    fn new(
+        mut payload: Payload,
+        watermarks: &mut TimestampCache,
+        settings: HandlerSettings,
+    ) -> Self {
+        let mut headers = Headers::from(payload.headers);
+        settings.decompress_payload(&mut headers, &mut payload.value);
+        Self {
+            keys: payload.keys,
+            value: payload.value,
+            watermark: watermarks.convert(payload.watermark.as_ref()),
+            eventtime: shared::utc_from_timestamp(payload.event_time),
+            headers,
+            id: payload.id,
+        }
+    }
+}
+
+impl Datum for OwnedAccumulatorRequest {
+    fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    fn watermark(&self) -> DateTime<Utc> {
+        self.watermark
+    }
+
+    fn event_time(&self) -> DateTime<Utc> {
+        self.eventtime
+    }
+
+    fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// The task running the user's handle for a set of keys.
+struct KeyedTask {
+    tx: mpsc::Sender<OwnedAccumulatorRequest>,
+    handle: JoinHandle<()>,
+    // id of the element the keys were opened with, an OPEN carrying it again is a redelivery
+    open_id: Option<String>,
+}
+
+impl KeyedTask {
+    fn spawn<T>(
+        handler: Arc<T>,
+        window: KeyedWindow,
+        resp_tx: mpsc::Sender<Result<AccumulatorResponse, Status>>,
+        channel_size: usize,
+        // headers of the first element of the keys, the span of the handle follows its trace
+        headers: Option<&HashMap<String, String>>,
+        settings: HandlerSettings,
+    ) -> Self
+    where
+        T: Accumulator + Send + Sync + 'static,
+    {
+        let (tx, rx) = mpsc::channel::<OwnedAccumulatorRequest>(channel_size);
+
+        let name = format!(
+            "accumulator:task:{}",
+            keys::join(&window.keys, keys::DEFAULT_KEY_JOIN_DELIMITER)
+        );
+        let span = settings.handler_span("accumulator", headers);
+        let handle = tasks::spawn(&name, async move {
+            // channel the user's handle writes into, the output is closed once the handle returns
+            let (output_tx, mut output_rx) = mpsc::channel::<Message>(channel_size);
+
+            // output_rx is dropped as soon as the client is gone which closes the output of the
+            // handle
+            let forwarder = async move {
+                while let Some(message) = tokio::select! {
+                    message = output_rx.recv() => message,
+                    _ = resp_tx.closed() => None,
+                } {
+                    let response = AccumulatorResponse {
+                        window: Some(KeyedWindow {
+                            start: window.start.clone(),
+                            // the output watermark of the keys is carried by the window end
+                            end: Some(shared::prost_timestamp_from_utc(message.watermark)),
+                            slot: window.slot.clone(),
+                            keys: window.keys.clone(),
+                        }),
+                        payload: Some(Payload {
+                            keys: message.keys,
+                            value: message.value,
+                            event_time: Some(shared::prost_timestamp_from_utc(message.event_time)),
+                            watermark: Some(shared::prost_timestamp_from_utc(message.watermark)),
+                            id: message.id,
+                            headers: message.headers,
+                        }),
+                        tags: message.tags,
+                        eof: false,
+                    };
+                    metrics::messages_emitted("accumulator", 1);
+                    metrics::channel_saturation("accumulator", &resp_tx);
+                    if resp_tx.send(Ok(response)).await.is_err() {
+                        // client is gone, nothing more to do
+                        break;
+                    }
+                }
+            };
+
+            tokio::join!(
+                settings
+                    .watch("accumulator", handler.accumulate(rx, output_tx))
+                    .instrument(span),
+                forwarder
+            );
+        });
+
+        Self {
+            tx,
+            handle,
+            open_id: None,
+        }
+    }
+}
+
+#[async_trait]
+impl<T> accumulator_server::Accumulator for AccumulatorService<T>
+where
+    T: Accumulator + Send + Sync + 'static,
+{
+    type AccumulateFnStream = ReceiverStream<Result<AccumulatorResponse, Status>>;
+
+    async fn accumulate_fn(
+        &self,
+        request: Request<Streaming<AccumulatorRequest>>,
+    ) -> Result<Response<Self::AccumulateFnStream>, Status> {
+        let mut stream = request.into_inner();
+
+        // channel to respond to numaflow main car as it expects streaming results.
+        let (resp_tx, resp_rx) =
+            mpsc::channel::<Result<AccumulatorResponse, Status>>(self.channel_size);
+
+        let handler = Arc::clone(&self.handler);
+        let settings = self.settings;
+        let channel_size = self.channel_size;
+
+        // the input stream is unbounded, so it is read in the background while the results are
+        // streamed out.
+        tasks::spawn("accumulator:stream-reader", async move {
+            let mut keyed_tasks: HashMap<Keys, KeyedTask> = HashMap::new();
+            let mut watermarks = TimestampCache::default();
+
+            loop {
+                let request = match stream.message().await {
+                    Ok(Some(request)) => request,
+                    Ok(None) => break,
+                    Err(e) => {
+                        // dropping the keyed tasks closes the inputs of the handles
+                        let _ = resp_tx.send(Err(e)).await;
+                        return;
+                    }
+                };
+
+                let Some(operation) = request.operation else {
+                    let _ = resp_tx
+                        .send(Err(settings.to_status(Error::AccumulatorError(
+                            ErrorKind::ProtocolViolation("window operation is not set".to_string()),
+                        ))))
+                        .await;
+                    return;
+                };
+                let window = operation.keyed_window.unwrap_or_default();
+
+                match Event::from_i32(operation.event) {
+                    Some(event @ (Event::Open | Event::Append)) => {
+                        let payload_id = request.payload.as_ref().map(|payload| &payload.id);
+                        // the keys are only copied when the task of the keys is spawned
+                        let task = match keyed_tasks.get_mut(&window.keys) {
+                            Some(task) => {
+                                // an OPEN of keys already open, e.g., resent after a
+                                // transient failure, goes to the running task, the element it was
+                                // opened with is not accumulated twice
+                                if event == Event::Open {
+                                    metrics::duplicate_open("accumulator");
+                                    let keys =
+                                        keys::join(&window.keys, keys::DEFAULT_KEY_JOIN_DELIMITER);
+                                    if payload_id.is_some() && payload_id == task.open_id.as_ref() {
+                                        tracing::warn!(
+                                            %keys,
+                                            id = payload_id.map_or("", |id| id.as_str()),
+                                            "keys are opened again with the same element, dropping \
+                                             the redelivered element"
+                                        );
+                                        continue;
+                                    }
+                                    tracing::warn!(
+                                        %keys,
+                                        "keys are already open, reusing their task"
+                                    );
+                                }
+                                task
+                            }
+                            None => {
+                                let keys = Arc::new(window.keys.clone());
+                                let mut task = KeyedTask::spawn(
+                                    Arc::clone(&handler),
+                                    window,
+                                    resp_tx.clone(),
+                                    channel_size,
+                                    request.payload.as_ref().map(|payload| &payload.headers),
+                                    settings,
+                                );
+                                if event == Event::Open {
+                                    task.open_id = payload_id.cloned();
+                                }
+                                keyed_tasks.entry(keys).or_insert(task)
+                            }
+                        };
+                        if let Some(payload) = request.payload {
+                            metrics::messages_received("accumulator", 1);
+                            // the handle has returned early if the send fails, the input is
+                            // dropped as there is nobody to process it.
+                            let _ = task
+                                .tx
+                                .send(OwnedAccumulatorRequest::new(
+                                    payload,
+                                    &mut watermarks,
+                                    settings,
+                                ))
+                                .await;
+                        }
+                    }
+                    Some(Event::Close) => {
+                        let Some(task) = keyed_tasks.remove(&window.keys) else {
+                            continue;
+                        };
+                        // close the input of the handle and mark the end of the keys once all of
+                        // its results have been streamed out.
+                        drop(task.tx);
+                        let resp_tx = resp_tx.clone();
+                        let name = format!(
+                            "accumulator:eof:{}",
+                            keys::join(&window.keys, keys::DEFAULT_KEY_JOIN_DELIMITER)
+                        );
+                        tasks::spawn(&name, async move {
+                            let response = match task.handle.await {
+                                Ok(()) => Ok(AccumulatorResponse {
+                                    payload: None,
+                                    window: Some(window),
+                                    tags: vec![],
+                                    eof: true,
+                                }),
+                                Err(e) => Err(settings.to_status(Error::AccumulatorError(
+                                    ErrorKind::InternalError(format!(
+                                        "accumulator handle failed: {}",
+                                        e
+                                    )),
+                                ))),
+                            };
+                            let _ = resp_tx.send(response).await;
+                        });
+                    }
+                    None => {
+                        let _ = resp_tx
+                            .send(Err(settings.to_status(Error::AccumulatorError(
+                                ErrorKind::ProtocolViolation(format!(
+                                    "unknown window event {}",
+                                    operation.event
+                                )),
+                            ))))
+                            .await;
+                        return;
+                    }
+                }
+            }
+
+            // close the inputs of the open keys and wait for their results to be streamed out
+            for (_, task) in keyed_tasks {
+                drop(task.tx);
+                if let Err(e) = task.handle.await {
+                    let _ = resp_tx
+                        .send(Err(settings.to_status(Error::AccumulatorError(
+                            ErrorKind::InternalError(format!("accumulator handle failed: {}", e)),
+                        ))))
+                        .await;
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(resp_rx)))
+    }
+
+    async fn is_ready(&self, _: Request<()>) -> Result<Response<ReadyResponse>, Status> {
+        Ok(Response::new(ReadyResponse { ready: true }))
+    }
+}
+
+/// FromFn is a [`Accumulator`] running a closure, see [`Server::from_fn`].
+pub struct FromFn<F>(F);
+
+#[async_trait]
+impl<F, Fut> Accumulator for FromFn<F>
+where
+    F: Fn(mpsc::Receiver<Box<dyn Datum + Send + Sync>>, mpsc::Sender<Message>) -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send,
+{
+    async fn accumulate<T: Datum + Send + Sync + 'static>(
+        &self,
+        input: mpsc::Receiver<T>,
+        output: mpsc::Sender<Message>,
+    ) {
+        shared::forward_input(
+            input,
+            |datum| Box::new(datum) as Box<dyn Datum + Send + Sync>,
+            |input| (self.0)(input, output),
+        )
+        .await
+    }
+}
+
+/// gRPC server to start an accumulator service
+pub struct Server<T> {
+    config: shared::ServerConfig,
+    svc: T,
+}
+
+impl<T> Server<T> {
+    /// Create a new accumulator server with the given [`Accumulator`] handler.
+    pub fn new(accumulator_svc: T) -> Self {
+        Self {
+            config: shared::ServerConfig::new(DEFAULT_SOCK_ADDR, PROTOCOL_VERSION),
+            svc: accumulator_svc,
+        }
+    }
+
+    shared::server_config_methods!();
+
+    /// Starts the gRPC server. The server runs until it is stopped or errors out.
+    pub async fn start(self) -> Result<(), shared::BoxError>
+    where
+        T: Accumulator + Send + Sync + 'static,
+    {
+        self.start_with_shutdown(std::future::pending()).await
+    }
+
+    /// Starts the gRPC server, it shuts down once `shutdown` resolves. The server then stops
+    /// accepting traffic and returns once the in-flight requests are done.
+    pub async fn start_with_shutdown<F>(self, shutdown: F) -> Result<(), shared::BoxError>
+    where
+        T: Accumulator + Send + Sync + 'static,
+        F: Future<Output = ()>,
+    {
+        let mut config = self.config;
+        let incoming = config.prepare().await?;
+
+        let accumulator_svc = AccumulatorService {
+            handler: Arc::new(self.svc),
+            channel_size: config.tuning.channel_size,
+            settings: config.handler_settings(),
+        };
+
+        shared::router!(
+            config,
+            accumulator_server::AccumulatorServer::new(accumulator_svc)
+        )
+        .serve_with_incoming_shutdown(incoming, shutdown)
+        .await
+        .map_err(|e| Error::AccumulatorError(ErrorKind::connection(e)))?;
+
+        Ok(())
+    }
+}
+
+impl<T> crate::server::Service for Server<T>
+where
+    T: Accumulator + Send + Sync + 'static,
+{
+    fn serve(
+        self,
+        shutdown: BoxFuture<'static, ()>,
+    ) -> BoxFuture<'static, Result<(), shared::BoxError>> {
+        Box::pin(self.start_with_shutdown(shutdown))
+    }
+}
+
+impl<F> Server<FromFn<F>> {
+    /// Create a new accumulator server running the closure as the [`Accumulator::accumulate`]
+    /// handler, for the handlers too small to be worth a type of their own. The elements are passed
+    /// to the closure as [`Datum`] trait objects.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use numaflow::accumulator::{self, Message};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     accumulator::Server::from_fn(|mut input, output| async move {
+    ///         while let Some(datum) = input.recv().await {
+    ///             if output.send(Message::from_datum(&*datum)).await.is_err() {
+    ///                 return;
+    ///             }
+    ///         }
+    ///     })
+    ///     .start()
+    ///     .await
+    /// }
+    /// ```
+    pub fn from_fn<Fut>(handler: F) -> Self
+    where
+        F: Fn(mpsc::Receiver<Box<dyn Datum + Send + Sync>>, mpsc::Sender<Message>) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        Self::new(FromFn(handler))
+    }
+}
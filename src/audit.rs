@@ -0,0 +1,159 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::shared;
+
+/// Lineage is the record of an input element and of the outputs the handler made out of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lineage {
+    /// The kind of the handler, e.g., `batchmap`.
+    pub handler: &'static str,
+    /// The id of the input element.
+    pub input_id: String,
+    /// The ids of the outputs. They are `{input_id}-{index}`, the index being the one of the
+    /// output in the results of the element, but for the local reduce outputs, which are named
+    /// after their window and are the result of all the inputs of the window.
+    pub output_ids: Vec<String>,
+    /// When the handler returned.
+    pub time: DateTime<Utc>,
+}
+
+impl Lineage {
+    /// Returns the record as a single line JSON object, as written by the [`FileSink`].
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "handler": self.handler,
+            "input_id": self.input_id,
+            "output_ids": self.output_ids,
+            "time": self.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+        })
+        .to_string()
+    }
+}
+
+/// AuditSink receives the sampled [`Lineage`] records. It is called on the path of the requests,
+/// hence it must be quick, e.g., hand the record over to a channel. It is implemented by the
+/// closures taking a `&Lineage`.
+pub trait AuditSink: Send + Sync {
+    /// Records the lineage of an input element.
+    fn record(&self, lineage: &Lineage);
+}
+
+impl<F> AuditSink for F
+where
+    F: Fn(&Lineage) + Send + Sync,
+{
+    fn record(&self, lineage: &Lineage) {
+        self(lineage)
+    }
+}
+
+/// FileSink appends the records to a file, one JSON object per line, e.g., to be shipped by the
+/// log collector of the pod. A record which cannot be written is logged as a warning and dropped.
+pub struct FileSink {
+    file: Mutex<File>,
+}
+
+impl FileSink {
+    /// Opens the file for appending, it is created if missing.
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl AuditSink for FileSink {
+    fn record(&self, lineage: &Lineage) {
+        let mut line = lineage.to_json();
+        line.push('\n');
+        let mut file = self.file.lock().expect("audit file lock is poisoned");
+        // a single write keeps the lines of concurrent writers apart
+        if let Err(e) = file.write_all(line.as_bytes()) {
+            tracing::warn!(input_id = %lineage.input_id, error = %e, "failed to write the lineage");
+        }
+    }
+}
+
+/// Audit samples the lineage of a fraction of the input elements into an [`AuditSink`], so that
+/// "where did this record go?" is answered from the records rather than by replaying the data.
+/// An element is sampled by the hash of its id, hence the same elements are sampled by every
+/// replica and on every redelivery.
+///
+/// The lineage is recorded by the [batch map server](crate::batchmap::Server::with_audit) and by
+/// the local pipeline (`local::Pipeline::with_audit`). The requests of the other kinds do
+/// not carry the ids of the elements.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::audit::{Audit, Lineage};
+///
+/// let audit = Audit::new(|lineage: &Lineage| println!("{}", lineage.to_json()), 0.01);
+/// assert_eq!(audit.is_sampled("offset-42"), audit.is_sampled("offset-42"));
+///
+/// assert!(Audit::new(|_: &Lineage| {}, 1.0).is_sampled("offset-42"));
+/// assert!(!Audit::new(|_: &Lineage| {}, 0.0).is_sampled("offset-42"));
+/// ```
+#[derive(Clone)]
+pub struct Audit {
+    sink: Arc<dyn AuditSink>,
+    fraction: f64,
+}
+
+impl Audit {
+    /// Create an audit sampling the given fraction of the elements, from 0.0 for none to 1.0 for
+    /// all of them.
+    pub fn new(sink: impl AuditSink + 'static, fraction: f64) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            fraction: fraction.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Returns the fraction of the elements which are sampled.
+    pub fn fraction(&self) -> f64 {
+        self.fraction
+    }
+
+    /// Returns whether the lineage of the element of the id is recorded.
+    pub fn is_sampled(&self, input_id: &str) -> bool {
+        let hash = shared::fnv1a(input_id.bytes());
+        (hash as f64 / u64::MAX as f64) < self.fraction || self.fraction >= 1.0
+    }
+
+    /// Records the lineage of the element if it is sampled, the ids of its outputs are only
+    /// built then.
+    pub(crate) fn record<I>(&self, handler: &'static str, input_id: &str, output_ids: I)
+    where
+        I: FnOnce() -> Vec<String>,
+    {
+        if !self.is_sampled(input_id) {
+            return;
+        }
+        self.sink.record(&Lineage {
+            handler,
+            input_id: input_id.to_string(),
+            output_ids: output_ids(),
+            time: Utc::now(),
+        });
+    }
+
+    /// Records the lineage of the element if it is sampled, its outputs being named after their
+    /// index.
+    pub(crate) fn record_indexed(&self, handler: &'static str, input_id: &str, outputs: usize) {
+        self.record(handler, input_id, || {
+            (0..outputs)
+                .map(|i| format!("{}-{}", input_id, i))
+                .collect()
+        });
+    }
+}
@@ -0,0 +1,1003 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{async_trait, Request, Response, Status, Streaming};
+use tracing::Instrument;
+
+use crate::audit::Audit;
+use crate::batchmap::batchmapper::{
+    batch_map_response, batch_map_server, BatchMapRequest, BatchMapResponse, ReadyResponse,
+};
+use crate::error::{Error, ErrorKind};
+use crate::headers::Headers;
+use crate::message::{self, MessageBuilder};
+use crate::shared::HandlerSettings;
+use crate::timestamp::TimestampCache;
+use crate::{metrics, shared, tasks};
+
+mod batchmapper {
+    tonic::include_proto!("batchmap.v1");
+}
+
+/// The gRPC messages, client and server of the batch map protocol, generated from its proto.
+pub mod proto {
+    pub use super::batchmapper::*;
+}
+
+/// Default unix domain socket file of the batch map server, see [`Server::with_socket_file`]. It is
+/// relocated by the [`SOCKET_ENV`](crate::shared::SOCKET_ENV) and
+/// [`RUN_DIR_ENV`](crate::shared::RUN_DIR_ENV) environment variables.
+pub const DEFAULT_SOCK_ADDR: &str = "/var/run/numaflow/batchmap.sock";
+
+/// Version of the batch map protocol, i.e., of the `batchmap.v1` proto package.
+pub const PROTOCOL_VERSION: &str = "v1";
+
+pub(crate) struct BatchMapService<T> {
+    handler: Arc<T>,
+    // buffer size of the channels between the gRPC streams and the user's handle
+    channel_size: usize,
+    cut: BatchCut,
+    audit: Option<Audit>,
+    settings: HandlerSettings,
+}
+
+/// BatchMapper trait for implementing the batch map handler.
+#[async_trait]
+pub trait BatchMapper {
+    /// batch takes in all the elements of a read batch as a stream of [`Datum`] and returns the
+    /// results as a [`Vec`] of [`BatchResponse`], one for each input element identified by
+    /// [`Datum::id`]. It is useful for high-throughput transformations which benefit from
+    /// processing many elements at once, e.g., bulk lookups against an external service. An element
+    /// which fails on its own is answered with a [`BatchResponse::failure`] rather than failing the
+    /// whole batch, and [`BatchResponses`] answers the elements the handler has left out.
+    ///
+    /// Every element of the batch gets exactly one response, even with no messages: numaflow
+    /// waits for the elements it has no response for, hence a batch with a missing, duplicate or
+    /// unknown id fails the stream instead of being forwarded, see [`validate_responses`]. A batch
+    /// ends with the stream, or earlier when cut by [`Server::with_max_batch_size`] or
+    /// [`Server::with_batch_timeout`].
+    ///
+    /// # Example
+    ///
+    /// Following is an example of a cat container that copies every input in the batch to output.
+    ///
+    /// ```no_run
+    /// use numaflow::batchmap::{self, BatchResponse, Datum, Message};
+    /// use tokio::sync::mpsc::Receiver;
+    ///
+    /// struct Cat {}
+    ///
+    /// #[tonic::async_trait]
+    /// impl batchmap::BatchMapper for Cat {
+    ///     async fn batch<T>(&self, mut input: Receiver<T>) -> Vec<BatchResponse>
+    ///     where
+    ///         T: Datum + Send + Sync + 'static,
+    ///     {
+    ///         let mut responses = vec![];
+    ///         while let Some(datum) = input.recv().await {
+    ///             responses.push(BatchResponse {
+    ///                 id: datum.id().to_string(),
+    ///                 messages: vec![Message {
+    ///                     keys: datum.keys().clone(),
+    ///                     value: datum.value().clone(),
+    ///                     tags: vec![],
+    ///                 }],
+    ///                 ..Default::default()
+    ///             });
+    ///         }
+    ///         responses
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     batchmap::Server::new(Cat {}).start().await
+    /// }
+    /// ```
+    async fn batch<T: Datum + Send + Sync + 'static>(
+        &self,
+        input: mpsc::Receiver<T>,
+    ) -> Vec<BatchResponse>;
+}
+
+/// BatchResponse holds the results of a single input element of the batch.
+///
+/// An element may also fail on its own, or be sent to the fallback sink, while the other
+/// elements of the batch succeed, see its [`ResponseStatus`].
+///
+/// # Example
+///
+/// ```
+/// use numaflow::batchmap::proto::{BatchMapRequest, Status};
+/// use numaflow::batchmap::{self, BatchResponse, Message};
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     // the elements which are not numbers fail, the negative ones go to the fallback sink
+///     let server = batchmap::Server::from_fn(|mut input| async move {
+///         let mut responses = vec![];
+///         while let Some(datum) = input.recv().await {
+///             let id = datum.id().to_string();
+///             let response = match std::str::from_utf8(datum.value()).unwrap().parse::<i64>() {
+///                 Ok(n) if n < 0 => BatchResponse::fallback(id),
+///                 Ok(n) => BatchResponse {
+///                     id,
+///                     messages: vec![Message {
+///                         keys: vec![],
+///                         value: (n * 2).to_string().into(),
+///                         tags: vec![],
+///                     }],
+///                     ..Default::default()
+///                 },
+///                 Err(e) => BatchResponse::failure(id, e.to_string()),
+///             };
+///             responses.push(response);
+///         }
+///         responses
+///     });
+///     let mut client = numaflow::testing::batchmap::client_for(server).await.unwrap();
+///
+///     let requests: Vec<BatchMapRequest> = ["21", "x", "-1"]
+///         .into_iter()
+///         .enumerate()
+///         .map(|(i, value)| BatchMapRequest {
+///             id: i.to_string(),
+///             value: value.into(),
+///             ..Default::default()
+///         })
+///         .collect();
+///     let mut responses = client
+///         .batch_map_fn(tokio_stream::iter(requests))
+///         .await
+///         .unwrap()
+///         .into_inner();
+///
+///     let mut statuses = vec![];
+///     while let Some(response) = responses.message().await.unwrap() {
+///         statuses.push((response.status(), response.id, response.err_msg, response.results.len()));
+///     }
+///     assert_eq!(
+///         statuses,
+///         [
+///             (Status::Success, "0".to_string(), String::new(), 1),
+///             (Status::Failure, "1".to_string(), "invalid digit found in string".to_string(), 0),
+///             (Status::Fallback, "2".to_string(), String::new(), 0),
+///         ]
+///     );
+/// }
+/// ```
+#[derive(Default)]
+pub struct BatchResponse {
+    /// id is the [`Datum::id`] of the input element the results belong to.
+    pub id: String,
+    /// messages are the results of the input element, it can be an empty collection.
+    pub messages: Vec<Message>,
+    /// status is the outcome of the input element, the messages are only forwarded on a
+    /// [`ResponseStatus::Success`].
+    pub status: ResponseStatus,
+}
+
+/// ResponseStatus is the outcome of an input element of the batch, sent to numaflow as the
+/// `status` of its response. New outcomes may be added, hence a `match` on it needs a wildcard
+/// arm.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ResponseStatus {
+    /// The element was processed, its messages are forwarded.
+    #[default]
+    Success,
+    /// The element could not be processed for the reason, numaflow reports it as failed.
+    Failure(String),
+    /// The element is to be written to the
+    /// [fallback sink](https://numaflow.numaproj.io/user-guide/sinks/fallback/).
+    Fallback,
+}
+
+impl BatchResponse {
+    /// failure creates the response of an input element which could not be processed, the element
+    /// is reported to numaflow as failed with the reason `err` while the rest of the batch goes on.
+    /// Messages pushed to it are not forwarded.
+    pub fn failure(id: impl Into<String>, err: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            messages: vec![],
+            status: ResponseStatus::Failure(err.into()),
+        }
+    }
+
+    /// fallback creates the response of an input element which is to be written to the
+    /// [fallback sink](https://numaflow.numaproj.io/user-guide/sinks/fallback/) rather than to be
+    /// forwarded. Like a [failure](BatchResponse::failure), its messages are not forwarded.
+    pub fn fallback(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            messages: vec![],
+            status: ResponseStatus::Fallback,
+        }
+    }
+
+    /// Returns why the input element failed, for a response created by [`BatchResponse::failure`].
+    pub fn err(&self) -> Option<&str> {
+        match &self.status {
+            ResponseStatus::Failure(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the input element is to be written to the fallback sink, see
+    /// [`BatchResponse::fallback`].
+    pub fn is_fallback(&self) -> bool {
+        self.status == ResponseStatus::Fallback
+    }
+}
+
+/// Message is a single result of an input element in the [`BatchResponse`].
+pub struct Message {
+    /// Keys are a collection of strings which will be passed on to the next vertex as is. It can
+    /// be an empty collection.
+    pub keys: Vec<String>,
+    /// Value is the value passed to the next vertex.
+    pub value: Bytes,
+    /// Tags are used for [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/).
+    pub tags: Vec<String>,
+}
+
+impl Message {
+    /// Returns a builder of the message, failing on a message numaflow would reject.
+    pub fn builder() -> MessageBuilder<Self> {
+        MessageBuilder::new()
+    }
+
+    /// Returns a message numaflow drops, to filter an input of the batch out.
+    pub fn dropped() -> Self {
+        Self {
+            keys: vec![],
+            value: Bytes::new(),
+            tags: vec![message::DROP.to_string()],
+        }
+    }
+
+    /// Returns whether numaflow drops the message, see [`DROP`](message::DROP).
+    pub fn is_dropped(&self) -> bool {
+        self.tags.iter().any(|tag| tag == message::DROP)
+    }
+}
+
+/// Datum trait represents an incoming element into the [`BatchMapper::batch`].
+pub trait Datum {
+    /// keys are the keys in the (key, value) terminology of map/reduce paradigm.
+    fn keys(&self) -> &Vec<String>;
+    /// value is the value in (key, value) terminology of map/reduce paradigm.
+    fn value(&self) -> &Bytes;
+    /// [watermark](https://numaflow.numaproj.io/core-concepts/watermarks/) represented by time is a guarantee that we will not see an element older than this
+    /// time.
+    fn watermark(&self) -> DateTime<Utc>;
+    /// event_time is the time of the element as seen at source or aligned after a reduce operation.
+    fn event_time(&self) -> DateTime<Utc>;
+    /// headers are the user defined headers set by the upstream vertices.
+    fn headers(&self) -> &Headers;
+    /// ID corresponds the unique ID of the element within the batch.
+    fn id(&self) -> &str;
+}
+
+/// Owned copy of BatchMapRequest from Datum.
+struct OwnedBatchMapRequest {
+    keys: Vec<String>,
+    value: Bytes,
+    watermark: DateTime<Utc>,
+    eventtime: DateTime<Utc>,
+    headers: Headers,
+    id: String,
+}
+
+impl OwnedBatchMapRequest {
+    fn new(
+        mut br: BatchMapRequest,
+        watermarks: &mut TimestampCache,
+        settings: HandlerSettings,
+    ) -> Self {
+        let mut headers = Headers::from(br.headers);
+        settings.decompress_payload(&mut headers, &mut br.value);
+        Self {
+            keys: br.keys,
+            value: br.value,
+            watermark: watermarks.convert(br.watermark.as_ref()),
+            eventtime: shared::utc_from_timestamp(br.event_time),
+            headers,
+            id: br.id,
+        }
+    }
+}
+
+impl Datum for OwnedBatchMapRequest {
+    fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    fn watermark(&self) -> DateTime<Utc> {
+        self.watermark
+    }
+
+    fn event_time(&self) -> DateTime<Utc> {
+        self.eventtime
+    }
+
+    fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl From<BatchResponse> for BatchMapResponse {
+    fn from(response: BatchResponse) -> Self {
+        let (status, err_msg) = match response.status {
+            ResponseStatus::Success => (batchmapper::Status::Success, String::new()),
+            ResponseStatus::Failure(err) => (batchmapper::Status::Failure, err),
+            ResponseStatus::Fallback => (batchmapper::Status::Fallback, String::new()),
+        };
+        // only the results of a success are forwarded
+        let results = match status {
+            batchmapper::Status::Success => response
+                .messages
+                .into_iter()
+                .map(|message| batch_map_response::Result {
+                    keys: message.keys,
+                    value: message.value,
+                    tags: message.tags,
+                })
+                .collect(),
+            _ => vec![],
+        };
+        Self {
+            results,
+            id: response.id,
+            status: status as i32,
+            err_msg,
+        }
+    }
+}
+
+#[async_trait]
+impl<T> batch_map_server::BatchMap for BatchMapService<T>
+where
+    T: BatchMapper + Send + Sync + 'static,
+{
+    type BatchMapFnStream = ReceiverStream<Result<BatchMapResponse, Status>>;
+
+    async fn batch_map_fn(
+        &self,
+        request: Request<Streaming<BatchMapRequest>>,
+    ) -> Result<Response<Self::BatchMapFnStream>, Status> {
+        Ok(Response::new(self.batch_map_stream(request.into_inner())))
+    }
+
+    async fn is_ready(&self, _: Request<()>) -> Result<Response<ReadyResponse>, Status> {
+        Ok(Response::new(ReadyResponse { ready: true }))
+    }
+}
+
+impl<T> BatchMapService<T>
+where
+    T: BatchMapper + Send + Sync + 'static,
+{
+    /// Cuts the elements of a stream into batches and returns the stream of their responses, a
+    /// read error of the stream fails the stream of the responses.
+    pub(crate) fn batch_map_stream<S>(
+        &self,
+        mut stream: S,
+    ) -> ReceiverStream<Result<BatchMapResponse, Status>>
+    where
+        S: Stream<Item = Result<BatchMapRequest, Status>> + Unpin + Send + 'static,
+    {
+        // read the elements from the gRPC stream, a read error ends the elements
+        let (elements_tx, mut elements) =
+            mpsc::channel::<Result<OwnedBatchMapRequest, Status>>(self.channel_size);
+        let settings = self.settings;
+        tasks::spawn("batchmap:stream-reader", async move {
+            // the elements of a batch mostly share the same watermark
+            let mut watermarks = TimestampCache::default();
+            loop {
+                let element = match stream.next().await {
+                    Some(Ok(datum)) => {
+                        metrics::messages_received("batchmap", 1);
+                        Ok(OwnedBatchMapRequest::new(datum, &mut watermarks, settings))
+                    }
+                    None => break,
+                    Some(Err(status)) => Err(status),
+                };
+                let failed = element.is_err();
+                if elements_tx.send(element).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        // channel to respond to numaflow main car as it expects streaming results.
+        let (resp_tx, resp_rx) =
+            mpsc::channel::<Result<BatchMapResponse, Status>>(self.channel_size);
+
+        // cut the elements into batches, the responses of a batch are streamed out once its handle
+        // has returned
+        let handler = Arc::clone(&self.handler);
+        let cut = self.cut;
+        let channel_size = self.channel_size;
+        let audit = self.audit.clone();
+        tasks::spawn("batchmap:batcher", async move {
+            while let Some(first) = elements.recv().await {
+                let responses = match first {
+                    Ok(first) => {
+                        run_batch(&*handler, first, &mut elements, cut, channel_size, settings)
+                            .await
+                    }
+                    Err(status) => Err(status),
+                };
+                let responses = match responses {
+                    Ok(responses) => responses,
+                    Err(status) => {
+                        let _ = resp_tx.send(Err(status)).await;
+                        return;
+                    }
+                };
+                for response in responses {
+                    let response = BatchMapResponse::from(response);
+                    if let Some(audit) = &audit {
+                        audit.record_indexed("batchmap", &response.id, response.results.len());
+                    }
+                    metrics::messages_emitted("batchmap", response.results.len());
+                    metrics::channel_saturation("batchmap", &resp_tx);
+                    if resp_tx.send(Ok(response)).await.is_err() {
+                        // client is gone, nothing more to do
+                        return;
+                    }
+                }
+            }
+        });
+
+        ReceiverStream::new(resp_rx)
+    }
+}
+
+/// Where the SDK cuts the elements of a stream into batches, besides the end of the stream.
+#[derive(Debug, Clone, Copy, Default)]
+struct BatchCut {
+    max_size: Option<usize>,
+    timeout: Option<Duration>,
+}
+
+/// Runs the handle over the batch starting with `first`, fed from `elements` until the batch is
+/// cut, and returns its responses once they are checked to cover every element of the batch.
+async fn run_batch<T: BatchMapper>(
+    handler: &T,
+    first: OwnedBatchMapRequest,
+    elements: &mut mpsc::Receiver<Result<OwnedBatchMapRequest, Status>>,
+    cut: BatchCut,
+    channel_size: usize,
+    settings: HandlerSettings,
+) -> Result<Vec<BatchResponse>, Status> {
+    // channel to send the batch to the user's handle, tx is dropped at the end of the batch which
+    // closes the user's rx.
+    let (tx, rx) = mpsc::channel::<OwnedBatchMapRequest>(channel_size);
+    let feed = async move {
+        let deadline = cut
+            .timeout
+            .map(|timeout| tokio::time::Instant::now() + timeout);
+        let mut ids = vec![];
+        let mut next = Some(first);
+        while let Some(datum) = next.take() {
+            ids.push(datum.id.clone());
+            // the handle stopped reading the batch, the element is left without a response which
+            // is reported once the handle returns, the next elements go to the next batch
+            if let Err(e) = tx.send(datum).await {
+                tracing::warn!(id = %e.0.id, "batchmap handler stopped reading the batch");
+                break;
+            }
+            if cut.max_size.is_some_and(|max_size| ids.len() >= max_size) {
+                break;
+            }
+            let timeout = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            };
+            // the batch is cut at the end of the stream or once past the timeout
+            next = tokio::select! {
+                element = elements.recv() => element.transpose()?,
+                _ = timeout => None,
+            };
+        }
+        Ok::<_, Status>(ids)
+    };
+
+    let start = Instant::now();
+    // the elements of a batch belong to different traces, so the span has no parent
+    let handle = settings
+        .watch("batchmap", handler.batch(rx))
+        .instrument(settings.handler_span("batchmap", None));
+    let (ids, responses) = tokio::join!(feed, AssertUnwindSafe(handle).catch_unwind());
+    metrics::handler_latency("batchmap", start.elapsed());
+
+    // the batch has many keys, none is blamed for the panic
+    let responses = responses.map_err(|panic| {
+        settings.to_status(Error::BatchMapError(ErrorKind::HandlerPanic {
+            message: shared::panic_message(panic),
+            keys: vec![],
+            window: None,
+        }))
+    })?;
+    // results of a batch which could not be read fully are not to be forwarded
+    let ids = ids?;
+    if let Err(mismatch) = validate_responses(&ids, &responses) {
+        return Err(
+            settings.to_status(Error::BatchMapError(ErrorKind::InvalidArgument(
+                mismatch.to_string(),
+            ))),
+        );
+    }
+    Ok(responses)
+}
+
+/// ResponseMismatch is returned when the responses of a batch do not have exactly one entry per
+/// element of the batch, see [`validate_responses`]. The ids are sorted.
+#[derive(Error, Debug, Clone, Default, PartialEq, Eq)]
+#[error(
+    "responses of the batch of {elements} elements do not match its ids: missing {missing:?}, \
+     duplicate {duplicate:?}, unknown {unknown:?}"
+)]
+pub struct ResponseMismatch {
+    /// Number of elements of the batch.
+    pub elements: usize,
+    /// Ids of the elements without a response.
+    pub missing: Vec<String>,
+    /// Ids of the elements with more than one response.
+    pub duplicate: Vec<String>,
+    /// Ids of the responses which are not of an element of the batch.
+    pub unknown: Vec<String>,
+}
+
+/// Checks that the responses have exactly one entry per id of the elements of the batch, as
+/// numaflow waits for the elements it has no response for and does not expect any other. The
+/// server fails the stream with an `InvalidArgument` error listing the offending ids when the
+/// responses of the handler do not pass, it is also meant for the tests of the handler.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::batchmap::{self, BatchResponse};
+///
+/// let response = |id: &str| BatchResponse {
+///     id: id.to_string(),
+///     ..Default::default()
+/// };
+///
+/// let ids = ["a", "b", "c"];
+/// assert!(batchmap::validate_responses(&ids, &[response("b"), response("a"), response("c")]).is_ok());
+///
+/// let mismatch =
+///     batchmap::validate_responses(&ids, &[response("a"), response("a"), response("d")]).unwrap_err();
+/// assert_eq!(mismatch.missing, ["b", "c"]);
+/// assert_eq!(mismatch.duplicate, ["a"]);
+/// assert_eq!(mismatch.unknown, ["d"]);
+/// ```
+pub fn validate_responses<I: AsRef<str>>(
+    ids: &[I],
+    responses: &[BatchResponse],
+) -> Result<(), ResponseMismatch> {
+    let known: HashSet<&str> = ids.iter().map(AsRef::as_ref).collect();
+    let mut missing = known.clone();
+    let mut duplicate = BTreeSet::new();
+    let mut unknown = BTreeSet::new();
+    for response in responses {
+        let id = response.id.as_str();
+        if missing.remove(id) {
+            continue;
+        }
+        if known.contains(id) {
+            duplicate.insert(id);
+        } else {
+            unknown.insert(id);
+        }
+    }
+    if missing.is_empty() && duplicate.is_empty() && unknown.is_empty() {
+        return Ok(());
+    }
+
+    let mut missing: Vec<String> = missing.into_iter().map(str::to_string).collect();
+    missing.sort_unstable();
+    Err(ResponseMismatch {
+        elements: ids.len(),
+        missing,
+        duplicate: duplicate.into_iter().map(str::to_string).collect(),
+        unknown: unknown.into_iter().map(str::to_string).collect(),
+    })
+}
+
+/// Unanswered is what [`BatchResponses`] answers an element the handler has not answered with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Unanswered {
+    /// The element is dropped, see [`Message::dropped`].
+    #[default]
+    Drop,
+    /// The element fails with the reason, see [`BatchResponse::failure`].
+    Failure(String),
+    /// The element is written to the fallback sink, see [`BatchResponse::fallback`].
+    Fallback,
+}
+
+/// BatchResponses keeps track of the elements of a batch and of the ones answered so far, so that
+/// an element the handler forgot about gets a response of the [`Unanswered`] kind rather than
+/// failing the whole batch, see [`validate_responses`]. The elements are tracked as they are read,
+/// and answered in any order by pushing their messages or their whole response.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::batchmap::{self, BatchResponse, BatchResponses, Message, Unanswered};
+///
+/// let ids = ["a", "b", "c"];
+/// let mut responses =
+///     BatchResponses::new().with_unanswered(Unanswered::Failure("lookup timed out".to_string()));
+/// for id in ids {
+///     responses.track(id);
+/// }
+///
+/// responses.push(
+///     "a",
+///     Message {
+///         keys: vec![],
+///         value: "found".into(),
+///         tags: vec![],
+///     },
+/// );
+/// responses.respond(BatchResponse::fallback("b"));
+/// assert_eq!(responses.pending().collect::<Vec<_>>(), ["c"]);
+///
+/// let responses = responses.into_responses();
+/// assert!(batchmap::validate_responses(&ids, &responses).is_ok());
+/// assert_eq!(responses[0].messages.len(), 1);
+/// assert!(responses[1].is_fallback());
+/// assert_eq!(responses[2].err(), Some("lookup timed out"));
+/// ```
+#[derive(Default)]
+pub struct BatchResponses {
+    // the responses in the order the elements were tracked, None until the element is answered
+    responses: Vec<(String, Option<BatchResponse>)>,
+    // position of the id in the responses
+    index: HashMap<String, usize>,
+    unanswered: Unanswered,
+}
+
+impl BatchResponses {
+    /// Create an empty set of responses, the unanswered elements are dropped.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set what the elements left unanswered are answered with. They are dropped by default.
+    pub fn with_unanswered(mut self, unanswered: Unanswered) -> Self {
+        self.unanswered = unanswered;
+        self
+    }
+
+    /// Get what the elements left unanswered are answered with.
+    pub fn unanswered(&self) -> &Unanswered {
+        &self.unanswered
+    }
+
+    /// Tracks the element of the [`Datum::id`], it is expected to be answered. Tracking an id
+    /// again is a no-op.
+    pub fn track(&mut self, id: impl Into<String>) {
+        self.position(id.into());
+    }
+
+    /// Answers the element with one more message, the messages of an element are forwarded in the
+    /// order they are pushed. An id which was not tracked is tracked, its response is then
+    /// reported as unknown by the server unless the id is of the batch.
+    pub fn push(&mut self, id: &str, message: Message) {
+        let position = self.position(id.to_string());
+        let (id, response) = &mut self.responses[position];
+        response
+            .get_or_insert_with(|| BatchResponse {
+                id: id.clone(),
+                ..Default::default()
+            })
+            .messages
+            .push(message);
+    }
+
+    /// Answers the element with the response, e.g., a [`BatchResponse::failure`], replacing the
+    /// messages pushed for it so far.
+    pub fn respond(&mut self, response: BatchResponse) {
+        let position = self.position(response.id.clone());
+        self.responses[position].1 = Some(response);
+    }
+
+    /// Returns whether the element has been answered.
+    pub fn is_answered(&self, id: &str) -> bool {
+        self.index
+            .get(id)
+            .is_some_and(|position| self.responses[*position].1.is_some())
+    }
+
+    /// Returns the ids of the tracked elements which have not been answered yet.
+    pub fn pending(&self) -> impl Iterator<Item = &str> {
+        self.responses
+            .iter()
+            .filter(|(_, response)| response.is_none())
+            .map(|(id, _)| id.as_str())
+    }
+
+    /// Returns a response per tracked element, in the order they were tracked, the unanswered ones
+    /// being answered as set by [`BatchResponses::with_unanswered`].
+    pub fn into_responses(self) -> Vec<BatchResponse> {
+        let unanswered = self.unanswered;
+        self.responses
+            .into_iter()
+            .map(|(id, response)| {
+                response.unwrap_or_else(|| match &unanswered {
+                    Unanswered::Drop => BatchResponse {
+                        id,
+                        messages: vec![Message::dropped()],
+                        status: ResponseStatus::Success,
+                    },
+                    Unanswered::Failure(err) => BatchResponse::failure(id, err.clone()),
+                    Unanswered::Fallback => BatchResponse::fallback(id),
+                })
+            })
+            .collect()
+    }
+
+    // position of the id in the responses, it is tracked if it is not yet
+    fn position(&mut self, id: String) -> usize {
+        if let Some(position) = self.index.get(&id) {
+            return *position;
+        }
+        let position = self.responses.len();
+        self.index.insert(id.clone(), position);
+        self.responses.push((id, None));
+        position
+    }
+}
+
+/// FromFn is a [`BatchMapper`] running a closure, see [`Server::from_fn`].
+pub struct FromFn<F>(F);
+
+#[async_trait]
+impl<F, Fut> BatchMapper for FromFn<F>
+where
+    F: Fn(mpsc::Receiver<Box<dyn Datum + Send + Sync>>) -> Fut + Send + Sync,
+    Fut: Future<Output = Vec<BatchResponse>> + Send,
+{
+    async fn batch<T: Datum + Send + Sync + 'static>(
+        &self,
+        input: mpsc::Receiver<T>,
+    ) -> Vec<BatchResponse> {
+        shared::forward_input(
+            input,
+            |datum| Box::new(datum) as Box<dyn Datum + Send + Sync>,
+            |input| (self.0)(input),
+        )
+        .await
+    }
+}
+
+/// gRPC server to start a batch map service
+pub struct Server<T> {
+    config: shared::ServerConfig,
+    svc: T,
+    audit: Option<Audit>,
+    cut: BatchCut,
+}
+
+impl<T> Server<T> {
+    /// Create a new batch map server with the given [`BatchMapper`] handler.
+    pub fn new(batch_map_svc: T) -> Self {
+        Self {
+            config: shared::ServerConfig::new(DEFAULT_SOCK_ADDR, PROTOCOL_VERSION),
+            svc: batch_map_svc,
+            audit: None,
+            cut: BatchCut::default(),
+        }
+    }
+
+    shared::server_config_methods!();
+
+    /// Set the [`Audit`] recording the lineage of a sample of the elements, an element maps to
+    /// the messages of its [`BatchResponse`]. No lineage is recorded by default.
+    pub fn with_audit(mut self, audit: Audit) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Get the [`Audit`] recording the lineage of the elements.
+    pub fn audit(&self) -> Option<&Audit> {
+        self.audit.as_ref()
+    }
+
+    /// Set the maximum number of elements of a batch, a stream of more elements is cut into
+    /// batches of at most that many elements, passed to the handler one after the other. The
+    /// batches are only cut at the end of the stream by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use numaflow::batchmap::proto::BatchMapRequest;
+    /// use numaflow::batchmap::{self, BatchResponse, Message};
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() {
+    ///     // every element is answered with the size of its batch
+    ///     let server = batchmap::Server::from_fn(|mut input| async move {
+    ///         let mut ids = vec![];
+    ///         while let Some(datum) = input.recv().await {
+    ///             ids.push(datum.id().to_string());
+    ///         }
+    ///         let size = ids.len();
+    ///         ids.into_iter()
+    ///             .map(|id| BatchResponse {
+    ///                 id,
+    ///                 messages: vec![Message {
+    ///                     keys: vec![],
+    ///                     value: size.to_string().into(),
+    ///                     tags: vec![],
+    ///                 }],
+    ///                 ..Default::default()
+    ///             })
+    ///             .collect()
+    ///     })
+    ///     .with_max_batch_size(2);
+    ///     let mut client = numaflow::testing::batchmap::client_for(server).await.unwrap();
+    ///
+    ///     let requests: Vec<BatchMapRequest> = (0..5)
+    ///         .map(|i| BatchMapRequest {
+    ///             id: i.to_string(),
+    ///             ..Default::default()
+    ///         })
+    ///         .collect();
+    ///     let mut responses = client
+    ///         .batch_map_fn(tokio_stream::iter(requests))
+    ///         .await
+    ///         .unwrap()
+    ///         .into_inner();
+    ///
+    ///     let mut sizes = vec![];
+    ///     while let Some(response) = responses.message().await.unwrap() {
+    ///         sizes.push(String::from_utf8(response.results[0].value.to_vec()).unwrap());
+    ///     }
+    ///     assert_eq!(sizes, ["2", "2", "2", "2", "1"]);
+    /// }
+    /// ```
+    pub fn with_max_batch_size(mut self, size: usize) -> Self {
+        self.cut.max_size = Some(size.max(1));
+        self
+    }
+
+    /// Get the maximum number of elements of a batch.
+    pub fn max_batch_size(&self) -> Option<usize> {
+        self.cut.max_size
+    }
+
+    /// Set the time a batch is given to fill up, counted from its first element. The batch is cut
+    /// once it has passed, even though the stream goes on, so that the elements of a slow stream
+    /// do not wait for the end of the stream. There is no timeout by default.
+    pub fn with_batch_timeout(mut self, timeout: Duration) -> Self {
+        self.cut.timeout = Some(timeout);
+        self
+    }
+
+    /// Get the time a batch is given to fill up.
+    pub fn batch_timeout(&self) -> Option<Duration> {
+        self.cut.timeout
+    }
+
+    /// Starts the gRPC server. The server runs until it is stopped or errors out.
+    pub async fn start(self) -> Result<(), shared::BoxError>
+    where
+        T: BatchMapper + Send + Sync + 'static,
+    {
+        self.start_with_shutdown(std::future::pending()).await
+    }
+
+    /// Starts the gRPC server, it shuts down once `shutdown` resolves. The server then stops
+    /// accepting traffic and returns once the in-flight requests are done.
+    pub async fn start_with_shutdown<F>(self, shutdown: F) -> Result<(), shared::BoxError>
+    where
+        T: BatchMapper + Send + Sync + 'static,
+        F: Future<Output = ()>,
+    {
+        let (mut config, batch_map_svc) = self.into_parts();
+        let incoming = config.prepare().await?;
+
+        shared::router!(config, batch_map_server::BatchMapServer::new(batch_map_svc))
+            .serve_with_incoming_shutdown(incoming, shutdown)
+            .await
+            .map_err(|e| Error::BatchMapError(ErrorKind::connection(e)))?;
+
+        Ok(())
+    }
+}
+
+impl<T> Server<T> {
+    /// Splits the server into its configuration and the service running its streams.
+    pub(crate) fn into_parts(self) -> (shared::ServerConfig, BatchMapService<T>) {
+        let service = BatchMapService {
+            handler: Arc::new(self.svc),
+            channel_size: self.config.tuning.channel_size,
+            cut: self.cut,
+            audit: self.audit,
+            settings: self.config.handler_settings(),
+        };
+        (self.config, service)
+    }
+}
+
+impl<T> crate::server::Service for Server<T>
+where
+    T: BatchMapper + Send + Sync + 'static,
+{
+    fn serve(
+        self,
+        shutdown: BoxFuture<'static, ()>,
+    ) -> BoxFuture<'static, Result<(), shared::BoxError>> {
+        Box::pin(self.start_with_shutdown(shutdown))
+    }
+}
+
+impl<F> Server<FromFn<F>> {
+    /// Create a new batch map server running the closure as the [`BatchMapper::batch`] handler, for
+    /// the handlers too small to be worth a type of their own. The elements are passed to the
+    /// closure as [`Datum`] trait objects.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use numaflow::batchmap::{self, BatchResponse, Message};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     batchmap::Server::from_fn(|mut input| async move {
+    ///         let mut responses = vec![];
+    ///         while let Some(datum) = input.recv().await {
+    ///             responses.push(BatchResponse {
+    ///                 id: datum.id().to_string(),
+    ///                 messages: vec![Message {
+    ///                     keys: datum.keys().clone(),
+    ///                     value: datum.value().clone(),
+    ///                     tags: vec![],
+    ///                 }],
+    ///                 ..Default::default()
+    ///             });
+    ///         }
+    ///         responses
+    ///     })
+    ///     .start()
+    ///     .await
+    /// }
+    /// ```
+    pub fn from_fn<Fut>(handler: F) -> Self
+    where
+        F: Fn(mpsc::Receiver<Box<dyn Datum + Send + Sync>>) -> Fut,
+        Fut: Future<Output = Vec<BatchResponse>>,
+    {
+        Self::new(FromFn(handler))
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use serde::Serialize;
+
+/// Default maximum number of idle buffers of a pool, see [`BufferPool::with_max_idle_buffers`].
+pub const DEFAULT_MAX_IDLE_BUFFERS: usize = 64;
+
+/// BufferPool hands out reusable buffers to build the payloads of the messages in, so that
+/// pipelines with large payloads at high rates do not allocate a fresh vector for every message.
+///
+/// A [`Buffer`] is frozen into the [`Bytes`] of a payload and goes back to the pool. Once the
+/// payload is dropped, i.e., after it has been sent to numaflow, its allocation is reused by a
+/// later [`BufferPool::get`], a buffer whose payload is still alive gets a fresh allocation. The
+/// pool is cheap to clone and can be shared by the handlers.
+///
+/// ```
+/// use numaflow::buffer::BufferPool;
+/// use bytes::BufMut;
+///
+/// let pool = BufferPool::new(64 * 1024);
+///
+/// let mut buffer = pool.get();
+/// buffer.put_slice(b"hello");
+/// assert_eq!(buffer.freeze(), "hello");
+///
+/// let payload = pool.json(&serde_json::json!({"count": 2})).unwrap();
+/// assert_eq!(payload, r#"{"count":2}"#);
+/// ```
+#[derive(Clone)]
+pub struct BufferPool {
+    // the buffers not in use, the ones returned first are the most likely to have their payloads
+    // gone, hence they are taken first
+    idle: Arc<Mutex<VecDeque<BytesMut>>>,
+    buffer_size: usize,
+    max_idle_buffers: usize,
+}
+
+impl BufferPool {
+    /// Creates a pool of buffers of `buffer_size` bytes, the size should be at least that of the
+    /// largest payload. A payload going past it is written to a fresh allocation.
+    pub fn new(buffer_size: usize) -> Self {
+        Self {
+            idle: Arc::new(Mutex::new(VecDeque::new())),
+            buffer_size,
+            max_idle_buffers: DEFAULT_MAX_IDLE_BUFFERS,
+        }
+    }
+
+    /// Set the maximum number of buffers kept in the pool while not in use, the extra buffers
+    /// returned to the pool are freed. Default value is [`DEFAULT_MAX_IDLE_BUFFERS`], 64.
+    pub fn with_max_idle_buffers(mut self, max: usize) -> Self {
+        self.max_idle_buffers = max;
+        self
+    }
+
+    /// Returns the size of the buffers.
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// Returns the maximum number of buffers kept in the pool while not in use.
+    pub fn max_idle_buffers(&self) -> usize {
+        self.max_idle_buffers
+    }
+
+    /// Takes an empty buffer out of the pool, it goes back to the pool when frozen or dropped.
+    pub fn get(&self) -> Buffer {
+        let mut idle = self
+            .idle
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut reclaimed = None;
+        if let Some(mut oldest) = idle.pop_front() {
+            // the allocation is reused once the payload frozen out of it is gone, otherwise the
+            // buffer waits at the back and the pool grows with the payloads in flight
+            if oldest.try_reclaim(self.buffer_size) {
+                reclaimed = Some(oldest);
+            } else {
+                idle.push_back(oldest);
+            }
+        }
+        drop(idle);
+        let buffer = reclaimed.unwrap_or_else(|| BytesMut::with_capacity(self.buffer_size));
+
+        Buffer {
+            buffer,
+            pool: self.clone(),
+        }
+    }
+
+    /// Serializes the value as JSON into a buffer of the pool.
+    pub fn json<T: Serialize + ?Sized>(&self, value: &T) -> serde_json::Result<Bytes> {
+        let mut buffer = self.get();
+        serde_json::to_writer((&mut *buffer).writer(), value)?;
+        Ok(buffer.freeze())
+    }
+}
+
+/// Buffer is a buffer taken from a [`BufferPool`], it derefs to a [`BytesMut`] to be written to.
+pub struct Buffer {
+    buffer: BytesMut,
+    pool: BufferPool,
+}
+
+impl Buffer {
+    /// Returns the written bytes as a payload, the buffer goes back to the pool.
+    pub fn freeze(mut self) -> Bytes {
+        self.buffer.split().freeze()
+    }
+}
+
+impl Deref for Buffer {
+    type Target = BytesMut;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl DerefMut for Buffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.clear();
+        let mut idle = self
+            .pool
+            .idle
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if idle.len() < self.pool.max_idle_buffers {
+            idle.push_back(buffer);
+        }
+    }
+}
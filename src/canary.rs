@@ -0,0 +1,219 @@
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures_util::FutureExt;
+use tokio::sync::mpsc;
+use tonic::async_trait;
+
+use crate::reduce::{Datum, Message, Metadata, Reducer};
+use crate::{metrics, shared};
+
+/// CanaryOutput tells what becomes of the results of the canary reducer of a [`Canary`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CanaryOutput {
+    /// The results are dropped once compared with the ones of the primary reducer, the outcome is
+    /// only counted in the `reduce_canary_windows_total` metric.
+    #[default]
+    Discard,
+    /// The results are returned next to the ones of the primary reducer with the tag added, so
+    /// that the [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/)
+    /// of the vertex routes them to a canary branch of the pipeline.
+    Tag(String),
+}
+
+/// Canary mirrors a percentage of the windows to a second reducer, the canary, so that a new
+/// aggregation logic is tried out on live traffic within the vertex before it replaces the
+/// current one, e.g., with a [`Switch`](crate::switch::Switch).
+///
+/// The primary reducer reduces every window as usual. A mirrored window is also reduced by the
+/// canary, which gets the same elements, and the results of both are compared: the windows whose
+/// results are the same, different or whose canary panicked are counted in the
+/// `reduce_canary_windows_total` metric. A panic of the canary does not fail the window.
+///
+/// The windows are picked by a stable hash of their [id](Metadata::window_id), hence a
+/// window replayed by numaflow is mirrored again. Both reducers read the elements in step, so a
+/// slow canary slows the mirrored windows down. They share the [`Metadata`] of the window as well,
+/// the canary must not keep the state of the window in its state store.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::canary::{Canary, CanaryOutput};
+/// use numaflow::reduce::{Datum, Message, Metadata, Reducer};
+/// use numaflow::testing::assert_messages;
+/// use numaflow::testing::reduce::TestDriver;
+/// use tokio::sync::mpsc::Receiver;
+///
+/// /// Counts the elements, the canary counts their bytes instead.
+/// struct Counter {
+///     bytes: bool,
+/// }
+///
+/// #[tonic::async_trait]
+/// impl Reducer for Counter {
+///     async fn reduce<T: Datum + Send + Sync + 'static, U: Metadata + Send + Sync + 'static>(
+///         &self,
+///         keys: Vec<String>,
+///         mut input: Receiver<T>,
+///         _md: &U,
+///     ) -> Vec<Message> {
+///         let mut count = 0;
+///         while let Some(datum) = input.recv().await {
+///             count += if self.bytes { datum.value().len() } else { 1 };
+///         }
+///         vec![Message::new(keys, count.to_string(), vec![])]
+///     }
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let canary = Canary::new(Counter { bytes: false }, Counter { bytes: true }, 100.0)
+///         .with_output(CanaryOutput::Tag("canary".to_string()));
+///
+///     let out = TestDriver::new(canary)
+///         .with_input(["a"], "12")
+///         .with_input(["a"], "345")
+///         .run()
+///         .await;
+///
+///     assert_messages(&out)
+///         .has_len(2)
+///         .message(0)
+///         .value_str("2")
+///         .message(1)
+///         .value_str("5")
+///         .tags(["canary"]);
+/// }
+/// ```
+pub struct Canary<P, C> {
+    primary: P,
+    canary: C,
+    percentage: f64,
+    output: CanaryOutput,
+}
+
+impl<P, C> Canary<P, C> {
+    /// Create a canary mirroring the given percentage of the windows, from 0.0 for none to 100.0
+    /// for all of them, from the `primary` reducer to the `canary` one. Its results are discarded
+    /// by default, see [`with_output`](Canary::with_output).
+    pub fn new(primary: P, canary: C, percentage: f64) -> Self {
+        Self {
+            primary,
+            canary,
+            percentage: percentage.clamp(0.0, 100.0),
+            output: CanaryOutput::default(),
+        }
+    }
+
+    /// Set what becomes of the results of the canary reducer. Default is
+    /// [`CanaryOutput::Discard`].
+    pub fn with_output(mut self, output: CanaryOutput) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Get the percentage of the windows mirrored to the canary reducer.
+    pub fn percentage(&self) -> f64 {
+        self.percentage
+    }
+
+    /// Get what becomes of the results of the canary reducer.
+    pub fn output(&self) -> &CanaryOutput {
+        &self.output
+    }
+
+    /// Returns whether the window of the id is mirrored to the canary reducer.
+    ///
+    /// ```
+    /// use numaflow::canary::Canary;
+    ///
+    /// let canary = Canary::new((), (), 10.0);
+    /// assert_eq!(canary.is_mirrored("3fa2c1"), canary.is_mirrored("3fa2c1"));
+    ///
+    /// assert!(Canary::new((), (), 100.0).is_mirrored("3fa2c1"));
+    /// assert!(!Canary::new((), (), 0.0).is_mirrored("3fa2c1"));
+    /// ```
+    pub fn is_mirrored(&self, window_id: &str) -> bool {
+        let hash = shared::fnv1a(window_id.bytes());
+        (hash as f64 / u64::MAX as f64) * 100.0 < self.percentage || self.percentage >= 100.0
+    }
+}
+
+/// Returns whether the two reducers returned the same messages, regardless of their order.
+fn same_results(primary: &[Message], canary: &[Message]) -> bool {
+    if primary.len() != canary.len() {
+        return false;
+    }
+    fn sorted(messages: &[Message]) -> Vec<(&Vec<String>, &Bytes, &Vec<String>)> {
+        let mut messages: Vec<_> = messages
+            .iter()
+            .map(|m| (&m.keys, &m.value, &m.tags))
+            .collect();
+        messages.sort();
+        messages
+    }
+    sorted(primary) == sorted(canary)
+}
+
+#[async_trait]
+impl<P, C> Reducer for Canary<P, C>
+where
+    P: Reducer + Send + Sync,
+    C: Reducer + Send + Sync,
+{
+    async fn reduce<T: Datum + Send + Sync + 'static, U: Metadata + Send + Sync + 'static>(
+        &self,
+        keys: Vec<String>,
+        mut input: mpsc::Receiver<T>,
+        md: &U,
+    ) -> Vec<Message> {
+        if !self.is_mirrored(md.window_id()) {
+            return self.primary.reduce(keys, input, md).await;
+        }
+
+        let (primary_tx, primary_rx) = mpsc::channel(input.max_capacity());
+        let (canary_tx, canary_rx) = mpsc::channel(input.max_capacity());
+        // the elements are handed over to both reducers until both of them are done with the input
+        let forward = async move {
+            while let Some(datum) = input.recv().await {
+                let datum = Arc::new(datum);
+                let (primary, canary) =
+                    tokio::join!(primary_tx.send(datum.clone()), canary_tx.send(datum));
+                if primary.is_err() && canary.is_err() {
+                    break;
+                }
+            }
+        };
+        let canary =
+            AssertUnwindSafe(self.canary.reduce(keys.clone(), canary_rx, md)).catch_unwind();
+        let (_, mut messages, canary) =
+            tokio::join!(forward, self.primary.reduce(keys, primary_rx, md), canary);
+
+        let canary = match canary {
+            Ok(canary) => canary,
+            Err(panic) => {
+                tracing::error!(
+                    task_id = md.task_id(),
+                    panic = %shared::panic_message(panic),
+                    "canary reducer panicked"
+                );
+                metrics::reduce_canary_window("panicked");
+                return messages;
+            }
+        };
+        metrics::reduce_canary_window(if same_results(&messages, &canary) {
+            "same"
+        } else {
+            "different"
+        });
+
+        if let CanaryOutput::Tag(tag) = &self.output {
+            messages.extend(canary.into_iter().map(|mut message| {
+                message.tags.push(tag.clone());
+                message
+            }));
+        }
+        messages
+    }
+}
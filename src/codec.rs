@@ -0,0 +1,353 @@
+use std::future::Future;
+use std::marker::PhantomData;
+
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tonic::async_trait;
+
+use crate::reduce::{IntervalWindow, Metadata, Reducer};
+use crate::shared::BoxError;
+use crate::{json, map, reduce, shared};
+
+/// CodecError is the error of decoding a payload or of encoding a value.
+#[derive(Error, Debug)]
+pub enum CodecError {
+    #[error("failed to decode the payload: {0}")]
+    Decode(BoxError),
+    #[error("failed to encode the value: {0}")]
+    Encode(BoxError),
+}
+
+/// Decode turns a payload into a `T`.
+pub trait Decode<T>: Send + Sync {
+    /// Decodes the payload.
+    fn decode(&self, payload: &[u8]) -> Result<T, CodecError>;
+}
+
+/// Encode turns a `T` into a payload.
+pub trait Encode<T>: Send + Sync {
+    /// Encodes the value.
+    fn encode(&self, value: &T) -> Result<Bytes, CodecError>;
+}
+
+/// Codec is the format of the payloads of a typed handler, e.g., [`TypedReducer::with_codec`].
+/// It is implemented by whatever decodes its `T` and encodes it: [`Json`], [`Protobuf`], `Avro`
+/// with the `avro` feature, or a format of its own by implementing [`Decode`] and [`Encode`].
+pub trait Codec<T>: Decode<T> + Encode<T> {}
+
+impl<T, C: Decode<T> + Encode<T>> Codec<T> for C {}
+
+/// Json is the [`Codec`] of the JSON payloads, decoded with [`json::from_slice`]. It is the codec
+/// of the typed handlers by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl<T: DeserializeOwned> Decode<T> for Json {
+    fn decode(&self, payload: &[u8]) -> Result<T, CodecError> {
+        json::from_slice(payload).map_err(|e| CodecError::Decode(e.into()))
+    }
+}
+
+impl<T: Serialize> Encode<T> for Json {
+    fn encode(&self, value: &T) -> Result<Bytes, CodecError> {
+        serde_json::to_vec(value)
+            .map(Into::into)
+            .map_err(|e| CodecError::Encode(e.into()))
+    }
+}
+
+/// Protobuf is the [`Codec`] of the Protobuf payloads, for the types generated by prost.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::codec::{Decode, Encode, Protobuf};
+///
+/// #[derive(Clone, PartialEq, prost::Message)]
+/// struct Order {
+///     #[prost(uint64, tag = "1")]
+///     amount: u64,
+/// }
+///
+/// let payload = Protobuf.encode(&Order { amount: 7 }).unwrap();
+/// let order: Order = Protobuf.decode(&payload).unwrap();
+/// assert_eq!(order.amount, 7);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Protobuf;
+
+impl<T: prost::Message + Default> Decode<T> for Protobuf {
+    fn decode(&self, payload: &[u8]) -> Result<T, CodecError> {
+        T::decode(payload).map_err(|e| CodecError::Decode(e.into()))
+    }
+}
+
+impl<T: prost::Message> Encode<T> for Protobuf {
+    fn encode(&self, value: &T) -> Result<Bytes, CodecError> {
+        Ok(value.encode_to_vec().into())
+    }
+}
+
+#[cfg(feature = "avro")]
+mod avro;
+#[cfg(feature = "avro")]
+pub use avro::{Avro, AvroSchema};
+
+/// TypedMapper is a [`Mapper`](map::Mapper) over typed payloads, so that the handler deals with
+/// its own types rather than with bytes. The value of every element is decoded into an `I` with
+/// the [`Codec`], [`Json`] by default, before it is passed to the closure, and every `O` the
+/// closure returns is encoded as the value of a [`Message`](map::Message) with the keys of the
+/// element and no tags. An element which cannot be decoded has no results and is logged as a
+/// warning, a result which cannot be encoded is dropped and logged as well.
+///
+/// # Example
+///
+/// ```
+/// use chrono::Utc;
+/// use numaflow::codec::{Encode, Protobuf, TypedMapper};
+/// use numaflow::local::Element;
+/// use numaflow::map::Mapper;
+/// use numaflow::testing::assert_messages;
+///
+/// #[derive(Clone, PartialEq, prost::Message)]
+/// struct Celsius {
+///     #[prost(double, tag = "1")]
+///     degrees: f64,
+/// }
+///
+/// #[derive(Clone, PartialEq, prost::Message)]
+/// struct Fahrenheit {
+///     #[prost(double, tag = "1")]
+///     degrees: f64,
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let mapper = TypedMapper::new(|celsius: Celsius| async move {
+///         vec![Fahrenheit {
+///             degrees: celsius.degrees * 1.8 + 32.0,
+///         }]
+///     })
+///     .with_codec(Protobuf);
+///
+///     let payload = Protobuf.encode(&Celsius { degrees: 100.0 }).unwrap();
+///     let out = mapper.map(Element::new(payload, Utc::now())).await;
+///
+///     let expected = Protobuf.encode(&Fahrenheit { degrees: 212.0 }).unwrap();
+///     assert_messages(&out).has_len(1).message(0).value(expected);
+/// }
+/// ```
+pub struct TypedMapper<I, O, F, C = Json> {
+    handler: F,
+    codec: C,
+    types: PhantomData<fn(I) -> O>,
+}
+
+impl<I, O, F, Fut> TypedMapper<I, O, F>
+where
+    F: Fn(I) -> Fut,
+    Fut: Future<Output = Vec<O>>,
+{
+    /// Create a mapper running the closure over the decoded value of every element, like
+    /// [`Server::from_fn`](crate::map::Server::from_fn) does over the elements.
+    pub fn new(handler: F) -> Self {
+        Self {
+            handler,
+            codec: Json,
+            types: PhantomData,
+        }
+    }
+}
+
+impl<I, O, F, C> TypedMapper<I, O, F, C> {
+    /// Change the [`Codec`] of the payloads, it decodes the values of the elements and encodes
+    /// the results. Default is [`Json`].
+    pub fn with_codec<D>(self, codec: D) -> TypedMapper<I, O, F, D> {
+        TypedMapper {
+            handler: self.handler,
+            codec,
+            types: PhantomData,
+        }
+    }
+
+    /// Get the [`Codec`] of the payloads.
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+}
+
+#[async_trait]
+impl<I, O, F, Fut, C> map::Mapper for TypedMapper<I, O, F, C>
+where
+    I: Send,
+    O: Send,
+    F: Fn(I) -> Fut + Send + Sync,
+    Fut: Future<Output = Vec<O>> + Send,
+    C: Decode<I> + Encode<O>,
+{
+    async fn map<T: map::Datum + Send + Sync + 'static>(&self, input: T) -> Vec<map::Message> {
+        let value = match self.codec.decode(input.value()) {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::warn!(keys = ?input.keys(), error = %e, "skipping an element");
+                return vec![];
+            }
+        };
+        let keys = input.keys();
+        encode_all(&self.codec, keys, (self.handler)(value).await)
+            .into_iter()
+            .map(|value| map::Message {
+                keys: keys.clone(),
+                value,
+                tags: vec![],
+            })
+            .collect()
+    }
+}
+
+/// TypedReducer is a [`Reducer`] over typed payloads, so that the handler deals with its own types
+/// rather than with bytes. The value of every element is decoded into an `I` with the [`Codec`],
+/// [`Json`] by default, before it is passed to the closure, and every `O` the closure returns is
+/// encoded as the value of a [`Message`](reduce::Message) with the keys of the reduce and no tags.
+/// An element which cannot be decoded is skipped and logged as a warning, as is a result which
+/// cannot be encoded.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::codec::TypedReducer;
+/// use numaflow::testing::assert_messages;
+/// use numaflow::testing::reduce::TestDriver;
+/// use tokio::sync::mpsc::Receiver;
+///
+/// #[derive(serde::Deserialize)]
+/// struct Order {
+///     amount: u64,
+/// }
+///
+/// #[derive(serde::Serialize)]
+/// struct Total {
+///     orders: usize,
+///     amount: u64,
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let reducer = TypedReducer::new(|_keys, mut input: Receiver<Order>, _md| async move {
+///         let mut total = Total { orders: 0, amount: 0 };
+///         while let Some(order) = input.recv().await {
+///             total.orders += 1;
+///             total.amount += order.amount;
+///         }
+///         vec![total]
+///     });
+///
+///     let out = TestDriver::new(reducer)
+///         .with_input(["eu"], r#"{"amount": 3}"#)
+///         .with_input(["eu"], "not json")
+///         .with_input(["eu"], r#"{"amount": 4}"#)
+///         .run()
+///         .await;
+///
+///     assert_messages(&out)
+///         .has_len(1)
+///         .message(0)
+///         .keys(["eu"])
+///         .value_str(r#"{"orders":2,"amount":7}"#);
+/// }
+/// ```
+pub struct TypedReducer<I, O, F, C = Json> {
+    handler: F,
+    codec: C,
+    types: PhantomData<fn(I) -> O>,
+}
+
+impl<I, O, F, Fut> TypedReducer<I, O, F>
+where
+    F: Fn(Vec<String>, mpsc::Receiver<I>, IntervalWindow) -> Fut,
+    Fut: Future<Output = Vec<O>>,
+{
+    /// Create a reducer running the closure over the decoded values of the elements of a set of
+    /// keys in a window, like [`Server::from_fn`](crate::reduce::Server::from_fn) does over the
+    /// elements.
+    pub fn new(handler: F) -> Self {
+        Self {
+            handler,
+            codec: Json,
+            types: PhantomData,
+        }
+    }
+}
+
+impl<I, O, F, C> TypedReducer<I, O, F, C> {
+    /// Change the [`Codec`] of the payloads, it decodes the values of the elements and encodes
+    /// the results. Default is [`Json`].
+    pub fn with_codec<D>(self, codec: D) -> TypedReducer<I, O, F, D> {
+        TypedReducer {
+            handler: self.handler,
+            codec,
+            types: PhantomData,
+        }
+    }
+
+    /// Get the [`Codec`] of the payloads.
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+}
+
+#[async_trait]
+impl<I, O, F, Fut, C> Reducer for TypedReducer<I, O, F, C>
+where
+    I: Send + 'static,
+    O: Send,
+    F: Fn(Vec<String>, mpsc::Receiver<I>, IntervalWindow) -> Fut + Send + Sync,
+    Fut: Future<Output = Vec<O>> + Send,
+    C: Decode<I> + Encode<O>,
+{
+    async fn reduce<
+        T: reduce::Datum + Send + Sync + 'static,
+        U: Metadata + Send + Sync + 'static,
+    >(
+        &self,
+        keys: Vec<String>,
+        input: mpsc::Receiver<T>,
+        md: &U,
+    ) -> Vec<reduce::Message> {
+        let window = IntervalWindow::from_metadata(md);
+        let outputs = shared::filter_input(
+            input,
+            |datum| match self.codec.decode(datum.value()) {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    tracing::warn!(keys = ?datum.keys(), error = %e, "skipping an element");
+                    None
+                }
+            },
+            |input| (self.handler)(keys.clone(), input, window),
+        )
+        .await;
+
+        encode_all(&self.codec, &keys, outputs)
+            .into_iter()
+            .map(|value| reduce::Message::new(keys.clone(), value, vec![]))
+            .collect()
+    }
+}
+
+/// Encodes the results of a typed handler, those which cannot be encoded are dropped.
+fn encode_all<O>(codec: &impl Encode<O>, keys: &[String], outputs: Vec<O>) -> Vec<Bytes> {
+    outputs
+        .iter()
+        .filter_map(|output| match codec.encode(output) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                tracing::warn!(?keys, error = %e, "dropping a result");
+                None
+            }
+        })
+        .collect()
+}
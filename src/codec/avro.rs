@@ -0,0 +1,103 @@
+use apache_avro::{from_avro_datum, from_value, to_avro_datum, to_value};
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::{CodecError, Decode, Encode};
+use crate::shared::BoxError;
+
+/// The schema of the [`Avro`] codec, from the [apache-avro](https://docs.rs/apache-avro) crate.
+pub use apache_avro::Schema as AvroSchema;
+
+/// Avro is the [`Codec`](super::Codec) of the payloads holding a datum of the schema in the Avro
+/// binary encoding, i.e., without the header of an object container file nor the fingerprint of
+/// the single object encoding. The values are encoded from and decoded into their serde
+/// representation by apache-avro: a record is a struct, an enum is a unit variant and a union with
+/// `null` is an `Option`.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::codec::{Avro, Decode, Encode};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Order {
+///     id: String,
+///     amount: i64,
+///     coupon: Option<String>,
+/// }
+///
+/// let avro = Avro::parse(
+///     r#"{
+///         "type": "record",
+///         "name": "Order",
+///         "fields": [
+///             {"name": "id", "type": "string"},
+///             {"name": "amount", "type": "long"},
+///             {"name": "coupon", "type": ["null", "string"]}
+///         ]
+///     }"#,
+/// )
+/// .unwrap();
+///
+/// let order = Order {
+///     id: "o-1".to_string(),
+///     amount: 7,
+///     coupon: None,
+/// };
+/// let payload = avro.encode(&order).unwrap();
+/// assert_eq!(payload.as_ref(), [6, b'o', b'-', b'1', 14, 0]);
+///
+/// let decoded: Order = avro.decode(&payload).unwrap();
+/// assert_eq!(decoded, order);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Avro {
+    schema: AvroSchema,
+}
+
+impl Avro {
+    /// Create the codec of the schema.
+    pub fn new(schema: AvroSchema) -> Self {
+        Self { schema }
+    }
+
+    /// Create the codec of the schema in its JSON form, e.g., as stored in a schema registry.
+    pub fn parse(schema: &str) -> Result<Self, BoxError> {
+        AvroSchema::parse_str(schema)
+            .map(Self::new)
+            .map_err(Into::into)
+    }
+
+    /// Get the schema of the payloads.
+    pub fn schema(&self) -> &AvroSchema {
+        &self.schema
+    }
+}
+
+impl<T: DeserializeOwned> Decode<T> for Avro {
+    fn decode(&self, mut payload: &[u8]) -> Result<T, CodecError> {
+        let value = from_avro_datum(&self.schema, &mut payload, None)
+            .map_err(|e| CodecError::Decode(e.into()))?;
+        if !payload.is_empty() {
+            return Err(CodecError::Decode(
+                format!("{} bytes left after the datum", payload.len()).into(),
+            ));
+        }
+        from_value(&value).map_err(|e| CodecError::Decode(e.into()))
+    }
+}
+
+impl<T: Serialize> Encode<T> for Avro {
+    fn encode(&self, value: &T) -> Result<Bytes, CodecError> {
+        // the serde representation is resolved against the schema first, e.g., an `Option` into
+        // the branch of its union
+        let value = to_value(value)
+            .and_then(|value| value.resolve(&self.schema))
+            .map_err(|e| CodecError::Encode(e.into()))?;
+        to_avro_datum(&self.schema, value)
+            .map(Into::into)
+            .map_err(|e| CodecError::Encode(e.into()))
+    }
+}
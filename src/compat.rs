@@ -0,0 +1,51 @@
+use crate::shared::IncompatibleVersion;
+
+/// Oldest numaflow release the SDK works with, written to the server info file.
+pub(crate) const MINIMUM_NUMAFLOW_VERSION: &str = "1.2.0";
+
+/// Environment variable in which numaflow passes its version to the UDF container.
+pub(crate) const NUMAFLOW_VERSION_ENV: &str = "NUMAFLOW_VERSION";
+
+/// Fails if the numaflow release running the UDF, as per [`NUMAFLOW_VERSION_ENV`], is older than
+/// `minimum`. An unset or unparsable version is let through with a warning, the platform may not
+/// tell its version.
+pub(crate) fn check_numaflow_version(minimum: &str) -> Result<(), IncompatibleVersion> {
+    let Ok(numaflow) = std::env::var(NUMAFLOW_VERSION_ENV) else {
+        tracing::warn!(
+            env = NUMAFLOW_VERSION_ENV,
+            "numaflow version is not set, skipping the numaflow version check"
+        );
+        return Ok(());
+    };
+    match (parse_version(&numaflow), parse_version(minimum)) {
+        (Some(running), Some(required)) if running < required => Err(IncompatibleVersion {
+            numaflow,
+            minimum: minimum.to_string(),
+        }),
+        (Some(_), Some(_)) => Ok(()),
+        _ => {
+            tracing::warn!(
+                %numaflow,
+                minimum,
+                "cannot compare the numaflow version, skipping the numaflow version check"
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Parses the major, minor and patch numbers of versions like `v1.2`, `1.2.3` or `v1.3.0-rc1`, a
+/// pre-release is compared like its release.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim();
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let version = version.split(['-', '+']).next()?;
+    let mut numbers = version.split('.').map(str::parse::<u64>);
+    let major = numbers.next()?.ok()?;
+    let minor = numbers.next().unwrap_or(Ok(0)).ok()?;
+    let patch = numbers.next().unwrap_or(Ok(0)).ok()?;
+    if numbers.next().is_some() {
+        return None;
+    }
+    Some((major, minor, patch))
+}
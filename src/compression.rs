@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+use bytes::Bytes;
+use flate2::read::{DeflateDecoder, GzDecoder};
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::Compression;
+use thiserror::Error;
+
+use crate::headers::Headers;
+
+/// The compression of the gRPC messages, see `with_grpc_compression` on the `Server` of the UDF
+/// kinds, e.g., [`map::Server::with_grpc_compression`](crate::map::Server::with_grpc_compression).
+pub use tonic::codec::CompressionEncoding;
+
+/// Header telling how the payload of a message is compressed, like the HTTP `Content-Encoding`.
+/// The payloads without it are not compressed.
+pub const CONTENT_ENCODING: &str = "content-encoding";
+
+/// Encoding is how the payload of a message is compressed, named after the values of the
+/// [`CONTENT_ENCODING`] header.
+///
+/// ```
+/// use numaflow::compression::Encoding;
+///
+/// let compressed = Encoding::Gzip.compress(b"hello hello hello");
+/// assert_eq!(Encoding::Gzip.decompress(&compressed).unwrap(), "hello hello hello");
+/// assert_eq!("gzip".parse::<Encoding>().unwrap(), Encoding::Gzip);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// `gzip`, RFC 1952.
+    Gzip,
+    /// `deflate`, the zlib-less deflate stream of RFC 1951.
+    Deflate,
+}
+
+/// UnsupportedEncoding is the error of parsing an [`Encoding`] the SDK does not implement.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("unsupported content encoding: {0}")]
+pub struct UnsupportedEncoding(pub String);
+
+impl Encoding {
+    /// Returns the value of the [`CONTENT_ENCODING`] header of the encoding.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    /// Returns the encoding of the payload as per the [`CONTENT_ENCODING`] header, None when the
+    /// payload is not compressed, i.e., without the header or with `identity`.
+    pub fn from_headers(headers: &Headers) -> Result<Option<Encoding>, UnsupportedEncoding> {
+        match headers.get(CONTENT_ENCODING) {
+            None => Ok(None),
+            Some(value) if value.trim().eq_ignore_ascii_case("identity") => Ok(None),
+            Some(value) => value.parse().map(Some),
+        }
+    }
+
+    /// Compresses the payload.
+    pub fn compress(&self, payload: &[u8]) -> Bytes {
+        let compressed = match self {
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(payload).and_then(|_| encoder.finish())
+            }
+            Encoding::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(payload).and_then(|_| encoder.finish())
+            }
+        };
+        compressed
+            .expect("compressing into memory does not fail")
+            .into()
+    }
+
+    /// Decompresses the payload, it fails if the payload is not of the encoding.
+    pub fn decompress(&self, payload: &[u8]) -> io::Result<Bytes> {
+        let mut decompressed = Vec::new();
+        match self {
+            Encoding::Gzip => GzDecoder::new(payload).read_to_end(&mut decompressed)?,
+            Encoding::Deflate => DeflateDecoder::new(payload).read_to_end(&mut decompressed)?,
+        };
+        Ok(decompressed.into())
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = UnsupportedEncoding;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            s if s.eq_ignore_ascii_case("gzip") || s.eq_ignore_ascii_case("x-gzip") => {
+                Ok(Encoding::Gzip)
+            }
+            s if s.eq_ignore_ascii_case("deflate") => Ok(Encoding::Deflate),
+            s => Err(UnsupportedEncoding(s.to_string())),
+        }
+    }
+}
+
+/// Decompresses the payload marked with the [`CONTENT_ENCODING`] header, the header is removed
+/// then so that the handler sees a plain payload. A payload which cannot be decompressed is left
+/// as is, along with its header, for the handler to deal with.
+pub(crate) fn decompress_payload(headers: &mut Headers, value: &mut Bytes) {
+    let encoding = match Encoding::from_headers(headers) {
+        Ok(Some(encoding)) => encoding,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!(error = %e, "passing the payload on as is");
+            return;
+        }
+    };
+    match encoding.decompress(value) {
+        Ok(decompressed) => {
+            *value = decompressed;
+            headers.remove(CONTENT_ENCODING);
+        }
+        Err(e) => tracing::warn!(
+            encoding = encoding.name(),
+            error = %e,
+            "failed to decompress the payload, passing it on as is"
+        ),
+    }
+}
+
+/// Compresses the payload with the encoding and marks it with the [`CONTENT_ENCODING`] header, a
+/// payload already marked with an encoding is left as is.
+pub(crate) fn compress_payload(
+    encoding: Encoding,
+    headers: &mut HashMap<String, String>,
+    value: &mut Bytes,
+) {
+    // header names are case-insensitive
+    if headers
+        .keys()
+        .any(|key| key.eq_ignore_ascii_case(CONTENT_ENCODING))
+    {
+        return;
+    }
+    *value = encoding.compress(value);
+    headers.insert(CONTENT_ENCODING.to_string(), encoding.name().to_string());
+}
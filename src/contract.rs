@@ -0,0 +1,794 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::{Map, Value};
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tonic::async_trait;
+
+use crate::{batchmap, map, metrics, reduce, shared, sink, sourcetransform};
+
+// the keywords which constrain the values but are not implemented, a schema using them is rejected
+// rather than passing everything they would have failed
+const UNSUPPORTED_KEYWORDS: &[&str] = &[
+    "$ref",
+    "$dynamicRef",
+    "pattern",
+    "patternProperties",
+    "propertyNames",
+    "prefixItems",
+    "contains",
+    "uniqueItems",
+    "dependencies",
+    "dependentRequired",
+    "dependentSchemas",
+    "if",
+    "then",
+    "else",
+    "unevaluatedItems",
+    "unevaluatedProperties",
+];
+
+/// SchemaError is the error of parsing a [`Schema`].
+#[derive(Error, Debug)]
+pub enum SchemaError {
+    /// The schema is not JSON.
+    #[error("schema is not valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    /// A keyword has a value of the wrong shape, e.g., a `required` which is not a list of names.
+    #[error("invalid schema at {path}: {reason}")]
+    Invalid { path: String, reason: String },
+    /// The schema uses a keyword the validator does not implement.
+    #[error("unsupported keyword {keyword} at {path}")]
+    Unsupported { path: String, keyword: String },
+}
+
+/// Violation is a value of a payload breaking its contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Where the value is in the payload, e.g., `$.items[2].price`, `$` being the payload.
+    pub path: String,
+    /// What is wrong with the value.
+    pub message: String,
+}
+
+impl Violation {
+    /// Create a violation of the value at the path.
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Validator checks a payload against a contract, returning what breaks it. It is implemented by
+/// the [`Schema`] and by the closures taking a `&[u8]`, e.g., one decoding a protobuf payload
+/// with prost.
+pub trait Validator: Send + Sync {
+    /// Returns the violations of the payload, none if it holds to the contract.
+    fn validate(&self, payload: &[u8]) -> Vec<Violation>;
+}
+
+impl<F> Validator for F
+where
+    F: Fn(&[u8]) -> Vec<Violation> + Send + Sync,
+{
+    fn validate(&self, payload: &[u8]) -> Vec<Violation> {
+        self(payload)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Null,
+    Boolean,
+    Object,
+    Array,
+    Number,
+    Integer,
+    String,
+}
+
+impl Type {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "null" => Type::Null,
+            "boolean" => Type::Boolean,
+            "object" => Type::Object,
+            "array" => Type::Array,
+            "number" => Type::Number,
+            "integer" => Type::Integer,
+            "string" => Type::String,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Type::Null => "null",
+            Type::Boolean => "boolean",
+            Type::Object => "object",
+            Type::Array => "array",
+            Type::Number => "number",
+            Type::Integer => "integer",
+            Type::String => "string",
+        }
+    }
+
+    fn matches(self, value: &Value) -> bool {
+        match (self, value) {
+            (Type::Null, Value::Null)
+            | (Type::Boolean, Value::Bool(_))
+            | (Type::Object, Value::Object(_))
+            | (Type::Array, Value::Array(_))
+            | (Type::Number, Value::Number(_))
+            | (Type::String, Value::String(_)) => true,
+            (Type::Integer, Value::Number(n)) => {
+                n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0)
+            }
+            _ => false,
+        }
+    }
+}
+
+// the kind of a value, as named in the violations
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+    }
+}
+
+// a parsed schema, the keywords missing from the schema are None or empty
+#[derive(Debug, Clone, Default)]
+struct Node {
+    // a `true` or `false` schema
+    always: Option<bool>,
+    types: Option<Vec<Type>>,
+    enumeration: Option<Vec<Value>>,
+    constant: Option<Value>,
+    properties: Vec<(String, Node)>,
+    required: Vec<String>,
+    additional_properties: Option<Box<Node>>,
+    min_properties: Option<usize>,
+    max_properties: Option<usize>,
+    items: Option<Box<Node>>,
+    min_items: Option<usize>,
+    max_items: Option<usize>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    exclusive_minimum: Option<f64>,
+    exclusive_maximum: Option<f64>,
+    multiple_of: Option<f64>,
+    all_of: Vec<Node>,
+    any_of: Vec<Node>,
+    one_of: Vec<Node>,
+    not: Option<Box<Node>>,
+}
+
+fn invalid(path: &str, reason: impl Into<String>) -> SchemaError {
+    SchemaError::Invalid {
+        path: path.to_string(),
+        reason: reason.into(),
+    }
+}
+
+fn parse_count(path: &str, keyword: &str, value: &Value) -> Result<usize, SchemaError> {
+    value
+        .as_u64()
+        .map(|n| n as usize)
+        .ok_or_else(|| invalid(path, format!("{} must be a non-negative integer", keyword)))
+}
+
+fn parse_number(path: &str, keyword: &str, value: &Value) -> Result<f64, SchemaError> {
+    value
+        .as_f64()
+        .ok_or_else(|| invalid(path, format!("{} must be a number", keyword)))
+}
+
+fn parse_list(path: &str, keyword: &str, value: &Value) -> Result<Vec<Node>, SchemaError> {
+    match value.as_array() {
+        Some(schemas) if !schemas.is_empty() => schemas
+            .iter()
+            .enumerate()
+            .map(|(i, schema)| Node::parse(&format!("{}/{}/{}", path, keyword, i), schema))
+            .collect(),
+        _ => Err(invalid(
+            path,
+            format!("{} must be a non-empty list of schemas", keyword),
+        )),
+    }
+}
+
+impl Node {
+    // the path is the JSON pointer of the schema in the document, for the errors
+    fn parse(path: &str, schema: &Value) -> Result<Node, SchemaError> {
+        let keywords: &Map<String, Value> = match schema {
+            Value::Bool(always) => {
+                return Ok(Node {
+                    always: Some(*always),
+                    ..Node::default()
+                })
+            }
+            Value::Object(keywords) => keywords,
+            _ => return Err(invalid(path, "a schema must be an object or a boolean")),
+        };
+
+        let mut node = Node::default();
+        for (keyword, value) in keywords {
+            let child = |name: &str| format!("{}/{}", path, name);
+            match keyword.as_str() {
+                "type" => {
+                    let names = match value {
+                        Value::Array(names) => names.iter().collect(),
+                        name => vec![name],
+                    };
+                    let types = names
+                        .iter()
+                        .map(|name| name.as_str().and_then(Type::parse))
+                        .collect::<Option<Vec<_>>>()
+                        .filter(|types| !types.is_empty())
+                        .ok_or_else(|| invalid(path, format!("unknown type {}", value)))?;
+                    node.types = Some(types);
+                }
+                "enum" => {
+                    let values = value
+                        .as_array()
+                        .ok_or_else(|| invalid(path, "enum must be a list"))?;
+                    node.enumeration = Some(values.clone());
+                }
+                "const" => node.constant = Some(value.clone()),
+                "properties" => {
+                    let properties = value
+                        .as_object()
+                        .ok_or_else(|| invalid(path, "properties must be an object"))?;
+                    for (name, schema) in properties {
+                        let schema = Node::parse(&format!("{}/{}", child(keyword), name), schema)?;
+                        node.properties.push((name.clone(), schema));
+                    }
+                }
+                "required" => {
+                    node.required = value
+                        .as_array()
+                        .and_then(|names| {
+                            names
+                                .iter()
+                                .map(|name| name.as_str().map(str::to_string))
+                                .collect()
+                        })
+                        .ok_or_else(|| invalid(path, "required must be a list of names"))?;
+                }
+                "additionalProperties" => {
+                    node.additional_properties =
+                        Some(Box::new(Node::parse(&child(keyword), value)?));
+                }
+                "items" => {
+                    if value.is_array() {
+                        return Err(SchemaError::Unsupported {
+                            path: path.to_string(),
+                            keyword: "items as a list".to_string(),
+                        });
+                    }
+                    node.items = Some(Box::new(Node::parse(&child(keyword), value)?));
+                }
+                "minProperties" => node.min_properties = Some(parse_count(path, keyword, value)?),
+                "maxProperties" => node.max_properties = Some(parse_count(path, keyword, value)?),
+                "minItems" => node.min_items = Some(parse_count(path, keyword, value)?),
+                "maxItems" => node.max_items = Some(parse_count(path, keyword, value)?),
+                "minLength" => node.min_length = Some(parse_count(path, keyword, value)?),
+                "maxLength" => node.max_length = Some(parse_count(path, keyword, value)?),
+                "minimum" => node.minimum = Some(parse_number(path, keyword, value)?),
+                "maximum" => node.maximum = Some(parse_number(path, keyword, value)?),
+                "exclusiveMinimum" => {
+                    node.exclusive_minimum = Some(parse_number(path, keyword, value)?)
+                }
+                "exclusiveMaximum" => {
+                    node.exclusive_maximum = Some(parse_number(path, keyword, value)?)
+                }
+                "multipleOf" => match parse_number(path, keyword, value)? {
+                    m if m > 0.0 => node.multiple_of = Some(m),
+                    _ => return Err(invalid(path, "multipleOf must be greater than 0")),
+                },
+                "allOf" => node.all_of = parse_list(path, keyword, value)?,
+                "anyOf" => node.any_of = parse_list(path, keyword, value)?,
+                "oneOf" => node.one_of = parse_list(path, keyword, value)?,
+                "not" => node.not = Some(Box::new(Node::parse(&child(keyword), value)?)),
+                keyword if UNSUPPORTED_KEYWORDS.contains(&keyword) => {
+                    return Err(SchemaError::Unsupported {
+                        path: path.to_string(),
+                        keyword: keyword.to_string(),
+                    })
+                }
+                // annotations, e.g., title, description or format, do not constrain the values
+                _ => {}
+            }
+        }
+        Ok(node)
+    }
+
+    // whether the value holds to the schema, without building the violations
+    fn accepts(&self, value: &Value) -> bool {
+        let mut violations = vec![];
+        self.check(value, "$", &mut violations);
+        violations.is_empty()
+    }
+
+    fn check(&self, value: &Value, path: &str, violations: &mut Vec<Violation>) {
+        let mut violate = |message: String| violations.push(Violation::new(path, message));
+
+        match self.always {
+            Some(true) => return,
+            Some(false) => return violate("no value is allowed".to_string()),
+            None => {}
+        }
+        if let Some(types) = &self.types {
+            if !types.iter().any(|t| t.matches(value)) {
+                let names: Vec<_> = types.iter().map(|t| t.name()).collect();
+                // the value is not what the other keywords expect either
+                return violate(format!(
+                    "expected {}, found {}",
+                    names.join(" or "),
+                    kind(value)
+                ));
+            }
+        }
+        if let Some(values) = &self.enumeration {
+            if !values.contains(value) {
+                violate(format!(
+                    "{} is not one of {}",
+                    value,
+                    Value::from(values.clone())
+                ));
+            }
+        }
+        if let Some(constant) = &self.constant {
+            if constant != value {
+                violate(format!("expected {}, found {}", constant, value));
+            }
+        }
+
+        match value {
+            Value::Object(fields) => {
+                for name in &self.required {
+                    if !fields.contains_key(name) {
+                        violate(format!("missing required property {}", name));
+                    }
+                }
+                if let Some(min) = self.min_properties.filter(|min| fields.len() < *min) {
+                    violate(format!("expected at least {} properties", min));
+                }
+                if let Some(max) = self.max_properties.filter(|max| fields.len() > *max) {
+                    violate(format!("expected at most {} properties", max));
+                }
+                for (name, field) in fields {
+                    let field_path = format!("{}.{}", path, name);
+                    match self
+                        .properties
+                        .iter()
+                        .find(|(property, _)| property == name)
+                    {
+                        Some((_, schema)) => schema.check(field, &field_path, violations),
+                        None => {
+                            if let Some(schema) = &self.additional_properties {
+                                if schema.always == Some(false) {
+                                    violations.push(Violation::new(
+                                        field_path,
+                                        "property is not allowed",
+                                    ));
+                                } else {
+                                    schema.check(field, &field_path, violations);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            Value::Array(items) => {
+                if let Some(min) = self.min_items.filter(|min| items.len() < *min) {
+                    violate(format!("expected at least {} items", min));
+                }
+                if let Some(max) = self.max_items.filter(|max| items.len() > *max) {
+                    violate(format!("expected at most {} items", max));
+                }
+                if let Some(schema) = &self.items {
+                    for (i, item) in items.iter().enumerate() {
+                        schema.check(item, &format!("{}[{}]", path, i), violations);
+                    }
+                }
+            }
+            Value::String(s) => {
+                let length = s.chars().count();
+                if let Some(min) = self.min_length.filter(|min| length < *min) {
+                    violate(format!("expected at least {} characters", min));
+                }
+                if let Some(max) = self.max_length.filter(|max| length > *max) {
+                    violate(format!("expected at most {} characters", max));
+                }
+            }
+            Value::Number(n) => {
+                let n = n.as_f64().unwrap_or(f64::NAN);
+                if let Some(min) = self.minimum.filter(|min| n < *min) {
+                    violate(format!("{} is less than {}", n, min));
+                }
+                if let Some(max) = self.maximum.filter(|max| n > *max) {
+                    violate(format!("{} is greater than {}", n, max));
+                }
+                if let Some(min) = self.exclusive_minimum.filter(|min| n <= *min) {
+                    violate(format!("{} is not greater than {}", n, min));
+                }
+                if let Some(max) = self.exclusive_maximum.filter(|max| n >= *max) {
+                    violate(format!("{} is not less than {}", n, max));
+                }
+                if let Some(m) = self.multiple_of.filter(|m| (n / m).fract() != 0.0) {
+                    violate(format!("{} is not a multiple of {}", n, m));
+                }
+            }
+            _ => {}
+        }
+
+        for schema in &self.all_of {
+            schema.check(value, path, violations);
+        }
+        if !self.any_of.is_empty() && !self.any_of.iter().any(|schema| schema.accepts(value)) {
+            violations.push(Violation::new(path, "matches none of the anyOf schemas"));
+        }
+        if !self.one_of.is_empty() {
+            let matched = self
+                .one_of
+                .iter()
+                .filter(|schema| schema.accepts(value))
+                .count();
+            if matched != 1 {
+                violations.push(Violation::new(
+                    path,
+                    format!("matches {} of the oneOf schemas instead of one", matched),
+                ));
+            }
+        }
+        if self
+            .not
+            .as_ref()
+            .is_some_and(|schema| schema.accepts(value))
+        {
+            violations.push(Violation::new(path, "matches the not schema"));
+        }
+    }
+}
+
+/// Schema is a [JSON Schema](https://json-schema.org) the JSON payloads are validated against.
+///
+/// It is the part of the specification constraining the values, that is the `type`, `enum`,
+/// `const`, `properties`, `required`, `additionalProperties`, `minProperties`, `maxProperties`,
+/// `items`, `minItems`, `maxItems`, `minLength`, `maxLength`, `minimum`, `maximum`,
+/// `exclusiveMinimum`, `exclusiveMaximum`, `multipleOf`, `allOf`, `anyOf`, `oneOf` and `not`
+/// keywords. The annotations, e.g., `title` or `format`, are ignored and a schema using the other
+/// keywords, e.g., `$ref` or `pattern`, is rejected.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::contract::{Schema, Validator};
+///
+/// let schema = Schema::parse(
+///     r#"{
+///         "type": "object",
+///         "required": ["id", "items"],
+///         "properties": {
+///             "id": {"type": "integer", "minimum": 1},
+///             "items": {"type": "array", "items": {"type": "string"}}
+///         }
+///     }"#,
+/// )
+/// .unwrap();
+///
+/// assert!(schema.validate(br#"{"id": 7, "items": ["tea"]}"#).is_empty());
+///
+/// let violations = schema.validate(br#"{"id": 0, "items": ["tea", 2]}"#);
+/// let violations: Vec<String> = violations.iter().map(ToString::to_string).collect();
+/// assert_eq!(
+///     violations,
+///     ["$.id: 0 is less than 1", "$.items[1]: expected string, found number"]
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Schema {
+    root: Node,
+}
+
+impl Schema {
+    /// Create a schema out of its JSON document.
+    pub fn new(schema: &Value) -> Result<Self, SchemaError> {
+        Ok(Self {
+            root: Node::parse("#", schema)?,
+        })
+    }
+
+    /// Create a schema out of its JSON text.
+    pub fn parse(schema: &str) -> Result<Self, SchemaError> {
+        Self::new(&serde_json::from_str(schema)?)
+    }
+
+    /// Returns the violations of the value, none if it holds to the schema.
+    pub fn validate_value(&self, value: &Value) -> Vec<Violation> {
+        let mut violations = vec![];
+        self.root.check(value, "$", &mut violations);
+        violations
+    }
+}
+
+impl Validator for Schema {
+    fn validate(&self, payload: &[u8]) -> Vec<Violation> {
+        match crate::json::from_slice::<Value>(payload) {
+            Ok(value) => self.validate_value(&value),
+            Err(e) => vec![Violation::new("$", format!("payload is not JSON: {}", e))],
+        }
+    }
+}
+
+// picks an exact share of the calls, spread evenly rather than at random
+struct Sampler {
+    fraction: f64,
+    calls: AtomicU64,
+}
+
+impl Sampler {
+    fn new(fraction: f64) -> Self {
+        Self {
+            fraction: fraction.clamp(0.0, 1.0),
+            calls: AtomicU64::new(0),
+        }
+    }
+
+    fn is_sampled(&self) -> bool {
+        let n = self.calls.fetch_add(1, Ordering::Relaxed) as f64;
+        ((n + 1.0) * self.fraction).floor() > (n * self.fraction).floor()
+    }
+}
+
+/// Contract validates a sampled fraction of the inputs and of the outputs of a handler against
+/// the [schemas](Validator) agreed on with the teams upstream and downstream, so that a breaking
+/// change of the payloads shows up as soon as it is deployed. A payload breaking the contract is
+/// logged as a warning and counted by the `contract_violations_total` metric, labelled by the
+/// handler and by the side, `input` or `output`. It is still passed on, the contract is a guard,
+/// not a filter.
+///
+/// An invocation of a map or of a source transformer is sampled as a whole, its input and its
+/// outputs. The elements of a batch, of a reduce window and of a sink are sampled one by one, the
+/// results of a batch element or of a reduce window as a whole.
+///
+/// # Example
+///
+/// ```no_run
+/// use numaflow::contract::{Contract, Schema};
+/// use numaflow::map::{self, Datum, Message};
+///
+/// struct Cat;
+///
+/// #[tonic::async_trait]
+/// impl map::Mapper for Cat {
+///     async fn map<T: Datum + Send + Sync + 'static>(&self, input: T) -> Vec<Message> {
+///         vec![Message {
+///             keys: input.keys().clone(),
+///             value: input.value().clone(),
+///             tags: vec![],
+///         }]
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+///     let order = Schema::parse(r#"{"type": "object", "required": ["id"]}"#)?;
+///     let contract = Contract::new(Cat)
+///         .with_input_schema(order.clone())
+///         .with_output_schema(order)
+///         .with_sample_rate(0.01);
+///     map::Server::new(contract).start().await
+/// }
+/// ```
+pub struct Contract<H> {
+    handler: H,
+    input: Option<Box<dyn Validator>>,
+    output: Option<Box<dyn Validator>>,
+    sampler: Sampler,
+}
+
+impl<H> Contract<H> {
+    /// Create a contract of the handler, validating every payload until a sample rate is set and
+    /// nothing until a schema is set.
+    pub fn new(handler: H) -> Self {
+        Self {
+            handler,
+            input: None,
+            output: None,
+            sampler: Sampler::new(1.0),
+        }
+    }
+
+    /// Set the contract of the inputs of the handler.
+    pub fn with_input_schema(mut self, schema: impl Validator + 'static) -> Self {
+        self.input = Some(Box::new(schema));
+        self
+    }
+
+    /// Set the contract of the outputs of the handler.
+    pub fn with_output_schema(mut self, schema: impl Validator + 'static) -> Self {
+        self.output = Some(Box::new(schema));
+        self
+    }
+
+    /// Change the fraction of the payloads validated, from 0.0 for none to 1.0 for all of them.
+    /// Default value is 1.0.
+    pub fn with_sample_rate(mut self, fraction: f64) -> Self {
+        self.sampler = Sampler::new(fraction);
+        self
+    }
+
+    /// Returns the fraction of the payloads validated.
+    pub fn sample_rate(&self) -> f64 {
+        self.sampler.fraction
+    }
+
+    /// Returns the wrapped handler.
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    fn check_input(&self, handler: &str, payload: &[u8]) {
+        if let Some(schema) = &self.input {
+            report(handler, "input", schema.validate(payload));
+        }
+    }
+
+    fn check_outputs<'a>(&self, handler: &str, payloads: impl IntoIterator<Item = &'a [u8]>) {
+        if let Some(schema) = &self.output {
+            for payload in payloads {
+                report(handler, "output", schema.validate(payload));
+            }
+        }
+    }
+}
+
+fn report(handler: &str, side: &str, violations: Vec<Violation>) {
+    let Some(first) = violations.first() else {
+        return;
+    };
+    metrics::contract_violation(handler, side);
+    match violations.len() {
+        1 => tracing::warn!(handler, side, violation = %first, "handler breaks its contract"),
+        n => tracing::warn!(
+            handler,
+            side,
+            violation = %first,
+            more = n - 1,
+            "handler breaks its contract"
+        ),
+    }
+}
+
+#[async_trait]
+impl<H> map::Mapper for Contract<H>
+where
+    H: map::Mapper + Send + Sync,
+{
+    async fn map<T: map::Datum + Send + Sync + 'static>(&self, input: T) -> Vec<map::Message> {
+        if !self.sampler.is_sampled() {
+            return self.handler.map(input).await;
+        }
+        self.check_input("map", input.value());
+        let messages = self.handler.map(input).await;
+        self.check_outputs("map", messages.iter().map(|m| m.value.as_ref()));
+        messages
+    }
+}
+
+#[async_trait]
+impl<H> sourcetransform::SourceTransformer for Contract<H>
+where
+    H: sourcetransform::SourceTransformer + Send + Sync,
+{
+    async fn transform<T: sourcetransform::Datum + Send + Sync + 'static>(
+        &self,
+        input: T,
+    ) -> Vec<sourcetransform::Message> {
+        if !self.sampler.is_sampled() {
+            return self.handler.transform(input).await;
+        }
+        self.check_input("sourcetransform", input.value());
+        let messages = self.handler.transform(input).await;
+        self.check_outputs("sourcetransform", messages.iter().map(|m| m.value.as_ref()));
+        messages
+    }
+}
+
+#[async_trait]
+impl<H> batchmap::BatchMapper for Contract<H>
+where
+    H: batchmap::BatchMapper + Send + Sync,
+{
+    async fn batch<T: batchmap::Datum + Send + Sync + 'static>(
+        &self,
+        input: mpsc::Receiver<T>,
+    ) -> Vec<batchmap::BatchResponse> {
+        let inspect = |datum: T| {
+            if self.sampler.is_sampled() {
+                self.check_input("batchmap", datum.value());
+            }
+            datum
+        };
+        let responses = shared::forward_input(input, inspect, |rx| self.handler.batch(rx)).await;
+        // a failure or a fallback has no results to check
+        let results = responses
+            .iter()
+            .filter(|response| response.status == batchmap::ResponseStatus::Success);
+        for response in results {
+            if self.sampler.is_sampled() {
+                self.check_outputs(
+                    "batchmap",
+                    response.messages.iter().map(|m| m.value.as_ref()),
+                );
+            }
+        }
+        responses
+    }
+}
+
+#[async_trait]
+impl<H> reduce::Reducer for Contract<H>
+where
+    H: reduce::Reducer + Send + Sync,
+{
+    async fn reduce<
+        T: reduce::Datum + Send + Sync + 'static,
+        U: reduce::Metadata + Send + Sync + 'static,
+    >(
+        &self,
+        keys: Vec<String>,
+        input: mpsc::Receiver<T>,
+        md: &U,
+    ) -> Vec<reduce::Message> {
+        let inspect = |datum: T| {
+            if self.sampler.is_sampled() {
+                self.check_input("reduce", datum.value());
+            }
+            datum
+        };
+        let messages =
+            shared::forward_input(input, inspect, |rx| self.handler.reduce(keys, rx, md)).await;
+        if self.sampler.is_sampled() {
+            self.check_outputs("reduce", messages.iter().map(|m| m.value.as_ref()));
+        }
+        messages
+    }
+}
+
+#[async_trait]
+impl<H> sink::Sinker for Contract<H>
+where
+    H: sink::Sinker + Send + Sync,
+{
+    async fn sink<T: sink::Datum + Send + Sync + 'static>(
+        &self,
+        input: mpsc::Receiver<T>,
+    ) -> Vec<sink::Response> {
+        let inspect = |datum: T| {
+            if self.sampler.is_sampled() {
+                self.check_input("sink", datum.value());
+            }
+            datum
+        };
+        shared::forward_input(input, inspect, |rx| self.handler.sink(rx)).await
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+use crate::shared::{self, BoxError};
+use crate::{cputime, reduce, tasks};
+
+/// Knob is a runtime setting, e.g., a rate limit, a concurrency or a sampling rate, which can be
+/// changed on a live server through the control socket without a restart. The handler keeps a
+/// clone of the knob and reads the current value with [`Knob::get`].
+///
+/// The control socket is enabled with `with_control_socket` and the knobs are registered with
+/// `with_knob` on the `Server` of every UDF kind. It accepts one command per line and answers each
+/// with a line starting with `ok` or `err`:
+///
+/// - `list` returns all the knobs with their values and bounds.
+/// - `get <knob>` returns the value of the knob.
+/// - `set <knob> <value>` changes the value of the knob, values out of the bounds are rejected.
+/// - `top-keys` returns the reduce keys which took the most CPU time in the last windows, one
+///   line per window, when the reduce server reports them, see `with_top_keys`.
+/// - `flush` closes the windows of the reduce streams of the server and sends their results
+///   tagged as force-flushed, see [`FlushHandle`](crate::reduce::FlushHandle). Only the reduce
+///   server accepts it.
+/// - `tasks` returns the number of internal tasks of the SDK running, followed by one line per
+///   task telling what it does and for how long it has been running or idle. It is only
+///   available with the `task-dump` feature.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// use numaflow::control::Knob;
+/// use numaflow::map::{self, Datum, Message};
+///
+/// struct Sampler {
+///     seen: AtomicU64,
+///     // keep one in every `rate` elements
+///     rate: Knob,
+/// }
+///
+/// #[tonic::async_trait]
+/// impl map::Mapper for Sampler {
+///     async fn map<T>(&self, input: T) -> Vec<Message>
+///     where
+///         T: Datum + Send + Sync + 'static,
+///     {
+///         if self.seen.fetch_add(1, Ordering::Relaxed) % self.rate.get() != 0 {
+///             return vec![];
+///         }
+///         vec![Message {
+///             keys: input.keys().clone(),
+///             value: input.value().clone(),
+///             tags: vec![],
+///         }]
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+///     let rate = Knob::new("sampling_rate", 10, 1, 1000);
+///
+///     let sampler = Sampler {
+///         seen: AtomicU64::new(0),
+///         rate: rate.clone(),
+///     };
+///
+///     map::Server::new(sampler)
+///         .with_control_socket("/var/run/numaflow/map-control.sock")
+///         .with_knob(rate)
+///         .start()
+///         .await
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Knob {
+    name: String,
+    min: u64,
+    max: u64,
+    value: Arc<AtomicU64>,
+}
+
+/// KnobOutOfRange is returned when a [`Knob`] is set to a value outside of its bounds.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{value} is out of the range [{min}, {max}] of {name}")]
+pub struct KnobOutOfRange {
+    pub name: String,
+    pub value: u64,
+    pub min: u64,
+    pub max: u64,
+}
+
+impl Knob {
+    /// Creates a knob with the initial value, it can only be set within `min` and `max`
+    /// (inclusive). The initial value is clamped to the bounds.
+    pub fn new(name: impl Into<String>, initial: u64, min: u64, max: u64) -> Self {
+        let max = max.max(min);
+        Self {
+            name: name.into(),
+            min,
+            max,
+            value: Arc::new(AtomicU64::new(initial.clamp(min, max))),
+        }
+    }
+
+    /// Returns the name of the knob.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the bounds `(min, max)` of the knob.
+    pub fn bounds(&self) -> (u64, u64) {
+        (self.min, self.max)
+    }
+
+    /// Returns the current value of the knob.
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    /// Changes the value of the knob, returning the previous value.
+    pub fn set(&self, value: u64) -> Result<u64, KnobOutOfRange> {
+        if value < self.min || value > self.max {
+            return Err(KnobOutOfRange {
+                name: self.name.clone(),
+                value,
+                min: self.min,
+                max: self.max,
+            });
+        }
+        Ok(self.value.swap(value, Ordering::Relaxed))
+    }
+}
+
+/// Binds the control socket and serves the knobs in the background for the lifetime of the
+/// process.
+pub(crate) fn serve(
+    path: &Path,
+    knobs: Vec<Knob>,
+    flush: Option<reduce::FlushHandle>,
+) -> Result<(), BoxError> {
+    let listener = shared::bind_unix_socket(path, false)?;
+
+    let knobs: Arc<HashMap<String, Knob>> = Arc::new(
+        knobs
+            .into_iter()
+            .map(|knob| (knob.name.clone(), knob))
+            .collect(),
+    );
+    let flush = Arc::new(flush);
+
+    tasks::spawn("control:listener", async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let knobs = Arc::clone(&knobs);
+                    let flush = Arc::clone(&flush);
+                    tasks::spawn("control:connection", async move {
+                        // the connection is simply dropped on an I/O error, e.g., client hung up
+                        let _ = handle_connection(stream, &knobs, flush.as_ref().as_ref()).await;
+                    });
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "control socket stopped accepting connections");
+                    return;
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+async fn handle_connection(
+    stream: UnixStream,
+    knobs: &HashMap<String, Knob>,
+    flush: Option<&reduce::FlushHandle>,
+) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let mut reply = execute(&line, knobs, flush);
+        reply.push('\n');
+        writer.write_all(reply.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+fn execute(
+    command: &str,
+    knobs: &HashMap<String, Knob>,
+    flush: Option<&reduce::FlushHandle>,
+) -> String {
+    let args: Vec<&str> = command.split_whitespace().collect();
+    match args.as_slice() {
+        ["list"] => {
+            let mut names: Vec<&String> = knobs.keys().collect();
+            names.sort();
+            let listing: Vec<String> = names
+                .into_iter()
+                .map(|name| {
+                    let knob = &knobs[name];
+                    format!("{}={} [{}, {}]", name, knob.get(), knob.min, knob.max)
+                })
+                .collect();
+            format!("ok {}", listing.join(" "))
+        }
+        ["get", name] => match knobs.get(*name) {
+            Some(knob) => format!("ok {}={}", name, knob.get()),
+            None => format!("err unknown knob {}", name),
+        },
+        ["set", name, value] => {
+            let Some(knob) = knobs.get(*name) else {
+                return format!("err unknown knob {}", name);
+            };
+            let Ok(value) = value.parse::<u64>() else {
+                return format!("err {} is not a valid value", value);
+            };
+            match knob.set(value) {
+                Ok(previous) => {
+                    tracing::info!(name, %previous, %value, "control: setting changed");
+                    format!("ok {}={}", name, value)
+                }
+                Err(e) => format!("err {}", e),
+            }
+        }
+        ["top-keys"] => {
+            let windows = cputime::dump();
+            let mut reply = format!("ok {} windows", windows.len());
+            for window in windows {
+                reply.push('\n');
+                reply.push_str(&window);
+            }
+            reply
+        }
+        ["flush"] => {
+            let Some(flush) = flush else {
+                return "err flush is only served by the reduce server".to_string();
+            };
+            let streams = flush.flush();
+            tracing::info!(
+                streams,
+                "control: flushed the windows of the reduce streams"
+            );
+            format!("ok {} streams flushed", streams)
+        }
+        #[cfg(feature = "task-dump")]
+        ["tasks"] => {
+            let tasks = tasks::dump();
+            let mut reply = format!("ok {} tasks", tasks.len());
+            for task in tasks {
+                reply.push('\n');
+                reply.push_str(&task);
+            }
+            reply
+        }
+        _ => {
+            "err unknown command, expected `list`, `get <knob>` or `set <knob> <value>`".to_string()
+        }
+    }
+}
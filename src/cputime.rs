@@ -0,0 +1,107 @@
+// The CPU time of a reduce handle is approximated by the time spent polling its future, i.e., the
+// time it held an executor thread. It counts the blocking work done on the thread and leaves out
+// the time spent waiting on the input or on I/O, which is close enough to tell the few keys
+// dominating a window apart from the others without the unstable task metrics of tokio.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::metrics;
+
+// number of window reports kept for the `top-keys` command of the control socket
+const KEPT_REPORTS: usize = 16;
+
+// the busiest keys are pointed out when they took at least this share of the window
+const DOMINANT_SHARE: f64 = 0.5;
+
+// windows with fewer keys or less time than these are not worth pointing out
+const DOMINANT_MIN_KEYS: usize = 4;
+const DOMINANT_MIN_TIME: Duration = Duration::from_millis(100);
+
+/// Runs the future, adding the time spent polling it to `busy` in nanoseconds. The time is added
+/// poll by poll so that it is known even if the future is dropped before it completes.
+pub(crate) async fn measure<F: Future>(future: F, busy: Option<&AtomicU64>) -> F::Output {
+    let Some(busy) = busy else {
+        return future.await;
+    };
+
+    let mut future = std::pin::pin!(future);
+    std::future::poll_fn(|cx| {
+        let start = Instant::now();
+        let poll = future.as_mut().poll(cx);
+        let nanos = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        busy.fetch_add(nanos, Ordering::Relaxed);
+        poll
+    })
+    .await
+}
+
+fn reports() -> MutexGuard<'static, VecDeque<String>> {
+    static REPORTS: OnceLock<Mutex<VecDeque<String>>> = OnceLock::new();
+    REPORTS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Reports the `top` keys of the window which took the most CPU time, given the time of every
+/// keys of the window: they are exposed in the metrics and through the control socket, and a
+/// window dominated by a few keys is logged with a hint on how to spread the work.
+pub(crate) fn report(window: &str, mut costs: Vec<(String, Duration)>, top: usize) {
+    if costs.is_empty() || top == 0 {
+        return;
+    }
+    let keys = costs.len();
+    let total: Duration = costs.iter().map(|(_, time)| *time).sum();
+    costs.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+    costs.truncate(top);
+
+    metrics::reduce_top_keys(&costs);
+
+    let share = |time: Duration| {
+        if total.is_zero() {
+            0.0
+        } else {
+            time.as_secs_f64() / total.as_secs_f64()
+        }
+    };
+    let listing: Vec<String> = costs
+        .iter()
+        .map(|(keys, time)| format!("{}={:?} ({:.0}%)", keys, time, share(*time) * 100.0))
+        .collect();
+    let line = format!(
+        "window {}: {} keys, {:?} in total, top {}",
+        window,
+        keys,
+        total,
+        listing.join(" ")
+    );
+    {
+        let mut reports = reports();
+        if reports.len() == KEPT_REPORTS {
+            reports.pop_front();
+        }
+        reports.push_back(line);
+    }
+
+    let (busiest, time) = &costs[0];
+    if keys >= DOMINANT_MIN_KEYS && total >= DOMINANT_MIN_TIME && share(*time) >= DOMINANT_SHARE {
+        tracing::warn!(
+            keys = %busiest,
+            share = %format_args!("{:.0}%", share(*time) * 100.0),
+            %window,
+            window_keys = keys,
+            "reduce keys took most of the CPU time of the window, consider re-keying the \
+             elements, e.g., with a salt, or pre-aggregating them upstream with a combiner so \
+             that a few keys do not dominate the window"
+        );
+    }
+}
+
+/// Returns one line per window reported last, the oldest first.
+pub(crate) fn dump() -> Vec<String> {
+    reports().iter().cloned().collect()
+}
@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use prost::Message;
+use thiserror::Error;
+use tonic::{Code, Status};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// StatusMapper converts the [`Error`](enum@Error) raised while serving a request into the [`Status`] returned
+/// to numaflow. It can be replaced via `with_status_mapper` on the `Server` of every UDF kind.
+pub type StatusMapper = fn(Error) -> Status;
+
+/// Domain of the `google.rpc.ErrorInfo` attached to the returned [`Status`].
+const ERROR_DOMAIN: &str = "numaflow.numaproj.io";
+
+/// Converts the error into the status returned to numaflow with the mapper, unless the error is
+/// fatal and the fatal exit mode is on, i.e., `fatal_exit_code` is the base exit code set by
+/// `with_fatal_exit_code`, the process exits then.
+pub(crate) fn to_status(
+    mapper: StatusMapper,
+    fatal_exit_code: Option<i32>,
+    error: Error,
+) -> Status {
+    if let Some(code) = fatal_exit_code.and_then(|code| error.fatal_exit_code(code)) {
+        tracing::error!(code, %error, "exiting on a fatal error");
+        std::process::exit(code);
+    }
+    mapper(error)
+}
+
+/// ErrorKind tells what went wrong while serving a request. New kinds may be added, a `match` on
+/// it needs a wildcard arm.
+#[derive(Error, Debug, Clone)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    #[error("User Defined Error: {0}")]
+    UserDefinedError(String, ErrorDetails),
+
+    /// The user's handle panicked on the input of the keys, in the window if any.
+    #[error("Handler Panic: {message}")]
+    HandlerPanic {
+        /// message of the panic.
+        message: String,
+        /// keys of the input.
+        keys: Vec<String>,
+        /// start and end time of the window of the input, if any.
+        window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    },
+
+    #[error("Deadline Exceeded: {0}")]
+    DeadlineExceeded(String),
+
+    #[error("Invalid Argument: {0}")]
+    InvalidArgument(String),
+
+    /// The request does not follow the protocol, e.g., it misses its window, it is a bug of the
+    /// platform or a mismatch between the protocols of the platform and of the SDK.
+    #[error("Protocol Violation: {0}")]
+    ProtocolViolation(String),
+
+    /// The server is shutting down and gave up on the request.
+    #[error("Shutdown In Progress: {0}")]
+    ShutdownInProgress(String),
+
+    /// The server failed to serve the connections, the source is the error of the transport.
+    #[error("Connection Error: {0}")]
+    ConnectionError(#[source] Arc<dyn std::error::Error + Send + Sync>),
+
+    #[error("Internal Error: {0}")]
+    InternalError(String),
+
+    #[error("Resource Exhausted: {0}")]
+    ResourceExhausted(String),
+}
+
+/// ErrorDetails tell which input the user's handle failed on, they are attached to the returned
+/// [`Status`] so that numaflow does not have to parse the message.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ErrorDetails {
+    /// keys of the input, if any.
+    pub keys: Vec<String>,
+    /// start and end time of the window of the input, if any.
+    pub window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+/// Error raised while serving a request, the variant tells the UDF kind serving it and its source
+/// is the [`ErrorKind`].
+///
+/// It is also the error the servers fail with when they cannot serve the connections anymore, so
+/// that a binary embedding the SDK tells it apart from a start up error by downcasting the error
+/// returned by `start`.
+///
+/// # Example
+///
+/// ```no_run
+/// use numaflow::error::{Error, ErrorKind};
+/// # use numaflow::map::{self, Datum, Message};
+/// # struct Cat;
+/// # #[tonic::async_trait]
+/// # impl map::Mapper for Cat {
+/// #     async fn map<T: Datum + Send + Sync + 'static>(&self, _: T) -> Vec<Message> {
+/// #         vec![]
+/// #     }
+/// # }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let Err(error) = map::Server::new(Cat).start().await else {
+///         return;
+///     };
+///     match error.downcast_ref::<Error>().map(Error::kind) {
+///         Some(ErrorKind::ConnectionError(source)) => {
+///             eprintln!("lost the connection to numaflow: {}", source);
+///             std::process::exit(75);
+///         }
+///         _ => panic!("map server failed to start: {}", error),
+///     }
+/// }
+/// ```
+#[derive(Error, Debug, Clone)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("Map Error - {0}")]
+    MapError(#[source] ErrorKind),
+
+    #[error("Reduce Error - {0}")]
+    ReduceError(#[source] ErrorKind),
+
+    #[error("Sink Error - {0}")]
+    SinkError(#[source] ErrorKind),
+
+    #[error("Source Error - {0}")]
+    SourceError(#[source] ErrorKind),
+
+    #[error("Source Transformer Error - {0}")]
+    SourceTransformerError(#[source] ErrorKind),
+
+    #[error("Map Stream Error - {0}")]
+    MapStreamError(#[source] ErrorKind),
+
+    #[error("Batch Map Error - {0}")]
+    BatchMapError(#[source] ErrorKind),
+
+    #[error("Accumulator Error - {0}")]
+    AccumulatorError(#[source] ErrorKind),
+
+    #[error("Side Input Error - {0}")]
+    SideInputError(#[source] ErrorKind),
+}
+
+impl Error {
+    /// kind returns the [`ErrorKind`] of the error irrespective of the UDF kind.
+    pub fn kind(&self) -> &ErrorKind {
+        match self {
+            Error::MapError(kind)
+            | Error::ReduceError(kind)
+            | Error::SinkError(kind)
+            | Error::SourceError(kind)
+            | Error::SourceTransformerError(kind)
+            | Error::MapStreamError(kind)
+            | Error::BatchMapError(kind)
+            | Error::AccumulatorError(kind)
+            | Error::SideInputError(kind) => kind,
+        }
+    }
+
+    /// fatal_exit_code returns the exit code of the process for a fatal error in the fatal exit
+    /// mode, see `with_fatal_exit_code` on the `Server` of the UDF kinds, given the code of the
+    /// mode. The errors which are fatal and their codes are:
+    ///
+    /// - [`UserDefinedError`](ErrorKind::UserDefinedError) and
+    ///   [`HandlerPanic`](ErrorKind::HandlerPanic), i.e., the handler failed or panicked: `code`,
+    /// - [`InternalError`](ErrorKind::InternalError), i.e., the SDK failed: `code + 1`.
+    ///
+    /// The other errors concern a single request or the server as a whole, they are returned to
+    /// numaflow or by the server as usual.
+    ///
+    /// ```
+    /// use numaflow::error::{Error, ErrorDetails, ErrorKind};
+    ///
+    /// let error = Error::MapError(ErrorKind::UserDefinedError(
+    ///     "boom".to_string(),
+    ///     ErrorDetails::default(),
+    /// ));
+    /// assert_eq!(error.fatal_exit_code(70), Some(70));
+    ///
+    /// let error = Error::ReduceError(ErrorKind::HandlerPanic {
+    ///     message: "index out of bounds".to_string(),
+    ///     keys: vec!["sensor-1".to_string()],
+    ///     window: None,
+    /// });
+    /// assert_eq!(error.fatal_exit_code(70), Some(70));
+    ///
+    /// let error = Error::ReduceError(ErrorKind::InternalError("channel closed".to_string()));
+    /// assert_eq!(error.fatal_exit_code(70), Some(71));
+    ///
+    /// let error = Error::ReduceError(ErrorKind::InvalidArgument("bad window".to_string()));
+    /// assert_eq!(error.fatal_exit_code(70), None);
+    /// ```
+    pub fn fatal_exit_code(&self, code: i32) -> Option<i32> {
+        match self.kind() {
+            ErrorKind::UserDefinedError(..) | ErrorKind::HandlerPanic { .. } => Some(code),
+            ErrorKind::InternalError(_) => Some(code.saturating_add(1)),
+            ErrorKind::DeadlineExceeded(_)
+            | ErrorKind::InvalidArgument(_)
+            | ErrorKind::ProtocolViolation(_)
+            | ErrorKind::ShutdownInProgress(_)
+            | ErrorKind::ConnectionError(_)
+            | ErrorKind::ResourceExhausted(_) => None,
+        }
+    }
+
+    /// handler returns the name of the UDF kind which raised the error.
+    pub fn handler(&self) -> &'static str {
+        match self {
+            Error::MapError(_) => "map",
+            Error::ReduceError(_) => "reduce",
+            Error::SinkError(_) => "sink",
+            Error::SourceError(_) => "source",
+            Error::SourceTransformerError(_) => "sourcetransform",
+            Error::MapStreamError(_) => "mapstream",
+            Error::BatchMapError(_) => "batchmap",
+            Error::AccumulatorError(_) => "accumulator",
+            Error::SideInputError(_) => "sideinput",
+        }
+    }
+}
+
+impl ErrorKind {
+    /// The kind of the error of the transport failing to serve the connections.
+    pub(crate) fn connection(error: tonic::transport::Error) -> Self {
+        ErrorKind::ConnectionError(Arc::new(error))
+    }
+
+    /// The gRPC code and the `google.rpc.ErrorInfo` reason of the kind.
+    fn code_and_reason(&self) -> (Code, &'static str) {
+        match self {
+            ErrorKind::UserDefinedError(..) => (Code::Internal, "USER_DEFINED_ERROR"),
+            ErrorKind::HandlerPanic { .. } => (Code::Internal, "HANDLER_PANIC"),
+            ErrorKind::DeadlineExceeded(_) => (Code::DeadlineExceeded, "DEADLINE_EXCEEDED"),
+            ErrorKind::InvalidArgument(_) => (Code::InvalidArgument, "INVALID_ARGUMENT"),
+            ErrorKind::ProtocolViolation(_) => (Code::InvalidArgument, "PROTOCOL_VIOLATION"),
+            ErrorKind::ShutdownInProgress(_) => (Code::Unavailable, "SHUTDOWN_IN_PROGRESS"),
+            ErrorKind::ConnectionError(_) => (Code::Unavailable, "CONNECTION_ERROR"),
+            ErrorKind::InternalError(_) => (Code::Internal, "INTERNAL_ERROR"),
+            ErrorKind::ResourceExhausted(_) => (Code::ResourceExhausted, "RESOURCE_EXHAUSTED"),
+        }
+    }
+}
+
+/// `google.rpc.Status`, the payload of the `grpc-status-details-bin` trailer.
+#[derive(Clone, PartialEq, Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<prost_types::Any>,
+}
+
+/// `google.rpc.ErrorInfo`
+#[derive(Clone, PartialEq, Message)]
+struct ErrorInfo {
+    #[prost(string, tag = "1")]
+    reason: String,
+    #[prost(string, tag = "2")]
+    domain: String,
+    #[prost(map = "string, string", tag = "3")]
+    metadata: HashMap<String, String>,
+}
+
+/// The default [`StatusMapper`]. The status carries a `google.rpc.ErrorInfo` whose reason is the
+/// [`ErrorKind`] and whose metadata holds the handler and, for the user defined errors and the
+/// panics, the keys and the window of the input.
+impl From<Error> for Status {
+    fn from(error: Error) -> Self {
+        let (code, reason) = error.kind().code_and_reason();
+
+        let mut metadata = HashMap::from([("handler".to_string(), error.handler().to_string())]);
+        let input = match error.kind() {
+            ErrorKind::UserDefinedError(_, details) => Some((&details.keys, details.window)),
+            ErrorKind::HandlerPanic { keys, window, .. } => Some((keys, *window)),
+            _ => None,
+        };
+        if let Some((keys, window)) = input {
+            if !keys.is_empty() {
+                metadata.insert(
+                    "keys".to_string(),
+                    serde_json::to_string(keys).unwrap_or_default(),
+                );
+            }
+            if let Some((start, end)) = window {
+                metadata.insert("window_start".to_string(), start.to_rfc3339());
+                metadata.insert("window_end".to_string(), end.to_rfc3339());
+            }
+        }
+
+        let message = error.to_string();
+        let error_info = ErrorInfo {
+            reason: reason.to_string(),
+            domain: ERROR_DOMAIN.to_string(),
+            metadata,
+        };
+        let details = RpcStatus {
+            code: code as i32,
+            message: message.clone(),
+            details: vec![prost_types::Any {
+                type_url: "type.googleapis.com/google.rpc.ErrorInfo".to_string(),
+                value: error_info.encode_to_vec(),
+            }],
+        };
+
+        Status::with_details(code, message, details.encode_to_vec().into())
+    }
+}
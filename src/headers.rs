@@ -0,0 +1,198 @@
+use std::collections::{hash_map, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+/// Looks the header up in a map regardless of the case of its name, the exact name is tried first
+/// as it is the common case.
+pub(crate) fn find<'a>(
+    map: &'a HashMap<String, String>,
+    name: &str,
+) -> Option<(&'a String, &'a String)> {
+    map.get_key_value(name)
+        .or_else(|| map.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)))
+}
+
+/// HeaderError is the error of parsing the value of a header.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("header {name} has an invalid value {value:?}: {reason}")]
+pub struct HeaderError {
+    /// The name of the header, as asked for.
+    pub name: String,
+    /// The value of the header.
+    pub value: String,
+    /// Why the value is invalid.
+    pub reason: String,
+}
+
+/// Headers are the user defined headers of an element, set by the upstream vertices, e.g., tracing
+/// IDs or tenant tags. The names are looked up regardless of their case, like the HTTP ones, since
+/// the SDKs of the other languages do not agree on a case. The names are kept as received, e.g.,
+/// for [`iter`](Headers::iter).
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use numaflow::headers::Headers;
+///
+/// let headers = Headers::from(HashMap::from([
+///     ("X-Retries".to_string(), "3".to_string()),
+///     ("x-sent-at".to_string(), "2024-05-01T10:00:00Z".to_string()),
+/// ]));
+///
+/// assert_eq!(headers.get("x-retries"), Some("3"));
+/// assert_eq!(headers.parse::<u32>("X-RETRIES"), Ok(Some(3)));
+/// assert_eq!(headers.parse::<u32>("x-missing"), Ok(None));
+/// assert!(headers.parse::<u32>("x-sent-at").is_err());
+/// assert_eq!(
+///     headers.timestamp("x-sent-at").unwrap().unwrap().to_rfc3339(),
+///     "2024-05-01T10:00:00+00:00"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers {
+    map: HashMap<String, String>,
+}
+
+impl Headers {
+    /// Create empty headers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn find(&self, name: &str) -> Option<(&String, &String)> {
+        find(&self.map, name)
+    }
+
+    /// Returns the value of the header, whatever the case of its name. Of several headers whose
+    /// names differ only by their case, the one of the exact name is returned, any one otherwise.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.find(name).map(|(_, value)| value.as_str())
+    }
+
+    /// Returns whether the header is set, whatever the case of its name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.find(name).is_some()
+    }
+
+    /// Returns the value of the header parsed as a `T`, e.g., an integer, None if it is not set.
+    /// The value is trimmed first.
+    pub fn parse<T>(&self, name: &str) -> Result<Option<T>, HeaderError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let Some(value) = self.get(name) else {
+            return Ok(None);
+        };
+        value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e: T::Err| HeaderError {
+                name: name.to_string(),
+                value: value.to_string(),
+                reason: e.to_string(),
+            })
+    }
+
+    /// Returns the value of the header parsed as a timestamp, None if it is not set. The value is
+    /// either an RFC 3339 date and time or the milliseconds since the epoch.
+    pub fn timestamp(&self, name: &str) -> Result<Option<DateTime<Utc>>, HeaderError> {
+        let Some(value) = self.get(name) else {
+            return Ok(None);
+        };
+        let trimmed = value.trim();
+        let parsed = match trimmed.parse::<i64>() {
+            Ok(millis) => DateTime::from_timestamp_millis(millis)
+                .ok_or_else(|| "milliseconds out of range".to_string()),
+            Err(_) => DateTime::parse_from_rfc3339(trimmed)
+                .map(|time| time.with_timezone(&Utc))
+                .map_err(|e| e.to_string()),
+        };
+        parsed.map(Some).map_err(|reason| HeaderError {
+            name: name.to_string(),
+            value: value.to_string(),
+            reason,
+        })
+    }
+
+    /// Sets the header, replacing the headers of the same name whatever their case, and returns
+    /// the previous value.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        let name = name.into();
+        let previous = self.remove(&name);
+        self.map.insert(name, value.into());
+        previous
+    }
+
+    /// Removes the header, whatever the case of its name, and returns its value.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let key = self.find(name).map(|(key, _)| key.clone())?;
+        self.map.remove(&key)
+    }
+
+    /// Returns the number of headers.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns whether there is no header.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the headers with their names as received, in no particular order.
+    pub fn iter(&self) -> hash_map::Iter<'_, String, String> {
+        self.map.iter()
+    }
+
+    /// Returns the headers as a map of their names as received, whose lookups are case-sensitive.
+    pub fn as_map(&self) -> &HashMap<String, String> {
+        &self.map
+    }
+}
+
+impl From<HashMap<String, String>> for Headers {
+    fn from(map: HashMap<String, String>) -> Self {
+        Self { map }
+    }
+}
+
+impl From<Headers> for HashMap<String, String> {
+    fn from(headers: Headers) -> Self {
+        headers.map
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Headers {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut headers = Headers::new();
+        for (name, value) in iter {
+            headers.insert(name, value);
+        }
+        headers
+    }
+}
+
+impl IntoIterator for Headers {
+    type Item = (String, String);
+    type IntoIter = hash_map::IntoIter<String, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Headers {
+    type Item = (&'a String, &'a String);
+    type IntoIter = hash_map::Iter<'a, String, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.iter()
+    }
+}
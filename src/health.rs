@@ -0,0 +1,160 @@
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::FutureExt;
+use tonic::async_trait;
+use tonic::server::NamedService;
+use tonic_health::pb::health_server::{Health, HealthServer};
+use tonic_health::ServingStatus;
+
+use crate::shared::ServerConfig;
+use crate::tasks;
+
+/// Default interval between two calls of the [`HealthCheck`], it is also the time the check is
+/// given to answer.
+pub const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// HealthCheck tells whether the handler is able to make progress, e.g., that a reducer is not
+/// deadlocked on a lock or that the connection pool of a sink is not exhausted. It is polled by
+/// the SDK every interval once registered with `with_health_check` on the `Server` of the UDF
+/// kind, and the answer is served by the standard gRPC health checking service
+/// (`grpc.health.v1.Health`), so that a Kubernetes probe restarts a wedged handler instead of only
+/// checking that the socket is alive.
+///
+/// A check which does not answer within the interval or panics counts as unhealthy.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+///
+/// use numaflow::health::HealthCheck;
+/// use numaflow::map::{self, Datum, Message};
+///
+/// struct Enricher {
+///     // flipped by the handler when its lookups keep failing
+///     degraded: Arc<AtomicBool>,
+/// }
+///
+/// #[tonic::async_trait]
+/// impl map::Mapper for Enricher {
+///     async fn map<T: Datum + Send + Sync + 'static>(&self, input: T) -> Vec<Message> {
+///         vec![Message {
+///             keys: input.keys().clone(),
+///             value: input.value().clone(),
+///             tags: vec![],
+///         }]
+///     }
+/// }
+///
+/// struct Degraded(Arc<AtomicBool>);
+///
+/// #[tonic::async_trait]
+/// impl HealthCheck for Degraded {
+///     async fn healthy(&self) -> bool {
+///         !self.0.load(Ordering::Relaxed)
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+///     let degraded = Arc::new(AtomicBool::new(false));
+///     map::Server::new(Enricher {
+///         degraded: degraded.clone(),
+///     })
+///     .with_health_check(Degraded(degraded))
+///     .start()
+///     .await
+/// }
+/// ```
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// Returns whether the handler is healthy.
+    async fn healthy(&self) -> bool;
+}
+
+#[async_trait]
+impl<T: HealthCheck + ?Sized> HealthCheck for Arc<T> {
+    async fn healthy(&self) -> bool {
+        (**self).healthy().await
+    }
+}
+
+/// FromFn is a [`HealthCheck`] running a closure, see [`from_fn`].
+pub struct FromFn<F>(F);
+
+#[async_trait]
+impl<F, Fut> HealthCheck for FromFn<F>
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = bool> + Send,
+{
+    async fn healthy(&self) -> bool {
+        (self.0)().await
+    }
+}
+
+/// Returns a [`HealthCheck`] running the closure, e.g., one pinging the database the handler
+/// depends on.
+pub fn from_fn<F, Fut>(check: F) -> FromFn<F>
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = bool> + Send,
+{
+    FromFn(check)
+}
+
+/// Returns the health checking service to serve next to the UDF service `S` when it is on, and
+/// starts polling the health check of the config, if any.
+pub(crate) fn service<S: NamedService>(
+    config: &ServerConfig,
+    _: &S,
+) -> Option<HealthServer<impl Health>> {
+    if !config.health_service {
+        return None;
+    }
+    let (mut reporter, service) = tonic_health::server::health_reporter();
+    let name = S::NAME;
+    let check = config.health_check.clone();
+    let interval = config.health_check_interval;
+
+    tasks::spawn("health:check", async move {
+        reporter
+            .set_service_status(name, ServingStatus::Serving)
+            .await;
+        let Some(check) = check else { return };
+
+        let mut ticker = tokio::time::interval(interval);
+        let mut was_healthy = true;
+        loop {
+            ticker.tick().await;
+            let checked = AssertUnwindSafe(check.healthy()).catch_unwind();
+            let healthy = matches!(tokio::time::timeout(interval, checked).await, Ok(Ok(true)));
+            if healthy == was_healthy {
+                continue;
+            }
+            was_healthy = healthy;
+
+            let status = if healthy {
+                tracing::info!(handler = name, "handler is healthy again");
+                ServingStatus::Serving
+            } else {
+                tracing::warn!(
+                    handler = name,
+                    ?interval,
+                    "handler failed its health check or did not answer in time, reporting it as \
+                     not serving"
+                );
+                ServingStatus::NotServing
+            };
+            // the empty name is the health of the whole server
+            reporter.set_service_status("", status).await;
+            reporter.set_service_status(name, status).await;
+        }
+    });
+
+    Some(service)
+}
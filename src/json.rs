@@ -0,0 +1,66 @@
+use serde::de::DeserializeOwned;
+
+/// Decodes a JSON payload into `T`, e.g., the value of a [`Datum`](crate::map::Datum).
+///
+/// With the `simd-json` feature the payload is parsed with [simd-json], which uses the SIMD
+/// instructions the CPU supports, picked at runtime. The gain depends on the shape of the payloads
+/// and on the CPU, so measure it with `cargo bench --bench json --features simd-json` before
+/// turning it on. A payload simd-json fails on is decoded again with serde_json, hence the errors
+/// are always those of serde_json. Without the feature it is [`serde_json::from_slice`].
+///
+/// ```
+/// #[derive(serde::Deserialize)]
+/// struct Order {
+///     id: u64,
+///     items: Vec<String>,
+/// }
+///
+/// let order: Order = numaflow::json::from_slice(br#"{"id": 7, "items": ["tea"]}"#).unwrap();
+/// assert_eq!(order.id, 7);
+/// assert_eq!(order.items, ["tea"]);
+///
+/// assert!(numaflow::json::from_slice::<Order>(b"{").is_err());
+/// ```
+///
+/// [simd-json]: https://docs.rs/simd-json
+pub fn from_slice<T: DeserializeOwned>(payload: &[u8]) -> serde_json::Result<T> {
+    #[cfg(feature = "simd-json")]
+    if let Some(value) = simd::from_slice(payload) {
+        return Ok(value);
+    }
+    serde_json::from_slice(payload)
+}
+
+#[cfg(feature = "simd-json")]
+mod simd {
+    use std::cell::RefCell;
+
+    use serde::de::DeserializeOwned;
+    use simd_json::Buffers;
+
+    // simd-json parses in place, the payload is copied to a scratch buffer of the thread which is
+    // reused along with the buffers of the parser
+    #[derive(Default)]
+    struct Scratch {
+        payload: Vec<u8>,
+        buffers: Buffers,
+    }
+
+    thread_local! {
+        static SCRATCH: RefCell<Scratch> = RefCell::new(Scratch::default());
+    }
+
+    pub(super) fn from_slice<T: DeserializeOwned>(payload: &[u8]) -> Option<T> {
+        SCRATCH.with(|scratch| {
+            // a deserializer decoding a nested payload finds the scratch buffer in use
+            let mut scratch = scratch.try_borrow_mut().ok()?;
+            let Scratch {
+                payload: copy,
+                buffers,
+            } = &mut *scratch;
+            copy.clear();
+            copy.extend_from_slice(payload);
+            simd_json::serde::from_slice_with_buffers(copy, buffers).ok()
+        })
+    }
+}
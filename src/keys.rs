@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Keys of an element as they are shared by the lookup tables and the tasks of a stream, a clone
+/// only bumps the reference count.
+pub(crate) type Keys = Arc<Vec<String>>;
+
+/// Interns the keys of a stream, the elements of the same keys share a single copy of them instead
+/// of every table and task holding one of its own.
+#[derive(Default)]
+pub(crate) struct KeyInterner {
+    keys: HashSet<Keys>,
+}
+
+impl KeyInterner {
+    /// Returns the shared copy of the keys, the keys are only moved to the heap the first time
+    /// they are seen.
+    pub(crate) fn intern(&mut self, keys: Vec<String>) -> Interned {
+        if let Some(interned) = self.keys.get(&keys) {
+            return Interned(Arc::clone(interned));
+        }
+        let interned = Arc::new(keys);
+        self.keys.insert(Arc::clone(&interned));
+        Interned(interned)
+    }
+}
+
+/// Keys handed out by a [`KeyInterner`]. As the interner hands out a single copy of the same keys,
+/// they are hashed and compared by their address, which is as cheap as it gets for a lookup key.
+/// Keys of different interners must not be mixed.
+#[derive(Clone)]
+pub(crate) struct Interned(Keys);
+
+impl Deref for Interned {
+    type Target = Vec<String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl PartialEq for Interned {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Interned {}
+
+impl Hash for Interned {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state)
+    }
+}
+
+/// Delimiter of the keys in the task names and ids unless set otherwise.
+pub(crate) const DEFAULT_KEY_JOIN_DELIMITER: char = ':';
+
+/// Joins the keys into a name, the delimiter and the backslash are escaped with a backslash inside
+/// of the keys so that different keys never get the same name, e.g., `["a:b"]` is `a\:b` while
+/// `["a", "b"]` is `a:b`. The keys without any of them are joined as they are.
+pub(crate) fn join(keys: &[String], delimiter: char) -> String {
+    let mut joined = String::with_capacity(keys.iter().map(|key| key.len() + 1).sum());
+    for (i, key) in keys.iter().enumerate() {
+        if i > 0 {
+            joined.push(delimiter);
+        }
+        for c in key.chars() {
+            if c == delimiter || c == '\\' {
+                joined.push('\\');
+            }
+            joined.push(c);
+        }
+    }
+    joined
+}
@@ -0,0 +1,263 @@
+//! The older numaflow releases talk to the map and reduce handlers over a single `function.v1`
+//! protocol, the `UserDefinedFunction` service of `proto/udf.proto`. With the `legacy-protocol`
+//! feature the map and reduce servers serve it next to their own protocol, translating its
+//! requests to the current ones, so that a UDF built with this SDK keeps working while the
+//! platform is upgraded.
+//!
+//! The older platforms connect to [`DEFAULT_SOCK_ADDR`](crate::legacy::DEFAULT_SOCK_ADDR), which
+//! is set on the server with `with_socket_file`, and do not tell their version, so the version
+//! check has to stay off.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use numaflow::map::{self, Datum, Message};
+//!
+//! struct Cat;
+//!
+//! #[tonic::async_trait]
+//! impl map::Mapper for Cat {
+//!     async fn map<T: Datum + Send + Sync + 'static>(&self, input: T) -> Vec<Message> {
+//!         vec![Message {
+//!             keys: input.keys().clone(),
+//!             value: input.value().clone(),
+//!             tags: vec![],
+//!         }]
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+//!     map::Server::new(Cat)
+//!         .with_socket_file(numaflow::legacy::DEFAULT_SOCK_ADDR)
+//!         .start()
+//!         .await
+//! }
+//! ```
+
+use std::sync::Arc;
+
+use futures_util::stream::BoxStream;
+use futures_util::{StreamExt, TryStreamExt};
+use tonic::{async_trait, Request, Response, Status, Streaming};
+
+use crate::legacy::function::user_defined_function_server::UserDefinedFunction;
+use crate::legacy::function::{DatumRequest, DatumResponse, DatumResponseList, ReadyResponse};
+use crate::map::proto::{map_response, map_server, MapRequest, MapResponse};
+use crate::reduce::proto::{reduce_response, ReduceRequest, ReduceResponse};
+use crate::reduce::{ReduceService, Reducer};
+
+mod function {
+    tonic::include_proto!("function.v1");
+}
+
+/// The gRPC messages, client and server of the `function.v1` protocol, generated from its proto.
+pub mod proto {
+    pub use super::function::*;
+}
+
+/// Unix domain socket file the older numaflow releases connect to, for both the map and the reduce
+/// handlers.
+pub const DEFAULT_SOCK_ADDR: &str = "/var/run/numaflow/function.sock";
+
+/// Translates the request of the older protocol to the request of the current map protocol. The
+/// id and the delivery count of the element are dropped, the map protocol has no place for them,
+/// and the request has no headers.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::legacy::{self, proto};
+///
+/// let timestamp = |seconds| prost_types::Timestamp { seconds, nanos: 0 };
+/// let request = legacy::map_request(proto::DatumRequest {
+///     keys: vec!["sensor-1".to_string()],
+///     value: "21.5".into(),
+///     event_time: Some(proto::EventTime {
+///         event_time: Some(timestamp(60)),
+///     }),
+///     watermark: Some(proto::Watermark {
+///         watermark: Some(timestamp(30)),
+///     }),
+///     metadata: Some(proto::Metadata {
+///         id: "0-1".to_string(),
+///         num_delivered: 1,
+///     }),
+/// });
+///
+/// assert_eq!(request.keys, vec!["sensor-1".to_string()]);
+/// assert_eq!(request.value, "21.5");
+/// assert_eq!(request.event_time, Some(timestamp(60)));
+/// assert_eq!(request.watermark, Some(timestamp(30)));
+/// assert!(request.headers.is_empty());
+/// ```
+pub fn map_request(request: DatumRequest) -> MapRequest {
+    MapRequest {
+        keys: request.keys,
+        value: request.value,
+        event_time: request.event_time.and_then(|e| e.event_time),
+        watermark: request.watermark.and_then(|w| w.watermark),
+        headers: Default::default(),
+    }
+}
+
+/// Translates the response of the current map protocol to the response of the older protocol.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::legacy;
+/// use numaflow::map::proto::{map_response, MapResponse};
+///
+/// let response = legacy::map_response(MapResponse {
+///     results: vec![map_response::Result {
+///         keys: vec!["sensor-1".to_string()],
+///         value: "70.7".into(),
+///         tags: vec!["fahrenheit".to_string()],
+///     }],
+/// });
+///
+/// assert_eq!(response.elements.len(), 1);
+/// assert_eq!(response.elements[0].keys, vec!["sensor-1".to_string()]);
+/// assert_eq!(response.elements[0].value, "70.7");
+/// assert_eq!(response.elements[0].tags, vec!["fahrenheit".to_string()]);
+/// ```
+pub fn map_response(response: MapResponse) -> DatumResponseList {
+    DatumResponseList {
+        elements: response
+            .results
+            .into_iter()
+            .map(|map_response::Result { keys, value, tags }| DatumResponse { keys, value, tags })
+            .collect(),
+    }
+}
+
+/// Translates the request of the older protocol to the request of the current reduce protocol.
+/// The older protocol has one window per stream, set in the gRPC metadata of the stream, so the
+/// request carries no window of its own and is reduced in the window of the stream. It has no
+/// headers either.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::legacy::{self, proto};
+///
+/// let timestamp = |seconds| prost_types::Timestamp { seconds, nanos: 0 };
+/// let request = legacy::reduce_request(proto::DatumRequest {
+///     keys: vec!["sensor-1".to_string()],
+///     value: "21.5".into(),
+///     event_time: Some(proto::EventTime {
+///         event_time: Some(timestamp(60)),
+///     }),
+///     watermark: None,
+///     metadata: None,
+/// });
+///
+/// assert_eq!(request.keys, vec!["sensor-1".to_string()]);
+/// assert_eq!(request.value, "21.5");
+/// assert_eq!(request.event_time, Some(timestamp(60)));
+/// assert_eq!(request.watermark, None);
+/// assert!(request.headers.is_empty());
+/// ```
+pub fn reduce_request(request: DatumRequest) -> ReduceRequest {
+    ReduceRequest {
+        keys: request.keys,
+        value: request.value,
+        event_time: request.event_time.and_then(|e| e.event_time),
+        watermark: request.watermark.and_then(|w| w.watermark),
+        headers: Default::default(),
+    }
+}
+
+/// Translates the response of the current reduce protocol to the response of the older protocol.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::legacy;
+/// use numaflow::reduce::proto::{reduce_response, ReduceResponse};
+///
+/// let response = legacy::reduce_response(ReduceResponse {
+///     results: vec![reduce_response::Result {
+///         keys: vec!["sensor-1".to_string()],
+///         value: "3".into(),
+///         tags: vec![],
+///         event_time: None,
+///     }],
+/// });
+///
+/// assert_eq!(response.elements.len(), 1);
+/// assert_eq!(response.elements[0].keys, vec!["sensor-1".to_string()]);
+/// assert_eq!(response.elements[0].value, "3");
+/// ```
+pub fn reduce_response(response: ReduceResponse) -> DatumResponseList {
+    DatumResponseList {
+        elements: response
+            .results
+            .into_iter()
+            .map(
+                |reduce_response::Result {
+                     keys, value, tags, ..
+                 }| DatumResponse { keys, value, tags },
+            )
+            .collect(),
+    }
+}
+
+/// The legacy service in front of the map service `S`.
+pub(crate) struct LegacyMap<S>(pub(crate) Arc<S>);
+
+#[async_trait]
+impl<S: map_server::Map> UserDefinedFunction for LegacyMap<S> {
+    async fn map_fn(
+        &self,
+        request: Request<DatumRequest>,
+    ) -> Result<Response<DatumResponseList>, Status> {
+        let (metadata, extensions, request) = request.into_parts();
+        let request = Request::from_parts(metadata, extensions, map_request(request));
+        let response = self.0.map_fn(request).await?;
+        Ok(response.map(map_response))
+    }
+
+    type ReduceFnStream = BoxStream<'static, Result<DatumResponseList, Status>>;
+
+    async fn reduce_fn(
+        &self,
+        _: Request<Streaming<DatumRequest>>,
+    ) -> Result<Response<Self::ReduceFnStream>, Status> {
+        Err(Status::unimplemented("the UDF is a map handler"))
+    }
+
+    async fn is_ready(&self, _: Request<()>) -> Result<Response<ReadyResponse>, Status> {
+        Ok(Response::new(ReadyResponse { ready: true }))
+    }
+}
+
+/// The legacy service in front of the reduce service of `T`.
+pub(crate) struct LegacyReduce<T>(pub(crate) Arc<ReduceService<T>>);
+
+#[async_trait]
+impl<T: Reducer + Send + Sync + 'static> UserDefinedFunction for LegacyReduce<T> {
+    async fn map_fn(
+        &self,
+        _: Request<DatumRequest>,
+    ) -> Result<Response<DatumResponseList>, Status> {
+        Err(Status::unimplemented("the UDF is a reduce handler"))
+    }
+
+    type ReduceFnStream = BoxStream<'static, Result<DatumResponseList, Status>>;
+
+    async fn reduce_fn(
+        &self,
+        request: Request<Streaming<DatumRequest>>,
+    ) -> Result<Response<Self::ReduceFnStream>, Status> {
+        let metadata = request.metadata().clone();
+        let requests = request.into_inner().map_ok(reduce_request);
+        let responses = self.0.reduce_stream(&metadata, requests).await?;
+        Ok(Response::new(responses.map_ok(reduce_response).boxed()))
+    }
+
+    async fn is_ready(&self, _: Request<()>) -> Result<Response<ReadyResponse>, Status> {
+        Ok(Response::new(ReadyResponse { ready: true }))
+    }
+}
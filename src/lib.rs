@@ -1,6 +1,6 @@
 //! A Rust SDK for [Numaflow]. The Rust SDK is experimental has only implemented the most important
-//! features. It will support all the core features eventually. It supports [Map], [Reduce], and
-//! [User Defined Sinks].
+//! features. It will support all the core features eventually. It supports [Map], [Reduce],
+//! [User Defined Sinks], and [User Defined Sources].
 //!
 //! Please note that the Rust SDK is experimental and will be refactor in the future to make it more
 //! idiomatic.
@@ -9,15 +9,141 @@
 //! [Map]: https://numaflow.numaproj.io/user-guide/user-defined-functions/map/map/
 //! [Reduce]: https://numaflow.numaproj.io/user-guide/user-defined-functions/reduce/reduce/
 //! [User Defined Sinks]: https://numaflow.numaproj.io/user-guide/sinks/user-defined-sinks/
+//! [User Defined Sources]: https://numaflow.numaproj.io/user-guide/sources/user-defined-sources/
 
-/// start up code
-mod shared;
+/// error is the error type returned by the servers.
+pub mod error;
+
+mod compat;
+
+/// keys interns the keys flowing through the per-key tasks.
+mod keys;
+
+/// timestamp converts the protobuf timestamps.
+mod timestamp;
+
+/// tasks names the internal tasks and dumps them on demand.
+mod tasks;
+
+/// trace wraps the handler invocations in `tracing` spans.
+mod trace;
+
+/// cputime accounts the CPU time of the reduce handles by keys.
+mod cputime;
+
+/// watchdog detects the handlers blocking the async runtime.
+mod watchdog;
+
+/// metrics of the SDK layer, e.g., how many messages flow through the handlers and how long the
+/// handlers take, and of the handlers, served over HTTP with the `metrics` feature.
+pub mod metrics;
+
+/// control is for changing the runtime settings of a live server.
+pub mod control;
+
+/// shared is the start up code and the configuration common to all the servers.
+pub mod shared;
 
 /// map is for writing the [map](https://numaflow.numaproj.io/user-guide/user-defined-functions/map/map/) handlers.
 pub mod map;
 
+/// mapstream is for writing the [map stream](https://numaflow.numaproj.io/user-guide/user-defined-functions/map/map/#streaming-mode) handlers.
+pub mod mapstream;
+
+/// batchmap is for writing the [batch map](https://numaflow.numaproj.io/user-guide/user-defined-functions/map/batchmap/) handlers.
+pub mod batchmap;
+
 /// reduce is for writing the [reduce](https://numaflow.numaproj.io/user-guide/user-defined-functions/reduce/reduce/) handlers.
 pub mod reduce;
 
+/// accumulator is for writing the [accumulator](https://numaflow.numaproj.io/user-guide/user-defined-functions/reduce/windowing/accumulator/) handlers.
+pub mod accumulator;
+
+/// sideinput is for writing the [side input](https://numaflow.numaproj.io/specifications/side-inputs/) retrievers and for watching the side inputs from the handlers.
+pub mod sideinput;
+
+/// state keeps the values of the reduce handlers across the windows.
+pub mod state;
+
 /// sink for writing [user defined sinks](https://numaflow.numaproj.io/user-guide/sinks/user-defined-sinks/).
 pub mod sink;
+
+/// source for writing [user defined sources](https://numaflow.numaproj.io/user-guide/sources/user-defined-sources/).
+pub mod source;
+
+/// sourcetransform is for writing the [source data transformers](https://numaflow.numaproj.io/user-guide/sources/transformer/overview/).
+pub mod sourcetransform;
+
+/// local is for running the handlers locally, e.g., on the records of a file. Its in-process
+/// pipeline is experimental and only available with the `unstable` feature.
+pub mod local;
+
+/// audit samples the lineage of the elements, i.e., the outputs made out of an input, into a sink.
+pub mod audit;
+
+/// contract validates a sample of the payloads of a handler against a schema, e.g., JSON Schema.
+pub mod contract;
+
+/// buffer is a pool of buffers to build the payloads in.
+pub mod buffer;
+
+/// headers are the user defined headers of the elements, looked up regardless of the case.
+pub mod headers;
+
+/// message builds the results of the handlers, checking them against the limits of numaflow.
+pub mod message;
+
+/// typedkeys converts the positional keys of the elements to and from the structs naming them.
+pub mod typedkeys;
+
+/// codec adapts the handlers to the types their payloads are decoded into, e.g., JSON, Protobuf or
+/// Avro.
+pub mod codec;
+
+/// json decodes the JSON payloads, with simd-json when the `simd-json` feature is enabled.
+pub mod json;
+
+/// compression compresses and decompresses the payloads marked with a `content-encoding` header.
+#[cfg(feature = "compression")]
+pub mod compression;
+
+/// health serves the gRPC health checking service and polls the health check of the handler.
+#[cfg(feature = "health")]
+pub mod health;
+
+/// legacy serves the map and reduce handlers over the protocol of the older numaflow releases.
+#[cfg(feature = "legacy-protocol")]
+pub mod legacy;
+
+/// plugin loads map handlers compiled as cdylib plugins at runtime.
+#[cfg(feature = "plugin")]
+pub mod plugin;
+
+/// canary mirrors a share of the reduce windows to a second reducer, for trying out new logic.
+pub mod canary;
+
+/// switch runs one of two handlers as per the value of a side input, for blue/green rollouts.
+pub mod switch;
+
+/// testing is for asserting the results of the handlers in tests.
+pub mod testing;
+
+/// prelude imports what writing a UDF takes with `use numaflow::prelude::*`.
+pub mod prelude;
+
+/// udf creates the servers of the UDF kinds with the defaults the numaflow sidecar expects.
+pub mod udf;
+
+/// server hosts the servers of several UDF kinds in one process.
+pub mod server;
+pub use server::Server;
+
+#[cfg(feature = "macros")]
+pub use numaflow_macros::{reducer, TypedKeys};
+
+// used by the code generated by the macros
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub mod __private {
+    pub use tokio;
+}
@@ -0,0 +1,318 @@
+use std::fs;
+use std::path::Path;
+
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+
+use crate::headers::Headers;
+use crate::shared::BoxError;
+use crate::{map, reduce, sink, sourcetransform};
+
+/// pipeline chains the handlers into an in-process pipeline, it is an unstable API.
+#[cfg(feature = "unstable")]
+mod pipeline;
+#[cfg(feature = "unstable")]
+pub use pipeline::Pipeline;
+
+/// Element is a payload flowing through the local pipeline (`Pipeline`, with the `unstable`
+/// feature). It implements the `Datum` trait of every UDF kind, so it can also be used to call a
+/// handler directly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Element {
+    /// keys are the keys in the (key, value) terminology of map/reduce paradigm.
+    pub keys: Vec<String>,
+    /// value is the value in (key, value) terminology of map/reduce paradigm.
+    pub value: Bytes,
+    /// event_time is the time of the element as seen at source or aligned after a reduce operation.
+    pub event_time: DateTime<Utc>,
+    /// watermark is simulated by the pipeline as the oldest event time of the elements still to
+    /// come, i.e., a perfect watermark.
+    pub watermark: DateTime<Utc>,
+    /// headers are the user defined headers.
+    pub headers: Headers,
+    /// id is unique within the run of the pipeline.
+    pub id: String,
+}
+
+impl Element {
+    /// Creates an element without keys and headers.
+    pub fn new(value: impl Into<Bytes>, event_time: DateTime<Utc>) -> Self {
+        Self {
+            keys: vec![],
+            value: value.into(),
+            event_time,
+            watermark: event_time,
+            headers: Headers::new(),
+            id: String::new(),
+        }
+    }
+
+    /// Sets the keys of the element.
+    pub fn with_keys(mut self, keys: Vec<String>) -> Self {
+        self.keys = keys;
+        self
+    }
+}
+
+impl map::Datum for Element {
+    fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    fn watermark(&self) -> DateTime<Utc> {
+        self.watermark
+    }
+
+    fn event_time(&self) -> DateTime<Utc> {
+        self.event_time
+    }
+
+    fn headers(&self) -> &Headers {
+        &self.headers
+    }
+}
+
+impl sourcetransform::Datum for Element {
+    fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    fn watermark(&self) -> DateTime<Utc> {
+        self.watermark
+    }
+
+    fn event_time(&self) -> DateTime<Utc> {
+        self.event_time
+    }
+
+    fn headers(&self) -> &Headers {
+        &self.headers
+    }
+}
+
+impl reduce::Datum for Element {
+    fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    fn watermark(&self) -> DateTime<Utc> {
+        self.watermark
+    }
+
+    fn event_time(&self) -> DateTime<Utc> {
+        self.event_time
+    }
+
+    fn headers(&self) -> &Headers {
+        &self.headers
+    }
+}
+
+impl sink::Datum for Element {
+    fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    fn watermark(&self) -> DateTime<Utc> {
+        self.watermark
+    }
+
+    fn event_time(&self) -> DateTime<Utc> {
+        self.event_time
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// TimeFormat tells how the event time field of a record is parsed.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum TimeFormat {
+    /// RFC 3339, e.g., `2023-06-01T12:00:00Z`.
+    #[default]
+    Rfc3339,
+    /// Milliseconds since the UNIX epoch.
+    EpochMillis,
+    /// Seconds since the UNIX epoch.
+    EpochSeconds,
+    /// A [chrono format](https://docs.rs/chrono/latest/chrono/format/strftime/index.html) of a
+    /// UTC time without a zone, e.g., `%Y-%m-%d %H:%M:%S`.
+    Custom(String),
+}
+
+impl TimeFormat {
+    fn parse(&self, time: &str) -> Result<DateTime<Utc>, String> {
+        let parsed = match self {
+            TimeFormat::Rfc3339 => DateTime::parse_from_rfc3339(time)
+                .map(|t| t.with_timezone(&Utc))
+                .ok(),
+            TimeFormat::EpochMillis => time
+                .parse::<i64>()
+                .ok()
+                .and_then(|t| Utc.timestamp_millis_opt(t).single()),
+            TimeFormat::EpochSeconds => time
+                .parse::<i64>()
+                .ok()
+                .and_then(|t| Utc.timestamp_opt(t, 0).single()),
+            TimeFormat::Custom(format) => NaiveDateTime::parse_from_str(time, format)
+                .map(|t| Utc.from_utc_datetime(&t))
+                .ok(),
+        };
+        parsed.ok_or_else(|| format!("{} is not a valid {:?} time", time, self))
+    }
+}
+
+/// RecordOptions tell how the records of a CSV or an NDJSON file are turned into [`Element`]s.
+/// By default the whole record is the value, there are no keys and the event time is the time
+/// the file is read at.
+///
+/// ```
+/// use numaflow::local::{RecordOptions, TimeFormat};
+///
+/// let options = RecordOptions::new()
+///     .with_key_fields(["user", "region"])
+///     .with_value_field("payload")
+///     .with_event_time_field("ts", TimeFormat::EpochMillis);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RecordOptions {
+    key_fields: Vec<String>,
+    value_field: Option<String>,
+    event_time: Option<(String, TimeFormat)>,
+}
+
+impl RecordOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the fields (CSV columns) whose values make up the keys of the element, in order.
+    pub fn with_key_fields<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.key_fields = fields.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the field (CSV column) holding the value of the element. Without it the value is the
+    /// whole record, the NDJSON line as is and the CSV row as a JSON object.
+    pub fn with_value_field(mut self, field: impl Into<String>) -> Self {
+        self.value_field = Some(field.into());
+        self
+    }
+
+    /// Set the field (CSV column) holding the event time of the element and its format.
+    pub fn with_event_time_field(mut self, field: impl Into<String>, format: TimeFormat) -> Self {
+        self.event_time = Some((field.into(), format));
+        self
+    }
+
+    // builds the element out of a record, `field` returns the value of a field of the record
+    fn element(
+        &self,
+        field: impl Fn(&str) -> Option<String>,
+        record: impl FnOnce() -> Vec<u8>,
+        read_at: DateTime<Utc>,
+    ) -> Result<Element, String> {
+        let required = |name: &str| field(name).ok_or_else(|| format!("missing field {}", name));
+
+        let keys = self
+            .key_fields
+            .iter()
+            .map(|name| required(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let value = match &self.value_field {
+            Some(name) => required(name)?.into_bytes(),
+            None => record(),
+        };
+        let event_time = match &self.event_time {
+            Some((name, format)) => format.parse(&required(name)?)?,
+            None => read_at,
+        };
+
+        Ok(Element::new(value, event_time).with_keys(keys))
+    }
+}
+
+/// Reads the elements out of a CSV file with a header row, see [`RecordOptions`].
+pub fn read_csv(path: impl AsRef<Path>, options: &RecordOptions) -> Result<Vec<Element>, BoxError> {
+    let path = path.as_ref();
+    let mut reader = csv::Reader::from_path(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let headers = reader.headers()?.clone();
+    let read_at = Utc::now();
+
+    let mut elements = vec![];
+    for (i, row) in reader.records().enumerate() {
+        let row = row?;
+        let field = |name: &str| {
+            let column = headers.iter().position(|header| header == name)?;
+            row.get(column).map(str::to_string)
+        };
+        let record = || {
+            let object: serde_json::Map<String, serde_json::Value> = headers
+                .iter()
+                .zip(row.iter())
+                .map(|(header, value)| (header.to_string(), value.into()))
+                .collect();
+            serde_json::Value::Object(object).to_string().into_bytes()
+        };
+        let element = options
+            .element(field, record, read_at)
+            // the header is line 1
+            .map_err(|e| format!("{} line {}: {}", path.display(), i + 2, e))?;
+        elements.push(element);
+    }
+    Ok(elements)
+}
+
+/// Reads the elements out of a file with a JSON object per line, see [`RecordOptions`]. The
+/// string fields are taken as is, the other ones as JSON.
+pub fn read_ndjson(
+    path: impl AsRef<Path>,
+    options: &RecordOptions,
+) -> Result<Vec<Element>, BoxError> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let read_at = Utc::now();
+
+    let mut elements = vec![];
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let object: serde_json::Value = serde_json::from_str(line)
+            .map_err(|e| format!("{} line {}: {}", path.display(), i + 1, e))?;
+        let field = |name: &str| {
+            object.get(name).map(|value| match value {
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            })
+        };
+        let element = options
+            .element(field, || line.as_bytes().to_vec(), read_at)
+            .map_err(|e| format!("{} line {}: {}", path.display(), i + 1, e))?;
+        elements.push(element);
+    }
+    Ok(elements)
+}
@@ -0,0 +1,406 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{TimeZone, Utc};
+use futures_util::future::BoxFuture;
+use tokio::sync::{mpsc, watch};
+
+use crate::audit::Audit;
+use crate::headers::Headers;
+use crate::map::Mapper;
+use crate::reduce::{self, AbortSignal, IntervalWindow, Metadata as _, Reducer, Watermark};
+use crate::shared::BoxError;
+use crate::sink::Sinker;
+use crate::source::{self, SourceReadRequest, Sourcer};
+use crate::sourcetransform::SourceTransformer;
+
+use super::{read_csv, read_ndjson, Element, RecordOptions};
+
+// number of messages asked for by a single read of the source
+const READ_BATCH_SIZE: usize = 500;
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+type Input = Box<dyn FnOnce() -> BoxFuture<'static, Result<Vec<Element>, BoxError>> + Send>;
+// start of the window (epoch millis) and the keys of a reduce group
+type WindowKeys = (i64, Vec<String>);
+type Stage = Box<
+    dyn FnOnce(Vec<Element>, Option<Audit>) -> BoxFuture<'static, Result<Vec<Element>, BoxError>>
+        + Send,
+>;
+
+/// Pipeline wires the handlers into an in-process chain of vertices and runs it over a bounded
+/// input, so that the logic can be validated locally before deploying to a cluster. The pipeline
+/// is experimental, it simulates the watermark and the fixed windows of numaflow but none of its
+/// delivery guarantees.
+///
+/// # Example
+///
+/// ```no_run
+/// use numaflow::local::Pipeline;
+/// use numaflow::map::{self, Datum, Message};
+///
+/// struct Upper {}
+///
+/// #[tonic::async_trait]
+/// impl map::Mapper for Upper {
+///     async fn map<T>(&self, input: T) -> Vec<Message>
+///     where
+///         T: Datum + Send + Sync + 'static,
+///     {
+///         vec![Message {
+///             keys: input.keys().clone(),
+///             value: input.value().to_ascii_uppercase().into(),
+///             tags: vec![],
+///         }]
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+///     let elements = Pipeline::from_file("words.txt").map(Upper {}).run().await?;
+///     for element in elements {
+///         println!("{}", String::from_utf8_lossy(&element.value));
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct Pipeline {
+    input: Input,
+    stages: Vec<Stage>,
+    audit: Option<Audit>,
+}
+
+impl Pipeline {
+    /// Creates a pipeline reading the given elements, the ids are assigned by the pipeline.
+    pub fn from_elements(elements: Vec<Element>) -> Self {
+        Self::with_input(Box::new(move || Box::pin(async move { Ok(elements) })))
+    }
+
+    /// Creates a pipeline reading a file with one payload per line. The event time of the
+    /// elements is the time they are read at, like a source without a transformer.
+    pub fn from_file(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self::with_input(Box::new(move || {
+            Box::pin(async move {
+                let content = fs::read_to_string(&path)
+                    .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+                let now = Utc::now();
+                Ok(content
+                    .lines()
+                    .map(|line| Element::new(line.to_owned(), now))
+                    .collect())
+            })
+        }))
+    }
+
+    /// Creates a pipeline reading a CSV file with a header row, see [`read_csv`].
+    pub fn from_csv(path: impl Into<PathBuf>, options: RecordOptions) -> Self {
+        let path = path.into();
+        Self::with_input(Box::new(move || {
+            Box::pin(async move { read_csv(path, &options) })
+        }))
+    }
+
+    /// Creates a pipeline reading a file with a JSON object per line, see [`read_ndjson`].
+    pub fn from_ndjson(path: impl Into<PathBuf>, options: RecordOptions) -> Self {
+        let path = path.into();
+        Self::with_input(Box::new(move || {
+            Box::pin(async move { read_ndjson(path, &options) })
+        }))
+    }
+
+    /// Creates a pipeline reading from the [`Sourcer`] until a read returns no messages, every
+    /// read batch is acknowledged before the next read.
+    pub fn from_source<S>(source: S) -> Self
+    where
+        S: Sourcer + Send + Sync + 'static,
+    {
+        Self::with_input(Box::new(move || {
+            Box::pin(async move {
+                let mut elements = vec![];
+                loop {
+                    let (tx, mut rx) = mpsc::channel::<source::Message>(READ_BATCH_SIZE);
+                    let request = SourceReadRequest {
+                        count: READ_BATCH_SIZE,
+                        timeout: READ_TIMEOUT,
+                    };
+                    let (_, batch) = tokio::join!(source.read(request, tx), async {
+                        let mut batch = vec![];
+                        while let Some(message) = rx.recv().await {
+                            batch.push(message);
+                        }
+                        batch
+                    });
+                    if batch.is_empty() {
+                        return Ok(elements);
+                    }
+
+                    let offsets = batch.iter().map(|m| m.offset.clone()).collect();
+                    elements.extend(batch.into_iter().map(|message| Element {
+                        keys: message.keys,
+                        value: message.value,
+                        event_time: message.event_time,
+                        watermark: message.event_time,
+                        headers: message.headers.into(),
+                        id: String::new(),
+                    }));
+                    source.ack(offsets).await;
+                }
+            })
+        }))
+    }
+
+    fn with_input(input: Input) -> Self {
+        let input: Input = Box::new(move || {
+            Box::pin(async move {
+                let mut elements = input().await?;
+                for (i, element) in elements.iter_mut().enumerate() {
+                    element.id = i.to_string();
+                }
+                assign_watermarks(&mut elements);
+                Ok(elements)
+            })
+        });
+        Self {
+            input,
+            stages: vec![],
+            audit: None,
+        }
+    }
+
+    /// Adds a [`SourceTransformer`], the watermark is recomputed from the new event times. The
+    /// [dropped](crate::sourcetransform::Message::dropped) results are not passed on.
+    pub fn transform<T>(mut self, transformer: T) -> Self
+    where
+        T: SourceTransformer + Send + Sync + 'static,
+    {
+        self.stages.push(Box::new(move |elements, audit| {
+            Box::pin(async move {
+                let mut results = vec![];
+                for element in elements {
+                    let (id, headers) = (element.id.clone(), element.headers.clone());
+                    let messages = transformer.transform(element).await;
+                    if let Some(audit) = &audit {
+                        audit.record_indexed("sourcetransform", &id, messages.len());
+                    }
+                    results.extend(
+                        messages
+                            .into_iter()
+                            .enumerate()
+                            .filter(|(_, message)| !message.is_dropped())
+                            .map(|(i, message)| Element {
+                                keys: message.keys,
+                                value: message.value,
+                                event_time: message.event_time,
+                                watermark: message.event_time,
+                                headers: headers.clone(),
+                                id: format!("{}-{}", id, i),
+                            }),
+                    );
+                }
+                assign_watermarks(&mut results);
+                Ok(results)
+            })
+        }));
+        self
+    }
+
+    /// Adds a [`Mapper`], its [dropped](crate::map::Message::dropped) results are not passed on.
+    pub fn map<M>(mut self, mapper: M) -> Self
+    where
+        M: Mapper + Send + Sync + 'static,
+    {
+        self.stages.push(Box::new(move |elements, audit| {
+            Box::pin(async move {
+                let mut results = vec![];
+                for element in elements {
+                    let parent = element.clone();
+                    let messages = mapper.map(element).await;
+                    if let Some(audit) = &audit {
+                        audit.record_indexed("map", &parent.id, messages.len());
+                    }
+                    results.extend(
+                        messages
+                            .into_iter()
+                            .enumerate()
+                            .filter(|(_, message)| !message.is_dropped())
+                            .map(|(i, message)| Element {
+                                keys: message.keys,
+                                value: message.value,
+                                id: format!("{}-{}", parent.id, i),
+                                ..parent.clone()
+                            }),
+                    );
+                }
+                Ok(results)
+            })
+        }));
+        self
+    }
+
+    /// Adds a [`Reducer`] over fixed windows of the given length. The elements are grouped by
+    /// window and keys, the results carry the end of the window (exclusive) as their event time,
+    /// unless the reducer [set one](reduce::Message::with_event_time), and the
+    /// [window id](crate::reduce::Metadata::window_id) in the [`reduce::WINDOW_ID_HEADER`] header.
+    /// The [dropped](reduce::Message::dropped) results are not passed on.
+    pub fn reduce<R>(mut self, reducer: R, window: Duration) -> Self
+    where
+        R: Reducer + Send + Sync + 'static,
+    {
+        self.stages.push(Box::new(move |elements, audit| {
+            Box::pin(async move {
+                let length = i64::try_from(window.as_millis())
+                    .ok()
+                    .filter(|length| *length > 0)
+                    .ok_or_else(|| format!("invalid window length {:?}", window))?;
+
+                // group by window and keys, in the order of the first element of the group
+                let mut groups: Vec<(WindowKeys, Vec<Element>)> = vec![];
+                for element in elements {
+                    let start = element.event_time.timestamp_millis().div_euclid(length) * length;
+                    let group = (start, element.keys.clone());
+                    match groups.iter_mut().find(|(g, _)| *g == group) {
+                        Some((_, group_elements)) => group_elements.push(element),
+                        None => groups.push((group, vec![element])),
+                    }
+                }
+                groups.sort_by_key(|((start, _), _)| *start);
+
+                // the sender is never fired, the windows of a local run are not aborted
+                let (_abort_tx, abort_rx) = watch::channel(None);
+
+                let mut results = vec![];
+                for ((start, keys), group_elements) in groups {
+                    let st = Utc.timestamp_millis_opt(start).unwrap();
+                    let et = Utc.timestamp_millis_opt(start + length).unwrap();
+                    // numaflow puts the fixed windows in the first slot
+                    let md = IntervalWindow::new(
+                        st,
+                        et,
+                        "slot-0".to_string(),
+                        reduce::task_id(
+                            &crate::keys::join(&keys, crate::keys::DEFAULT_KEY_JOIN_DELIMITER),
+                            st,
+                            et,
+                            "slot-0",
+                        ),
+                        reduce::window_id(&keys, st, et, "slot-0"),
+                        AbortSignal::new(abort_rx.clone()),
+                        // the whole group is known up front
+                        Watermark::fixed(
+                            group_elements
+                                .iter()
+                                .map(|element| element.watermark)
+                                .max()
+                                .unwrap_or(st),
+                        ),
+                    );
+
+                    let input_ids: Vec<String> = match audit {
+                        Some(_) => group_elements.iter().map(|e| e.id.clone()).collect(),
+                        None => vec![],
+                    };
+
+                    // the channel holds the whole group so that it can be filled up front
+                    let (tx, rx) = mpsc::channel::<Element>(group_elements.len());
+                    for element in group_elements {
+                        let _ = tx.send(element).await;
+                    }
+                    drop(tx);
+
+                    let messages = reducer.reduce(keys.clone(), rx, &md).await;
+                    let output_ids: Vec<String> = (0..messages.len())
+                        .map(|i| format!("{}-{}-{}", start, keys.join(":"), i))
+                        .collect();
+                    if let Some(audit) = &audit {
+                        for input_id in &input_ids {
+                            audit.record("reduce", input_id, || output_ids.clone());
+                        }
+                    }
+                    let headers = Headers::from_iter([(reduce::WINDOW_ID_HEADER, md.window_id())]);
+                    let window_end = et - chrono::Duration::milliseconds(1);
+                    results.extend(
+                        messages
+                            .into_iter()
+                            .zip(output_ids)
+                            .filter(|(message, _)| !message.is_dropped())
+                            .map(|(message, id)| Element {
+                                event_time: message.event_time().unwrap_or(window_end),
+                                keys: message.keys,
+                                value: message.value,
+                                watermark: window_end,
+                                headers: headers.clone(),
+                                id,
+                            }),
+                    );
+                }
+                Ok(results)
+            })
+        }));
+        self
+    }
+
+    /// Adds a [`Sinker`], it ends the pipeline. The run fails if any element is not written,
+    /// elements sent to the fallback sink are deemed written.
+    pub fn sink<K>(mut self, sinker: K) -> Self
+    where
+        K: Sinker + Send + Sync + 'static,
+    {
+        // the elements end in the sink, there is no lineage to record
+        self.stages.push(Box::new(move |elements, _| {
+            Box::pin(async move {
+                let (tx, rx) = mpsc::channel::<Element>(elements.len().max(1));
+                for element in elements {
+                    let _ = tx.send(element).await;
+                }
+                drop(tx);
+
+                let failures: Vec<String> = sinker
+                    .sink(rx)
+                    .await
+                    .into_iter()
+                    .filter_map(|response| {
+                        let err = response.err()?;
+                        Some(format!("{}: {}", response.id(), err))
+                    })
+                    .collect();
+                if !failures.is_empty() {
+                    return Err(format!("sink failed to write {}", failures.join(", ")).into());
+                }
+                Ok(vec![])
+            })
+        }));
+        self
+    }
+
+    /// Set the [`Audit`] recording the lineage of a sample of the elements of the map, transform
+    /// and reduce vertices. An element of a reduce maps to all the results of its window.
+    pub fn with_audit(mut self, audit: Audit) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Runs the pipeline to completion and returns the elements coming out of its last vertex,
+    /// none if it ends with a sink.
+    pub async fn run(self) -> Result<Vec<Element>, BoxError> {
+        let mut elements = (self.input)().await?;
+        for stage in self.stages {
+            elements = stage(elements, self.audit.clone()).await?;
+        }
+        Ok(elements)
+    }
+}
+
+// the watermark of an element is the oldest event time of the elements from there on
+fn assign_watermarks(elements: &mut [Element]) {
+    let mut watermark = None;
+    for element in elements.iter_mut().rev() {
+        let oldest = match watermark {
+            Some(watermark) if watermark < element.event_time => watermark,
+            _ => element.event_time,
+        };
+        element.watermark = oldest;
+        watermark = Some(oldest);
+    }
+}
@@ -1,15 +1,54 @@
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
 use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use tokio::sync::Semaphore;
+use tonic::transport::Channel;
 use tonic::{async_trait, Request, Response, Status};
+use tracing::Instrument;
 
-use crate::map::mapper::{map_response, map_server, MapRequest, MapResponse, ReadyResponse};
-use crate::shared;
+use crate::error::{Error, ErrorKind};
+use crate::headers::Headers;
+use crate::local::Element;
+use crate::map::mapper::{
+    map_client, map_response, map_server, MapRequest, MapResponse, ReadyResponse,
+};
+use crate::message::{self, MessageBuilder};
+use crate::shared::HandlerSettings;
+use crate::{metrics, shared};
 
 mod mapper {
     tonic::include_proto!("map.v1");
 }
 
+/// The gRPC messages, client and server of the map protocol, generated from its proto.
+pub mod proto {
+    pub use super::mapper::*;
+}
+
 struct MapService<T> {
     handler: T,
+    // bounds the invocations of the handler running at once, if set
+    parallelism: Option<Semaphore>,
+    map_timeout: Option<Duration>,
+    timeout_policy: TimeoutPolicy,
+    settings: HandlerSettings,
+}
+
+/// TimeoutPolicy decides what happens to a message whose [`Mapper::map`] invocation did not finish
+/// within the timeout set via [`Server::with_map_timeout`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeoutPolicy {
+    /// Fail the request with `DEADLINE_EXCEEDED` so that numaflow retries the message.
+    #[default]
+    Retry,
+    /// Treat the message as bad data and drop it, no results are forwarded for it.
+    Drop,
 }
 
 /// Mapper trait for implementing Map handler.
@@ -24,7 +63,7 @@ pub trait Mapper {
     ///
     /// Following is an example of a cat container that just copies the input to output.
     ///
-    /// ```rust
+    /// ```no_run
     /// use numaflow::map::start_uds_server;
     ///
     /// #[tokio::main]
@@ -72,9 +111,52 @@ where
 {
     async fn map_fn(&self, request: Request<MapRequest>) -> Result<Response<MapResponse>, Status> {
         let request = request.into_inner();
+        metrics::messages_received("map", 1);
+        // the semaphore is never closed, the request waits for its turn
+        let _permit = match &self.parallelism {
+            Some(parallelism) => parallelism.acquire().await.ok(),
+            None => None,
+        };
+        let start = Instant::now();
 
-        // call the map handle
-        let result = self.handler.map(OwnedMapRequest::new(request)).await;
+        // call the map handle, aborting it if it does not finish within the deadline
+        let span = self.settings.handler_span("map", Some(&request.headers));
+        // the keys are kept for the error of a panic
+        let keys = request.keys.clone();
+        let request = OwnedMapRequest::new(request, self.settings);
+        let map_handle = self
+            .settings
+            .watch("map", self.handler.map(request))
+            .instrument(span);
+        // a panic of the handle fails the request rather than the connection
+        let map_handle = AssertUnwindSafe(map_handle).catch_unwind();
+        let result = match self.map_timeout {
+            None => map_handle.await,
+            Some(timeout) => match tokio::time::timeout(timeout, map_handle).await {
+                Ok(result) => result,
+                Err(_) => match self.timeout_policy {
+                    TimeoutPolicy::Retry => {
+                        return Err(self.settings.to_status(Error::MapError(
+                            ErrorKind::DeadlineExceeded(format!(
+                                "map handler did not finish within {:?}",
+                                timeout
+                            )),
+                        )))
+                    }
+                    TimeoutPolicy::Drop => Ok(vec![]),
+                },
+            },
+        };
+        let result = result.map_err(|panic| {
+            self.settings
+                .to_status(Error::MapError(ErrorKind::HandlerPanic {
+                    message: shared::panic_message(panic),
+                    keys,
+                    window: None,
+                }))
+        })?;
+        metrics::handler_latency("map", start.elapsed());
+        metrics::messages_emitted("map", result.len());
 
         let mut response_list = vec![];
         // build the response struct
@@ -104,11 +186,32 @@ pub struct Message {
     /// be an empty collection.
     pub keys: Vec<String>,
     /// Value is the value passed to the next vertex.
-    pub value: Vec<u8>,
+    pub value: Bytes,
     /// Tags are used for [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/).
     pub tags: Vec<String>,
 }
 
+impl Message {
+    /// Returns a builder of the message which checks the tags, keys and value when it builds.
+    pub fn builder() -> MessageBuilder<Self> {
+        MessageBuilder::new()
+    }
+
+    /// Returns a message numaflow drops, e.g., for an input which does not pass a filter.
+    pub fn dropped() -> Self {
+        Self {
+            keys: vec![],
+            value: Bytes::new(),
+            tags: vec![message::DROP.to_string()],
+        }
+    }
+
+    /// Returns whether numaflow drops the message, see [`DROP`](message::DROP).
+    pub fn is_dropped(&self) -> bool {
+        self.tags.iter().any(|tag| tag == message::DROP)
+    }
+}
+
 /// Datum trait represents an incoming element into the map/reduce handles of [`FnHandler`].
 pub trait Datum {
     /// keys are the keys in the (key, value) terminology of map/reduce paradigm.
@@ -116,29 +219,35 @@ pub trait Datum {
     fn keys(&self) -> &Vec<String>;
     /// value is the value in (key, value) terminology of map/reduce paradigm.
     /// Once called, it will replace the content with None, so subsequent calls will return None
-    fn value(&self) -> &Vec<u8>;
+    fn value(&self) -> &Bytes;
     /// [watermark](https://numaflow.numaproj.io/core-concepts/watermarks/) represented by time is a guarantee that we will not see an element older than this
     /// time.
     fn watermark(&self) -> DateTime<Utc>;
     /// event_time is the time of the element as seen at source or aligned after a reduce operation.
     fn event_time(&self) -> DateTime<Utc>;
+    /// headers are the user defined headers set by the upstream vertices.
+    fn headers(&self) -> &Headers;
 }
 
 /// Owned copy of MapRequest from Datum.
 struct OwnedMapRequest {
     keys: Vec<String>,
-    value: Vec<u8>,
+    value: Bytes,
     watermark: DateTime<Utc>,
     eventtime: DateTime<Utc>,
+    headers: Headers,
 }
 
 impl OwnedMapRequest {
-    fn new(mr: MapRequest) -> Self {
+    fn new(mut mr: MapRequest, settings: HandlerSettings) -> Self {
+        let mut headers = Headers::from(mr.headers);
+        settings.decompress_payload(&mut headers, &mut mr.value);
         Self {
             keys: mr.keys,
             value: mr.value,
             watermark: shared::utc_from_timestamp(mr.watermark),
             eventtime: shared::utc_from_timestamp(mr.event_time),
+            headers,
         }
     }
 }
@@ -148,7 +257,7 @@ impl Datum for OwnedMapRequest {
         &self.keys
     }
 
-    fn value(&self) -> &Vec<u8> {
+    fn value(&self) -> &Bytes {
         &self.value
     }
 
@@ -159,26 +268,267 @@ impl Datum for OwnedMapRequest {
     fn event_time(&self) -> DateTime<Utc> {
         self.eventtime
     }
+
+    fn headers(&self) -> &Headers {
+        &self.headers
+    }
 }
 
-pub async fn start_uds_server<T>(m: T) -> Result<(), Box<dyn std::error::Error>>
+/// Default unix domain socket file of the map server, see [`Server::with_socket_file`]. It is
+/// relocated by the [`SOCKET_ENV`](crate::shared::SOCKET_ENV) and
+/// [`RUN_DIR_ENV`](crate::shared::RUN_DIR_ENV) environment variables.
+pub const DEFAULT_SOCK_ADDR: &str = "/var/run/numaflow/map.sock";
+
+/// Version of the map protocol, i.e., of the `map.v1` proto package.
+pub const PROTOCOL_VERSION: &str = "v1";
+
+/// FromFn is a [`Mapper`] running a closure, see [`Server::from_fn`].
+pub struct FromFn<F>(F);
+
+#[async_trait]
+impl<F, Fut> Mapper for FromFn<F>
+where
+    F: Fn(Box<dyn Datum + Send + Sync>) -> Fut + Send + Sync,
+    Fut: Future<Output = Vec<Message>> + Send,
+{
+    async fn map<T: Datum + Send + Sync + 'static>(&self, input: T) -> Vec<Message> {
+        (self.0)(Box::new(input)).await
+    }
+}
+
+/// gRPC server to start a map service
+pub struct Server<T> {
+    config: shared::ServerConfig,
+    svc: T,
+    parallelism: Option<usize>,
+    map_timeout: Option<Duration>,
+    timeout_policy: TimeoutPolicy,
+}
+
+impl<T> Server<T> {
+    /// Create a new map server with the given [`Mapper`] handler.
+    pub fn new(map_svc: T) -> Self {
+        Self {
+            config: shared::ServerConfig::new(DEFAULT_SOCK_ADDR, PROTOCOL_VERSION),
+            svc: map_svc,
+            parallelism: None,
+            map_timeout: None,
+            timeout_policy: TimeoutPolicy::default(),
+        }
+    }
+
+    shared::server_config_methods!();
+
+    /// Set how many [`Mapper::map`] invocations run at once, the requests beyond it wait for an
+    /// invocation to finish. The requests are served concurrently, every request being its own
+    /// gRPC call, so this bounds the load an I/O bound handler puts on the services it calls
+    /// rather than the work of the server. The deadline of
+    /// [`with_map_timeout`](Server::with_map_timeout) starts once the invocation runs. There is no
+    /// bound by default, a parallelism of 0 is taken as 1.
+    pub fn with_parallelism(mut self, n: usize) -> Self {
+        self.parallelism = Some(n.max(1));
+        self
+    }
+
+    /// Get how many [`Mapper::map`] invocations run at once.
+    pub fn parallelism(&self) -> Option<usize> {
+        self.parallelism
+    }
+
+    /// Set the deadline for a single [`Mapper::map`] invocation. An invocation exceeding the
+    /// deadline is aborted and handled as per the [`TimeoutPolicy`]. There is no deadline by
+    /// default.
+    pub fn with_map_timeout(mut self, timeout: Duration) -> Self {
+        self.map_timeout = Some(timeout);
+        self
+    }
+
+    /// Get the deadline for a single [`Mapper::map`] invocation.
+    pub fn map_timeout(&self) -> Option<Duration> {
+        self.map_timeout
+    }
+
+    /// Set the [`TimeoutPolicy`] applied when a [`Mapper::map`] invocation exceeds the deadline.
+    /// Default is [`TimeoutPolicy::Retry`].
+    pub fn with_map_timeout_policy(mut self, policy: TimeoutPolicy) -> Self {
+        self.timeout_policy = policy;
+        self
+    }
+
+    /// Starts the gRPC server. The server runs until it is stopped or errors out.
+    pub async fn start(self) -> Result<(), shared::BoxError>
+    where
+        T: Mapper + Send + Sync + 'static,
+    {
+        self.start_with_shutdown(std::future::pending()).await
+    }
+
+    /// Starts the gRPC server, it shuts down once `shutdown` resolves. The server then stops
+    /// accepting traffic and returns once the in-flight requests are done.
+    pub async fn start_with_shutdown<F>(self, shutdown: F) -> Result<(), shared::BoxError>
+    where
+        T: Mapper + Send + Sync + 'static,
+        F: Future<Output = ()>,
+    {
+        let mut config = self.config;
+        let incoming = config.prepare().await?;
+
+        let map_svc = MapService {
+            handler: self.svc,
+            parallelism: self.parallelism.map(Semaphore::new),
+            map_timeout: self.map_timeout,
+            timeout_policy: self.timeout_policy,
+            settings: config.handler_settings(),
+        };
+
+        let map_svc = std::sync::Arc::new(map_svc);
+        let router = shared::router!(config, map_server::MapServer::from_arc(map_svc.clone()));
+        #[cfg(feature = "legacy-protocol")]
+        let router = router.add_service(shared::grpc_service!(
+            config,
+            crate::legacy::proto::user_defined_function_server::UserDefinedFunctionServer::new(
+                crate::legacy::LegacyMap(map_svc)
+            )
+        ));
+        router
+            .serve_with_incoming_shutdown(incoming, shutdown)
+            .await
+            .map_err(|e| Error::MapError(ErrorKind::connection(e)))?;
+
+        Ok(())
+    }
+}
+
+impl<T> crate::server::Service for Server<T>
 where
     T: Mapper + Send + Sync + 'static,
 {
-    shared::write_info_file();
+    fn serve(
+        self,
+        shutdown: BoxFuture<'static, ()>,
+    ) -> BoxFuture<'static, Result<(), shared::BoxError>> {
+        Box::pin(self.start_with_shutdown(shutdown))
+    }
+}
 
-    let path = "/var/run/numaflow/map.sock";
-    std::fs::create_dir_all(std::path::Path::new(path).parent().unwrap())?;
+impl<F> Server<FromFn<F>> {
+    /// Create a new map server running the closure as the [`Mapper::map`] handler, for the handlers
+    /// too small to be worth a type of their own. The input is passed to the closure as a [`Datum`]
+    /// trait object.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use numaflow::map::{self, Message};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     map::Server::from_fn(|input| async move {
+    ///         vec![Message {
+    ///             keys: input.keys().clone(),
+    ///             value: input.value().clone(),
+    ///             tags: vec![],
+    ///         }]
+    ///     })
+    ///     .start()
+    ///     .await
+    /// }
+    /// ```
+    pub fn from_fn<Fut>(handler: F) -> Self
+    where
+        F: Fn(Box<dyn Datum + Send + Sync>) -> Fut,
+        Fut: Future<Output = Vec<Message>>,
+    {
+        Self::new(FromFn(handler))
+    }
+}
+
+/// Client drives a map server, e.g., the one of a UDF written in another language, with the types
+/// of the crate rather than the gRPC messages: the elements are [`Element`]s and the results are
+/// decoded into [`Message`]s. It is meant for tooling, conformance tests and emulators of the
+/// platform.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use chrono::Utc;
+/// use numaflow::headers::Headers;
+/// use numaflow::local::Element;
+/// use numaflow::map::{self, Client, Message};
+/// use numaflow::testing::ephemeral_server;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+///     let server = ephemeral_server(map::Server::from_fn(|input| async move {
+///         vec![Message {
+///             keys: input.keys().clone(),
+///             value: input.value().to_ascii_uppercase().into(),
+///             tags: input.headers().get("tenant").map(String::from).into_iter().collect(),
+///         }]
+///     }))
+///     .await?;
+///
+///     let mut element = Element::new("hello", Utc::now()).with_keys(vec!["k".to_string()]);
+///     element.headers = Headers::from(HashMap::from([("tenant".to_string(), "acme".to_string())]));
+///
+///     let mut client = Client::connect(server.socket_file()).await?;
+///     let results = client.map(element).await?;
+///     assert_eq!(results[0].keys, ["k"]);
+///     assert_eq!(results[0].value, "HELLO");
+///     assert_eq!(results[0].tags, ["acme"]);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Client {
+    inner: map_client::MapClient<Channel>,
+}
 
-    let uds = tokio::net::UnixListener::bind(path)?;
-    let _uds_stream = tokio_stream::wrappers::UnixListenerStream::new(uds);
+impl Client {
+    /// Create a client over a connected channel, e.g., to a server listening on TCP.
+    pub fn new(channel: Channel) -> Self {
+        Self {
+            inner: map_client::MapClient::new(channel),
+        }
+    }
 
-    let map_svc = MapService { handler: m };
+    /// Connect to the server listening on the unix domain socket file.
+    pub async fn connect(socket_file: impl AsRef<Path>) -> Result<Self, shared::BoxError> {
+        Ok(Self::new(shared::connect_unix(socket_file.as_ref()).await?))
+    }
 
-    tonic::transport::Server::builder()
-        .add_service(map_server::MapServer::new(map_svc))
-        .serve_with_incoming(_uds_stream)
-        .await?;
+    /// Maps the element, returns the results of the handler.
+    pub async fn map(&mut self, element: Element) -> Result<Vec<Message>, Status> {
+        let request = MapRequest {
+            keys: element.keys,
+            value: element.value,
+            event_time: Some(shared::prost_timestamp_from_utc(element.event_time)),
+            watermark: Some(shared::prost_timestamp_from_utc(element.watermark)),
+            headers: element.headers.into(),
+        };
+        let response = self.inner.map_fn(request).await?.into_inner();
+        Ok(response
+            .results
+            .into_iter()
+            .map(|result| Message {
+                keys: result.keys,
+                value: result.value,
+                tags: result.tags,
+            })
+            .collect())
+    }
+}
 
-    Ok(())
+/// start_uds_server starts a map gRPC server over an UDS (unix-domain-socket) endpoint with the
+/// default settings. Use [`Server`] to customize the server.
+pub async fn start_uds_server<T>(m: T) -> Result<(), Box<dyn std::error::Error>>
+where
+    T: Mapper + Send + Sync + 'static,
+{
+    Server::new(m)
+        .start()
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)
 }
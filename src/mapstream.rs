@@ -0,0 +1,377 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{async_trait, Request, Response, Status};
+use tracing::Instrument;
+
+use crate::headers::Headers;
+use crate::mapstream::mapstreamer::{
+    map_stream_response, map_stream_server, MapStreamRequest, MapStreamResponse, ReadyResponse,
+};
+use crate::message::{self, MessageBuilder};
+use crate::shared::HandlerSettings;
+use crate::{error, metrics, shared, tasks};
+
+mod mapstreamer {
+    tonic::include_proto!("mapstream.v1");
+}
+
+/// The gRPC messages, client and server of the map stream protocol, generated from its proto.
+pub mod proto {
+    pub use super::mapstreamer::*;
+}
+
+/// Default unix domain socket file of the map stream server, see [`Server::with_socket_file`]. It is
+/// relocated by the [`SOCKET_ENV`](crate::shared::SOCKET_ENV) and
+/// [`RUN_DIR_ENV`](crate::shared::RUN_DIR_ENV) environment variables.
+pub const DEFAULT_SOCK_ADDR: &str = "/var/run/numaflow/mapstream.sock";
+
+/// Version of the map stream protocol, i.e., of the `mapstream.v1` proto package.
+pub const PROTOCOL_VERSION: &str = "v1";
+
+struct MapStreamService<T> {
+    handler: Arc<T>,
+    // buffer size of the channels between the gRPC streams and the user's handle
+    channel_size: usize,
+    settings: HandlerSettings,
+}
+
+/// MapStreamer trait for implementing the streaming Map handler.
+#[async_trait]
+pub trait MapStreamer {
+    /// map_stream takes in an input element and can produce 0, 1, or more results. Unlike
+    /// [`map`](crate::map::Mapper::map), the results are streamed out one at a time as they are
+    /// written to the `tx` instead of being collected into a [`Vec`], so an input fanning out
+    /// into thousands of results does not have to be buffered. The stream ends once the handle
+    /// returns. Long running handles can check
+    /// [`is_output_alive`](crate::shared::OutputAlive::is_output_alive) on the `tx` to stop once
+    /// the client is gone, and wrap it in an [`OutputGuard`](crate::shared::OutputGuard) so that
+    /// a handle returning early marks its results as partial. More about map streaming can be read
+    /// [here](https://numaflow.numaproj.io/user-guide/user-defined-functions/map/map/#streaming-mode).
+    ///
+    /// # Example
+    ///
+    /// Following is an example of a flatmap which splits the input by commas.
+    ///
+    /// ```no_run
+    /// use numaflow::mapstream::{self, Datum, Message};
+    /// use tokio::sync::mpsc::Sender;
+    ///
+    /// struct FlatMap {}
+    ///
+    /// #[tonic::async_trait]
+    /// impl mapstream::MapStreamer for FlatMap {
+    ///     async fn map_stream<T>(&self, input: T, tx: Sender<Message>)
+    ///     where
+    ///         T: Datum + Send + Sync + 'static,
+    ///     {
+    ///         let value = String::from_utf8_lossy(input.value()).to_string();
+    ///         for part in value.split(',') {
+    ///             let message = Message {
+    ///                 keys: input.keys().clone(),
+    ///                 value: part.as_bytes().to_vec().into(),
+    ///                 tags: vec![],
+    ///             };
+    ///             if tx.send(message).await.is_err() {
+    ///                 return;
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     mapstream::Server::new(FlatMap {}).start().await
+    /// }
+    /// ```
+    async fn map_stream<T: Datum + Send + Sync + 'static>(
+        &self,
+        input: T,
+        tx: mpsc::Sender<Message>,
+    );
+}
+
+#[async_trait]
+impl<T> map_stream_server::MapStream for MapStreamService<T>
+where
+    T: MapStreamer + Send + Sync + 'static,
+{
+    type MapStreamFnStream = ReceiverStream<Result<MapStreamResponse, Status>>;
+
+    async fn map_stream_fn(
+        &self,
+        request: Request<MapStreamRequest>,
+    ) -> Result<Response<Self::MapStreamFnStream>, Status> {
+        let request = request.into_inner();
+        metrics::messages_received("mapstream", 1);
+
+        // channel the user's handle writes into
+        let (tx, mut rx) = mpsc::channel::<Message>(self.channel_size);
+        // channel to respond to numaflow main car as it expects streaming results.
+        let (resp_tx, resp_rx) =
+            mpsc::channel::<Result<MapStreamResponse, Status>>(self.channel_size);
+
+        // call the map stream handle, tx is dropped once the handle returns which ends the stream
+        let handler = Arc::clone(&self.handler);
+        let settings = self.settings;
+        let span = settings.handler_span("mapstream", Some(&request.headers));
+        tasks::spawn("mapstream:task", async move {
+            let start = Instant::now();
+            let request = OwnedMapStreamRequest::new(request, settings);
+            let map_stream_handle = handler.map_stream(request, tx);
+            settings
+                .watch("mapstream", map_stream_handle)
+                .instrument(span)
+                .await;
+            metrics::handler_latency("mapstream", start.elapsed());
+        });
+
+        // stream the results out to the client
+        tasks::spawn("mapstream:response-writer", async move {
+            // rx is dropped as soon as the client is gone which closes the output of the handle
+            while let Some(message) = tokio::select! {
+                message = rx.recv() => message,
+                _ = resp_tx.closed() => None,
+            } {
+                let response = MapStreamResponse {
+                    result: Some(map_stream_response::Result {
+                        keys: message.keys,
+                        value: message.value,
+                        tags: message.tags,
+                    }),
+                };
+                metrics::messages_emitted("mapstream", 1);
+                metrics::channel_saturation("mapstream", &resp_tx);
+                if resp_tx.send(Ok(response)).await.is_err() {
+                    // client is gone, nothing more to do
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(resp_rx)))
+    }
+
+    async fn is_ready(&self, _: Request<()>) -> Result<Response<ReadyResponse>, Status> {
+        Ok(Response::new(ReadyResponse { ready: true }))
+    }
+}
+
+/// Message is the result streamed out of [`MapStreamer::map_stream`].
+pub struct Message {
+    /// Keys are a collection of strings which will be passed on to the next vertex as is. It can
+    /// be an empty collection.
+    pub keys: Vec<String>,
+    /// Value is the value passed to the next vertex.
+    pub value: Bytes,
+    /// Tags are used for [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/).
+    pub tags: Vec<String>,
+}
+
+impl Message {
+    /// Returns a builder of the message, see [`MessageBuilder`].
+    pub fn builder() -> MessageBuilder<Self> {
+        MessageBuilder::new()
+    }
+
+    /// Returns a message numaflow drops, e.g., for an input the stream filters out.
+    pub fn dropped() -> Self {
+        Self {
+            keys: vec![],
+            value: Bytes::new(),
+            tags: vec![message::DROP.to_string()],
+        }
+    }
+
+    /// Returns whether numaflow drops the message, see [`DROP`](message::DROP).
+    pub fn is_dropped(&self) -> bool {
+        self.tags.iter().any(|tag| tag == message::DROP)
+    }
+}
+
+/// Datum trait represents an incoming element into the [`MapStreamer::map_stream`].
+pub trait Datum {
+    /// keys are the keys in the (key, value) terminology of map/reduce paradigm.
+    fn keys(&self) -> &Vec<String>;
+    /// value is the value in (key, value) terminology of map/reduce paradigm.
+    fn value(&self) -> &Bytes;
+    /// [watermark](https://numaflow.numaproj.io/core-concepts/watermarks/) represented by time is a guarantee that we will not see an element older than this
+    /// time.
+    fn watermark(&self) -> DateTime<Utc>;
+    /// event_time is the time of the element as seen at source or aligned after a reduce operation.
+    fn event_time(&self) -> DateTime<Utc>;
+    /// headers are the user defined headers set by the upstream vertices.
+    fn headers(&self) -> &Headers;
+}
+
+/// Owned copy of MapStreamRequest from Datum.
+struct OwnedMapStreamRequest {
+    keys: Vec<String>,
+    value: Bytes,
+    watermark: DateTime<Utc>,
+    eventtime: DateTime<Utc>,
+    headers: Headers,
+}
+
+impl OwnedMapStreamRequest {
+    fn new(mut mr: MapStreamRequest, settings: HandlerSettings) -> Self {
+        let mut headers = Headers::from(mr.headers);
+        settings.decompress_payload(&mut headers, &mut mr.value);
+        Self {
+            keys: mr.keys,
+            value: mr.value,
+            watermark: shared::utc_from_timestamp(mr.watermark),
+            eventtime: shared::utc_from_timestamp(mr.event_time),
+            headers,
+        }
+    }
+}
+
+impl Datum for OwnedMapStreamRequest {
+    fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    fn watermark(&self) -> DateTime<Utc> {
+        self.watermark
+    }
+
+    fn event_time(&self) -> DateTime<Utc> {
+        self.eventtime
+    }
+
+    fn headers(&self) -> &Headers {
+        &self.headers
+    }
+}
+
+/// FromFn is a [`MapStreamer`] running a closure, see [`Server::from_fn`].
+pub struct FromFn<F>(F);
+
+#[async_trait]
+impl<F, Fut> MapStreamer for FromFn<F>
+where
+    F: Fn(Box<dyn Datum + Send + Sync>, mpsc::Sender<Message>) -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send,
+{
+    async fn map_stream<T: Datum + Send + Sync + 'static>(
+        &self,
+        input: T,
+        tx: mpsc::Sender<Message>,
+    ) {
+        (self.0)(Box::new(input), tx).await
+    }
+}
+
+/// gRPC server to start a map stream service
+pub struct Server<T> {
+    config: shared::ServerConfig,
+    svc: T,
+}
+
+impl<T> Server<T> {
+    /// Create a new map stream server with the given [`MapStreamer`] handler.
+    pub fn new(map_stream_svc: T) -> Self {
+        Self {
+            config: shared::ServerConfig::new(DEFAULT_SOCK_ADDR, PROTOCOL_VERSION),
+            svc: map_stream_svc,
+        }
+    }
+
+    shared::server_config_methods!();
+
+    /// Starts the gRPC server. The server runs until it is stopped or errors out.
+    pub async fn start(self) -> Result<(), shared::BoxError>
+    where
+        T: MapStreamer + Send + Sync + 'static,
+    {
+        self.start_with_shutdown(std::future::pending()).await
+    }
+
+    /// Starts the gRPC server, it shuts down once `shutdown` resolves. The server then stops
+    /// accepting traffic and returns once the in-flight requests are done.
+    pub async fn start_with_shutdown<F>(self, shutdown: F) -> Result<(), shared::BoxError>
+    where
+        T: MapStreamer + Send + Sync + 'static,
+        F: Future<Output = ()>,
+    {
+        let mut config = self.config;
+        let incoming = config.prepare().await?;
+
+        let map_stream_svc = MapStreamService {
+            handler: Arc::new(self.svc),
+            channel_size: config.tuning.channel_size,
+            settings: config.handler_settings(),
+        };
+
+        shared::router!(
+            config,
+            map_stream_server::MapStreamServer::new(map_stream_svc)
+        )
+        .serve_with_incoming_shutdown(incoming, shutdown)
+        .await
+        .map_err(|e| error::Error::MapStreamError(error::ErrorKind::connection(e)))?;
+
+        Ok(())
+    }
+}
+
+impl<T> crate::server::Service for Server<T>
+where
+    T: MapStreamer + Send + Sync + 'static,
+{
+    fn serve(
+        self,
+        shutdown: BoxFuture<'static, ()>,
+    ) -> BoxFuture<'static, Result<(), shared::BoxError>> {
+        Box::pin(self.start_with_shutdown(shutdown))
+    }
+}
+
+impl<F> Server<FromFn<F>> {
+    /// Create a new map stream server running the closure as the [`MapStreamer::map_stream`]
+    /// handler, for the handlers too small to be worth a type of their own. The input is passed to
+    /// the closure as a [`Datum`] trait object.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use numaflow::mapstream::{self, Message};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     mapstream::Server::from_fn(|input, tx| async move {
+    ///         let value = String::from_utf8_lossy(input.value()).to_string();
+    ///         for part in value.split(',') {
+    ///             let message = Message {
+    ///                 keys: input.keys().clone(),
+    ///                 value: part.to_string().into(),
+    ///                 tags: vec![],
+    ///             };
+    ///             if tx.send(message).await.is_err() {
+    ///                 return;
+    ///             }
+    ///         }
+    ///     })
+    ///     .start()
+    ///     .await
+    /// }
+    /// ```
+    pub fn from_fn<Fut>(handler: F) -> Self
+    where
+        F: Fn(Box<dyn Datum + Send + Sync>, mpsc::Sender<Message>) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        Self::new(FromFn(handler))
+    }
+}
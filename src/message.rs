@@ -0,0 +1,236 @@
+use std::marker::PhantomData;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+use crate::{batchmap, map, mapstream, reduce, sourcetransform};
+
+/// Tag of a message numaflow drops instead of forwarding it, e.g., the result of a filter. A
+/// message is dropped as soon as it carries the tag, whatever its other tags.
+pub const DROP: &str = "U+005C__DROP__";
+
+/// Default maximum size of the value of a message, the largest gRPC message numaflow accepts.
+pub const DEFAULT_MAX_VALUE_SIZE: usize = 64 * 1024 * 1024;
+
+/// MessageError is the error of building a message numaflow would reject or mishandle.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MessageError {
+    /// A tag is empty, it matches no condition of the forwarding.
+    #[error("tag {index} is empty")]
+    EmptyTag { index: usize },
+    /// The message has more keys than allowed, see [`MessageBuilder::with_max_keys`].
+    #[error("message has {count} keys, more than the maximum of {max}")]
+    TooManyKeys { count: usize, max: usize },
+    /// The value is larger than allowed, see [`MessageBuilder::with_max_value_size`].
+    #[error("value of {size} bytes is larger than the maximum of {max}")]
+    ValueTooLarge { size: usize, max: usize },
+    /// The message of a source transformer is built without an event time.
+    #[error("message has no event time")]
+    MissingEventTime,
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// BuildMessage is a message the [`MessageBuilder`] builds, i.e., the result of a map, map stream,
+/// batch map, reduce or source transformer.
+pub trait BuildMessage: sealed::Sealed + Sized {
+    #[doc(hidden)]
+    const REQUIRES_EVENT_TIME: bool = false;
+
+    #[doc(hidden)]
+    fn assemble(builder: MessageBuilder<Self>) -> Self;
+}
+
+/// EventTimed is a message carrying an event time of its own.
+pub trait EventTimed: BuildMessage {}
+
+/// MessageBuilder builds a message checking what numaflow would otherwise reject or silently
+/// mishandle later on, e.g., an empty tag or a value too large for the gRPC message, so that the
+/// handler gets a typed error at the line building the message. It is created by the `builder` of
+/// the message, e.g., [`map::Message::builder`].
+///
+/// # Example
+///
+/// ```
+/// use numaflow::map::Message;
+/// use numaflow::message::MessageError;
+///
+/// let message = Message::builder()
+///     .with_keys(["user-1"])
+///     .with_value("hello")
+///     .with_tag("greeting")
+///     .build()
+///     .unwrap();
+/// assert_eq!(message.keys, ["user-1"]);
+///
+/// let error = Message::builder().with_tag("").build().err();
+/// assert_eq!(error, Some(MessageError::EmptyTag { index: 0 }));
+///
+/// let error = Message::builder()
+///     .with_value(vec![0; 2048])
+///     .with_max_value_size(1024)
+///     .build()
+///     .err()
+///     .unwrap();
+/// assert_eq!(error.to_string(), "value of 2048 bytes is larger than the maximum of 1024");
+/// ```
+pub struct MessageBuilder<M> {
+    keys: Vec<String>,
+    value: Bytes,
+    tags: Vec<String>,
+    event_time: Option<DateTime<Utc>>,
+    max_keys: Option<usize>,
+    max_value_size: usize,
+    message: PhantomData<fn() -> M>,
+}
+
+impl<M: BuildMessage> Default for MessageBuilder<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: BuildMessage> MessageBuilder<M> {
+    /// Create a builder of a message without keys, value and tags.
+    pub fn new() -> Self {
+        Self {
+            keys: vec![],
+            value: Bytes::new(),
+            tags: vec![],
+            event_time: None,
+            max_keys: None,
+            max_value_size: DEFAULT_MAX_VALUE_SIZE,
+            message: PhantomData,
+        }
+    }
+
+    /// Set the keys of the message.
+    pub fn with_keys<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the value of the message.
+    pub fn with_value(mut self, value: impl Into<Bytes>) -> Self {
+        self.value = value.into();
+        self
+    }
+
+    /// Set the tags of the message, replacing the ones added so far.
+    pub fn with_tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Add a tag to the message.
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Limit the number of keys of the message, e.g., to the one the downstream reduce expects.
+    /// There is no limit by default.
+    pub fn with_max_keys(mut self, max: usize) -> Self {
+        self.max_keys = Some(max);
+        self
+    }
+
+    /// Limit the size of the value of the message. Default value is [`DEFAULT_MAX_VALUE_SIZE`].
+    pub fn with_max_value_size(mut self, max: usize) -> Self {
+        self.max_value_size = max;
+        self
+    }
+
+    /// Returns the message, or the first constraint it breaks.
+    pub fn build(self) -> Result<M, MessageError> {
+        if let Some(index) = self.tags.iter().position(String::is_empty) {
+            return Err(MessageError::EmptyTag { index });
+        }
+        if let Some(max) = self.max_keys.filter(|max| self.keys.len() > *max) {
+            return Err(MessageError::TooManyKeys {
+                count: self.keys.len(),
+                max,
+            });
+        }
+        if self.value.len() > self.max_value_size {
+            return Err(MessageError::ValueTooLarge {
+                size: self.value.len(),
+                max: self.max_value_size,
+            });
+        }
+        if M::REQUIRES_EVENT_TIME && self.event_time.is_none() {
+            return Err(MessageError::MissingEventTime);
+        }
+        Ok(M::assemble(self))
+    }
+}
+
+impl<M: EventTimed> MessageBuilder<M> {
+    /// Set the event time of the message, it is required by a source transformer.
+    pub fn with_event_time(mut self, event_time: DateTime<Utc>) -> Self {
+        self.event_time = Some(event_time);
+        self
+    }
+}
+
+// the messages made of keys, a value and tags only
+macro_rules! impl_build_message {
+    ($($message:ty),* $(,)?) => {
+        $(impl sealed::Sealed for $message {}
+
+        impl BuildMessage for $message {
+            fn assemble(builder: MessageBuilder<Self>) -> Self {
+                Self {
+                    keys: builder.keys,
+                    value: builder.value,
+                    tags: builder.tags,
+                }
+            }
+        })*
+    };
+}
+
+impl_build_message!(map::Message, mapstream::Message, batchmap::Message);
+
+impl sealed::Sealed for reduce::Message {}
+
+impl BuildMessage for reduce::Message {
+    fn assemble(builder: MessageBuilder<Self>) -> Self {
+        let message = Self::new(builder.keys, builder.value, builder.tags);
+        match builder.event_time {
+            Some(event_time) => message.with_event_time(event_time),
+            None => message,
+        }
+    }
+}
+
+impl EventTimed for reduce::Message {}
+
+impl sealed::Sealed for sourcetransform::Message {}
+
+impl BuildMessage for sourcetransform::Message {
+    const REQUIRES_EVENT_TIME: bool = true;
+
+    fn assemble(builder: MessageBuilder<Self>) -> Self {
+        Self {
+            keys: builder.keys,
+            value: builder.value,
+            // checked by the build
+            event_time: builder.event_time.unwrap_or_default(),
+            tags: builder.tags,
+        }
+    }
+}
+
+impl EventTimed for sourcetransform::Message {}
@@ -0,0 +1,641 @@
+// The metrics are only recorded with the `metrics` feature, the functions are no-ops otherwise so
+// that the servers do not have to care.
+#![cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+
+use std::time::Duration;
+
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+#[cfg(feature = "metrics")]
+mod registry {
+    use std::collections::HashMap;
+    use std::sync::{Mutex, OnceLock};
+
+    use prometheus::{
+        exponential_buckets, CounterVec, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec,
+        IntGauge, Opts, Registry,
+    };
+
+    // a metric of the handlers, by its name
+    #[derive(Clone)]
+    pub(super) enum User {
+        Counter(CounterVec),
+        Gauge(GaugeVec),
+        Histogram(HistogramVec),
+    }
+
+    impl User {
+        pub(super) fn kind(&self) -> &'static str {
+            match self {
+                User::Counter(_) => "counter",
+                User::Gauge(_) => "gauge",
+                User::Histogram(_) => "histogram",
+            }
+        }
+
+        fn collector(&self) -> Box<dyn prometheus::core::Collector> {
+            match self {
+                User::Counter(metric) => Box::new(metric.clone()),
+                User::Gauge(metric) => Box::new(metric.clone()),
+                User::Histogram(metric) => Box::new(metric.clone()),
+            }
+        }
+    }
+
+    pub(super) struct Metrics {
+        pub(super) registry: Registry,
+        pub(super) received: IntCounterVec,
+        pub(super) emitted: IntCounterVec,
+        pub(super) latency: HistogramVec,
+        pub(super) reduce_tasks: IntGauge,
+        pub(super) saturation: GaugeVec,
+        pub(super) saturated: IntCounterVec,
+        pub(super) contract_violations: IntCounterVec,
+        pub(super) duplicate_opens: IntCounterVec,
+        pub(super) top_keys: GaugeVec,
+        pub(super) canary_windows: IntCounterVec,
+        pub(super) burst_messages: IntGauge,
+        pub(super) burst_bytes: IntGauge,
+        pub(super) draining_tasks: IntGauge,
+        pub(super) user: Mutex<HashMap<String, User>>,
+    }
+
+    pub(super) fn get() -> &'static Metrics {
+        static METRICS: OnceLock<Metrics> = OnceLock::new();
+        METRICS.get_or_init(|| {
+            let registry = Registry::new_custom(Some("numaflow_sdk".to_string()), None)
+                .expect("metrics prefix is valid");
+
+            let received = IntCounterVec::new(
+                Opts::new(
+                    "messages_received_total",
+                    "Number of messages passed to the handler",
+                ),
+                &["handler"],
+            )
+            .expect("metric is valid");
+            let emitted = IntCounterVec::new(
+                Opts::new(
+                    "messages_emitted_total",
+                    "Number of messages returned by the handler",
+                ),
+                &["handler"],
+            )
+            .expect("metric is valid");
+            let latency = HistogramVec::new(
+                HistogramOpts::new(
+                    "handler_latency_seconds",
+                    "Time taken by a single invocation of the handler",
+                )
+                .buckets(exponential_buckets(0.0005, 2.0, 18).expect("buckets are valid")),
+                &["handler"],
+            )
+            .expect("metric is valid");
+            let reduce_tasks = IntGauge::new(
+                "reduce_tasks",
+                "Number of reduce handles running, i.e., open windows times their keys",
+            )
+            .expect("metric is valid");
+            let saturation = GaugeVec::new(
+                Opts::new(
+                    "channel_saturation_ratio",
+                    "Share of the capacity used of the channel between the handler and numaflow",
+                ),
+                &["handler"],
+            )
+            .expect("metric is valid");
+
+            let saturated = IntCounterVec::new(
+                Opts::new(
+                    "response_queue_saturated_total",
+                    "Number of times the response queue went over its high watermark",
+                ),
+                &["handler"],
+            )
+            .expect("metric is valid");
+
+            let contract_violations = IntCounterVec::new(
+                Opts::new(
+                    "contract_violations_total",
+                    "Number of sampled payloads breaking the contract of the handler",
+                ),
+                &["handler", "side"],
+            )
+            .expect("metric is valid");
+
+            let duplicate_opens = IntCounterVec::new(
+                Opts::new(
+                    "duplicate_opens_total",
+                    "Number of OPEN operations received for keys which are already open",
+                ),
+                &["handler"],
+            )
+            .expect("metric is valid");
+
+            let top_keys = GaugeVec::new(
+                Opts::new(
+                    "reduce_top_keys_cpu_seconds",
+                    "CPU time taken by the busiest reduce keys of the last window, by rank",
+                ),
+                &["rank", "keys"],
+            )
+            .expect("metric is valid");
+
+            let canary_windows = IntCounterVec::new(
+                Opts::new(
+                    "reduce_canary_windows_total",
+                    "Number of windows mirrored to the canary reducer, by how its results compare",
+                ),
+                &["outcome"],
+            )
+            .expect("metric is valid");
+
+            let burst_messages = IntGauge::new(
+                "reduce_burst_buffer_messages",
+                "Number of elements read ahead into the burst buffers of the reduce streams",
+            )
+            .expect("metric is valid");
+            let burst_bytes = IntGauge::new(
+                "reduce_burst_buffer_bytes",
+                "Payload bytes of the elements read ahead into the burst buffers of the reduce streams",
+            )
+            .expect("metric is valid");
+
+            let draining_tasks = IntGauge::new(
+                "reduce_draining_tasks",
+                "Number of reduce handles left to finish in the streams draining on shutdown",
+            )
+            .expect("metric is valid");
+
+            for collector in [
+                Box::new(received.clone()) as Box<dyn prometheus::core::Collector>,
+                Box::new(emitted.clone()),
+                Box::new(latency.clone()),
+                Box::new(reduce_tasks.clone()),
+                Box::new(saturation.clone()),
+                Box::new(saturated.clone()),
+                Box::new(contract_violations.clone()),
+                Box::new(duplicate_opens.clone()),
+                Box::new(top_keys.clone()),
+                Box::new(canary_windows.clone()),
+                Box::new(burst_messages.clone()),
+                Box::new(burst_bytes.clone()),
+                Box::new(draining_tasks.clone()),
+            ] {
+                registry
+                    .register(collector)
+                    .expect("metrics are registered once");
+            }
+
+            Metrics {
+                registry,
+                received,
+                emitted,
+                latency,
+                reduce_tasks,
+                saturation,
+                saturated,
+                contract_violations,
+                duplicate_opens,
+                top_keys,
+                canary_windows,
+                burst_messages,
+                burst_bytes,
+                draining_tasks,
+                user: Mutex::new(HashMap::new()),
+            }
+        })
+    }
+
+    /// Returns the metric of the handlers of the name, it is registered by the first call.
+    pub(super) fn user(
+        name: &str,
+        new: impl FnOnce() -> prometheus::Result<User>,
+    ) -> prometheus::Result<User> {
+        let metrics = get();
+        let mut user = metrics.user.lock().expect("metrics lock is poisoned");
+        if let Some(metric) = user.get(name) {
+            return Ok(metric.clone());
+        }
+        let metric = new()?;
+        metrics.registry.register(metric.collector())?;
+        user.insert(name.to_string(), metric.clone());
+        Ok(metric)
+    }
+}
+
+/// Records the messages passed to the handler.
+pub(crate) fn messages_received(handler: &str, count: usize) {
+    #[cfg(feature = "metrics")]
+    registry::get()
+        .received
+        .with_label_values(&[handler])
+        .inc_by(count as u64);
+}
+
+/// Records the messages returned by the handler.
+pub(crate) fn messages_emitted(handler: &str, count: usize) {
+    #[cfg(feature = "metrics")]
+    registry::get()
+        .emitted
+        .with_label_values(&[handler])
+        .inc_by(count as u64);
+}
+
+/// Records the time taken by an invocation of the handler.
+pub(crate) fn handler_latency(handler: &str, elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    registry::get()
+        .latency
+        .with_label_values(&[handler])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Records a reduce handle being started.
+pub(crate) fn reduce_task_started() {
+    #[cfg(feature = "metrics")]
+    registry::get().reduce_tasks.inc();
+}
+
+/// Records a reduce handle being done, be it finished, panicked or aborted.
+pub(crate) fn reduce_task_finished() {
+    #[cfg(feature = "metrics")]
+    registry::get().reduce_tasks.dec();
+}
+
+/// Records how full the channel is, a channel which stays full means numaflow does not keep up.
+pub(crate) fn channel_saturation<T>(handler: &str, tx: &mpsc::Sender<T>) {
+    #[cfg(feature = "metrics")]
+    {
+        let max = tx.max_capacity();
+        let used = max - tx.capacity();
+        registry::get()
+            .saturation
+            .with_label_values(&[handler])
+            .set(used as f64 / max as f64);
+    }
+}
+
+/// Records the response queue going over its high watermark.
+pub(crate) fn response_queue_saturated(handler: &str) {
+    #[cfg(feature = "metrics")]
+    registry::get()
+        .saturated
+        .with_label_values(&[handler])
+        .inc();
+}
+
+/// Records a payload breaking the contract of the handler, on the `input` or the `output` side.
+pub(crate) fn contract_violation(handler: &str, side: &str) {
+    #[cfg(feature = "metrics")]
+    registry::get()
+        .contract_violations
+        .with_label_values(&[handler, side])
+        .inc();
+}
+
+/// Records an OPEN of keys which are already open, their task is reused.
+pub(crate) fn duplicate_open(handler: &str) {
+    #[cfg(feature = "metrics")]
+    registry::get()
+        .duplicate_opens
+        .with_label_values(&[handler])
+        .inc();
+}
+
+/// Records the reduce keys which took the most CPU time in a window, the busiest first. They
+/// replace the keys of the previous window so that the keys do not pile up in the labels.
+pub(crate) fn reduce_top_keys(keys: &[(String, Duration)]) {
+    #[cfg(feature = "metrics")]
+    {
+        let top_keys = &registry::get().top_keys;
+        top_keys.reset();
+        for (rank, (keys, time)) in keys.iter().enumerate() {
+            top_keys
+                .with_label_values(&[&(rank + 1).to_string(), keys])
+                .set(time.as_secs_f64());
+        }
+    }
+}
+
+/// Records a window mirrored to the canary reducer, the outcome tells whether its results are
+/// the `same` as the ones of the primary reducer, `different` or whether it `panicked`.
+pub(crate) fn reduce_canary_window(outcome: &str) {
+    #[cfg(feature = "metrics")]
+    registry::get()
+        .canary_windows
+        .with_label_values(&[outcome])
+        .inc();
+}
+
+/// Records an element of the given payload size entering the burst buffer of a reduce stream, or
+/// leaving it with negative numbers.
+pub(crate) fn reduce_burst_buffered(messages: i64, bytes: i64) {
+    #[cfg(feature = "metrics")]
+    {
+        let metrics = registry::get();
+        metrics.burst_messages.add(messages);
+        metrics.burst_bytes.add(bytes);
+    }
+}
+
+/// Records a change of the number of reduce handles left to finish in the draining streams.
+pub(crate) fn reduce_draining_tasks(delta: i64) {
+    #[cfg(feature = "metrics")]
+    registry::get().draining_tasks.add(delta);
+}
+
+/// Serves the metrics in the prometheus text format on `/metrics` of the port in the background
+/// for the lifetime of the process.
+#[cfg(feature = "metrics")]
+pub(crate) fn serve(port: u16) -> Result<(), crate::shared::BoxError> {
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Response, StatusCode};
+    use prometheus::{Encoder, TextEncoder};
+
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(|request| async move {
+            if request.uri().path() != "/metrics" {
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::NOT_FOUND;
+                return Ok::<_, Infallible>(response);
+            }
+
+            let encoder = TextEncoder::new();
+            let mut buffer = vec![];
+            if let Err(e) = encoder.encode(&registry::get().registry.gather(), &mut buffer) {
+                tracing::error!(error = %e, "failed to encode the metrics");
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                return Ok(response);
+            }
+            let mut response = Response::new(Body::from(buffer));
+            response.headers_mut().insert(
+                hyper::header::CONTENT_TYPE,
+                hyper::header::HeaderValue::from_static("text/plain; version=0.0.4"),
+            );
+            Ok(response)
+        }))
+    });
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let server = hyper::Server::try_bind(&addr)?.serve(make_service);
+    tracing::info!(%addr, "serving the metrics on /metrics");
+
+    crate::tasks::spawn("metrics:server", async move {
+        if let Err(e) = server.await {
+            tracing::error!(error = %e, "metrics server stopped");
+        }
+    });
+
+    Ok(())
+}
+
+/// MetricsError is the error of registering a metric of a handler.
+#[derive(Error, Debug)]
+pub enum MetricsError {
+    /// The name is not a valid prometheus metric name.
+    #[error("invalid metric name {0:?}")]
+    InvalidName(String),
+    /// The name is taken by a metric of another kind, e.g., a counter asked for as a gauge.
+    #[error("metric {name} is already registered as a {kind}")]
+    Conflict { name: String, kind: &'static str },
+    /// The registry refused the metric.
+    #[cfg(feature = "metrics")]
+    #[error("failed to register the metric: {0}")]
+    Registry(#[from] prometheus::Error),
+}
+
+fn check_name(name: &str) -> Result<(), MetricsError> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(MetricsError::InvalidName(name.to_string()))
+    }
+}
+
+/// Metrics registers the business metrics of a handler, e.g., the orders it rejected, next to the
+/// metrics of the SDK, so that they are served on the metrics port of the server, see
+/// `with_metrics_port`, rather than by a server of the handler's own. The metrics are named
+/// `numaflow_sdk_user_{name}` and labelled by the name of the handler, hence the handlers sharing
+/// a process, e.g., through the [`Server`](crate::Server), are told apart. Without the `metrics`
+/// feature the metrics are not recorded.
+///
+/// The handle is cheap to clone, it is meant to be created along with the handler and the metrics
+/// to be kept in the handler. A metric is registered by the first call for its name, the later
+/// calls return it, whatever the help and the buckets they pass.
+///
+/// # Example
+///
+/// ```no_run
+/// use numaflow::map::{self, Datum, Message};
+/// use numaflow::metrics::{Counter, Metrics};
+///
+/// struct Filter {
+///     rejected: Counter,
+/// }
+///
+/// #[tonic::async_trait]
+/// impl map::Mapper for Filter {
+///     async fn map<T: Datum + Send + Sync + 'static>(&self, input: T) -> Vec<Message> {
+///         if input.value().is_empty() {
+///             self.rejected.inc();
+///             return vec![];
+///         }
+///         vec![Message {
+///             keys: input.keys().clone(),
+///             value: input.value().clone(),
+///             tags: vec![],
+///         }]
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+///     let metrics = Metrics::new("filter");
+///     let rejected = metrics.counter("rejected_total", "Number of empty payloads dropped")?;
+///     map::Server::new(Filter { rejected }).start().await
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    handler: String,
+}
+
+impl Metrics {
+    /// Create the handle of the metrics of the handler of the name.
+    pub fn new(handler: impl Into<String>) -> Self {
+        Self {
+            handler: handler.into(),
+        }
+    }
+
+    /// Returns the name of the handler the metrics are labelled by.
+    pub fn handler(&self) -> &str {
+        &self.handler
+    }
+
+    /// Returns the counter of the name.
+    pub fn counter(&self, name: &str, help: &str) -> Result<Counter, MetricsError> {
+        check_name(name)?;
+        #[cfg(feature = "metrics")]
+        {
+            let metric = registry::user(name, || {
+                let opts = prometheus::Opts::new(name, help).namespace("user");
+                Ok(registry::User::Counter(prometheus::CounterVec::new(
+                    opts,
+                    &["handler"],
+                )?))
+            })?;
+            match metric {
+                registry::User::Counter(metric) => Ok(Counter {
+                    inner: metric.with_label_values(&[&self.handler]),
+                }),
+                other => Err(MetricsError::Conflict {
+                    name: name.to_string(),
+                    kind: other.kind(),
+                }),
+            }
+        }
+        #[cfg(not(feature = "metrics"))]
+        Ok(Counter {})
+    }
+
+    /// Returns the gauge of the name.
+    pub fn gauge(&self, name: &str, help: &str) -> Result<Gauge, MetricsError> {
+        check_name(name)?;
+        #[cfg(feature = "metrics")]
+        {
+            let metric = registry::user(name, || {
+                let opts = prometheus::Opts::new(name, help).namespace("user");
+                Ok(registry::User::Gauge(prometheus::GaugeVec::new(
+                    opts,
+                    &["handler"],
+                )?))
+            })?;
+            match metric {
+                registry::User::Gauge(metric) => Ok(Gauge {
+                    inner: metric.with_label_values(&[&self.handler]),
+                }),
+                other => Err(MetricsError::Conflict {
+                    name: name.to_string(),
+                    kind: other.kind(),
+                }),
+            }
+        }
+        #[cfg(not(feature = "metrics"))]
+        Ok(Gauge {})
+    }
+
+    /// Returns the histogram of the name, with the given upper bounds of its buckets.
+    pub fn histogram(
+        &self,
+        name: &str,
+        help: &str,
+        buckets: &[f64],
+    ) -> Result<Histogram, MetricsError> {
+        check_name(name)?;
+        #[cfg(feature = "metrics")]
+        {
+            let metric = registry::user(name, || {
+                let opts = prometheus::HistogramOpts::new(name, help)
+                    .namespace("user")
+                    .buckets(buckets.to_vec());
+                Ok(registry::User::Histogram(prometheus::HistogramVec::new(
+                    opts,
+                    &["handler"],
+                )?))
+            })?;
+            match metric {
+                registry::User::Histogram(metric) => Ok(Histogram {
+                    inner: metric.with_label_values(&[&self.handler]),
+                }),
+                other => Err(MetricsError::Conflict {
+                    name: name.to_string(),
+                    kind: other.kind(),
+                }),
+            }
+        }
+        #[cfg(not(feature = "metrics"))]
+        Ok(Histogram {})
+    }
+}
+
+/// Counter is a value which only goes up, e.g., a number of events, see [`Metrics::counter`].
+#[derive(Clone)]
+pub struct Counter {
+    #[cfg(feature = "metrics")]
+    inner: prometheus::Counter,
+}
+
+impl Counter {
+    /// Adds one.
+    pub fn inc(&self) {
+        #[cfg(feature = "metrics")]
+        self.inner.inc();
+    }
+
+    /// Adds the value, which must not be negative.
+    pub fn inc_by(&self, value: f64) {
+        #[cfg(feature = "metrics")]
+        self.inner.inc_by(value);
+    }
+}
+
+/// Gauge is a value which goes up and down, e.g., the size of a cache, see [`Metrics::gauge`].
+#[derive(Clone)]
+pub struct Gauge {
+    #[cfg(feature = "metrics")]
+    inner: prometheus::Gauge,
+}
+
+impl Gauge {
+    /// Sets the value.
+    pub fn set(&self, value: f64) {
+        #[cfg(feature = "metrics")]
+        self.inner.set(value);
+    }
+
+    /// Adds the value, it may be negative.
+    pub fn add(&self, value: f64) {
+        #[cfg(feature = "metrics")]
+        self.inner.add(value);
+    }
+
+    /// Adds one.
+    pub fn inc(&self) {
+        #[cfg(feature = "metrics")]
+        self.inner.inc();
+    }
+
+    /// Subtracts one.
+    pub fn dec(&self) {
+        #[cfg(feature = "metrics")]
+        self.inner.dec();
+    }
+}
+
+/// Histogram counts the observed values by bucket, e.g., the sizes of the orders, see
+/// [`Metrics::histogram`].
+#[derive(Clone)]
+pub struct Histogram {
+    #[cfg(feature = "metrics")]
+    inner: prometheus::Histogram,
+}
+
+impl Histogram {
+    /// Records a value.
+    pub fn observe(&self, value: f64) {
+        #[cfg(feature = "metrics")]
+        self.inner.observe(value);
+    }
+}
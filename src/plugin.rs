@@ -0,0 +1,463 @@
+//! A map handler can be compiled as a `cdylib` plugin and loaded by a generic UDF image at start
+//! up, so that one image serves the logic of many pipelines.
+//!
+//! The plugin crate depends on `numaflow` with the `plugin` feature, implements [`MapHandler`](crate::plugin::MapHandler) and
+//! exports it with [`export_map_plugin!`](crate::export_map_plugin):
+//!
+//! ```ignore
+//! // Cargo.toml: [lib] crate-type = ["cdylib"]
+//! use numaflow::map::{Datum, Message};
+//! use numaflow::plugin::{Input, MapHandler};
+//!
+//! struct Upper;
+//!
+//! impl MapHandler for Upper {
+//!     fn map(&self, input: Input) -> Vec<Message> {
+//!         vec![Message {
+//!             keys: input.keys().clone(),
+//!             value: input.value().to_ascii_uppercase().into(),
+//!             tags: vec![],
+//!         }]
+//!     }
+//! }
+//!
+//! numaflow::export_map_plugin!("upper", Upper);
+//! ```
+//!
+//! The image loads the plugin from a directory and serves it like any other [`Mapper`](crate::map::Mapper):
+//!
+//! ```no_run
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+//!     let name = std::env::var("NUMAFLOW_PLUGIN").ok();
+//!     // SAFETY: the directory only holds the plugins built for the image
+//!     let plugin = unsafe { numaflow::plugin::load_dir("/opt/numaflow/plugins", name.as_deref())? };
+//!     numaflow::map::Server::new(plugin).start().await
+//! }
+//! ```
+//!
+//! The handlers are called through the C ABI of [`abi`](crate::plugin::abi), the plugin and the image only have to
+//! agree on its version, which is part of the name of the [`ENTRY`](crate::plugin::abi::ENTRY) symbol. They do not share
+//! their allocator nor their runtime, hence the handlers of the plugins are synchronous and every
+//! value crossing the boundary is copied by the side receiving it.
+
+use std::ffi::c_void;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use libloading::Library;
+use thiserror::Error;
+use tonic::async_trait;
+
+use crate::headers::Headers;
+use crate::map::{self, Mapper, Message};
+use crate::shared;
+
+/// The C ABI between the plugins and the image loading them, both sides build it through this
+/// module hence it is only of interest to the plugins not written in Rust.
+pub mod abi {
+    use std::ffi::c_void;
+
+    /// Name of the function a plugin exports, of type [`Entry`]. It is bumped along with any
+    /// change to the types of this module.
+    pub const ENTRY: &str = "numaflow_plugin_v1";
+
+    /// [`VTable::map`] returns it once all the results are emitted.
+    pub const OK: i32 = 0;
+    /// [`VTable::map`] returns it when the handler panicked.
+    pub const PANICKED: i32 = 1;
+
+    /// Bytes borrowed for the duration of a call.
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Slice {
+        pub ptr: *const u8,
+        pub len: usize,
+    }
+
+    /// A point in time, like the protobuf timestamps.
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy)]
+    pub struct Time {
+        pub seconds: i64,
+        pub nanos: u32,
+    }
+
+    /// The input of [`VTable::map`].
+    #[repr(C)]
+    pub struct MapInput {
+        pub keys: *const Slice,
+        pub keys_len: usize,
+        pub value: Slice,
+        pub watermark: Time,
+        pub event_time: Time,
+    }
+
+    /// A result of [`VTable::map`].
+    #[repr(C)]
+    pub struct MapOutput {
+        pub keys: *const Slice,
+        pub keys_len: usize,
+        pub value: Slice,
+        pub tags: *const Slice,
+        pub tags_len: usize,
+    }
+
+    /// Called by [`VTable::map`] for every result, with the context it was given. The result is
+    /// copied before it returns.
+    pub type Emit = unsafe extern "C" fn(ctx: *mut c_void, output: *const MapOutput);
+
+    /// The handler of a plugin.
+    #[repr(C)]
+    pub struct VTable {
+        /// Name of the plugin, it lives as long as the plugin is loaded.
+        pub name: Slice,
+        /// The handler, passed to the functions below.
+        pub state: *mut c_void,
+        /// Maps the input, calling `emit` for every result, and returns [`OK`] or [`PANICKED`].
+        pub map: unsafe extern "C" fn(
+            state: *const c_void,
+            input: *const MapInput,
+            emit: Emit,
+            ctx: *mut c_void,
+        ) -> i32,
+        /// Drops the handler, it is called once before the plugin is unloaded.
+        pub drop: unsafe extern "C" fn(state: *mut c_void),
+    }
+
+    /// The function exported as [`ENTRY`], it creates the handler.
+    pub type Entry = unsafe extern "C" fn() -> VTable;
+}
+
+/// MapHandler is the map handler of a plugin. Unlike [`Mapper`] it is synchronous, the plugin
+/// does not share the runtime of the image, hence it should not block for long.
+pub trait MapHandler: Send + Sync + 'static {
+    /// map takes in an input element and produces 0, 1, or more results, see [`Mapper::map`].
+    fn map(&self, input: Input) -> Vec<Message>;
+}
+
+/// Input is the element passed to a [`MapHandler`], copied out of the image.
+pub struct Input {
+    keys: Vec<String>,
+    value: Bytes,
+    watermark: DateTime<Utc>,
+    event_time: DateTime<Utc>,
+    // the ABI of the plugins does not carry the headers
+    headers: Headers,
+}
+
+impl map::Datum for Input {
+    fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    fn watermark(&self) -> DateTime<Utc> {
+        self.watermark
+    }
+
+    fn event_time(&self) -> DateTime<Utc> {
+        self.event_time
+    }
+
+    fn headers(&self) -> &Headers {
+        &self.headers
+    }
+}
+
+/// Exports a [`MapHandler`] as the handler of a `cdylib` plugin, along with the name it is picked
+/// by in [`load_dir`](crate::plugin::load_dir). The handler is created when the plugin is loaded.
+#[macro_export]
+macro_rules! export_map_plugin {
+    ($name:expr, $handler:expr) => {
+        #[no_mangle]
+        pub unsafe extern "C" fn numaflow_plugin_v1() -> $crate::plugin::abi::VTable {
+            $crate::plugin::__vtable($name, $handler)
+        }
+    };
+}
+
+#[doc(hidden)]
+pub fn __vtable<T: MapHandler>(name: &'static str, handler: T) -> abi::VTable {
+    abi::VTable {
+        name: slice(name.as_bytes()),
+        state: Box::into_raw(Box::new(handler)) as *mut c_void,
+        map: map_trampoline::<T>,
+        drop: drop_trampoline::<T>,
+    }
+}
+
+unsafe extern "C" fn map_trampoline<T: MapHandler>(
+    state: *const c_void,
+    input: *const abi::MapInput,
+    emit: abi::Emit,
+    ctx: *mut c_void,
+) -> i32 {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let handler = &*(state as *const T);
+        let input = &*input;
+        let input = Input {
+            keys: strings(input.keys, input.keys_len),
+            value: Bytes::copy_from_slice(bytes(input.value)),
+            watermark: utc(input.watermark),
+            event_time: utc(input.event_time),
+            headers: Headers::default(),
+        };
+
+        for message in handler.map(input) {
+            let keys: Vec<abi::Slice> = message.keys.iter().map(|k| slice(k.as_bytes())).collect();
+            let tags: Vec<abi::Slice> = message.tags.iter().map(|t| slice(t.as_bytes())).collect();
+            let output = abi::MapOutput {
+                keys: keys.as_ptr(),
+                keys_len: keys.len(),
+                value: slice(&message.value),
+                tags: tags.as_ptr(),
+                tags_len: tags.len(),
+            };
+            emit(ctx, &output);
+        }
+    }));
+
+    match result {
+        Ok(()) => abi::OK,
+        Err(payload) => {
+            tracing::error!(
+                panic = %shared::panic_message(payload),
+                "map plugin handler panicked"
+            );
+            abi::PANICKED
+        }
+    }
+}
+
+unsafe extern "C" fn drop_trampoline<T: MapHandler>(state: *mut c_void) {
+    drop(Box::from_raw(state as *mut T));
+}
+
+fn slice(bytes: &[u8]) -> abi::Slice {
+    abi::Slice {
+        ptr: bytes.as_ptr(),
+        len: bytes.len(),
+    }
+}
+
+// the slice must be valid for the duration of the call
+unsafe fn bytes<'a>(slice: abi::Slice) -> &'a [u8] {
+    if slice.len == 0 {
+        return &[];
+    }
+    std::slice::from_raw_parts(slice.ptr, slice.len)
+}
+
+unsafe fn strings(slices: *const abi::Slice, len: usize) -> Vec<String> {
+    if len == 0 {
+        return vec![];
+    }
+    std::slice::from_raw_parts(slices, len)
+        .iter()
+        .map(|&s| String::from_utf8_lossy(bytes(s)).into_owned())
+        .collect()
+}
+
+fn time(t: DateTime<Utc>) -> abi::Time {
+    abi::Time {
+        seconds: t.timestamp(),
+        nanos: t.timestamp_subsec_nanos(),
+    }
+}
+
+fn utc(t: abi::Time) -> DateTime<Utc> {
+    DateTime::from_timestamp(t.seconds, t.nanos).unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+/// PluginError is the error of loading a plugin.
+#[derive(Error, Debug)]
+pub enum PluginError {
+    /// The plugin directory could not be read.
+    #[error("failed to read the plugin directory {}: {source}", .dir.display())]
+    ReadDir {
+        dir: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// The library could not be loaded or does not export the [`abi::ENTRY`] function, e.g.,
+    /// because it was built against another version of the ABI.
+    #[error("failed to load the plugin {}: {source}", .path.display())]
+    Load {
+        path: PathBuf,
+        #[source]
+        source: libloading::Error,
+    },
+    /// No plugin of the directory has the requested name, or the directory holds no plugin.
+    #[error("no plugin {} in {}", .name.as_deref().unwrap_or("at all"), .dir.display())]
+    NotFound { dir: PathBuf, name: Option<String> },
+    /// The directory holds several plugins and none was requested by name.
+    #[error("several plugins in {}, pick one of {names:?}", .dir.display())]
+    Ambiguous { dir: PathBuf, names: Vec<String> },
+}
+
+/// Plugin is the handler of a loaded plugin, it is a [`Mapper`]. The library is unloaded once the
+/// plugin is dropped.
+pub struct Plugin {
+    name: String,
+    vtable: abi::VTable,
+    // dropped after the handler, see the Drop impl
+    _library: Library,
+}
+
+// the handler is a MapHandler, which is Send and Sync
+unsafe impl Send for Plugin {}
+unsafe impl Sync for Plugin {}
+
+impl Plugin {
+    /// Loads the plugin at `path` and creates its handler.
+    ///
+    /// # Safety
+    ///
+    /// Loading a library runs its initialization code and the plugin is trusted to implement
+    /// [`abi`], which only holds for the libraries exporting a handler with
+    /// [`export_map_plugin!`](crate::export_map_plugin) or implementing the ABI to the letter.
+    pub unsafe fn load(path: impl AsRef<Path>) -> Result<Self, PluginError> {
+        let path = path.as_ref();
+        let load_error = |source| PluginError::Load {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        let library = Library::new(path).map_err(load_error)?;
+        let entry = *library
+            .get::<abi::Entry>(abi::ENTRY.as_bytes())
+            .map_err(load_error)?;
+        let vtable = entry();
+        Ok(Self {
+            name: String::from_utf8_lossy(bytes(vtable.name)).into_owned(),
+            vtable,
+            _library: library,
+        })
+    }
+
+    /// Returns the name the plugin was exported with.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn call(&self, input: &Input) -> Vec<Message> {
+        let keys: Vec<abi::Slice> = input.keys.iter().map(|k| slice(k.as_bytes())).collect();
+        let map_input = abi::MapInput {
+            keys: keys.as_ptr(),
+            keys_len: keys.len(),
+            value: slice(&input.value),
+            watermark: time(input.watermark),
+            event_time: time(input.event_time),
+        };
+
+        let mut results: Vec<Message> = vec![];
+        // SAFETY: the plugin implements the ABI, see `load`, and the input outlives the call
+        let status = unsafe {
+            (self.vtable.map)(
+                self.vtable.state,
+                &map_input,
+                emit,
+                &mut results as *mut Vec<Message> as *mut c_void,
+            )
+        };
+        if status != abi::OK {
+            panic!("map plugin {} failed with status {}", self.name, status);
+        }
+        results
+    }
+}
+
+unsafe extern "C" fn emit(ctx: *mut c_void, output: *const abi::MapOutput) {
+    let results = &mut *(ctx as *mut Vec<Message>);
+    let output = &*output;
+    results.push(Message {
+        keys: strings(output.keys, output.keys_len),
+        value: Bytes::copy_from_slice(bytes(output.value)),
+        tags: strings(output.tags, output.tags_len),
+    });
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        // SAFETY: the handler is dropped once, while the library is still loaded
+        unsafe { (self.vtable.drop)(self.vtable.state) }
+    }
+}
+
+#[async_trait]
+impl Mapper for Plugin {
+    async fn map<T: map::Datum + Send + Sync + 'static>(&self, input: T) -> Vec<Message> {
+        self.call(&Input {
+            keys: input.keys().clone(),
+            value: input.value().clone(),
+            watermark: input.watermark(),
+            event_time: input.event_time(),
+            headers: Headers::default(),
+        })
+    }
+}
+
+/// Loads the plugins of `dir`, i.e., its files with the extension of the dynamic libraries of the
+/// platform (`.so` on Linux), in the order of their paths.
+///
+/// # Safety
+///
+/// Every plugin of the directory is loaded, see [`Plugin::load`].
+pub unsafe fn discover(dir: impl AsRef<Path>) -> Result<Vec<Plugin>, PluginError> {
+    let dir = dir.as_ref();
+    let read_dir_error = |source| PluginError::ReadDir {
+        dir: dir.to_path_buf(),
+        source,
+    };
+
+    let mut paths = vec![];
+    for entry in fs::read_dir(dir).map_err(read_dir_error)? {
+        let path = entry.map_err(read_dir_error)?.path();
+        if path.is_file()
+            && path.extension().and_then(|e| e.to_str()) == Some(std::env::consts::DLL_EXTENSION)
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    paths.into_iter().map(|path| Plugin::load(path)).collect()
+}
+
+/// Loads the plugins of `dir` and returns the one named `name`, or the only one when no name is
+/// given. The other plugins are unloaded.
+///
+/// # Safety
+///
+/// Every plugin of the directory is loaded, see [`Plugin::load`].
+pub unsafe fn load_dir(dir: impl AsRef<Path>, name: Option<&str>) -> Result<Plugin, PluginError> {
+    let dir = dir.as_ref();
+    let mut plugins = discover(dir)?;
+    let not_found = || PluginError::NotFound {
+        dir: dir.to_path_buf(),
+        name: name.map(str::to_string),
+    };
+
+    match name {
+        Some(name) => {
+            let position = plugins
+                .iter()
+                .position(|p| p.name == name)
+                .ok_or_else(not_found)?;
+            Ok(plugins.swap_remove(position))
+        }
+        None if plugins.len() > 1 => Err(PluginError::Ambiguous {
+            dir: dir.to_path_buf(),
+            names: plugins.iter().map(|p| p.name.clone()).collect(),
+        }),
+        None => plugins.pop().ok_or_else(not_found),
+    }
+}
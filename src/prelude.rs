@@ -0,0 +1,56 @@
+//! The `Server`, `Message` and `Datum` of the UDF kinds share their names, hence the prelude
+//! brings the modules of the UDF kinds into scope for them, e.g., `map::Server`, along with the
+//! handler traits. The `Datum` traits are imported anonymously, so that their methods can be
+//! called without naming them.
+//!
+//! ```no_run
+//! use numaflow::prelude::*;
+//!
+//! struct Cat;
+//!
+//! #[async_trait]
+//! impl Mapper for Cat {
+//!     async fn map<T: map::Datum + Send + Sync + 'static>(&self, input: T) -> Vec<map::Message> {
+//!         vec![map::Message {
+//!             keys: input.keys().clone(),
+//!             value: input.value().clone(),
+//!             tags: vec![],
+//!         }]
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), BoxError> {
+//!     map::Server::new(Cat).start().await
+//! }
+//! ```
+
+pub use crate::{
+    accumulator, batchmap, map, mapstream, reduce, sideinput, sink, source, sourcetransform,
+};
+
+pub use crate::accumulator::Accumulator;
+pub use crate::batchmap::BatchMapper;
+pub use crate::map::Mapper;
+pub use crate::mapstream::MapStreamer;
+pub use crate::reduce::{Metadata, Reducer};
+pub use crate::sideinput::SideInputer;
+pub use crate::sink::Sinker;
+pub use crate::source::Sourcer;
+pub use crate::sourcetransform::SourceTransformer;
+
+pub use crate::accumulator::Datum as _;
+pub use crate::batchmap::Datum as _;
+pub use crate::map::Datum as _;
+pub use crate::mapstream::Datum as _;
+pub use crate::reduce::Datum as _;
+pub use crate::sink::Datum as _;
+pub use crate::sourcetransform::Datum as _;
+
+pub use crate::buffer::BufferPool;
+pub use crate::shared::BoxError;
+
+pub use bytes::Bytes;
+pub use chrono::{DateTime, Utc};
+pub use tokio::sync::mpsc;
+pub use tonic::async_trait;
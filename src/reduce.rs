@@ -1,18 +1,40 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use bytes::Bytes;
 use chrono::{DateTime, TimeZone, Utc};
+use futures_util::future::{self, BoxFuture, Either};
+use futures_util::FutureExt;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::Sender;
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
 use tokio::task::JoinSet;
 use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{Stream, StreamExt};
 use tonic::metadata::MetadataMap;
+use tonic::transport::Channel;
 use tonic::{async_trait, Request, Response, Status};
+use tracing::Instrument;
 
+use crate::error::{self, ErrorKind};
+use crate::headers::Headers;
+use crate::keys::{self, Interned, KeyInterner};
+use crate::local::Element;
+use crate::message::{self, MessageBuilder};
 use crate::reduce::reducer::{
-    reduce_response, reduce_server, ReadyResponse, ReduceRequest, ReduceResponse,
+    reduce_client, reduce_response, reduce_server, ReadyResponse, ReduceRequest, ReduceResponse,
 };
-use crate::shared;
+use crate::shared::HandlerSettings;
+use crate::state::{FileStore, StateError, StateStore};
+use crate::timestamp::TimestampCache;
+use crate::{cputime, metrics, shared, tasks, trace};
 
 use self::reducer::reduce_server::Reduce;
 
@@ -20,8 +42,67 @@ mod reducer {
     tonic::include_proto!("reduce.v1");
 }
 
-struct ReduceService<T> {
+/// The gRPC messages, client and server of the reduce protocol, generated from its proto.
+pub mod proto {
+    pub use super::reducer::*;
+}
+
+pub(crate) struct ReduceService<T> {
     handler: Arc<T>,
+    task_channel_size: usize,
+    response_channel_size: usize,
+    response_high_watermark: Option<usize>,
+    max_concurrent_keys: Option<usize>,
+    inflight_bytes: Option<InflightBytes>,
+    burst_buffer: Option<BurstBuffer>,
+    max_window_duration: Duration,
+    key_limits: KeyLimits,
+    key_policy: KeyPolicy,
+    key_join_delimiter: char,
+    contextual_logging: bool,
+    panic_policy: PanicPolicy,
+    handler_timeout: Option<Duration>,
+    drain_timeout: Option<Duration>,
+    // number of the keys taking the most CPU time reported per window
+    top_keys: Option<usize>,
+    #[cfg(feature = "unstable")]
+    state_store: Option<Arc<dyn StateStore>>,
+    // the store and the interval of the checkpoints of the windows
+    checkpoints: Option<(Arc<dyn StateStore>, Duration)>,
+    // flips to true once the server is shutting down
+    shutdown: watch::Receiver<bool>,
+    flush: FlushHandle,
+    settings: HandlerSettings,
+}
+
+// the handler is shared, not cloned
+impl<T> Clone for ReduceService<T> {
+    fn clone(&self) -> Self {
+        Self {
+            handler: Arc::clone(&self.handler),
+            task_channel_size: self.task_channel_size,
+            response_channel_size: self.response_channel_size,
+            response_high_watermark: self.response_high_watermark,
+            max_concurrent_keys: self.max_concurrent_keys,
+            inflight_bytes: self.inflight_bytes.clone(),
+            burst_buffer: self.burst_buffer,
+            max_window_duration: self.max_window_duration,
+            key_limits: self.key_limits.clone(),
+            key_policy: self.key_policy.clone(),
+            key_join_delimiter: self.key_join_delimiter,
+            contextual_logging: self.contextual_logging,
+            panic_policy: self.panic_policy,
+            handler_timeout: self.handler_timeout,
+            drain_timeout: self.drain_timeout,
+            top_keys: self.top_keys,
+            #[cfg(feature = "unstable")]
+            state_store: self.state_store.clone(),
+            checkpoints: self.checkpoints.clone(),
+            shutdown: self.shutdown.clone(),
+            flush: self.flush.clone(),
+            settings: self.settings,
+        }
+    }
 }
 
 /// Trait implemented Reduce reduce handler.
@@ -36,8 +117,8 @@ pub trait Reducer {
     ///
     /// Below is a reduce code to count the number of elements for a given set of keys and window.
     ///
-    /// ```rust
-    // use numaflow::reduce::start_uds_server;
+    /// ```no_run
+    /// use numaflow::reduce::start_uds_server;
     /// #[tokio::main]
     /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
     ///     let reduce_handler = counter::Counter::new();
@@ -71,11 +152,7 @@ pub trait Reducer {
     ///             while (input.recv().await).is_some() {
     ///                 counter += 1;
     ///             }
-    ///             vec![Message {
-    ///                 keys: keys.clone(),
-    ///                 value: counter.to_string().into_bytes(),
-    ///                 tags: vec![],
-    ///             }]
+    ///             vec![Message::new(keys.clone(), counter.to_string().into_bytes(), vec![])]
     ///         }
     ///     }
     /// }
@@ -90,25 +167,153 @@ pub trait Reducer {
 }
 
 /// IntervalWindow is the start and end boundary of the window.
-struct IntervalWindow {
+#[derive(Clone)]
+pub struct IntervalWindow {
     // st is start time
     st: DateTime<Utc>,
     // et is end time
     et: DateTime<Utc>,
+    // slot tells apart the windows with the same boundaries, e.g., the partitions of a reduce
+    slot: String,
+    // task_id identifies the task of the keys in the window
+    task_id: String,
+    // window_id is the stable identifier of the keys in the window
+    window_id: String,
+    // abort_signal is fired when the inbound stream of the window errors out or the window is cut
+    // off by the shutdown of the server
+    abort_signal: AbortSignal,
+    // watermark of the input of the window
+    watermark: Watermark,
+    // state_store keeps the values of the keys across the windows
+    #[cfg(feature = "unstable")]
+    state_store: Option<Arc<dyn StateStore>>,
+    // checkpoint saves the partial aggregate of the window
+    checkpoint: Checkpoint,
 }
 
 impl IntervalWindow {
-    fn new(st: DateTime<Utc>, et: DateTime<Utc>) -> Self {
-        Self { st, et }
+    pub(crate) fn new(
+        st: DateTime<Utc>,
+        et: DateTime<Utc>,
+        slot: String,
+        task_id: String,
+        window_id: String,
+        abort_signal: AbortSignal,
+        watermark: Watermark,
+    ) -> Self {
+        Self {
+            st,
+            et,
+            slot,
+            task_id,
+            window_id,
+            abort_signal,
+            watermark,
+            #[cfg(feature = "unstable")]
+            state_store: None,
+            checkpoint: Checkpoint::disabled(),
+        }
+    }
+
+    #[cfg(feature = "unstable")]
+    pub(crate) fn with_state_store(mut self, store: Option<Arc<dyn StateStore>>) -> Self {
+        self.state_store = store;
+        self
+    }
+
+    pub(crate) fn with_checkpoint(mut self, checkpoint: Checkpoint) -> Self {
+        self.checkpoint = checkpoint;
+        self
+    }
+
+    /// Returns a copy of the window the metadata describes, for the handlers taking the window by
+    /// value.
+    pub(crate) fn from_metadata<U: Metadata>(md: &U) -> Self {
+        let window = Self::new(
+            *md.start_time(),
+            *md.end_time(),
+            md.slot().to_string(),
+            md.task_id().to_string(),
+            md.window_id().to_string(),
+            md.abort_signal().clone(),
+            md.watermark().clone(),
+        )
+        .with_checkpoint(md.checkpoint().clone());
+        #[cfg(feature = "unstable")]
+        let window = window.with_state_store(md.state_store().cloned());
+        window
     }
 }
 
-/// Metadata are additional information passed into the [`FnHandler::reduce_handle`].
+/// Returns the [window id](Metadata::window_id) of the keys in the window.
+pub(crate) fn window_id(
+    keys: &[String],
+    st: DateTime<Utc>,
+    et: DateTime<Utc>,
+    slot: &str,
+) -> String {
+    let boundaries = [st.timestamp_millis(), et.timestamp_millis()].map(i64::to_le_bytes);
+    // 0xff is not part of any UTF-8 string, hence it delimits the slot and the keys unambiguously
+    let fields = std::iter::once(slot).chain(keys.iter().map(String::as_str));
+    let bytes = boundaries
+        .iter()
+        .flatten()
+        .copied()
+        .chain(fields.flat_map(|field| field.bytes().chain(std::iter::once(0xff))));
+    format!("{:016x}", shared::fnv1a(bytes))
+}
+
+/// Returns the [task id](Metadata::task_id) of the keys, already joined, in the window.
+pub(crate) fn task_id(
+    joined_keys: &str,
+    st: DateTime<Utc>,
+    et: DateTime<Utc>,
+    slot: &str,
+) -> String {
+    format!("{}@{}#{}", joined_keys, window_name(st, et), slot)
+}
+
+// the boundaries of the window in milliseconds, as in the logs and the task ids
+fn window_name(st: DateTime<Utc>, et: DateTime<Utc>) -> String {
+    format!("{}..{}", st.timestamp_millis(), et.timestamp_millis())
+}
+
+/// Metadata are additional information passed into the [`Reducer::reduce`].
 pub trait Metadata {
     /// start_time is the window start time.
     fn start_time(&self) -> &DateTime<Utc>;
     /// end_time is the window end time.
     fn end_time(&self) -> &DateTime<Utc>;
+    /// slot is the slot of the window, the windows with the same boundaries are told apart by
+    /// their slots. It is empty for the windows of the gRPC streams, whose protocol does not
+    /// carry it.
+    fn slot(&self) -> &str;
+    /// task_id identifies the handle of the keys in the window, it is meant for debugging, e.g.,
+    /// in the logs. It is made of the keys joined by the [key join delimiter], the start and end
+    /// times of the window in milliseconds and the slot, as `keys@start..end#slot`.
+    ///
+    /// [key join delimiter]: Server::with_key_join_delimiter
+    fn task_id(&self) -> &str;
+    /// window_id is a stable identifier of the keys in the window, the same across the restarts
+    /// and the replicas of the UDF unlike the task id, e.g., to key the state of the window in a
+    /// store or to correlate the logs, the metrics and the audits of the window. It is the FNV-1a
+    /// hash of the window boundaries, the slot and the keys, as 16 hex digits.
+    fn window_id(&self) -> &str;
+    /// abort_signal notifies the handler when the inbound stream of the window errors out, the
+    /// server shuts down before the input of the window is complete or the window does not finish
+    /// within the drain timeout of a shutdown.
+    fn abort_signal(&self) -> &AbortSignal;
+    /// watermark is the watermark of the input of the window, it moves on while the window is
+    /// reduced, e.g., to emit provisional results once it passes a point in time.
+    fn watermark(&self) -> &Watermark;
+    /// state_store keeps values beyond the window, e.g., a running total of the keys, it is None
+    /// unless the server has one (see [`Server::with_state_store`]). It is an unstable API, only
+    /// available with the `unstable` feature.
+    #[cfg(feature = "unstable")]
+    fn state_store(&self) -> Option<&Arc<dyn StateStore>>;
+    /// checkpoint saves and restores the partial aggregate of the window, so that a long window
+    /// survives the restart of the UDF (see [`Server::with_checkpoint_interval`]).
+    fn checkpoint(&self) -> &Checkpoint;
 }
 
 impl Metadata for IntervalWindow {
@@ -119,6 +324,252 @@ impl Metadata for IntervalWindow {
     fn end_time(&self) -> &DateTime<Utc> {
         &self.et
     }
+
+    fn slot(&self) -> &str {
+        &self.slot
+    }
+
+    fn task_id(&self) -> &str {
+        &self.task_id
+    }
+
+    fn window_id(&self) -> &str {
+        &self.window_id
+    }
+
+    fn abort_signal(&self) -> &AbortSignal {
+        &self.abort_signal
+    }
+
+    fn watermark(&self) -> &Watermark {
+        &self.watermark
+    }
+
+    #[cfg(feature = "unstable")]
+    fn state_store(&self) -> Option<&Arc<dyn StateStore>> {
+        self.state_store.as_ref()
+    }
+
+    fn checkpoint(&self) -> &Checkpoint {
+        &self.checkpoint
+    }
+}
+
+/// Checkpoint saves the partial aggregate of a window, the snapshot, so that the handler of the
+/// window restarted along with the UDF in the middle of a long window resumes from it instead of
+/// starting over, see [`Metadata::checkpoint`]. The snapshots are kept by the window id, the same
+/// across the restarts, and are removed once the results of the window are sent.
+///
+/// The handler restores the snapshot when it starts and saves one whenever [`Checkpoint::due`]
+/// fires, every [checkpoint interval](Server::with_checkpoint_interval). The elements numaflow
+/// replays to the restarted window may already be in the snapshot, hence the snapshot should tell
+/// what it covers, e.g., the ids or the event times of its elements, for the recovery to be
+/// at-least-once rather than to double count. Checkpoints are off by default, then there is
+/// nothing to restore and the checkpoint is never due.
+///
+/// # Example
+///
+/// ```no_run
+/// use numaflow::reduce::{self, Datum, Message, Metadata, Reducer};
+/// use tokio::sync::mpsc::Receiver;
+///
+/// struct Counter;
+///
+/// #[tonic::async_trait]
+/// impl Reducer for Counter {
+///     async fn reduce<T: Datum + Send + Sync + 'static, U: Metadata + Send + Sync + 'static>(
+///         &self,
+///         keys: Vec<String>,
+///         mut input: Receiver<T>,
+///         md: &U,
+///     ) -> Vec<Message> {
+///         let checkpoint = md.checkpoint();
+///         let mut count: u64 = match checkpoint.restore().await {
+///             Ok(Some(snapshot)) => String::from_utf8_lossy(&snapshot).parse().unwrap_or(0),
+///             _ => 0,
+///         };
+///         loop {
+///             tokio::select! {
+///                 datum = input.recv() => match datum {
+///                     Some(_) => count += 1,
+///                     None => break,
+///                 },
+///                 _ = checkpoint.due() => {
+///                     if let Err(e) = checkpoint.save(count.to_string().into()).await {
+///                         eprintln!("failed to checkpoint the count: {}", e);
+///                     }
+///                 }
+///             }
+///         }
+///         vec![Message::new(keys, count.to_string(), vec![])]
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+///     reduce::Server::new(Counter)
+///         .with_checkpoint_interval(std::time::Duration::from_secs(30))
+///         .start()
+///         .await
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Checkpoint {
+    store: Option<Arc<dyn StateStore>>,
+    interval: Duration,
+    key: String,
+    // when the checkpoint is due next, it is shared by the clones
+    next: Arc<std::sync::Mutex<tokio::time::Instant>>,
+}
+
+impl Checkpoint {
+    pub(crate) fn new(store: Arc<dyn StateStore>, interval: Duration, window_id: &str) -> Self {
+        Self {
+            store: Some(store),
+            interval,
+            key: format!("numaflow-checkpoint-{}", window_id),
+            next: Arc::new(std::sync::Mutex::new(
+                tokio::time::Instant::now() + interval,
+            )),
+        }
+    }
+
+    pub(crate) fn disabled() -> Self {
+        Self {
+            store: None,
+            interval: Duration::ZERO,
+            key: String::new(),
+            next: Arc::new(std::sync::Mutex::new(tokio::time::Instant::now())),
+        }
+    }
+
+    /// Returns whether the checkpoints are on.
+    pub fn is_enabled(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// Returns the last snapshot saved for the window, None if there is none, e.g., because the
+    /// window has just been opened.
+    pub async fn restore(&self) -> Result<Option<Bytes>, StateError> {
+        match &self.store {
+            Some(store) => store.get(&self.key).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Saves the snapshot of the window, replacing the previous one.
+    pub async fn save(&self, snapshot: Bytes) -> Result<(), StateError> {
+        match &self.store {
+            Some(store) => store.put(&self.key, snapshot).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Resolves once a snapshot is due, i.e., a checkpoint interval after the window was opened
+    /// or after it was last due. It is meant to be raced against the input, a cancelled call
+    /// leaves the schedule as is. It never resolves when the checkpoints are off.
+    pub async fn due(&self) {
+        if self.store.is_none() {
+            return std::future::pending().await;
+        }
+        let next = *self.next.lock().expect("checkpoint lock is poisoned");
+        tokio::time::sleep_until(next).await;
+        *self.next.lock().expect("checkpoint lock is poisoned") =
+            tokio::time::Instant::now() + self.interval;
+    }
+
+    // removes the snapshot of the window once its results are out
+    pub(crate) async fn clear(&self) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.delete(&self.key).await {
+                tracing::warn!(key = %self.key, error = %e, "failed to remove the checkpoint");
+            }
+        }
+    }
+}
+
+/// Watermark is the highest [watermark](https://numaflow.numaproj.io/core-concepts/watermarks/)
+/// of the elements received so far by the stream of a window, see [`Metadata::watermark`]. It is
+/// shared by all the windows of the stream, hence it moves on also with the elements of the other
+/// windows and keys.
+#[derive(Clone)]
+pub struct Watermark {
+    rx: watch::Receiver<DateTime<Utc>>,
+}
+
+impl Watermark {
+    pub(crate) fn new(rx: watch::Receiver<DateTime<Utc>>) -> Self {
+        Self { rx }
+    }
+
+    /// A watermark which does not move, for the windows whose input is known up front.
+    pub(crate) fn fixed(watermark: DateTime<Utc>) -> Self {
+        Self::new(watch::channel(watermark).1)
+    }
+
+    /// current returns the watermark as of now.
+    pub fn current(&self) -> DateTime<Utc> {
+        *self.rx.borrow()
+    }
+
+    /// changed resolves with the watermark once it moves on, or with None once it does not move
+    /// anymore because the input of the stream is over.
+    pub async fn changed(&mut self) -> Option<DateTime<Utc>> {
+        self.rx.changed().await.ok()?;
+        Some(*self.rx.borrow_and_update())
+    }
+}
+
+/// StreamAborted is the notification sent to the active [`Reducer::reduce`] handles when the
+/// inbound gRPC stream of the window errors out, when the server shuts down before the input of
+/// the window is complete, or when the handles do not finish within the drain timeout of a
+/// shutdown (see [`Server::with_drain_timeout`]). The results of an aborted window are discarded,
+/// hence the handlers may stop their work right away.
+#[derive(Debug, Clone)]
+pub struct StreamAborted {
+    /// reason is the error the inbound stream failed with.
+    pub reason: String,
+}
+
+impl fmt::Display for StreamAborted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "reduce input stream aborted: {}", self.reason)
+    }
+}
+
+impl Error for StreamAborted {}
+
+/// AbortSignal lets the [`Reducer::reduce`] handle find out that the window was aborted. It can be
+/// awaited alongside the input channel, e.g., in a `tokio::select!`.
+#[derive(Clone)]
+pub struct AbortSignal {
+    rx: watch::Receiver<Option<StreamAborted>>,
+}
+
+impl AbortSignal {
+    pub(crate) fn new(rx: watch::Receiver<Option<StreamAborted>>) -> Self {
+        Self { rx }
+    }
+
+    /// is_aborted returns the [`StreamAborted`] notification if the window has been aborted.
+    pub fn is_aborted(&self) -> Option<StreamAborted> {
+        self.rx.borrow().clone()
+    }
+
+    /// aborted resolves once the window is aborted, it never resolves for a window whose stream
+    /// completes successfully.
+    pub async fn aborted(&self) -> StreamAborted {
+        let mut rx = self.rx.clone();
+        loop {
+            if let Some(aborted) = rx.borrow_and_update().clone() {
+                return aborted;
+            }
+            if rx.changed().await.is_err() {
+                // the stream completed without errors
+                std::future::pending::<()>().await;
+            }
+        }
+    }
 }
 
 /// Message is the response from the user's [`Reducer::reduce`].
@@ -127,9 +578,70 @@ pub struct Message {
     /// be an empty collection. It is mainly used in creating a partition in [`Reducer::reduce`].
     pub keys: Vec<String>,
     /// Value is the value passed to the next vertex.
-    pub value: Vec<u8>,
+    pub value: Bytes,
     /// Tags are used for [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/).
     pub tags: Vec<String>,
+    // the event time of the message, None for the end of the window
+    event_time: Option<DateTime<Utc>>,
+}
+
+impl Message {
+    /// Create a message of the keys, the value and the tags, its event time is the end of the
+    /// window unless it is set with [`Message::with_event_time`].
+    pub fn new(keys: Vec<String>, value: impl Into<Bytes>, tags: Vec<String>) -> Self {
+        Self {
+            keys,
+            value: value.into(),
+            tags,
+            event_time: None,
+        }
+    }
+
+    /// Returns a builder of the message, it checks the message when it builds it.
+    pub fn builder() -> MessageBuilder<Self> {
+        MessageBuilder::new()
+    }
+
+    /// Returns a message numaflow drops, e.g., for the keys of a window which turned out to be
+    /// noise.
+    pub fn dropped() -> Self {
+        Self {
+            keys: vec![],
+            value: Bytes::new(),
+            tags: vec![message::DROP.to_string()],
+            event_time: None,
+        }
+    }
+
+    /// Returns whether numaflow drops the message, see [`DROP`](message::DROP).
+    pub fn is_dropped(&self) -> bool {
+        self.tags.iter().any(|tag| tag == message::DROP)
+    }
+
+    /// Sets the event time of the message, e.g., the one of the latest input it aggregates. It is
+    /// sent in the `event_time` of the result, a platform which does not read it assigns the end
+    /// of the window instead. It is honored by the local pipeline (`local::Pipeline`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{TimeZone, Utc};
+    /// use numaflow::reduce::Message;
+    ///
+    /// let latest = Utc.timestamp_millis_opt(1_700_000_000_123).unwrap();
+    /// let message = Message::new(vec!["sensor-1".to_string()], "42", vec![])
+    ///     .with_event_time(latest);
+    /// assert_eq!(message.event_time(), Some(latest));
+    /// ```
+    pub fn with_event_time(mut self, event_time: DateTime<Utc>) -> Self {
+        self.event_time = Some(event_time);
+        self
+    }
+
+    /// Returns the event time set by [`Message::with_event_time`], None for the end of the window.
+    pub fn event_time(&self) -> Option<DateTime<Utc>> {
+        self.event_time
+    }
 }
 
 /// Datum trait represents an incoming element into the reduce handle of [`Reducer`].
@@ -137,31 +649,162 @@ pub trait Datum {
     /// keys are the keys in the (key, value) terminology of map/reduce paradigm.
     fn keys(&self) -> &Vec<String>;
     /// value is the value in (key, value) terminology of map/reduce paradigm.
-    fn value(&self) -> &Vec<u8>;
+    fn value(&self) -> &Bytes;
     /// [watermark](https://numaflow.numaproj.io/core-concepts/watermarks/) represented by time is a guarantee that we will not see an element older than this
     /// time.
     fn watermark(&self) -> DateTime<Utc>;
     /// event_time is the time of the element as seen at source or aligned after a reduce operation.
     fn event_time(&self) -> DateTime<Utc>;
+    /// headers are the user defined headers set by the upstream vertices, e.g., tracing IDs or
+    /// tenant tags.
+    fn headers(&self) -> &Headers;
+}
+
+/// A shared element is a datum too, e.g., to hand the same element over to two handles.
+impl<D: Datum + ?Sized> Datum for Arc<D> {
+    fn keys(&self) -> &Vec<String> {
+        (**self).keys()
+    }
+
+    fn value(&self) -> &Bytes {
+        (**self).value()
+    }
+
+    fn watermark(&self) -> DateTime<Utc> {
+        (**self).watermark()
+    }
+
+    fn event_time(&self) -> DateTime<Utc> {
+        (**self).event_time()
+    }
+
+    fn headers(&self) -> &Headers {
+        (**self).headers()
+    }
 }
 
 /// Owned copy of ReduceRequest from Datum.
 struct OwnedReduceRequest {
-    keys: Vec<String>,
-    value: Vec<u8>,
+    keys: Interned,
+    value: Bytes,
     watermark: DateTime<Utc>,
     eventtime: DateTime<Utc>,
+    headers: Arc<Headers>,
+    // the share of the in-flight bytes budget held by the element
+    _inflight: Option<OwnedSemaphorePermit>,
 }
 
 impl OwnedReduceRequest {
-    fn new(mr: ReduceRequest) -> Self {
+    fn new(
+        mut mr: ReduceRequest,
+        keys: Interned,
+        watermarks: &mut TimestampCache,
+        settings: HandlerSettings,
+    ) -> Self {
+        let mut headers = Headers::from(mr.headers);
+        settings.decompress_payload(&mut headers, &mut mr.value);
         Self {
-            keys: mr.keys,
+            keys,
             value: mr.value,
-            watermark: shared::utc_from_timestamp(mr.watermark),
+            watermark: watermarks.convert(mr.watermark.as_ref()),
             eventtime: shared::utc_from_timestamp(mr.event_time),
+            headers: Arc::new(headers),
+            _inflight: None,
+        }
+    }
+}
+
+// the budget of the payload bytes held by the handles, a byte is a permit of the semaphore
+#[derive(Clone)]
+struct InflightBytes {
+    semaphore: Arc<Semaphore>,
+    max: usize,
+}
+
+impl InflightBytes {
+    fn new(max: usize) -> Self {
+        let max = max.min(Semaphore::MAX_PERMITS);
+        Self {
+            semaphore: Arc::new(Semaphore::new(max)),
+            max,
         }
     }
+
+    // waits for the bytes of the payload to fit in the budget, a payload larger than the whole
+    // budget waits for all of it
+    async fn acquire(&self, len: usize) -> OwnedSemaphorePermit {
+        let permits = len.min(self.max).min(u32::MAX as usize) as u32;
+        self.semaphore
+            .clone()
+            .acquire_many_owned(permits)
+            .await
+            .expect("in-flight bytes semaphore is never closed")
+    }
+}
+
+// the elements of a stream read ahead of their dispatch to the handles, bounded by their number
+// and the bytes of their payloads
+#[derive(Debug, Clone, Copy)]
+struct BurstBuffer {
+    messages: usize,
+    bytes: usize,
+}
+
+// an element in the burst buffer, it holds its bytes until it leaves the buffer
+struct Buffered {
+    element: Option<Result<ReduceRequest, Status>>,
+    bytes: OwnedSemaphorePermit,
+}
+
+impl Buffered {
+    fn new(element: Result<ReduceRequest, Status>, bytes: OwnedSemaphorePermit) -> Self {
+        metrics::reduce_burst_buffered(1, bytes.num_permits() as i64);
+        Self {
+            element: Some(element),
+            bytes,
+        }
+    }
+}
+
+impl Drop for Buffered {
+    fn drop(&mut self) {
+        metrics::reduce_burst_buffered(-1, -(self.bytes.num_permits() as i64));
+    }
+}
+
+impl BurstBuffer {
+    // reads the stream ahead in a task of its own, so that a burst is taken off the transport
+    // while the dispatch waits on the handles. A payload larger than all the bytes takes all of
+    // them.
+    fn read_ahead<S>(self, mut stream: S) -> impl Stream<Item = Result<ReduceRequest, Status>>
+    where
+        S: Stream<Item = Result<ReduceRequest, Status>> + Unpin + Send + 'static,
+    {
+        let bytes = self
+            .bytes
+            .clamp(1, Semaphore::MAX_PERMITS.min(u32::MAX as usize));
+        let semaphore = Arc::new(Semaphore::new(bytes));
+        let (tx, rx) = mpsc::channel::<Buffered>(self.messages.max(1));
+        tasks::spawn("reduce:burst-buffer", async move {
+            while let Some(element) = stream.next().await {
+                let len = element
+                    .as_ref()
+                    .map_or(0, |datum| datum.value.len())
+                    .min(bytes);
+                let permit = tokio::select! {
+                    permit = semaphore.clone().acquire_many_owned(len as u32) => permit,
+                    // the stream is not dispatched anymore
+                    _ = tx.closed() => break,
+                };
+                let Ok(permit) = permit else { break };
+                let failed = element.is_err();
+                if tx.send(Buffered::new(element, permit)).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+        ReceiverStream::new(rx).filter_map(|mut buffered| buffered.element.take())
+    }
 }
 
 impl Datum for OwnedReduceRequest {
@@ -169,7 +812,7 @@ impl Datum for OwnedReduceRequest {
         &self.keys
     }
 
-    fn value(&self) -> &Vec<u8> {
+    fn value(&self) -> &Bytes {
         &self.value
     }
 
@@ -180,39 +823,238 @@ impl Datum for OwnedReduceRequest {
     fn event_time(&self) -> DateTime<Utc> {
         self.eventtime
     }
+
+    fn headers(&self) -> &Headers {
+        &self.headers
+    }
 }
 
-// key delimiter
-const KEY_JOIN_DELIMITER: &str = ":";
 // grpc window metadata
 const WIN_START_TIME: &str = "x-numaflow-win-start-time";
 const WIN_END_TIME: &str = "x-numaflow-win-end-time";
 
+// interval of the progress reports of a draining stream
+const DRAIN_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
 // extract start and end time from the gRPC MetadataMap
 // https://youtu.be/s5S2Ed5T-dc?t=662
-fn get_window_details(request: &MetadataMap) -> (DateTime<Utc>, DateTime<Utc>) {
-    let (st, et) = (
-        request
-            .get(WIN_START_TIME)
-            .unwrap_or_else(|| panic!("expected key {}", WIN_START_TIME))
-            .to_str()
-            .unwrap()
-            .to_string()
-            .parse::<i64>()
-            .unwrap(),
-        request
-            .get(WIN_END_TIME)
-            .unwrap_or_else(|| panic!("expected key {}", WIN_END_TIME))
+fn get_window_details(request: &MetadataMap) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+    let window_time = |key: &str| {
+        let millis = request
+            .get(key)
+            .ok_or_else(|| format!("expected key {}", key))?
             .to_str()
-            .unwrap()
+            .map_err(|e| format!("{} is not a valid string: {}", key, e))?
             .parse::<i64>()
-            .unwrap(),
-    );
+            .map_err(|e| format!("{} is not a valid timestamp: {}", key, e))?;
+        Utc.timestamp_millis_opt(millis)
+            .single()
+            .ok_or_else(|| format!("{} is out of range: {}", key, millis))
+    };
+
+    Ok((window_time(WIN_START_TIME)?, window_time(WIN_END_TIME)?))
+}
+
+// a corrupt window must not flow into the user's handle
+fn validate_window(
+    st: DateTime<Utc>,
+    et: DateTime<Utc>,
+    max_window_duration: Duration,
+) -> Result<(), String> {
+    if st >= et {
+        return Err(format!(
+            "window start {} is not before the window end {}",
+            st.to_rfc3339(),
+            et.to_rfc3339()
+        ));
+    }
+    let duration = (et - st).to_std().unwrap_or(Duration::MAX);
+    if duration > max_window_duration {
+        return Err(format!(
+            "window [{}, {}) is longer than the maximum of {:?}",
+            st.to_rfc3339(),
+            et.to_rfc3339(),
+            max_window_duration
+        ));
+    }
+    Ok(())
+}
+
+// identifies a window of the stream
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct WindowId {
+    st: DateTime<Utc>,
+    et: DateTime<Utc>,
+    slot: String,
+}
+
+// limits on the keys of an element
+#[derive(Clone)]
+struct KeyLimits {
+    max_keys: Option<usize>,
+    max_key_length: Option<usize>,
+}
+
+impl KeyLimits {
+    // tells how the keys go beyond the limits, if they do
+    fn violation(&self, keys: &[String]) -> Option<String> {
+        if let Some(max_keys) = self.max_keys {
+            if keys.len() > max_keys {
+                return Some(format!(
+                    "element has {} keys, more than the maximum of {}",
+                    keys.len(),
+                    max_keys
+                ));
+            }
+        }
+        if let Some(max_key_length) = self.max_key_length {
+            if let Some(key) = keys.iter().find(|key| key.len() > max_key_length) {
+                return Some(format!(
+                    "key of {} bytes is longer than the maximum of {}",
+                    key.len(),
+                    max_key_length
+                ));
+            }
+        }
+        None
+    }
 
-    (
-        Utc.timestamp_millis_opt(st).unwrap(),
-        Utc.timestamp_millis_opt(et).unwrap(),
-    )
+    // drops the extra keys and cuts the long ones short, on a character boundary
+    fn trim(&self, keys: &mut Vec<String>) {
+        if let Some(max_keys) = self.max_keys {
+            keys.truncate(max_keys);
+        }
+        if let Some(max_key_length) = self.max_key_length {
+            for key in keys.iter_mut() {
+                if key.len() > max_key_length {
+                    let mut end = max_key_length;
+                    while !key.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    key.truncate(end);
+                }
+            }
+        }
+    }
+}
+
+// results of the reduce handle of a window and keys
+struct TaskResult {
+    window: WindowId,
+    keys: Interned,
+    messages: Result<Vec<Message>, TaskFailure>,
+    checkpoint: Checkpoint,
+    // the CPU time taken by the handle, when it is accounted
+    busy: Option<Duration>,
+}
+
+// the reading state of a stream, kept across the generations of its windows
+struct StreamState<S> {
+    stream: S,
+    stream_window: Result<WindowId, String>,
+    abort_tx: watch::Sender<Option<StreamAborted>>,
+    abort_signal: AbortSignal,
+    // the tasks and the elements of the same keys share a single copy of them
+    interner: KeyInterner,
+    watermarks: TimestampCache,
+    // the highest watermark of the stream, it seeds the watermark of every generation
+    watermark: DateTime<Utc>,
+    flush: watch::Receiver<u64>,
+    // the element read but not sent to a handle when the windows were flushed
+    pending: Option<OwnedReduceRequest>,
+}
+
+// the handles of the windows read until the end of the stream or a flush
+struct Generation {
+    set: JoinSet<TaskResult>,
+    // the number of handles of every window still running
+    running: HashMap<WindowId, usize>,
+    // whether the windows are closed by a flush
+    flushed: bool,
+    // the tasks which missed elements of their keys, their results are not complete
+    missed: HashSet<(WindowId, Interned)>,
+}
+
+// why the reduce handle of a window and keys has no results
+enum TaskFailure {
+    // the handle panicked, with the panic message
+    Panicked(String),
+    // the handle did not finish within the handler timeout once its input was closed
+    TimedOut,
+}
+
+/// Warns once the response queue fills up over its high watermark, i.e., numaflow does not read the
+/// results as fast as the windows close. The warning is re-armed once the queue is back under half
+/// of the high watermark.
+struct HighWatermark {
+    level: Option<usize>,
+    saturated: bool,
+}
+
+impl HighWatermark {
+    fn observe<T>(&mut self, tx: &Sender<T>) {
+        let Some(level) = self.level else { return };
+        let queued = tx.max_capacity() - tx.capacity();
+        if !self.saturated && queued >= level {
+            self.saturated = true;
+            metrics::response_queue_saturated("reduce");
+            tracing::warn!(
+                queued,
+                capacity = tx.max_capacity(),
+                "reduce response queue is saturated, the results are waiting for numaflow to \
+                 read them and the windows are backpressured"
+            );
+        } else if self.saturated && queued < level.div_ceil(2) {
+            self.saturated = false;
+        }
+    }
+}
+
+// marks a reduce task as done in the metrics when dropped
+struct TaskDone;
+
+impl Drop for TaskDone {
+    fn drop(&mut self) {
+        metrics::reduce_task_finished();
+    }
+}
+
+// the handles of a draining stream, counted in the metrics until the stream is done
+#[derive(Default)]
+struct DrainingTasks(usize);
+
+impl DrainingTasks {
+    fn set(&mut self, remaining: usize) {
+        metrics::reduce_draining_tasks(remaining as i64 - self.0 as i64);
+        self.0 = remaining;
+    }
+}
+
+impl Drop for DrainingTasks {
+    fn drop(&mut self) {
+        self.set(0);
+    }
+}
+
+/// Lets the active handles of the stream know that it is aborted and discards their results,
+/// partial results of an aborted window must not be emitted. Returns the `status` to fail the
+/// stream with.
+fn abort_window(
+    status: Status,
+    abort_tx: &watch::Sender<Option<StreamAborted>>,
+    task_to_tx: HashMap<(WindowId, Interned), Sender<OwnedReduceRequest>>,
+    mut set: JoinSet<TaskResult>,
+) -> Status {
+    // let the active handlers know right away so that they can stop their work
+    let _ = abort_tx.send(Some(StreamAborted {
+        reason: status.message().to_string(),
+    }));
+    // close the input channels and discard whatever the handlers return
+    drop(task_to_tx);
+    tasks::spawn("reduce:abort-cleanup", async move {
+        while set.join_next().await.is_some() {}
+    });
+    status
 }
 
 #[async_trait]
@@ -225,100 +1067,1587 @@ where
         &self,
         request: Request<tonic::Streaming<ReduceRequest>>,
     ) -> Result<Response<Self::ReduceFnStream>, Status> {
-        // get gRPC window from metadata
-        let (start_win, end_win) = get_window_details(request.metadata());
-        let md = Arc::new(IntervalWindow::new(start_win, end_win));
+        let metadata = request.metadata().clone();
+        self.reduce_stream(&metadata, request.into_inner())
+            .await
+            .map(Response::new)
+    }
+
+    async fn is_ready(&self, _: Request<()>) -> Result<Response<ReadyResponse>, Status> {
+        Ok(Response::new(ReadyResponse { ready: true }))
+    }
+}
+
+impl<T> ReduceService<T>
+where
+    T: Reducer + Send + Sync + 'static,
+{
+    /// Reduces the elements of a stream, whether read off the wire or translated from another
+    /// protocol, and returns the stream of the results of its windows. The stream is read in the
+    /// background, its errors are sent on the returned stream.
+    pub(crate) async fn reduce_stream<S>(
+        &self,
+        metadata: &MetadataMap,
+        stream: S,
+    ) -> Result<ReceiverStream<Result<ReduceResponse, Status>>, Status>
+    where
+        S: Stream<Item = Result<ReduceRequest, Status>> + Unpin + Send + 'static,
+    {
+        let stream = match self.burst_buffer {
+            Some(burst_buffer) => Either::Left(Box::pin(burst_buffer.read_ahead(stream))),
+            None => Either::Right(stream),
+        };
+        // the window of the stream set by the metadata, the protocol does not carry a slot. It is
+        // only required once an element comes in, an empty stream may not have one.
+        let stream_window = get_window_details(metadata).and_then(|(st, et)| {
+            validate_window(st, et, self.max_window_duration)?;
+            Ok(WindowId {
+                st,
+                et,
+                slot: String::new(),
+            })
+        });
+        let (abort_tx, abort_rx) = watch::channel(None);
+        let mut state = StreamState {
+            stream,
+            stream_window,
+            abort_tx,
+            abort_signal: AbortSignal::new(abort_rx),
+            interner: KeyInterner::default(),
+            watermarks: TimestampCache::default(),
+            watermark: shared::utc_from_timestamp(None),
+            flush: self.flush.subscribe(),
+            pending: None,
+        };
+
+        // channel to respond to numaflow main car as it expects streaming results.
+        let (tx, rx) = mpsc::channel::<Result<ReduceResponse, Status>>(self.response_channel_size);
+
+        let service = self.clone();
+        tasks::spawn("reduce:stream", async move {
+            loop {
+                let generation = match service.read_generation(&mut state).await {
+                    Ok(generation) => generation,
+                    Err(status) => {
+                        let _ = tx.send(Err(status)).await;
+                        return;
+                    }
+                };
+                let flushed = generation.flushed;
+                // the stream is read again once the results of the flushed windows are out, the
+                // elements not read yet wait in the stream meanwhile
+                if !service
+                    .write_results(generation, &tx, &state.abort_tx)
+                    .await
+                    || !flushed
+                {
+                    return;
+                }
+            }
+        });
+
+        // return the rx as the streaming endpoint
+        Ok(ReceiverStream::new(rx))
+    }
 
-        let mut key_to_tx: HashMap<String, Sender<OwnedReduceRequest>> = HashMap::new();
+    // Reads the stream and spawns the handles of its keys until the end of the stream or a flush,
+    // then closes the inputs of the handles. An error or the shutdown of the server fails the
+    // stream, the handles are aborted.
+    async fn read_generation<S>(&self, state: &mut StreamState<S>) -> Result<Generation, Status>
+    where
+        S: Stream<Item = Result<ReduceRequest, Status>> + Unpin + Send + 'static,
+    {
+        // flips to true once the inputs of the handles are closed
+        let (input_closed_tx, input_closed_rx) = watch::channel(false);
+
+        let mut task_to_tx: HashMap<(WindowId, Interned), Sender<OwnedReduceRequest>> =
+            HashMap::new();
+        // the highest watermark of the stream, passed to the handles in the metadata
+        let (watermark_tx, watermark_rx) = watch::channel(state.watermark);
 
         // we will be creating a set of tasks for this stream
         let mut set = JoinSet::new();
 
-        let mut stream = request.into_inner();
-
-        while let Some(datum) = stream.message().await.unwrap() {
-            let task_name = datum.keys.join(KEY_JOIN_DELIMITER);
+        let mut shutdown = self.shutdown.clone();
+        // a flush asked while the previous windows were flushed is already served
+        state.flush.borrow_and_update();
+        let flush = &mut state.flush;
+        // whether the windows are closed by a flush rather than by the end of the stream
+        let mut flushed = false;
+        // the tasks whose handle returned before an element of its keys was sent to it
+        let mut missed = HashSet::new();
 
-            if let Some(tx) = key_to_tx.get(&task_name) {
-                tx.send(OwnedReduceRequest::new(datum)).await.unwrap();
+        'read: loop {
+            // the element read while the previous windows were flushed goes first
+            let mut datum = if let Some(datum) = state.pending.take() {
+                datum
             } else {
+                let message = tokio::select! {
+                    message = state.stream.next() => message.transpose(),
+                    // the windows of a stream still reading are partial, the stream is failed so
+                    // that numaflow replays them rather than taking their results for final
+                    Ok(_) = shutdown.wait_for(|shutting_down| *shutting_down) => {
+                        Err(self.shutdown_status())
+                    }
+                    Ok(_) = flush.changed() => {
+                        flushed = true;
+                        Ok(None)
+                    }
+                };
+                let mut datum = match message {
+                    Ok(Some(datum)) => datum,
+                    Ok(None) => break,
+                    Err(e) => return Err(abort_window(e, &state.abort_tx, task_to_tx, set)),
+                };
+
+                if let Some(violation) = self.key_limits.violation(&datum.keys) {
+                    match &self.key_policy {
+                        KeyPolicy::Error => {
+                            let status = self.settings.to_status(error::Error::ReduceError(
+                                ErrorKind::InvalidArgument(violation),
+                            ));
+                            return Err(abort_window(status, &state.abort_tx, task_to_tx, set));
+                        }
+                        KeyPolicy::Trim => self.key_limits.trim(&mut datum.keys),
+                        KeyPolicy::DeadLetter(dead_letters) => {
+                            // the element is dropped if nobody is reading the dead letters anymore
+                            let _ = dead_letters
+                                .send(DeadLetter {
+                                    keys: datum.keys,
+                                    value: datum.value,
+                                    event_time: shared::utc_from_timestamp(datum.event_time),
+                                    headers: datum.headers.into(),
+                                    reason: violation,
+                                })
+                                .await;
+                            continue;
+                        }
+                    }
+                }
+
+                metrics::messages_received("reduce", 1);
+                let keys = state.interner.intern(std::mem::take(&mut datum.keys));
+                OwnedReduceRequest::new(datum, keys, &mut state.watermarks, self.settings)
+            };
+            let keys = datum.keys.clone();
+
+            let window = match &state.stream_window {
+                Ok(window) => window,
+                Err(e) => {
+                    let status = self.settings.to_status(error::Error::ReduceError(
+                        ErrorKind::ProtocolViolation(e.clone()),
+                    ));
+                    return Err(abort_window(status, &state.abort_tx, task_to_tx, set));
+                }
+            };
+
+            if let Some(inflight_bytes) = &self.inflight_bytes {
+                // the stream is not read until the handles have let go of enough bytes
+                let permit = tokio::select! {
+                    permit = inflight_bytes.acquire(datum.value.len()) => permit,
+                    Ok(_) = shutdown.wait_for(|shutting_down| *shutting_down) => {
+                        let status = self.shutdown_status();
+                        return Err(abort_window(status, &state.abort_tx, task_to_tx, set));
+                    }
+                    Ok(_) = flush.changed() => {
+                        // the element is not dropped, it is reduced once the stream is read again
+                        state.pending = Some(datum);
+                        flushed = true;
+                        break;
+                    }
+                };
+                datum._inflight = Some(permit);
+            }
+            if datum.watermark > state.watermark {
+                state.watermark = datum.watermark;
+                let _ = watermark_tx.send(datum.watermark);
+            }
+
+            // the task of the keys in the window of the stream
+            let task_name = (window.clone(), keys.clone());
+
+            if !task_to_tx.contains_key(&task_name) {
+                // the stream is not read until the handle of another key is done, a handle is
+                // done once it has returned, i.e., its input channel is closed
+                if let Some(max_concurrent_keys) = self.max_concurrent_keys {
+                    task_to_tx.retain(|_, tx| !tx.is_closed());
+                    while task_to_tx.len() >= max_concurrent_keys {
+                        let done =
+                            future::select_all(task_to_tx.values().map(|tx| Box::pin(tx.closed())));
+                        // whether the windows are flushed while waiting, or the shutdown error
+                        let flushing = tokio::select! {
+                            _ = done => Ok(false),
+                            Ok(_) = shutdown.wait_for(|shutting_down| *shutting_down) => {
+                                Err(self.shutdown_status())
+                            }
+                            Ok(_) = flush.changed() => Ok(true),
+                        };
+                        match flushing {
+                            Ok(false) => task_to_tx.retain(|_, tx| !tx.is_closed()),
+                            Ok(true) => {
+                                // the element is reduced once the stream is read again
+                                state.pending = Some(datum);
+                                flushed = true;
+                                break 'read;
+                            }
+                            Err(status) => {
+                                return Err(abort_window(status, &state.abort_tx, task_to_tx, set));
+                            }
+                        }
+                    }
+                }
+
                 // channel to send data to the user's reduce handle
-                let (tx, rx) = mpsc::channel::<OwnedReduceRequest>(1);
+                let (tx, rx) = mpsc::channel::<OwnedReduceRequest>(self.task_channel_size);
 
                 // since we are calling this in a loop, we need make sure that there is reference counting
                 // and the lifetime of self is more than the async function.
                 // try Arc<Self> https://doc.rust-lang.org/reference/items/associated-items.html#methods ?
                 let v = Arc::clone(&self.handler);
-                let m = Arc::clone(&md);
-
-                // spawn task for each unique key
-                let keys = datum.keys.clone();
-                set.spawn(async move { v.reduce(keys, rx, m.as_ref()).await });
+                let window = task_name.0.clone();
+                let joined_keys = keys::join(&keys, self.key_join_delimiter);
+                let task_id = task_id(&joined_keys, window.st, window.et, &window.slot);
+                let name = format!("reduce:task:{}", task_id);
+                let id = window_id(&keys, window.st, window.et, &window.slot);
+                let m = IntervalWindow::new(
+                    window.st,
+                    window.et,
+                    window.slot.clone(),
+                    task_id,
+                    id.clone(),
+                    state.abort_signal.clone(),
+                    Watermark::new(watermark_rx.clone()),
+                )
+                .with_checkpoint(match &self.checkpoints {
+                    Some((store, interval)) => Checkpoint::new(store.clone(), *interval, &id),
+                    None => Checkpoint::disabled(),
+                });
+                #[cfg(feature = "unstable")]
+                let m = m.with_state_store(self.state_store.clone());
 
-                // write data into the channel
-                tx.send(OwnedReduceRequest::new(datum)).await.unwrap();
+                // spawn task for each unique window and key
+                let keys = keys.clone();
+                metrics::reduce_task_started();
+                // the span follows the trace of the first element of the keys
+                let mut span = self
+                    .settings
+                    .handler_span("reduce", Some(datum.headers.as_map()));
+                if self.contextual_logging {
+                    span = trace::reduce_task_span(
+                        &span,
+                        &joined_keys,
+                        window.st,
+                        window.et,
+                        &window.slot,
+                        &id,
+                    );
+                }
+                let mut input_closed = input_closed_rx.clone();
+                let handler_timeout = self.handler_timeout;
+                let top_keys = self.top_keys;
+                let settings = self.settings;
+                let task = async move {
+                    // the task is done also when it is aborted, i.e., the future is dropped
+                    let _done = TaskDone;
+                    let start = Instant::now();
+                    let busy = top_keys.map(|_| AtomicU64::new(0));
+                    let reduce_handle = cputime::measure(
+                        settings.watch("reduce", v.reduce(keys.to_vec(), rx, &m)),
+                        busy.as_ref(),
+                    )
+                    .instrument(span);
+                    let deadline = async move {
+                        match handler_timeout {
+                            Some(timeout)
+                                if input_closed.wait_for(|closed| *closed).await.is_ok() =>
+                            {
+                                tokio::time::sleep(timeout).await
+                            }
+                            _ => std::future::pending().await,
+                        }
+                    };
+                    // the handle is dropped, i.e., aborted, once past the deadline
+                    let messages = tokio::select! {
+                        messages = AssertUnwindSafe(reduce_handle).catch_unwind() => messages
+                            .map_err(|panic| TaskFailure::Panicked(shared::panic_message(panic))),
+                        _ = deadline => Err(TaskFailure::TimedOut),
+                    };
+                    metrics::handler_latency("reduce", start.elapsed());
+                    TaskResult {
+                        window,
+                        keys,
+                        messages,
+                        checkpoint: m.checkpoint,
+                        busy: busy.map(|busy| Duration::from_nanos(busy.load(Ordering::Relaxed))),
+                    }
+                };
+                tasks::spawn_on(&mut set, &name, task);
 
                 // save the key and for future look up as long as the stream is active
-                key_to_tx.insert(task_name, tx);
+                task_to_tx.insert(task_name.clone(), tx);
+            }
+
+            // write data into the channel, it fails only if the handle has already returned or
+            // panicked, which is reported once the input ends.
+            if task_to_tx[&task_name].send(datum).await.is_err() {
+                missed.insert(task_name);
             }
         }
 
+        // the windows of the handles still running, the oldest one is reported while draining
+        let mut running: HashMap<WindowId, usize> = HashMap::new();
+        for (window, _) in task_to_tx.keys() {
+            *running.entry(window.clone()).or_default() += 1;
+        }
+
         // close all the tx channels to tasks to close their corresponding rx
-        key_to_tx.clear();
+        task_to_tx.clear();
+        // the watermark does not move anymore
+        drop(watermark_tx);
+        // the deadlines of the handles start now
+        let _ = input_closed_tx.send(true);
 
-        // channel to respond to numaflow main car as it expects streaming results.
-        let (tx, rx) = mpsc::channel::<Result<ReduceResponse, Status>>(1);
-
-        // start the result streamer
-        tokio::spawn(async move {
-            while let Some(res) = set.join_next().await {
-                let messages = res.unwrap();
-                let mut datum_responses = vec![];
-                for message in messages {
-                    datum_responses.push(reduce_response::Result {
-                        keys: message.keys,
-                        value: message.value,
-                        tags: message.tags,
+        Ok(Generation {
+            set,
+            running,
+            flushed,
+            missed,
+        })
+    }
+
+    // the error failing the streams still reading their input once the server is shutting down
+    fn shutdown_status(&self) -> Status {
+        let error = error::Error::ReduceError(ErrorKind::ShutdownInProgress(
+            "server is shutting down before the input of the stream ended".to_string(),
+        ));
+        self.settings.to_status(error)
+    }
+
+    // Streams out the results of the handles of a generation as they finish. Returns whether the
+    // stream goes on, i.e., it is not failed and numaflow is still reading the results.
+    async fn write_results(
+        &self,
+        generation: Generation,
+        tx: &Sender<Result<ReduceResponse, Status>>,
+        abort_tx: &watch::Sender<Option<StreamAborted>>,
+    ) -> bool {
+        let Generation {
+            mut set,
+            mut running,
+            flushed,
+            missed,
+        } = generation;
+        let settings = self.settings;
+        let handler_timeout = self.handler_timeout;
+        let drain_timeout = self.drain_timeout;
+        let mut high_watermark = HighWatermark {
+            level: self.response_high_watermark,
+            saturated: false,
+        };
+
+        let mut shutdown = self.shutdown.clone();
+        let mut draining = self.shutdown.clone();
+        // the handles get the drain timeout to finish once the server is shutting down
+        let drain = async move {
+            if shutdown
+                .wait_for(|shutting_down| *shutting_down)
+                .await
+                .is_err()
+            {
+                std::future::pending::<()>().await;
+            }
+            tokio::time::sleep(drain_timeout.unwrap_or_default()).await;
+        };
+
+        // the CPU time of the keys of every window, reported once all the windows are done
+        let mut costs: HashMap<WindowId, Vec<(String, Duration)>> = HashMap::new();
+        tokio::pin!(drain);
+        let mut drain_started = false;
+        let mut progress = tokio::time::interval(DRAIN_PROGRESS_INTERVAL);
+        let mut draining_tasks = DrainingTasks::default();
+
+        loop {
+            let res = tokio::select! {
+                res = set.join_next() => match res {
+                    Some(res) => res,
+                    None => {
+                        for (window, costs) in costs {
+                            let window = format!(
+                                "{}#{}",
+                                window_name(window.st, window.et),
+                                window.slot
+                            );
+                            cputime::report(&window, costs, self.top_keys.unwrap_or_default());
+                        }
+                        return true;
+                    }
+                },
+                _ = &mut drain => {
+                    let reason = format!(
+                        "server is shutting down, {} reduce tasks did not finish within the drain timeout of {:?}",
+                        set.len(),
+                        drain_timeout.unwrap_or_default()
+                    );
+                    tracing::warn!(%reason, "aborting the reduce stream");
+                    let _ = abort_tx.send(Some(StreamAborted {
+                        reason: reason.clone(),
+                    }));
+                    // dropping the set aborts the handles still running, the results of the
+                    // finished windows have been sent and the stream is failed so that the
+                    // unfinished windows are not taken for done.
+                    let error = error::Error::ReduceError(ErrorKind::ShutdownInProgress(reason));
+                    let _ = tx.send(Err(settings.to_status(error))).await;
+                    return false;
+                }
+                true = draining
+                    .wait_for(|shutting_down| *shutting_down)
+                    .map(|shutting_down| shutting_down.is_ok()),
+                    if !drain_started =>
+                {
+                    drain_started = true;
+                    continue;
+                }
+                _ = progress.tick(), if drain_started => {
+                    draining_tasks.set(set.len());
+                    if let Some(oldest) = running.keys().min_by_key(|window| window.st) {
+                        tracing::info!(
+                            tasks = set.len(),
+                            oldest_window = %format_args!(
+                                "{}..{} ({})",
+                                oldest.st.timestamp_millis(),
+                                oldest.et.timestamp_millis(),
+                                oldest.slot
+                            ),
+                            "draining the reduce tasks"
+                        );
+                    }
+                    continue;
+                }
+            };
+
+            // panics are caught within the task and the tasks are never cancelled
+            let Ok(result) = res else { continue };
+            if let Some(count) = running.get_mut(&result.window) {
+                *count -= 1;
+                if *count == 0 {
+                    running.remove(&result.window);
+                }
+            }
+
+            if let Some(busy) = result.busy {
+                costs
+                    .entry(result.window.clone())
+                    .or_default()
+                    .push((keys::join(&result.keys, self.key_join_delimiter), busy));
+            }
+
+            let messages = match result.messages {
+                Ok(_) if missed.contains(&(result.window.clone(), result.keys.clone())) => {
+                    let error = error::Error::ReduceError(ErrorKind::InternalError(format!(
+                        "reduce handle of keys {:?} in window {}..{} returned before the end of its input, its results are not complete",
+                        result.keys.as_slice(),
+                        result.window.st,
+                        result.window.et
+                    )));
+                    // dropping the set aborts the handles still running
+                    let _ = tx.send(Err(settings.to_status(error))).await;
+                    return false;
+                }
+                Ok(messages) => messages,
+                Err(TaskFailure::TimedOut) => {
+                    let error = error::Error::ReduceError(ErrorKind::DeadlineExceeded(format!(
+                        "reduce handle of keys {:?} in window {}..{} did not finish within {:?} after its input was closed",
+                        result.keys.as_slice(),
+                        result.window.st,
+                        result.window.et,
+                        handler_timeout.unwrap_or_default()
+                    )));
+                    // dropping the set aborts the handles still running
+                    let _ = tx.send(Err(settings.to_status(error))).await;
+                    return false;
+                }
+                Err(TaskFailure::Panicked(panic)) => {
+                    let error = error::Error::ReduceError(ErrorKind::HandlerPanic {
+                        message: panic,
+                        keys: result.keys.to_vec(),
+                        window: Some((result.window.st, result.window.et)),
                     });
+                    match self.panic_policy {
+                        PanicPolicy::FailStream => {
+                            // dropping the set aborts the handles still running
+                            let _ = tx.send(Err(settings.to_status(error))).await;
+                            return false;
+                        }
+                        PanicPolicy::AbortWindow => {
+                            tracing::warn!(%error, "aborting the reduce task of the keys");
+                            vec![Message::new(
+                                result.keys.to_vec(),
+                                error.to_string(),
+                                vec![HANDLER_PANIC_TAG.to_string()],
+                            )]
+                        }
+                    }
                 }
-                // stream it out to the client
-                tx.send(Ok(ReduceResponse {
-                    results: datum_responses,
-                }))
-                .await
-                .unwrap();
+            };
+
+            metrics::messages_emitted("reduce", messages.len());
+            let mut datum_responses = vec![];
+            for mut message in messages {
+                if flushed {
+                    message.tags.push(FORCE_FLUSHED_TAG.to_string());
+                }
+                datum_responses.push(reduce_response::Result {
+                    keys: message.keys,
+                    value: message.value,
+                    tags: message.tags,
+                    event_time: message.event_time.map(shared::prost_timestamp_from_utc),
+                });
+            }
+            // stream it out to the client
+            let response = ReduceResponse {
+                results: datum_responses,
+            };
+            metrics::channel_saturation("reduce", tx);
+            high_watermark.observe(tx);
+            if tx.send(Ok(response)).await.is_err() {
+                // client is gone, nothing more to do
+                return false;
             }
+            // the window is not to be recovered anymore
+            result.checkpoint.clear().await;
+        }
+    }
+}
+
+/// Default unix domain socket file of the reduce server, see [`Server::with_socket_file`]. It is
+/// relocated by the [`SOCKET_ENV`](crate::shared::SOCKET_ENV) and
+/// [`RUN_DIR_ENV`](crate::shared::RUN_DIR_ENV) environment variables.
+pub const DEFAULT_SOCK_ADDR: &str = "/var/run/numaflow/reduce.sock";
+
+/// Version of the reduce protocol, i.e., of the `reduce.v1` proto package.
+pub const PROTOCOL_VERSION: &str = "v1";
+
+/// Header carrying the [window id](Metadata::window_id) of the results of a reduce, stamped by the
+/// local pipeline (`local::Pipeline::reduce`). The results sent to numaflow do not have
+/// headers, a reducer which needs the id downstream puts it in the value.
+pub const WINDOW_ID_HEADER: &str = "x-numaflow-window-id";
+
+/// Directory of the checkpoints of the windows when no store is set, see
+/// [`Server::with_checkpoint_interval`].
+pub const DEFAULT_CHECKPOINT_DIR: &str = "/var/numaflow/checkpoints";
+
+/// Default maximum duration of a window, see [`Server::with_max_window_duration`].
+pub const DEFAULT_MAX_WINDOW_DURATION: Duration = Duration::from_secs(366 * 24 * 60 * 60);
+
+/// Tag added to the results of the windows closed by a [`FlushHandle`] rather than by numaflow, so
+/// that the [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/)
+/// of the vertex tells the partial results apart.
+pub const FORCE_FLUSHED_TAG: &str = "force-flushed";
+
+/// Tag of the error result sent in place of the results of a [`Reducer::reduce`] handle which
+/// panicked, with the [`PanicPolicy::AbortWindow`] policy. The value of the result is the error.
+pub const HANDLER_PANIC_TAG: &str = "handler-panic";
+
+/// FlushHandle forces the windows of the reduce streams of a [`Server`] to close, e.g., before a
+/// planned maintenance or to drain a pipeline by hand. It is taken from the server with
+/// [`Server::flush_handle`] before the server is started.
+///
+/// A flushed stream closes the inputs of its handles as at the end of the stream, the windows are
+/// done with what they have got and their results are sent tagged with [`FORCE_FLUSHED_TAG`].
+/// The stream then goes on reading its elements, which are reduced by new handles of the same
+/// windows, no element is dropped.
+///
+/// It is the `flush` command of the [control socket](crate::control), and can be hooked to a
+/// signal, e.g., `SIGUSR1`, with the signal handling of the application.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::reduce::proto::ReduceRequest;
+/// use numaflow::reduce::{self, Datum, Message, Metadata, Reducer, FORCE_FLUSHED_TAG};
+/// use tokio::sync::mpsc::{self, Receiver};
+/// use tokio_stream::wrappers::ReceiverStream;
+///
+/// struct Counter;
+///
+/// #[tonic::async_trait]
+/// impl Reducer for Counter {
+///     async fn reduce<T: Datum + Send + Sync + 'static, U: Metadata + Send + Sync + 'static>(
+///         &self,
+///         keys: Vec<String>,
+///         mut input: Receiver<T>,
+///         _md: &U,
+///     ) -> Vec<Message> {
+///         let mut count = 0;
+///         while input.recv().await.is_some() {
+///             count += 1;
+///         }
+///         vec![Message::new(keys, count.to_string(), vec![])]
+///     }
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let server = reduce::Server::new(Counter);
+///     let flush = server.flush_handle();
+///     let mut client = numaflow::testing::reduce::client_for(server).await.unwrap();
+///
+///     // the stream is kept open, its window would never close on its own
+///     let (tx, rx) = mpsc::channel(1);
+///     tx.send(ReduceRequest {
+///         keys: vec!["a".to_string()],
+///         value: "1".into(),
+///         ..Default::default()
+///     })
+///     .await
+///     .unwrap();
+///     let mut request = tonic::Request::new(ReceiverStream::new(rx));
+///     request.metadata_mut().insert("x-numaflow-win-start-time", 0.into());
+///     request.metadata_mut().insert("x-numaflow-win-end-time", 60000.into());
+///
+///     let mut results = client.reduce_fn(request).await.unwrap().into_inner();
+///     tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+///     assert_eq!(flush.flush(), 1);
+///
+///     let response = results.message().await.unwrap().unwrap();
+///     assert_eq!(response.results[0].value, "1");
+///     assert_eq!(response.results[0].tags, vec![FORCE_FLUSHED_TAG.to_string()]);
+///
+///     // the elements sent after the flush are reduced once the stream ends
+///     for _ in 0..2 {
+///         tx.send(ReduceRequest {
+///             keys: vec!["a".to_string()],
+///             value: "1".into(),
+///             ..Default::default()
+///         })
+///         .await
+///         .unwrap();
+///     }
+///     drop(tx);
+///     let response = results.message().await.unwrap().unwrap();
+///     assert_eq!(response.results[0].value, "2");
+///     assert!(response.results[0].tags.is_empty());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FlushHandle {
+    // bumped by every flush, each reduce stream watches it from its start
+    signal: Arc<watch::Sender<u64>>,
+}
+
+impl FlushHandle {
+    fn new() -> Self {
+        Self {
+            signal: Arc::new(watch::channel(0).0),
+        }
+    }
+
+    /// Flushes the windows of the reduce streams open on the server and returns the number of
+    /// streams flushed. The streams started after the call are not flushed.
+    pub fn flush(&self) -> usize {
+        self.signal.send_modify(|generation| *generation += 1);
+        self.signal.receiver_count()
+    }
+
+    fn subscribe(&self) -> watch::Receiver<u64> {
+        self.signal.subscribe()
+    }
+}
+
+/// PanicPolicy tells what happens when a [`Reducer::reduce`] handle panics, the panic is caught
+/// either way and does not take the server down.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::reduce::proto::ReduceRequest;
+/// use numaflow::reduce::{self, Datum, Message, Metadata, PanicPolicy, Reducer, HANDLER_PANIC_TAG};
+/// use tokio::sync::mpsc::Receiver;
+///
+/// struct Counter;
+///
+/// #[tonic::async_trait]
+/// impl Reducer for Counter {
+///     async fn reduce<T: Datum + Send + Sync + 'static, U: Metadata + Send + Sync + 'static>(
+///         &self,
+///         keys: Vec<String>,
+///         mut input: Receiver<T>,
+///         _md: &U,
+///     ) -> Vec<Message> {
+///         let mut count = 0;
+///         while input.recv().await.is_some() {
+///             count += 1;
+///         }
+///         assert_ne!(keys, vec!["bad".to_string()], "bad keys");
+///         vec![Message::new(keys, count.to_string(), vec![])]
+///     }
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let server = reduce::Server::new(Counter).with_panic_policy(PanicPolicy::AbortWindow);
+///     let mut client = numaflow::testing::reduce::client_for(server).await.unwrap();
+///
+///     let requests = ["good", "bad"].map(|key| ReduceRequest {
+///         keys: vec![key.to_string()],
+///         value: "1".into(),
+///         ..Default::default()
+///     });
+///     let mut request = tonic::Request::new(tokio_stream::iter(requests));
+///     request.metadata_mut().insert("x-numaflow-win-start-time", 0.into());
+///     request.metadata_mut().insert("x-numaflow-win-end-time", 60000.into());
+///
+///     // the other keys of the window are not affected by the panic
+///     let mut results = client.reduce_fn(request).await.unwrap().into_inner();
+///     let mut tags = vec![];
+///     while let Some(response) = results.message().await.unwrap() {
+///         tags.push((response.results[0].keys[0].clone(), response.results[0].tags.clone()));
+///     }
+///     tags.sort();
+///     assert_eq!(
+///         tags,
+///         vec![
+///             ("bad".to_string(), vec![HANDLER_PANIC_TAG.to_string()]),
+///             ("good".to_string(), vec![]),
+///         ]
+///     );
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicPolicy {
+    /// Fail the whole stream with a [`HandlerPanic`](ErrorKind::HandlerPanic) error carrying the
+    /// keys and the window of the panicking handle.
+    #[default]
+    FailStream,
+    /// Abort the task of the keys and the window of the panicking handle only, an error result
+    /// tagged with [`HANDLER_PANIC_TAG`] is sent in place of its results and the error is logged.
+    /// The other keys of the window and the other windows of the stream are not affected.
+    AbortWindow,
+}
+
+/// KeyPolicy tells what happens to an element whose keys go beyond the limits set with
+/// [`Server::with_max_keys_per_message`] and [`Server::with_max_key_length`].
+#[derive(Debug, Clone, Default)]
+pub enum KeyPolicy {
+    /// Fail the stream with an `InvalidArgument` error.
+    #[default]
+    Error,
+    /// Drop the extra keys and cut the long keys short, the element is then reduced with the
+    /// trimmed keys. Keys which only differ past the limits end up in the same set of keys.
+    Trim,
+    /// Skip the element and send it to the channel instead, e.g., to be written to a dead letter
+    /// topic by the user. The element is dropped if the receiver is gone.
+    DeadLetter(mpsc::Sender<DeadLetter>),
+}
+
+/// DeadLetter is an element skipped by the [`KeyPolicy::DeadLetter`] policy.
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    /// keys of the element, as received.
+    pub keys: Vec<String>,
+    /// value of the element.
+    pub value: Bytes,
+    /// event_time of the element.
+    pub event_time: DateTime<Utc>,
+    /// headers of the element.
+    pub headers: Headers,
+    /// reason tells which limit the keys go beyond.
+    pub reason: String,
+}
+
+/// FromFn is a [`Reducer`] running a closure, see [`Server::from_fn`].
+pub struct FromFn<F>(F);
+
+#[async_trait]
+impl<F, Fut> Reducer for FromFn<F>
+where
+    F: Fn(Vec<String>, mpsc::Receiver<Box<dyn Datum + Send + Sync>>, IntervalWindow) -> Fut
+        + Send
+        + Sync,
+    Fut: Future<Output = Vec<Message>> + Send,
+{
+    async fn reduce<T: Datum + Send + Sync + 'static, U: Metadata + Send + Sync + 'static>(
+        &self,
+        keys: Vec<String>,
+        input: mpsc::Receiver<T>,
+        md: &U,
+    ) -> Vec<Message> {
+        let window = IntervalWindow::from_metadata(md);
+        shared::forward_input(
+            input,
+            |datum| Box::new(datum) as Box<dyn Datum + Send + Sync>,
+            |input| (self.0)(keys, input, window),
+        )
+        .await
+    }
+}
+
+/// gRPC server to start a reduce service
+pub struct Server<T> {
+    config: shared::ServerConfig,
+    svc: T,
+    // the channel sizes set one by one, they take precedence over the one of the profile
+    task_channel_size: Option<usize>,
+    response_channel_size: Option<usize>,
+    response_high_watermark: Option<usize>,
+    max_concurrent_keys: Option<usize>,
+    max_inflight_bytes: Option<usize>,
+    burst_buffer: Option<BurstBuffer>,
+    max_window_duration: Duration,
+    max_keys_per_message: Option<usize>,
+    max_key_length: Option<usize>,
+    key_policy: KeyPolicy,
+    key_join_delimiter: char,
+    contextual_logging: bool,
+    panic_policy: PanicPolicy,
+    handler_timeout: Option<Duration>,
+    drain_timeout: Option<Duration>,
+    top_keys: Option<usize>,
+    #[cfg(feature = "unstable")]
+    state_store: Option<Arc<dyn StateStore>>,
+    checkpoint_interval: Option<Duration>,
+    checkpoint_store: Option<Arc<dyn StateStore>>,
+    flush: FlushHandle,
+}
+
+impl<T> Server<T> {
+    /// Create a new reduce server with the given [`Reducer`] handler.
+    pub fn new(reduce_svc: T) -> Self {
+        Self {
+            config: shared::ServerConfig::new(DEFAULT_SOCK_ADDR, PROTOCOL_VERSION),
+            svc: reduce_svc,
+            task_channel_size: None,
+            response_channel_size: None,
+            response_high_watermark: None,
+            max_concurrent_keys: None,
+            max_inflight_bytes: None,
+            burst_buffer: None,
+            max_window_duration: DEFAULT_MAX_WINDOW_DURATION,
+            max_keys_per_message: None,
+            max_key_length: None,
+            key_policy: KeyPolicy::default(),
+            key_join_delimiter: keys::DEFAULT_KEY_JOIN_DELIMITER,
+            contextual_logging: false,
+            panic_policy: PanicPolicy::default(),
+            handler_timeout: None,
+            drain_timeout: None,
+            top_keys: None,
+            #[cfg(feature = "unstable")]
+            state_store: None,
+            checkpoint_interval: None,
+            checkpoint_store: None,
+            flush: FlushHandle::new(),
+        }
+    }
+
+    shared::server_config_methods!();
+
+    /// Set the capacity of the channel feeding the [`Reducer::reduce`] handle of a set of keys.
+    /// A larger channel lets the elements of other keys through while a handle is busy at the
+    /// cost of memory. Default value is the channel size of the
+    /// [profile](Server::with_profile), a size of 0 is taken as 1.
+    pub fn with_task_channel_size(mut self, size: usize) -> Self {
+        self.task_channel_size = Some(size.max(1));
+        self
+    }
+
+    /// Get the capacity of the channel feeding the [`Reducer::reduce`] handle of a set of keys.
+    pub fn task_channel_size(&self) -> usize {
+        self.task_channel_size
+            .unwrap_or(self.config.tuning.channel_size)
+    }
+
+    /// Set the capacity of the channel buffering the results of the window until they are
+    /// streamed out to numaflow. Default value is the channel size of the
+    /// [profile](Server::with_profile), a size of 0 is taken as 1.
+    pub fn with_response_channel_size(mut self, size: usize) -> Self {
+        self.response_channel_size = Some(size.max(1));
+        self
+    }
+
+    /// Get the capacity of the channel buffering the results of the window.
+    pub fn response_channel_size(&self) -> usize {
+        self.response_channel_size
+            .unwrap_or(self.config.tuning.channel_size)
+    }
+
+    /// Set the number of results queued for numaflow above which the response queue of a stream is
+    /// considered saturated. A warning is logged and the `response_queue_saturated_total` metric
+    /// is incremented when the queue goes over it, telling that the downstream backpressures the
+    /// windows. It is meant to be used with a [response channel size](Server::with_response_channel_size)
+    /// larger than 1. It is disabled by default.
+    pub fn with_response_high_watermark(mut self, level: usize) -> Self {
+        self.response_high_watermark = Some(level.max(1));
+        self
+    }
+
+    /// Get the number of queued results above which the response queue is considered saturated.
+    pub fn response_high_watermark(&self) -> Option<usize> {
+        self.response_high_watermark
+    }
+
+    /// Set the maximum number of keys processed concurrently in a stream, i.e., the number of
+    /// [`Reducer::reduce`] handles of its windows running at once. Once a stream reaches it, the
+    /// stream is not read until one of the handles returns, which backpressures numaflow. As the
+    /// handles usually return once the input of their window is complete, a stream with more
+    /// keys than the limit in a window waits for a [flush](Server::flush_handle) or the shutdown
+    /// of the server. There is no limit by default, a limit of 0 is taken as 1.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use numaflow::reduce::proto::ReduceRequest;
+    /// use numaflow::reduce::{self, Datum, Message, Metadata, Reducer};
+    /// use tokio::sync::mpsc::Receiver;
+    ///
+    /// // returns as soon as it has got an element, freeing its key
+    /// struct First;
+    ///
+    /// #[tonic::async_trait]
+    /// impl Reducer for First {
+    ///     async fn reduce<T: Datum + Send + Sync + 'static, U: Metadata + Send + Sync + 'static>(
+    ///         &self,
+    ///         keys: Vec<String>,
+    ///         mut input: Receiver<T>,
+    ///         _md: &U,
+    ///     ) -> Vec<Message> {
+    ///         let first = input.recv().await.unwrap();
+    ///         vec![Message::new(keys, first.value().clone(), vec![])]
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() {
+    ///     let server = reduce::Server::new(First).with_max_concurrent_keys(1);
+    ///     let mut client = numaflow::testing::reduce::client_for(server).await.unwrap();
+    ///
+    ///     let requests = ["a", "b", "c"].map(|key| ReduceRequest {
+    ///         keys: vec![key.to_string()],
+    ///         value: key.into(),
+    ///         ..Default::default()
+    ///     });
+    ///     let mut request = tonic::Request::new(tokio_stream::iter(requests));
+    ///     request.metadata_mut().insert("x-numaflow-win-start-time", 0.into());
+    ///     request.metadata_mut().insert("x-numaflow-win-end-time", 60000.into());
+    ///
+    ///     // the keys are reduced one after the other
+    ///     let mut results = client.reduce_fn(request).await.unwrap().into_inner();
+    ///     let mut values = vec![];
+    ///     while let Some(response) = results.message().await.unwrap() {
+    ///         values.push(response.results[0].value.clone());
+    ///     }
+    ///     values.sort();
+    ///     assert_eq!(values, vec!["a", "b", "c"]);
+    /// }
+    /// ```
+    pub fn with_max_concurrent_keys(mut self, max: usize) -> Self {
+        self.max_concurrent_keys = Some(max.max(1));
+        self
+    }
+
+    /// Get the maximum number of keys processed concurrently in a stream.
+    pub fn max_concurrent_keys(&self) -> Option<usize> {
+        self.max_concurrent_keys
+    }
+
+    /// Set the maximum number of payload bytes held by the [`Reducer::reduce`] handles of all the
+    /// windows, e.g., queued for a hot key whose handle lags. Once it is reached the incoming
+    /// streams are not read anymore, pushing back on numaflow, until the handles let go of enough
+    /// bytes. The bytes of an element are held until the handle of its window has dropped it,
+    /// hence a handle keeping the elements of its window, e.g., to sort them, holds them until
+    /// it returns and the limit must be above what such windows hold at once. There is no limit
+    /// by default.
+    pub fn with_max_inflight_bytes(mut self, max: usize) -> Self {
+        self.max_inflight_bytes = Some(max);
+        self
+    }
+
+    /// Get the maximum number of payload bytes held by the handles.
+    pub fn max_inflight_bytes(&self) -> Option<usize> {
+        self.max_inflight_bytes
+    }
+
+    /// Set the buffer the elements of a stream are read ahead into, up to `messages` elements and
+    /// `bytes` bytes of payloads, so that a short burst is taken off the gRPC stream while the
+    /// handles catch up instead of pushing back on numaflow right away. The elements count in the
+    /// [in-flight bytes](Server::with_max_inflight_bytes) once they leave the buffer. Its
+    /// occupancy is in the `reduce_burst_buffer_messages` and `reduce_burst_buffer_bytes`
+    /// metrics. There is no buffer by default.
+    pub fn with_burst_buffer(mut self, messages: usize, bytes: usize) -> Self {
+        self.burst_buffer = Some(BurstBuffer {
+            messages: messages.max(1),
+            bytes: bytes.max(1),
         });
+        self
+    }
 
-        // return the rx as the streaming endpoint
-        Ok(Response::new(ReceiverStream::new(rx)))
+    /// Get the maximum number of elements and of payload bytes of the burst buffer.
+    pub fn burst_buffer(&self) -> Option<(usize, usize)> {
+        self.burst_buffer
+            .map(|burst_buffer| (burst_buffer.messages, burst_buffer.bytes))
     }
 
-    async fn is_ready(&self, _: Request<()>) -> Result<Response<ReadyResponse>, Status> {
-        Ok(Response::new(ReadyResponse { ready: true }))
+    /// Set the maximum duration of a window, a window which is longer or whose start is not before
+    /// its end is rejected with a [`ProtocolViolation`](ErrorKind::ProtocolViolation) error before
+    /// reaching the [`Reducer::reduce`] handle. Default value is [`DEFAULT_MAX_WINDOW_DURATION`],
+    /// 366 days.
+    pub fn with_max_window_duration(mut self, duration: Duration) -> Self {
+        self.max_window_duration = duration;
+        self
+    }
+
+    /// Get the maximum duration of a window.
+    pub fn max_window_duration(&self) -> Duration {
+        self.max_window_duration
+    }
+
+    /// Set the maximum number of keys of an element, an element with more keys is handled as per
+    /// the [`KeyPolicy`]. There is no limit by default.
+    pub fn with_max_keys_per_message(mut self, max: usize) -> Self {
+        self.max_keys_per_message = Some(max);
+        self
+    }
+
+    /// Get the maximum number of keys of an element.
+    pub fn max_keys_per_message(&self) -> Option<usize> {
+        self.max_keys_per_message
+    }
+
+    /// Set the maximum length in bytes of a key, an element with a longer key is handled as per
+    /// the [`KeyPolicy`]. There is no limit by default.
+    pub fn with_max_key_length(mut self, max: usize) -> Self {
+        self.max_key_length = Some(max);
+        self
+    }
+
+    /// Get the maximum length in bytes of a key.
+    pub fn max_key_length(&self) -> Option<usize> {
+        self.max_key_length
+    }
+
+    /// Set what happens to an element whose keys go beyond the limits. Default is
+    /// [`KeyPolicy::Error`].
+    pub fn with_key_policy(mut self, policy: KeyPolicy) -> Self {
+        self.key_policy = policy;
+        self
+    }
+
+    /// Get what happens to an element whose keys go beyond the limits.
+    pub fn key_policy(&self) -> &KeyPolicy {
+        &self.key_policy
+    }
+
+    /// Set the delimiter the keys are joined with in the [task id](Metadata::task_id) and the task
+    /// names, a delimiter or a backslash inside of a key is escaped with a backslash so that
+    /// different keys never share a task id. The keys are not joined to tell the tasks apart.
+    /// Default value is `:`.
+    pub fn with_key_join_delimiter(mut self, delimiter: char) -> Self {
+        self.key_join_delimiter = delimiter;
+        self
+    }
+
+    /// Get the delimiter the keys are joined with in the task ids.
+    pub fn key_join_delimiter(&self) -> char {
+        self.key_join_delimiter
+    }
+
+    /// Set whether the [`Reducer::reduce`] handles run in a `tracing` span carrying the keys and
+    /// the window of the handle. The events the handler logs through `tracing` are then tagged
+    /// with the `keys`, `window_start`, `window_end` and `slot` fields, e.g., to grep the logs of
+    /// a misbehaving key with `tracing_subscriber::fmt`. It does not need the
+    /// [tracing](Server::with_tracing) to be on. It is disabled by default.
+    pub fn with_contextual_logging(mut self, enabled: bool) -> Self {
+        self.contextual_logging = enabled;
+        self
+    }
+
+    /// Get whether the handles run in a span carrying their keys and window.
+    pub fn contextual_logging(&self) -> bool {
+        self.contextual_logging
+    }
+
+    /// Set what happens when a [`Reducer::reduce`] handle panics. Default is
+    /// [`PanicPolicy::FailStream`].
+    pub fn with_panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
+    }
+
+    /// Get the policy applied when a [`Reducer::reduce`] handle panics.
+    pub fn panic_policy(&self) -> PanicPolicy {
+        self.panic_policy
+    }
+
+    /// Set how long a [`Reducer::reduce`] handle is given to return once its input is closed,
+    /// i.e., once numaflow has sent the whole window. A handle going beyond it is aborted and the
+    /// stream is failed with a `DeadlineExceeded` error telling its keys and window, instead of
+    /// the stream waiting for it forever. There is no limit by default.
+    pub fn with_handler_timeout(mut self, timeout: Duration) -> Self {
+        self.handler_timeout = Some(timeout);
+        self
+    }
+
+    /// Get how long a handle is given to return once its input is closed.
+    pub fn handler_timeout(&self) -> Option<Duration> {
+        self.handler_timeout
+    }
+
+    /// Set how long the windows whose input is complete are given to finish once the server is
+    /// shutting down, see [`Server::start_with_shutdown`]. The windows still running after it are
+    /// aborted and their stream fails with a [`ShutdownInProgress`](ErrorKind::ShutdownInProgress)
+    /// error, numaflow then replays them. By default they are aborted right away.
+    ///
+    /// The number of handles left and the oldest of their windows are logged every second while
+    /// draining, the handles left are also counted in the `reduce_draining_tasks` metric.
+    pub fn with_drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = Some(timeout);
+        self
+    }
+
+    /// Get how long the complete windows are given to finish once the server is shutting down.
+    pub fn drain_timeout(&self) -> Option<Duration> {
+        self.drain_timeout
+    }
+
+    /// Turn the accounting of the CPU time of the [`Reducer::reduce`] handles on, reporting the
+    /// `n` keys which took the most of it once the windows of a stream are done: in the
+    /// `reduce_top_keys_cpu_seconds` metric and through the `top-keys` command of the
+    /// [control socket](crate::control). A window where a few keys take most of the time is
+    /// logged, such keys are better re-keyed or pre-aggregated upstream. The CPU time is the time
+    /// spent polling the handle, i.e., waiting on the input is not counted. It is off by default.
+    pub fn with_top_keys(mut self, n: usize) -> Self {
+        self.top_keys = Some(n);
+        self
+    }
+
+    /// Get the number of keys taking the most CPU time reported per window.
+    pub fn top_keys(&self) -> Option<usize> {
+        self.top_keys
+    }
+
+    /// Get the [`FlushHandle`] forcing the windows of the reduce streams of the server to close.
+    /// The handle is shared by the clones of it and is also the `flush` command of the
+    /// [control socket](crate::control).
+    pub fn flush_handle(&self) -> FlushHandle {
+        self.flush.clone()
+    }
+
+    /// Set the [`StateStore`] passed to the handler in the [`Metadata::state_store`], e.g., a
+    /// [`FileStore`] on a persistent volume. No store is passed by default. It is an unstable API,
+    /// only available with the `unstable` feature.
+    ///
+    /// # Example
+    ///
+    /// A running count of the elements of the keys across the windows.
+    ///
+    /// ```
+    /// use numaflow::reduce::{Datum, Message, Metadata, Reducer};
+    /// use numaflow::state::MemoryStore;
+    /// use numaflow::testing::assert_messages;
+    /// use numaflow::testing::reduce::TestDriver;
+    /// use tokio::sync::mpsc::Receiver;
+    ///
+    /// struct RunningCount;
+    ///
+    /// #[tonic::async_trait]
+    /// impl Reducer for RunningCount {
+    ///     async fn reduce<T: Datum + Send + Sync + 'static, U: Metadata + Send + Sync + 'static>(
+    ///         &self,
+    ///         keys: Vec<String>,
+    ///         mut input: Receiver<T>,
+    ///         md: &U,
+    ///     ) -> Vec<Message> {
+    ///         let store = md.state_store().expect("the server has a state store");
+    ///         let key = keys.join(":");
+    ///         let mut count: u64 = match store.get(&key).await {
+    ///             Ok(Some(value)) => String::from_utf8_lossy(&value).parse().unwrap_or(0),
+    ///             Ok(None) => 0,
+    ///             Err(e) => panic!("failed to read the count of {}: {}", key, e),
+    ///         };
+    ///         while input.recv().await.is_some() {
+    ///             count += 1;
+    ///         }
+    ///         store.put(&key, count.to_string().into()).await.unwrap();
+    ///         vec![Message::new(keys, count.to_string(), vec![])]
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() {
+    ///     let store = MemoryStore::new();
+    ///     for expected in ["2", "4"] {
+    ///         let out = TestDriver::new(RunningCount)
+    ///             .with_state_store(store.clone())
+    ///             .with_input(["a"], "1")
+    ///             .with_input(["a"], "2")
+    ///             .run()
+    ///             .await;
+    ///         assert_messages(&out).has_len(1).message(0).value_str(expected);
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "unstable")]
+    pub fn with_state_store(mut self, store: impl StateStore + 'static) -> Self {
+        self.state_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Get the [`StateStore`] passed to the handler.
+    #[cfg(feature = "unstable")]
+    pub fn state_store(&self) -> Option<&Arc<dyn StateStore>> {
+        self.state_store.as_ref()
+    }
+
+    /// Turn the [checkpoints](Checkpoint) of the windows on, a snapshot is due every `interval`.
+    /// They are kept in a [`FileStore`] in [`DEFAULT_CHECKPOINT_DIR`] unless another store is set
+    /// with [`Server::with_checkpoint_store`]. Checkpoints are off by default.
+    pub fn with_checkpoint_interval(mut self, interval: Duration) -> Self {
+        self.checkpoint_interval = Some(interval);
+        self
+    }
+
+    /// Get how often a checkpoint of the windows is due, None when the checkpoints are off.
+    pub fn checkpoint_interval(&self) -> Option<Duration> {
+        self.checkpoint_interval
+    }
+
+    /// Set the [`StateStore`] keeping the checkpoints, e.g., one on a volume outliving the pod.
+    pub fn with_checkpoint_store(mut self, store: impl StateStore + 'static) -> Self {
+        self.checkpoint_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Get the [`StateStore`] keeping the checkpoints, None for the default one.
+    pub fn checkpoint_store(&self) -> Option<&Arc<dyn StateStore>> {
+        self.checkpoint_store.as_ref()
+    }
+
+    /// Starts the gRPC server. The server runs until it is stopped or errors out.
+    pub async fn start(self) -> Result<(), shared::BoxError>
+    where
+        T: Reducer + Send + Sync + 'static,
+    {
+        self.start_with_shutdown(std::future::pending()).await
+    }
+
+    /// Starts the gRPC server, it shuts down once `shutdown` resolves. The server then stops
+    /// accepting traffic. The streams still reading their input fail right away with a
+    /// [`ShutdownInProgress`](ErrorKind::ShutdownInProgress) error, numaflow replays their windows
+    /// rather than taking partial results for final. The windows whose input is complete are
+    /// given the [drain timeout](Server::with_drain_timeout) to return their results before the
+    /// remaining ones are aborted and their streams failed the same way.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use numaflow::reduce::{self, Datum, Message, Metadata};
+    /// # use tokio::sync::mpsc::Receiver;
+    /// # struct Counter;
+    /// # #[tonic::async_trait]
+    /// # impl reduce::Reducer for Counter {
+    /// #     async fn reduce<T: Datum + Send + Sync + 'static, U: Metadata + Send + Sync + 'static>(
+    /// #         &self,
+    /// #         keys: Vec<String>,
+    /// #         mut input: Receiver<T>,
+    /// #         _md: &U,
+    /// #     ) -> Vec<Message> {
+    /// #         vec![]
+    /// #     }
+    /// # }
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    ///     // hand `stop_tx` over to whatever decides that the server has to stop, e.g., a signal
+    ///     // handler
+    /// #   drop(stop_tx);
+    ///
+    ///     reduce::Server::new(Counter)
+    ///         .with_drain_timeout(Duration::from_secs(10))
+    ///         .start_with_shutdown(async {
+    ///             let _ = stop_rx.await;
+    ///         })
+    ///         .await
+    /// }
+    /// ```
+    pub async fn start_with_shutdown<F>(self, shutdown: F) -> Result<(), shared::BoxError>
+    where
+        T: Reducer + Send + Sync + 'static,
+        F: Future<Output = ()>,
+    {
+        let (mut config, reduce_svc, shutdown_tx) = self.into_parts()?;
+        let incoming = config.prepare().await?;
+
+        let signal = async {
+            shutdown.await;
+            tracing::info!(
+                "reduce server is shutting down, failing the streams still reading and draining \
+                 the complete windows"
+            );
+            let _ = shutdown_tx.send(true);
+        };
+
+        let reduce_svc = Arc::new(reduce_svc);
+        let router = shared::router!(
+            config,
+            reduce_server::ReduceServer::from_arc(reduce_svc.clone())
+        );
+        #[cfg(feature = "legacy-protocol")]
+        let router = router.add_service(shared::grpc_service!(
+            config,
+            crate::legacy::proto::user_defined_function_server::UserDefinedFunctionServer::new(
+                crate::legacy::LegacyReduce(reduce_svc)
+            )
+        ));
+        router
+            .serve_with_incoming_shutdown(incoming, signal)
+            .await
+            .map_err(|e| error::Error::ReduceError(ErrorKind::connection(e)))?;
+
+        Ok(())
+    }
+
+    /// Splits the server into its configuration, the service running its streams and the sender
+    /// telling the service that the server is shutting down.
+    pub(crate) fn into_parts(self) -> Result<ServerParts<T>, shared::BoxError> {
+        let mut config = self.config;
+        config.flush = Some(self.flush.clone());
+
+        let checkpoints = match (self.checkpoint_interval, self.checkpoint_store) {
+            (Some(interval), Some(store)) => Some((store, interval)),
+            (Some(interval), None) => {
+                let store: Arc<dyn StateStore> = Arc::new(FileStore::new(DEFAULT_CHECKPOINT_DIR)?);
+                Some((store, interval))
+            }
+            (None, _) => None,
+        };
+
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let reduce_svc = ReduceService {
+            handler: Arc::new(self.svc),
+            task_channel_size: self.task_channel_size.unwrap_or(config.tuning.channel_size),
+            response_channel_size: self
+                .response_channel_size
+                .unwrap_or(config.tuning.channel_size),
+            response_high_watermark: self.response_high_watermark,
+            max_concurrent_keys: self.max_concurrent_keys,
+            inflight_bytes: self.max_inflight_bytes.map(InflightBytes::new),
+            burst_buffer: self.burst_buffer,
+            max_window_duration: self.max_window_duration,
+            key_limits: KeyLimits {
+                max_keys: self.max_keys_per_message,
+                max_key_length: self.max_key_length,
+            },
+            key_policy: self.key_policy,
+            key_join_delimiter: self.key_join_delimiter,
+            contextual_logging: self.contextual_logging,
+            panic_policy: self.panic_policy,
+            handler_timeout: self.handler_timeout,
+            drain_timeout: self.drain_timeout,
+            top_keys: self.top_keys,
+            #[cfg(feature = "unstable")]
+            state_store: self.state_store,
+            checkpoints,
+            shutdown: shutdown_rx,
+            flush: self.flush,
+            settings: config.handler_settings(),
+        };
+        Ok((config, reduce_svc, shutdown_tx))
+    }
+
+    /// Runs a stream of the window `start..end` over the service of the server, without the
+    /// transport, and returns its responses or the status it failed with, see
+    /// [`testing::reduce::run_stream`](crate::testing::reduce::run_stream).
+    pub(crate) async fn run_stream<S>(
+        self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        requests: S,
+    ) -> Result<Vec<ReduceResponse>, Status>
+    where
+        T: Reducer + Send + Sync + 'static,
+        S: Stream<Item = Result<ReduceRequest, Status>> + Send + 'static,
+    {
+        let (_, reduce_svc, _shutdown_tx) = self
+            .into_parts()
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let mut metadata = MetadataMap::new();
+        for (key, time) in [(WIN_START_TIME, start), (WIN_END_TIME, end)] {
+            metadata.insert(key, time.timestamp_millis().into());
+        }
+        let mut stream = reduce_svc
+            .reduce_stream(&metadata, Box::pin(requests))
+            .await?;
+        let mut responses = vec![];
+        while let Some(response) = stream.next().await {
+            responses.push(response?);
+        }
+        Ok(responses)
     }
 }
 
-pub async fn start_uds_server<T>(m: T) -> Result<(), Box<dyn std::error::Error>>
+// the configuration, the service and the shutdown sender of a server
+type ServerParts<T> = (shared::ServerConfig, ReduceService<T>, watch::Sender<bool>);
+
+impl<T> crate::server::Service for Server<T>
 where
     T: Reducer + Send + Sync + 'static,
 {
-    shared::write_info_file();
+    fn serve(
+        self,
+        shutdown: BoxFuture<'static, ()>,
+    ) -> BoxFuture<'static, Result<(), shared::BoxError>> {
+        Box::pin(self.start_with_shutdown(shutdown))
+    }
+}
 
-    let path = "/var/run/numaflow/reduce.sock";
-    std::fs::create_dir_all(std::path::Path::new(path).parent().unwrap())?;
+impl<F> Server<FromFn<F>> {
+    /// Create a new reduce server running the closure as the [`Reducer::reduce`] handle, for the
+    /// handlers too small to be worth a type of their own. The elements are passed to the closure
+    /// as [`Datum`] trait objects.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use numaflow::reduce::{self, Message};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     reduce::Server::from_fn(|keys, mut input, _md| async move {
+    ///         let mut counter = 0;
+    ///         while input.recv().await.is_some() {
+    ///             counter += 1;
+    ///         }
+    ///         vec![Message::new(keys, counter.to_string(), vec![])]
+    ///     })
+    ///     .start()
+    ///     .await
+    /// }
+    /// ```
+    pub fn from_fn<Fut>(handler: F) -> Self
+    where
+        F: Fn(Vec<String>, mpsc::Receiver<Box<dyn Datum + Send + Sync>>, IntervalWindow) -> Fut,
+        Fut: Future<Output = Vec<Message>>,
+    {
+        Self::new(FromFn(handler))
+    }
+}
 
-    let uds = tokio::net::UnixListener::bind(path)?;
-    let _uds_stream = tokio_stream::wrappers::UnixListenerStream::new(uds);
+/// Client drives a reduce server, e.g., the one of a UDF written in another language, with the
+/// types of the crate rather than the gRPC messages: the elements of a window are [`Element`]s and
+/// the results are decoded into [`Message`]s. The window is set in the metadata of the stream,
+/// like numaflow does. It is meant for tooling, conformance tests and emulators of the platform.
+///
+/// # Example
+///
+/// ```
+/// use chrono::{DateTime, Duration};
+/// use numaflow::local::Element;
+/// use numaflow::reduce::{self, Client, Message};
+/// use numaflow::testing::ephemeral_server;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+///     let server = ephemeral_server(reduce::Server::from_fn(|keys, mut input, _md| async move {
+///         let mut count = 0;
+///         while input.recv().await.is_some() {
+///             count += 1;
+///         }
+///         vec![Message::new(keys, count.to_string(), vec![])]
+///     }))
+///     .await?;
+///
+///     let start = DateTime::UNIX_EPOCH;
+///     let element = |key: &str| Element::new("1", start).with_keys(vec![key.to_string()]);
+///
+///     let mut client = Client::connect(server.socket_file()).await?;
+///     let mut results = client
+///         .reduce(
+///             start,
+///             start + Duration::minutes(1),
+///             [element("a"), element("b"), element("a")],
+///         )
+///         .await?;
+///     results.sort_by(|a, b| a.keys.cmp(&b.keys));
+///     assert_eq!(results[0].keys, ["a"]);
+///     assert_eq!(results[0].value, "2");
+///     assert_eq!(results[1].value, "1");
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Client {
+    inner: reduce_client::ReduceClient<Channel>,
+}
 
-    let reduce_svc = ReduceService {
-        handler: Arc::new(m),
-    };
+impl Client {
+    /// Create a client over a connected channel, e.g., to a server listening on TCP.
+    pub fn new(channel: Channel) -> Self {
+        Self {
+            inner: reduce_client::ReduceClient::new(channel),
+        }
+    }
+
+    /// Connect to the server listening on the unix domain socket file.
+    pub async fn connect(socket_file: impl AsRef<Path>) -> Result<Self, shared::BoxError> {
+        Ok(Self::new(shared::connect_unix(socket_file.as_ref()).await?))
+    }
 
-    tonic::transport::Server::builder()
-        .add_service(reduce_server::ReduceServer::new(reduce_svc))
-        .serve_with_incoming(_uds_stream)
-        .await?;
+    /// Reduces the elements over the window from `start` to `end` and returns the results of the
+    /// keys of the window once the server has closed it. The results of the keys come in the
+    /// order the server finishes them.
+    pub async fn reduce(
+        &mut self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        elements: impl IntoIterator<Item = Element>,
+    ) -> Result<Vec<Message>, Status> {
+        let requests: Vec<ReduceRequest> = elements
+            .into_iter()
+            .map(|element| ReduceRequest {
+                keys: element.keys,
+                value: element.value,
+                event_time: Some(shared::prost_timestamp_from_utc(element.event_time)),
+                watermark: Some(shared::prost_timestamp_from_utc(element.watermark)),
+                headers: element.headers.into(),
+            })
+            .collect();
 
-    Ok(())
+        let mut request = Request::new(tokio_stream::iter(requests));
+        for (key, time) in [(WIN_START_TIME, start), (WIN_END_TIME, end)] {
+            request
+                .metadata_mut()
+                .insert(key, time.timestamp_millis().into());
+        }
+
+        let mut stream = self.inner.reduce_fn(request).await?.into_inner();
+        let mut messages = vec![];
+        while let Some(response) = stream.message().await? {
+            messages.extend(response.results.into_iter().map(|result| {
+                Message {
+                    keys: result.keys,
+                    value: result.value,
+                    tags: result.tags,
+                    event_time: result
+                        .event_time
+                        .map(|event_time| shared::utc_from_timestamp(Some(event_time))),
+                }
+            }));
+        }
+        Ok(messages)
+    }
+}
+
+/// start_uds_server starts a reduce gRPC server over an UDS (unix-domain-socket) endpoint with the
+/// default settings. Use [`Server`] to customize the server.
+pub async fn start_uds_server<T>(m: T) -> Result<(), Box<dyn std::error::Error>>
+where
+    T: Reducer + Send + Sync + 'static,
+{
+    Server::new(m)
+        .start()
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)
 }
@@ -0,0 +1,112 @@
+use std::future::Future;
+
+use futures_util::future::{self, BoxFuture, FutureExt};
+use tokio::sync::watch;
+
+use crate::shared::BoxError;
+
+/// Service is a UDF server which can be hosted by a [`Server`] along with others, it is
+/// implemented by the `Server` of every UDF kind.
+pub trait Service: Send + 'static {
+    /// Serves the UDF until `shutdown` resolves or the server errors out, see the
+    /// `start_with_shutdown` of the `Server` of the UDF kind.
+    fn serve(self, shutdown: BoxFuture<'static, ()>) -> BoxFuture<'static, Result<(), BoxError>>;
+}
+
+type Serve =
+    Box<dyn FnOnce(BoxFuture<'static, ()>) -> BoxFuture<'static, Result<(), BoxError>> + Send>;
+
+/// Server hosts the servers of several UDF kinds in one process, e.g., a map and a sink
+/// colocated in a container. Every [`Service`] keeps its own settings, the socket and the server
+/// info file in particular must be told apart, which the [`udf::builder`](crate::udf::builder)
+/// of each kind takes care of. The services run concurrently on the runtime of the caller and are
+/// shut down together, if one of them errors out the others are stopped.
+///
+/// # Example
+///
+/// ```no_run
+/// # use numaflow::{map, sink};
+/// use numaflow::udf::{self, UdfKind};
+///
+/// # struct Cat;
+/// # #[tonic::async_trait]
+/// # impl map::Mapper for Cat {
+/// #     async fn map<T: map::Datum + Send + Sync + 'static>(&self, _: T) -> Vec<map::Message> {
+/// #         vec![]
+/// #     }
+/// # }
+/// # struct Log;
+/// # #[tonic::async_trait]
+/// # impl sink::Sinker for Log {
+/// #     async fn sink<T: sink::Datum + Send + Sync + 'static>(
+/// #         &self,
+/// #         _: tokio::sync::mpsc::Receiver<T>,
+/// #     ) -> Vec<sink::Response> {
+/// #         vec![]
+/// #     }
+/// # }
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+///     numaflow::Server::new()
+///         .with_service(udf::builder(UdfKind::Map).map(Cat))
+///         .with_service(udf::builder(UdfKind::Sink).sink(Log))
+///         .start()
+///         .await
+/// }
+/// ```
+#[derive(Default)]
+pub struct Server {
+    services: Vec<Serve>,
+}
+
+impl Server {
+    /// Create a server hosting no service yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a service to be hosted.
+    pub fn with_service<S: Service>(mut self, service: S) -> Self {
+        self.services
+            .push(Box::new(move |shutdown| service.serve(shutdown)));
+        self
+    }
+
+    /// Starts the services. They run until one of them is stopped or errors out.
+    pub async fn start(self) -> Result<(), BoxError> {
+        self.start_with_shutdown(future::pending()).await
+    }
+
+    /// Starts the services, they shut down once `shutdown` resolves and this returns once all of
+    /// them are done.
+    pub async fn start_with_shutdown<F>(self, shutdown: F) -> Result<(), BoxError>
+    where
+        F: Future<Output = ()>,
+    {
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let services = self.services.into_iter().map(|serve| {
+            let mut shutdown_rx = shutdown_rx.clone();
+            serve(
+                async move {
+                    let _ = shutdown_rx.wait_for(|shutting_down| *shutting_down).await;
+                }
+                .boxed(),
+            )
+        });
+        // dropping the services stops them, hence the first one failing stops the others
+        let services = future::try_join_all(services);
+
+        let signal = async {
+            shutdown.await;
+            let _ = shutdown_tx.send(true);
+            // the services are given the time to shut down
+            future::pending::<()>().await
+        };
+
+        tokio::select! {
+            result = services => result.map(|_| ()),
+            _ = signal => unreachable!("the signal never completes"),
+        }
+    }
+}
@@ -1,36 +1,1410 @@
+use std::any::Any;
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use std::fs;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
 
-use chrono::{DateTime, TimeZone, Utc};
-use prost_types::Timestamp;
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{TcpListenerStream, UnixListenerStream};
+use tokio_stream::Stream;
+use tonic::codegen::Service;
+use tonic::transport::server::Connected;
+use tonic::transport::{Channel, Endpoint, Uri};
+use tonic::Status;
 
-pub(crate) fn write_info_file() {
-    let path = if std::env::var_os("NUMAFLOW_POD").is_some() {
-        "/var/run/numaflow/server-info"
+use crate::control::{self, Knob};
+use crate::error::StatusMapper;
+use crate::headers::Headers;
+pub(crate) use crate::timestamp::{prost_timestamp_from_utc, utc_from_timestamp};
+
+/// Boxed error returned by the servers and the user provided hooks.
+pub type BoxError = Box<dyn Error + Send + Sync>;
+
+/// OutputAlive tells a streaming handler whether the results written to its output still reach
+/// numaflow, so that long running loops can bail out early once the client is gone instead of
+/// computing results nobody will receive.
+pub trait OutputAlive {
+    /// is_output_alive returns false once the results are not going to be received anymore.
+    fn is_output_alive(&self) -> bool;
+}
+
+impl<T> OutputAlive for mpsc::Sender<T> {
+    fn is_output_alive(&self) -> bool {
+        !self.is_closed()
+    }
+}
+
+/// OutputGuard guards the output of a streaming handler, e.g., the `tx` of a
+/// [`MapStreamer`](crate::mapstream::MapStreamer) or the `output` of an
+/// [`Accumulator`](crate::accumulator::Accumulator), against being taken for complete when the
+/// handler did not get to the end of it: unless [`complete`](OutputGuard::complete) is called,
+/// dropping the guard, be it on an early return, a panic or the handle being aborted, runs the
+/// cleanup and emits the terminal marker, if any. The results already sent cannot be taken back,
+/// the marker, e.g., a result tagged as partial, tells the next vertices to not trust them.
+///
+/// The guard dereferences to the [`Sender`](mpsc::Sender) it wraps. The marker is sent without
+/// waiting, or from a task when the output is full.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::mapstream::Message;
+/// use numaflow::shared::OutputGuard;
+/// use tokio::sync::mpsc;
+///
+/// fn partial() -> Message {
+///     Message {
+///         keys: vec![],
+///         value: "partial".into(),
+///         tags: vec!["partial".to_string()],
+///     }
+/// }
+///
+/// async fn split(input: &str, tx: mpsc::Sender<Message>) -> Result<(), std::num::ParseIntError> {
+///     let output = OutputGuard::new(tx).with_marker(partial);
+///     for part in input.split(',') {
+///         // an unparsable part returns early, the marker is emitted after the parts before it
+///         let part: u64 = part.parse()?;
+///         let message = Message {
+///             keys: vec![],
+///             value: part.to_string().into(),
+///             tags: vec![],
+///         };
+///         if output.send(message).await.is_err() {
+///             return Ok(());
+///         }
+///     }
+///     output.complete();
+///     Ok(())
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let (tx, mut rx) = mpsc::channel(8);
+///     assert!(split("1,x,3", tx).await.is_err());
+///     assert_eq!(rx.recv().await.unwrap().value, "1");
+///     assert_eq!(rx.recv().await.unwrap().tags, vec!["partial".to_string()]);
+///     assert!(rx.recv().await.is_none());
+///
+///     let (tx, mut rx) = mpsc::channel(8);
+///     assert!(split("1,2", tx).await.is_ok());
+///     assert_eq!(rx.recv().await.unwrap().value, "1");
+///     assert_eq!(rx.recv().await.unwrap().value, "2");
+///     assert!(rx.recv().await.is_none());
+/// }
+/// ```
+pub struct OutputGuard<M: Send + 'static> {
+    tx: mpsc::Sender<M>,
+    marker: Option<Box<dyn FnOnce() -> M + Send>>,
+    cleanup: Option<Box<dyn FnOnce() + Send>>,
+    complete: bool,
+}
+
+impl<M: Send + 'static> OutputGuard<M> {
+    /// Create a guard of the output, without a marker nor a cleanup.
+    pub fn new(tx: mpsc::Sender<M>) -> Self {
+        Self {
+            tx,
+            marker: None,
+            cleanup: None,
+            complete: false,
+        }
+    }
+
+    /// Set the terminal marker emitted when the output is not complete.
+    pub fn with_marker(mut self, marker: impl FnOnce() -> M + Send + 'static) -> Self {
+        self.marker = Some(Box::new(marker));
+        self
+    }
+
+    /// Set the cleanup run when the output is not complete, before the marker is emitted, e.g.,
+    /// to release what the handler holds for the results it did not get to.
+    pub fn with_cleanup(mut self, cleanup: impl FnOnce() + Send + 'static) -> Self {
+        self.cleanup = Some(Box::new(cleanup));
+        self
+    }
+
+    /// Marks the output as complete, the guard is dropped without running the cleanup nor
+    /// emitting the marker.
+    pub fn complete(mut self) {
+        self.complete = true;
+    }
+}
+
+impl<M: Send + 'static> std::ops::Deref for OutputGuard<M> {
+    type Target = mpsc::Sender<M>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tx
+    }
+}
+
+impl<M: Send + 'static> Drop for OutputGuard<M> {
+    fn drop(&mut self) {
+        if self.complete {
+            return;
+        }
+        if let Some(cleanup) = self.cleanup.take() {
+            cleanup();
+        }
+        let Some(marker) = self.marker.take() else {
+            return;
+        };
+        match self.tx.try_send(marker()) {
+            Ok(()) | Err(mpsc::error::TrySendError::Closed(_)) => {}
+            Err(mpsc::error::TrySendError::Full(marker)) => {
+                // drop cannot wait for room in the output, the send is handed over to a task
+                let tx = self.tx.clone();
+                if tokio::runtime::Handle::try_current().is_ok() {
+                    crate::tasks::spawn("output:marker", async move {
+                        let _ = tx.send(marker).await;
+                    });
+                } else {
+                    tracing::warn!("output is full, the terminal marker of the output is lost");
+                }
+            }
+        }
+    }
+}
+
+/// Returns the 64-bit FNV-1a hash of the bytes, it is stable across the processes and the
+/// versions of the SDK, unlike the hasher of the std.
+pub(crate) fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    bytes.into_iter().fold(OFFSET_BASIS, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(PRIME)
+    })
+}
+
+/// Returns the message of a panic caught with `catch_unwind`.
+pub(crate) fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
     } else {
-        "/tmp/numaflow.server-info"
-    };
+        "unknown panic".to_string()
+    }
+}
+
+/// Directory numaflow shares with the UDF container for the sockets and the server info files.
+pub const RUN_DIR: &str = "/var/run/numaflow";
 
-    // TODO: make port-number and CPU meta-data configurable, e.g., ("CPU_LIMIT", "1")
-    let metadata: HashMap<String, String> = HashMap::new();
-    let info = serde_json::json!({
-        "protocol": "uds",
-        "language": "rust",
-        "version": "0.0.1",
-        "metadata": metadata,
-    });
-
-    // Convert to a string of JSON and print it out
-    let content = info.to_string();
-    let content = format!("{}U+005C__END__", content);
-    println!("wrote to {} {}", path, content);
-    fs::write(path, content).unwrap();
-}
-
-pub(crate) fn utc_from_timestamp(t: Option<Timestamp>) -> DateTime<Utc> {
-    if let Some(ref t) = t {
-        Utc.timestamp_nanos(t.seconds * (t.nanos as i64))
+/// Environment variable relocating the [`RUN_DIR`] of the default sockets and server info files,
+/// e.g., when `/var/run` is read-only in the container. Unlike [`SOCKET_ENV`] it suits the
+/// processes hosting several servers, each keeps a socket of its own.
+pub const RUN_DIR_ENV: &str = "NUMAFLOW_UDF_RUN_DIR";
+
+/// Environment variable replacing the default socket file of the server, e.g., the
+/// [map](crate::map::DEFAULT_SOCK_ADDR) one. A socket set with `with_socket_file` takes
+/// precedence.
+pub const SOCKET_ENV: &str = "NUMAFLOW_UDF_SOCKET";
+
+/// Environment variable replacing the default server info file of the server, see
+/// [`DEFAULT_SERVER_INFO_FILE`]. A file set with `with_server_info_file` takes precedence.
+pub const SERVER_INFO_ENV: &str = "NUMAFLOW_UDF_SERVER_INFO";
+
+/// Default server info file outside of a numaflow pod, `server-info` in the [`RUN_DIR`] is used
+/// inside of one (`NUMAFLOW_POD` is set).
+pub const DEFAULT_SERVER_INFO_FILE: &str = "/tmp/numaflow.server-info";
+
+/// Default capacity of the channels between the gRPC streams and the handlers, that of the
+/// [`Profile::Balanced`] profile.
+pub const DEFAULT_CHANNEL_SIZE: usize = 1000;
+
+/// Returns the directory of the default sockets and server info files.
+fn run_dir() -> PathBuf {
+    std::env::var_os(RUN_DIR_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| RUN_DIR.into())
+}
+
+/// Returns the socket file of a server whose default is the given one, as relocated by the
+/// environment.
+pub(crate) fn socket_file(default: &str) -> PathBuf {
+    if let Some(file) = std::env::var_os(SOCKET_ENV) {
+        return file.into();
+    }
+    match Path::new(default).strip_prefix(RUN_DIR) {
+        Ok(name) => run_dir().join(name),
+        Err(_) => default.into(),
+    }
+}
+
+pub(crate) fn default_server_info_file() -> PathBuf {
+    server_info_file("server-info")
+}
+
+/// Returns the path of the server info file of the name, in the directory shared with numaflow
+/// when running inside a numaflow pod and in the temporary directory otherwise, unless the
+/// environment tells otherwise.
+pub(crate) fn server_info_file(name: &str) -> PathBuf {
+    if let Some(file) = std::env::var_os(SERVER_INFO_ENV) {
+        file.into()
+    } else if std::env::var_os("NUMAFLOW_POD").is_some() {
+        run_dir().join(name)
     } else {
-        Utc.timestamp_nanos(-1)
+        format!("/tmp/numaflow.{}", name).into()
+    }
+}
+
+/// ServerInfo is the content of the server info file, it tells numaflow how to talk to the UDF
+/// server. The `protocol` of the file, i.e., `uds` or `tcp`, and the `address` of a TCP listener
+/// are filled in from the listener of the server, the rest is what is set here.
+///
+/// ```
+/// use numaflow::shared::{ListenerKind, ServerInfo};
+///
+/// let info = ServerInfo::new()
+///     .with_protocol_version("1.2.0")
+///     .with_metadata("CPU_LIMIT", "1");
+/// let content = info.content(&ListenerKind::Uds("/var/run/numaflow/map.sock".into()));
+/// assert!(content.starts_with(r#"{"language":"rust","metadata":{"CPU_LIMIT":"1"},"#));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    language: String,
+    version: String,
+    protocol_version: Option<String>,
+    minimum_numaflow_version: String,
+    metadata: HashMap<String, String>,
+}
+
+impl Default for ServerInfo {
+    fn default() -> Self {
+        Self {
+            language: "rust".to_string(),
+            version: "0.0.1".to_string(),
+            protocol_version: None,
+            minimum_numaflow_version: crate::compat::MINIMUM_NUMAFLOW_VERSION.to_string(),
+            metadata: HashMap::new(),
+        }
+    }
+}
+
+impl ServerInfo {
+    /// Create the server info written by default, the `rust` language and the version of the
+    /// SDK without any metadata.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Change the language of the UDF. Default value is `rust`.
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = language.into();
+        self
+    }
+
+    /// Get the language of the UDF.
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Change the version of the SDK. Default value is `0.0.1`.
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Get the version of the SDK.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Set the version of the numaflow protocol the server speaks. Default value is the
+    /// `PROTOCOL_VERSION` of the module of the server, e.g.,
+    /// [`map::PROTOCOL_VERSION`](crate::map::PROTOCOL_VERSION).
+    pub fn with_protocol_version(mut self, version: impl Into<String>) -> Self {
+        self.protocol_version = Some(version.into());
+        self
+    }
+
+    /// Get the version of the numaflow protocol the server speaks.
+    pub fn protocol_version(&self) -> Option<&str> {
+        self.protocol_version.as_deref()
+    }
+
+    /// Change the oldest numaflow release the server works with, it is what the version check of
+    /// the servers compares the version of numaflow to. Default value is the oldest release the
+    /// SDK works with.
+    pub fn with_minimum_numaflow_version(mut self, version: impl Into<String>) -> Self {
+        self.minimum_numaflow_version = version.into();
+        self
+    }
+
+    /// Get the oldest numaflow release the server works with.
+    pub fn minimum_numaflow_version(&self) -> &str {
+        &self.minimum_numaflow_version
+    }
+
+    /// Add an entry to the metadata, e.g., a capability flag read by numaflow. The `address` key
+    /// is overwritten by the address of a TCP listener.
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Get the metadata.
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
+
+    /// Returns the content of the server info file of a server listening on `listener`, i.e., the
+    /// info as JSON followed by the end marker numaflow waits for.
+    pub fn content(&self, listener: &ListenerKind) -> String {
+        let mut metadata = self.metadata.clone();
+        let protocol = match listener {
+            ListenerKind::Uds(_) => "uds",
+            ListenerKind::Tcp(addr) => {
+                metadata.insert("address".to_string(), addr.to_string());
+                "tcp"
+            }
+        };
+        let mut info = serde_json::json!({
+            "protocol": protocol,
+            "language": self.language,
+            "version": self.version,
+            "minimum_numaflow_version": self.minimum_numaflow_version,
+            "metadata": metadata,
+        });
+        if let Some(protocol_version) = &self.protocol_version {
+            info["protocol_version"] = protocol_version.as_str().into();
+        }
+
+        format!("{}U+005C__END__", info)
+    }
+}
+
+pub(crate) fn write_info_file(
+    path: &PathBuf,
+    info: &ServerInfo,
+    listener: &ListenerKind,
+) -> std::io::Result<()> {
+    let content = info.content(listener);
+    tracing::info!(path = %path.display(), %content, "wrote the server info");
+    fs::write(path, content)
+}
+
+/// Runs the handler with a channel of the converted elements of the input, it is how the closure
+/// handlers get their input as trait objects. Forwarding stops as soon as the handler returns, so
+/// an early return drops the input like it does for a trait handler.
+pub(crate) async fn forward_input<T, U, F, Fut>(
+    input: mpsc::Receiver<T>,
+    convert: impl Fn(T) -> U,
+    handler: F,
+) -> Fut::Output
+where
+    F: FnOnce(mpsc::Receiver<U>) -> Fut,
+    Fut: Future,
+{
+    filter_input(input, |item| Some(convert(item)), handler).await
+}
+
+/// Like [`forward_input`], but the elements the conversion returns None for are not forwarded,
+/// e.g., those which cannot be decoded.
+pub(crate) async fn filter_input<T, U, F, Fut>(
+    mut input: mpsc::Receiver<T>,
+    convert: impl Fn(T) -> Option<U>,
+    handler: F,
+) -> Fut::Output
+where
+    F: FnOnce(mpsc::Receiver<U>) -> Fut,
+    Fut: Future,
+{
+    let (tx, rx) = mpsc::channel(1);
+    let forward = async move {
+        while let Some(item) = input.recv().await {
+            let Some(item) = convert(item) else {
+                continue;
+            };
+            if tx.send(item).await.is_err() {
+                break;
+            }
+        }
+        // the handler sees the end of its input once tx is dropped
+        drop(tx);
+        std::future::pending().await
+    };
+
+    tokio::select! {
+        output = handler(rx) => output,
+        never = forward => never,
+    }
+}
+
+/// PreStartError is returned when the hook registered via `with_pre_start` did not succeed, the
+/// server does not accept any traffic in that case.
+#[derive(Debug)]
+pub enum PreStartError {
+    /// The hook did not finish within the given timeout.
+    TimedOut(Duration),
+    /// The hook returned an error.
+    Failed(BoxError),
+}
+
+impl fmt::Display for PreStartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreStartError::TimedOut(timeout) => {
+                write!(f, "pre-start hook did not finish within {:?}", timeout)
+            }
+            PreStartError::Failed(e) => write!(f, "pre-start hook failed: {}", e),
+        }
+    }
+}
+
+impl Error for PreStartError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PreStartError::TimedOut(_) => None,
+            PreStartError::Failed(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+/// IncompatibleVersion is returned by the servers with the version check on when numaflow is
+/// older than the [minimum version](ServerInfo::minimum_numaflow_version) of the server. The
+/// version of numaflow is read from the `NUMAFLOW_VERSION` environment variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncompatibleVersion {
+    /// The version of numaflow.
+    pub numaflow: String,
+    /// The oldest version of numaflow the server works with.
+    pub minimum: String,
+}
+
+impl fmt::Display for IncompatibleVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "numaflow {} is older than {}, the oldest version the UDF works with",
+            self.numaflow, self.minimum
+        )
+    }
+}
+
+impl Error for IncompatibleVersion {}
+
+/// SocketInUse is returned on start up when another server is listening on the unix domain socket
+/// file of the server. The file left behind by a server which is gone, e.g., which crashed, is
+/// removed instead. The socket is taken over with `with_force_bind`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketInUse {
+    /// The socket file.
+    pub path: PathBuf,
+}
+
+impl fmt::Display for SocketInUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "another server is listening on {}, stop it or take the socket over with \
+             with_force_bind",
+            self.path.display()
+        )
+    }
+}
+
+impl Error for SocketInUse {}
+
+/// Binds the unix domain socket file, removing the file left behind by a server which is gone,
+/// i.e., which nothing answers on. The file of a live server is only removed if `force` is set.
+/// A file which is not a socket is left alone and the bind fails.
+pub(crate) fn bind_unix_socket(path: &Path, force: bool) -> Result<UnixListener, BoxError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        let live = std::os::unix::net::UnixStream::connect(path).is_ok();
+        if live && !force {
+            return Err(SocketInUse {
+                path: path.to_path_buf(),
+            }
+            .into());
+        }
+        if live {
+            tracing::warn!(
+                path = %path.display(),
+                "taking the socket over from the server listening on it"
+            );
+        } else {
+            tracing::info!(path = %path.display(), "removing the stale socket");
+        }
+        fs::remove_file(path)?;
+    }
+    Ok(UnixListener::bind(path)?)
+}
+
+/// The socket file bound by the server, removed once the server is done with it unless another
+/// server has taken it over since.
+pub(crate) struct SocketFile {
+    path: PathBuf,
+    inode: Option<u64>,
+}
+
+impl SocketFile {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            inode: fs::symlink_metadata(path)
+                .ok()
+                .map(|metadata| metadata.ino()),
+        }
+    }
+}
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let inode = fs::symlink_metadata(&self.path)
+            .ok()
+            .map(|metadata| metadata.ino());
+        if inode.is_some() && inode == self.inode {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+type PreStartFn = Box<dyn FnOnce() -> BoxFuture<'static, Result<(), BoxError>> + Send>;
+
+/// Hook run after the socket is bound but before the server accepts any traffic.
+pub(crate) struct PreStartHook {
+    timeout: Duration,
+    hook: PreStartFn,
+}
+
+impl PreStartHook {
+    pub(crate) fn new<F, Fut, E>(timeout: Duration, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        Self {
+            timeout,
+            hook: Box::new(move || {
+                let fut = hook();
+                Box::pin(async move { fut.await.map_err(Into::into) })
+            }),
+        }
+    }
+
+    async fn run(self) -> Result<(), PreStartError> {
+        match tokio::time::timeout(self.timeout, (self.hook)()).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(PreStartError::Failed(e)),
+            Err(_) => Err(PreStartError::TimedOut(self.timeout)),
+        }
     }
 }
+
+/// Profile is a preset of the buffer sizes and the transport settings of the server, for the users
+/// who would rather pick the class of their workload than tune every knob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Profile {
+    /// Balanced settings, used when no profile is set.
+    #[default]
+    Balanced,
+    /// Small buffers so that the elements are not queued up, at the cost of throughput.
+    LowLatency,
+    /// Large buffers and HTTP/2 flow control windows to keep the pipes full, at the cost of
+    /// memory and latency.
+    HighThroughput,
+    /// Small buffers and a bounded number of in-flight requests per connection, for UDFs running
+    /// with tight memory limits.
+    LowMemory,
+}
+
+/// The transport settings set one by one, they take precedence over those of the [`Profile`].
+#[derive(Default)]
+pub(crate) struct Transport {
+    pub(crate) keepalive_interval: Option<Duration>,
+    pub(crate) keepalive_timeout: Option<Duration>,
+    pub(crate) tcp_nodelay: bool,
+    pub(crate) stream_window_size: Option<u32>,
+    pub(crate) connection_window_size: Option<u32>,
+    pub(crate) adaptive_window: Option<bool>,
+    pub(crate) concurrency_limit: Option<usize>,
+    pub(crate) max_concurrent_streams: Option<u32>,
+    pub(crate) max_frame_size: Option<u32>,
+}
+
+/// The knobs set by a [`Profile`].
+pub(crate) struct Tuning {
+    /// capacity of the channels between the gRPC streams and the user's handles
+    pub(crate) channel_size: usize,
+    /// maximum number of in-flight requests per connection
+    pub(crate) concurrency_limit: Option<usize>,
+    /// HTTP/2 flow control windows of a stream and of the whole connection
+    pub(crate) stream_window_size: Option<u32>,
+    pub(crate) connection_window_size: Option<u32>,
+    pub(crate) adaptive_window: Option<bool>,
+}
+
+impl Profile {
+    pub(crate) fn tuning(self) -> Tuning {
+        match self {
+            Profile::Balanced => Tuning {
+                channel_size: DEFAULT_CHANNEL_SIZE,
+                concurrency_limit: None,
+                stream_window_size: None,
+                connection_window_size: None,
+                adaptive_window: None,
+            },
+            Profile::LowLatency => Tuning {
+                channel_size: 16,
+                concurrency_limit: None,
+                stream_window_size: None,
+                connection_window_size: None,
+                adaptive_window: None,
+            },
+            Profile::HighThroughput => Tuning {
+                channel_size: 10_000,
+                concurrency_limit: None,
+                stream_window_size: Some(4 * 1024 * 1024),
+                connection_window_size: Some(16 * 1024 * 1024),
+                adaptive_window: Some(true),
+            },
+            Profile::LowMemory => Tuning {
+                channel_size: 64,
+                concurrency_limit: Some(64),
+                stream_window_size: Some(64 * 1024),
+                connection_window_size: Some(256 * 1024),
+                adaptive_window: Some(false),
+            },
+        }
+    }
+}
+
+/// ListenerKind is where the server listens for the connections of numaflow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenerKind {
+    /// Unix domain socket at the given file path, this is how numaflow talks to the UDF container
+    /// of its pod.
+    Uds(PathBuf),
+    /// TCP socket at the given address, e.g., for local development on platforms where the unix
+    /// domain socket paths like `/var/run/numaflow` do not exist.
+    Tcp(SocketAddr),
+}
+
+/// A connection accepted on either kind of listener.
+pub(crate) enum Connection {
+    Uds(UnixStream),
+    Tcp(TcpStream),
+}
+
+/// What is known of the peer of a connection, it is attached to the requests of the connection.
+#[derive(Clone)]
+pub(crate) struct ConnectionInfo {
+    /// address of the client, the clients of a unix domain socket have none
+    pub(crate) peer: Option<SocketAddr>,
+}
+
+impl Connected for Connection {
+    type ConnectInfo = ConnectionInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        ConnectionInfo {
+            peer: match self {
+                Connection::Uds(_) => None,
+                Connection::Tcp(stream) => stream.peer_addr().ok(),
+            },
+        }
+    }
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Connection::Uds(stream) => Pin::new(stream).poll_read(cx, buf),
+            Connection::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Connection::Uds(stream) => Pin::new(stream).poll_write(cx, buf),
+            Connection::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Connection::Uds(stream) => Pin::new(stream).poll_flush(cx),
+            Connection::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Connection::Uds(stream) => Pin::new(stream).poll_shutdown(cx),
+            Connection::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+// connects the channels to a unix domain socket
+struct UdsConnector(PathBuf);
+
+impl Service<Uri> for UdsConnector {
+    type Response = UnixStream;
+    type Error = io::Error;
+    type Future = BoxFuture<'static, io::Result<UnixStream>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: Uri) -> Self::Future {
+        Box::pin(UnixStream::connect(self.0.clone()))
+    }
+}
+
+/// Connects a gRPC channel to the server listening on the unix domain socket file.
+pub(crate) async fn connect_unix(path: &Path) -> Result<Channel, tonic::transport::Error> {
+    // the uri is not used, the connector connects to the socket
+    Endpoint::from_static("http://[::]:50051")
+        .connect_with_connector(UdsConnector(path.to_path_buf()))
+        .await
+}
+
+/// The stream of the connections accepted by the server.
+pub(crate) enum Incoming {
+    Uds {
+        listener: UnixListenerStream,
+        // removes the socket file once the server is done with it
+        _socket: SocketFile,
+    },
+    /// the listener and whether Nagle's algorithm is disabled on the connections
+    Tcp(TcpListenerStream, bool),
+}
+
+impl Stream for Incoming {
+    type Item = io::Result<Connection>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut() {
+            Incoming::Uds { listener, .. } => Pin::new(listener)
+                .poll_next(cx)
+                .map(|conn| conn.map(|conn| conn.map(Connection::Uds))),
+            Incoming::Tcp(listener, nodelay) => {
+                let nodelay = *nodelay;
+                Pin::new(listener).poll_next(cx).map(|conn| {
+                    conn.map(|conn| {
+                        conn.and_then(|stream| {
+                            if nodelay {
+                                stream.set_nodelay(true)?;
+                            }
+                            Ok(Connection::Tcp(stream))
+                        })
+                    })
+                })
+            }
+        }
+    }
+}
+
+/// Configuration common to the gRPC servers of all the UDF kinds.
+pub(crate) struct ServerConfig {
+    pub(crate) sock_addr: PathBuf,
+    /// takes precedence over the `sock_addr` when set
+    pub(crate) tcp_addr: Option<SocketAddr>,
+    pub(crate) server_info_file: PathBuf,
+    pub(crate) server_info: ServerInfo,
+    /// version of the protocol of the UDF kind, written to the server info file unless set
+    pub(crate) protocol_version: &'static str,
+    pub(crate) version_check: bool,
+    pub(crate) pre_start: Option<PreStartHook>,
+    pub(crate) status_mapper: StatusMapper,
+    pub(crate) fatal_exit_code: Option<i32>,
+    pub(crate) tuning: Tuning,
+    pub(crate) transport: Transport,
+    pub(crate) force_bind: bool,
+    pub(crate) control_sock_addr: Option<PathBuf>,
+    pub(crate) knobs: Vec<Knob>,
+    /// the flush of the reduce streams, served by the control socket
+    pub(crate) flush: Option<crate::reduce::FlushHandle>,
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics_port: Option<u16>,
+    pub(crate) blocking_threshold: Option<Duration>,
+    pub(crate) tracing: bool,
+    pub(crate) stream_context: bool,
+    #[cfg(feature = "compression")]
+    pub(crate) decompression: bool,
+    #[cfg(feature = "compression")]
+    pub(crate) grpc_compression: Option<tonic::codec::CompressionEncoding>,
+    #[cfg(feature = "health")]
+    pub(crate) health_service: bool,
+    #[cfg(feature = "health")]
+    pub(crate) health_check: Option<std::sync::Arc<dyn crate::health::HealthCheck>>,
+    #[cfg(feature = "health")]
+    pub(crate) health_check_interval: Duration,
+}
+
+impl ServerConfig {
+    pub(crate) fn new(sock_addr: &str, protocol_version: &'static str) -> Self {
+        Self {
+            sock_addr: socket_file(sock_addr),
+            tcp_addr: None,
+            server_info_file: default_server_info_file(),
+            server_info: ServerInfo::default(),
+            protocol_version,
+            version_check: false,
+            pre_start: None,
+            status_mapper: Status::from,
+            fatal_exit_code: None,
+            tuning: Profile::default().tuning(),
+            transport: Transport::default(),
+            force_bind: false,
+            control_sock_addr: None,
+            knobs: vec![],
+            flush: None,
+            #[cfg(feature = "metrics")]
+            metrics_port: None,
+            blocking_threshold: None,
+            tracing: false,
+            stream_context: false,
+            #[cfg(feature = "compression")]
+            decompression: false,
+            #[cfg(feature = "compression")]
+            grpc_compression: None,
+            #[cfg(feature = "health")]
+            health_service: false,
+            #[cfg(feature = "health")]
+            health_check: None,
+            #[cfg(feature = "health")]
+            health_check_interval: crate::health::DEFAULT_CHECK_INTERVAL,
+        }
+    }
+
+    /// Returns where the server listens for connections.
+    pub(crate) fn listener(&self) -> ListenerKind {
+        match self.tcp_addr {
+            Some(addr) => ListenerKind::Tcp(addr),
+            None => ListenerKind::Uds(self.sock_addr.clone()),
+        }
+    }
+
+    /// Returns the gRPC server builder with the transport settings of the [`Profile`] applied,
+    /// and those set one by one on top of them.
+    pub(crate) fn transport(&self) -> tonic::transport::Server {
+        let (tuning, transport) = (&self.tuning, &self.transport);
+        let mut builder = tonic::transport::Server::builder()
+            .initial_stream_window_size(transport.stream_window_size.or(tuning.stream_window_size))
+            .initial_connection_window_size(
+                transport
+                    .connection_window_size
+                    .or(tuning.connection_window_size),
+            )
+            .http2_adaptive_window(transport.adaptive_window.or(tuning.adaptive_window))
+            .http2_keepalive_interval(transport.keepalive_interval)
+            .http2_keepalive_timeout(transport.keepalive_timeout)
+            .max_concurrent_streams(transport.max_concurrent_streams)
+            .max_frame_size(transport.max_frame_size);
+        if let Some(limit) = transport.concurrency_limit.or(tuning.concurrency_limit) {
+            builder = builder.concurrency_limit_per_connection(limit);
+        }
+        if self.stream_context {
+            builder = builder.trace_fn(crate::trace::stream_span);
+        }
+        builder
+    }
+
+    /// Returns the settings of the handler invocations, to be kept by the service of the server.
+    pub(crate) fn handler_settings(&self) -> HandlerSettings {
+        HandlerSettings {
+            status_mapper: self.status_mapper,
+            fatal_exit_code: self.fatal_exit_code,
+            tracing: self.tracing,
+            #[cfg(feature = "compression")]
+            decompression: self.decompression,
+            blocking_threshold: self.blocking_threshold,
+        }
+    }
+
+    /// Binds the socket, runs the pre-start hook and writes the server-info file. The returned
+    /// stream is ready to be served.
+    pub(crate) async fn prepare(&mut self) -> Result<Incoming, BoxError> {
+        if self.version_check {
+            crate::compat::check_numaflow_version(&self.server_info.minimum_numaflow_version)?;
+        }
+
+        let listener = self.listener();
+        let incoming = match &listener {
+            ListenerKind::Uds(path) => {
+                let listener = bind_unix_socket(path, self.force_bind)?;
+                Incoming::Uds {
+                    listener: UnixListenerStream::new(listener),
+                    _socket: SocketFile::new(path),
+                }
+            }
+            ListenerKind::Tcp(addr) => {
+                let listener = TcpListenerStream::new(TcpListener::bind(addr).await?);
+                Incoming::Tcp(listener, self.transport.tcp_nodelay)
+            }
+        };
+
+        if let Some(hook) = self.pre_start.take() {
+            hook.run().await?;
+        }
+
+        if let Some(control_sock_addr) = &self.control_sock_addr {
+            control::serve(
+                control_sock_addr,
+                std::mem::take(&mut self.knobs),
+                self.flush.clone(),
+            )?;
+        }
+
+        #[cfg(feature = "metrics")]
+        if let Some(port) = self.metrics_port {
+            crate::metrics::serve(port)?;
+        }
+
+        if self.server_info.protocol_version.is_none() {
+            self.server_info.protocol_version = Some(self.protocol_version.to_string());
+        }
+        write_info_file(&self.server_info_file, &self.server_info, &listener)?;
+
+        Ok(incoming)
+    }
+}
+
+/// Settings of the handler invocations taken from the [`ServerConfig`], each service keeps its own
+/// copy so that the servers of a process do not share them.
+#[derive(Clone, Copy)]
+pub(crate) struct HandlerSettings {
+    pub(crate) status_mapper: StatusMapper,
+    pub(crate) fatal_exit_code: Option<i32>,
+    pub(crate) tracing: bool,
+    #[cfg(feature = "compression")]
+    pub(crate) decompression: bool,
+    pub(crate) blocking_threshold: Option<Duration>,
+}
+
+// the settings of a server left as configured by default
+impl Default for HandlerSettings {
+    fn default() -> Self {
+        Self {
+            status_mapper: Status::from,
+            fatal_exit_code: None,
+            tracing: false,
+            #[cfg(feature = "compression")]
+            decompression: false,
+            blocking_threshold: None,
+        }
+    }
+}
+
+impl HandlerSettings {
+    /// Converts the error into the status returned to numaflow, see [`crate::error::to_status`].
+    pub(crate) fn to_status(self, error: crate::error::Error) -> Status {
+        crate::error::to_status(self.status_mapper, self.fatal_exit_code, error)
+    }
+
+    /// Returns the span to run an invocation of the handler in, see
+    /// [`crate::trace::handler_span`].
+    pub(crate) fn handler_span(
+        self,
+        handler: &str,
+        headers: Option<&HashMap<String, String>>,
+    ) -> tracing::Span {
+        crate::trace::handler_span(self.tracing, handler, headers)
+    }
+
+    /// Runs the future of the handler, logging the polls blocking the async runtime, see
+    /// [`crate::watchdog::watch`].
+    pub(crate) async fn watch<F: Future>(self, handler: &str, future: F) -> F::Output {
+        crate::watchdog::watch(self.blocking_threshold, handler, future).await
+    }
+
+    /// Decompresses the payload marked with the `content-encoding` header when the decompression
+    /// is on, it is a no-op without the `compression` feature.
+    pub(crate) fn decompress_payload(self, headers: &mut Headers, value: &mut Bytes) {
+        #[cfg(feature = "compression")]
+        if self.decompression {
+            crate::compression::decompress_payload(headers, value);
+        }
+        #[cfg(not(feature = "compression"))]
+        let _ = (headers, value);
+    }
+}
+
+/// Builder methods common to the `Server` of every UDF kind. The `Server` is expected to have a
+/// `config` field of type [`ServerConfig`].
+macro_rules! server_config_methods {
+    () => {
+        /// Set the unix domain socket file path used by the gRPC server to listen for incoming
+        /// connections. Default value is the `DEFAULT_SOCK_ADDR` of the UDF kind, as relocated by
+        /// the [`SOCKET_ENV`](crate::shared::SOCKET_ENV) and
+        /// [`RUN_DIR_ENV`](crate::shared::RUN_DIR_ENV) environment variables.
+        pub fn with_socket_file(mut self, file: impl Into<std::path::PathBuf>) -> Self {
+            self.config.sock_addr = file.into();
+            self
+        }
+
+        /// Get the unix domain socket file path where the gRPC server listens for incoming
+        /// connections.
+        pub fn socket_file(&self) -> &std::path::Path {
+            self.config.sock_addr.as_path()
+        }
+
+        /// Listen for incoming connections on the TCP address instead of the unix domain socket,
+        /// e.g., for local development on platforms without `/var/run/numaflow`. The socket file
+        /// is not used then.
+        pub fn with_tcp_listener(mut self, addr: std::net::SocketAddr) -> Self {
+            self.config.tcp_addr = Some(addr);
+            self
+        }
+
+        /// Get where the gRPC server listens for incoming connections.
+        pub fn listener(&self) -> $crate::shared::ListenerKind {
+            self.config.listener()
+        }
+
+        /// Change the file in which numaflow server information is stored on start up to the new
+        /// value. Default value is `/tmp/numaflow.server-info` (`/var/run/numaflow/server-info`
+        /// when running inside a numaflow pod), unless set by the
+        /// [`SERVER_INFO_ENV`](crate::shared::SERVER_INFO_ENV) environment variable.
+        pub fn with_server_info_file(mut self, file: impl Into<std::path::PathBuf>) -> Self {
+            self.config.server_info_file = file.into();
+            self
+        }
+
+        /// Get the path to the file where numaflow server info is stored.
+        pub fn server_info_file(&self) -> &std::path::Path {
+            self.config.server_info_file.as_path()
+        }
+
+        /// Change the content of the server info file, e.g., to advertise the capabilities of a
+        /// fork of the SDK. Default value is [`ServerInfo::new`](crate::shared::ServerInfo::new).
+        pub fn with_server_info(mut self, info: $crate::shared::ServerInfo) -> Self {
+            self.config.server_info = info;
+            self
+        }
+
+        /// Get the content of the server info file.
+        pub fn server_info(&self) -> &$crate::shared::ServerInfo {
+            &self.config.server_info
+        }
+
+        /// Check on start up that numaflow, as per the `NUMAFLOW_VERSION` environment variable, is
+        /// not older than the [minimum version](crate::shared::ServerInfo::minimum_numaflow_version)
+        /// of the server. The server does not start otherwise and an
+        /// [`IncompatibleVersion`](crate::shared::IncompatibleVersion) is returned. The check is
+        /// skipped when the variable is not set. It is off by default.
+        pub fn with_version_check(mut self, enabled: bool) -> Self {
+            self.config.version_check = enabled;
+            self
+        }
+
+        /// Register a hook that is run after the socket is bound but before the server accepts
+        /// any traffic, e.g., to wait for a downstream dependency (database, schema registry) to
+        /// come up. If the hook fails or does not finish within `timeout`, the server does not
+        /// start and a [`PreStartError`](crate::shared::PreStartError) is returned.
+        pub fn with_pre_start<F, Fut, E>(mut self, timeout: std::time::Duration, hook: F) -> Self
+        where
+            F: FnOnce() -> Fut + Send + 'static,
+            Fut: std::future::Future<Output = Result<(), E>> + Send + 'static,
+            E: Into<$crate::shared::BoxError>,
+        {
+            self.config.pre_start = Some($crate::shared::PreStartHook::new(timeout, hook));
+            self
+        }
+
+        /// Set the function converting the [`Error`](crate::error::Error) raised while serving a
+        /// request into the [`Status`](tonic::Status) returned to numaflow, e.g., to pick the
+        /// status codes numaflow keys its retries off. Default is the
+        /// [`From<Error>`](crate::error::Error) implementation of `Status`.
+        pub fn with_status_mapper(mut self, mapper: $crate::error::StatusMapper) -> Self {
+            self.config.status_mapper = mapper;
+            self
+        }
+
+        /// Exit the process with a distinct code on a fatal error, e.g., a panic of the reduce handler,
+        /// rather than returning the error to numaflow, so that the cause is not hidden by the
+        /// shutdown which follows. The error is logged first. The code of an error is
+        /// given by [`Error::fatal_exit_code`](crate::error::Error::fatal_exit_code) out of
+        /// `code`. It is off by default.
+        pub fn with_fatal_exit_code(mut self, code: i32) -> Self {
+            self.config.fatal_exit_code = Some(code);
+            self
+        }
+
+        /// Get the exit code of the fatal errors, if the fatal exit mode is on.
+        pub fn fatal_exit_code(&self) -> Option<i32> {
+            self.config.fatal_exit_code
+        }
+
+        /// Set the [`Profile`](crate::shared::Profile) picking the buffer sizes and the transport
+        /// settings for the class of the workload. Default is
+        /// [`Profile::Balanced`](crate::shared::Profile::Balanced).
+        pub fn with_profile(mut self, profile: $crate::shared::Profile) -> Self {
+            self.config.tuning = profile.tuning();
+            self
+        }
+
+        /// Send HTTP/2 pings to numaflow at the interval, to detect a connection which is gone
+        /// while no message flows, e.g., between the windows of a reduce. No pings are sent by
+        /// default.
+        pub fn with_http2_keepalive_interval(mut self, interval: std::time::Duration) -> Self {
+            self.config.transport.keepalive_interval = Some(interval);
+            self
+        }
+
+        /// Set how long the answer to an HTTP/2 ping is awaited before the connection is closed,
+        /// see `with_http2_keepalive_interval`. Default value is 20 seconds.
+        pub fn with_http2_keepalive_timeout(mut self, timeout: std::time::Duration) -> Self {
+            self.config.transport.keepalive_timeout = Some(timeout);
+            self
+        }
+
+        /// Disable Nagle's algorithm on the TCP connections, so that the small messages are not
+        /// delayed, see `with_tcp_listener`. It has no effect on the unix domain socket. It is
+        /// off by default.
+        pub fn with_tcp_nodelay(mut self, enabled: bool) -> Self {
+            self.config.transport.tcp_nodelay = enabled;
+            self
+        }
+
+        /// Set the HTTP/2 flow control window of a stream, in bytes, e.g., larger for messages of
+        /// several MB. It takes precedence over the [`Profile`](crate::shared::Profile).
+        pub fn with_initial_stream_window_size(mut self, size: u32) -> Self {
+            self.config.transport.stream_window_size = Some(size);
+            self
+        }
+
+        /// Set the HTTP/2 flow control window of the whole connection, in bytes. It takes
+        /// precedence over the [`Profile`](crate::shared::Profile).
+        pub fn with_initial_connection_window_size(mut self, size: u32) -> Self {
+            self.config.transport.connection_window_size = Some(size);
+            self
+        }
+
+        /// Let HTTP/2 size the flow control windows as per the measured bandwidth and latency,
+        /// the window sizes set are then the initial ones. It takes precedence over the
+        /// [`Profile`](crate::shared::Profile).
+        pub fn with_http2_adaptive_window(mut self, enabled: bool) -> Self {
+            self.config.transport.adaptive_window = Some(enabled);
+            self
+        }
+
+        /// Limit the number of requests served at once on a connection, the others wait. It takes
+        /// precedence over the [`Profile`](crate::shared::Profile).
+        pub fn with_concurrency_limit_per_connection(mut self, limit: usize) -> Self {
+            self.config.transport.concurrency_limit = Some(limit);
+            self
+        }
+
+        /// Limit the number of HTTP/2 streams numaflow may open at once on a connection. There is
+        /// no limit by default.
+        pub fn with_max_concurrent_streams(mut self, max: u32) -> Self {
+            self.config.transport.max_concurrent_streams = Some(max);
+            self
+        }
+
+        /// Set the largest HTTP/2 frame the server accepts, in bytes, from 16 KiB to 16 MiB.
+        /// Default value is 16 KiB.
+        pub fn with_max_frame_size(mut self, size: u32) -> Self {
+            self.config.transport.max_frame_size = Some(size);
+            self
+        }
+
+        /// Take the unix domain socket file over even if another server is listening on it, e.g.,
+        /// the previous instance of the UDF still shutting down. Without it the server fails to
+        /// start with a [`SocketInUse`](crate::shared::SocketInUse) error then. The file left
+        /// behind by a server which is gone is removed either way. It is disabled by default.
+        pub fn with_force_bind(mut self, enabled: bool) -> Self {
+            self.config.force_bind = enabled;
+            self
+        }
+
+        /// Get whether the socket file is taken over from a live server.
+        pub fn force_bind(&self) -> bool {
+            self.config.force_bind
+        }
+
+        /// Enable the control socket at the given unix domain socket file path, it allows changing
+        /// the [`Knob`](crate::control::Knob)s registered with `with_knob` on the live server.
+        /// It is disabled by default.
+        pub fn with_control_socket(mut self, file: impl Into<std::path::PathBuf>) -> Self {
+            self.config.control_sock_addr = Some(file.into());
+            self
+        }
+
+        /// Register a [`Knob`](crate::control::Knob) to be served on the control socket.
+        pub fn with_knob(mut self, knob: $crate::control::Knob) -> Self {
+            self.config.knobs.push(knob);
+            self
+        }
+
+        /// Run every invocation of the handler in a `tracing` span named `numaflow.handler`. The
+        /// W3C trace context of the `traceparent` header of the message, if any, is recorded as the
+        /// `trace_id` and `parent_span_id` fields of the span, so that the latency of the UDF can
+        /// be joined with the distributed trace. It is disabled by default.
+        pub fn with_tracing(mut self, enabled: bool) -> Self {
+            self.config.tracing = enabled;
+            self
+        }
+
+        /// Run every gRPC stream opened by numaflow in a `tracing` span named `numaflow.stream`,
+        /// so that the spans and the events logged through `tracing` within a stream, including
+        /// those of the handler, can be told apart from those of the concurrent streams. The span
+        /// carries a `stream_id` unique in the process, the gRPC `method`, the `peer` address with
+        /// the [TCP listener](Self::with_tcp_listener) and the `vertex` and `pod` of numaflow, when
+        /// known. It is disabled by default.
+        pub fn with_stream_context(mut self, enabled: bool) -> Self {
+            self.config.stream_context = enabled;
+            self
+        }
+
+        /// Log a warning whenever the handler blocks the async runtime for longer than `threshold`
+        /// at once, e.g., with blocking I/O or heavy computation which should have been run with
+        /// `tokio::task::spawn_blocking`. A blocked executor thread stalls the other requests
+        /// scheduled on it. It is disabled by default.
+        pub fn with_blocking_detection(mut self, threshold: std::time::Duration) -> Self {
+            self.config.blocking_threshold = Some(threshold);
+            self
+        }
+
+        /// Serve the prometheus metrics of the server, e.g., messages received and emitted and the
+        /// latency of the handler, on `/metrics` of the HTTP port. It is disabled by default.
+        #[cfg(feature = "metrics")]
+        pub fn with_metrics_port(mut self, port: u16) -> Self {
+            self.config.metrics_port = Some(port);
+            self
+        }
+
+        /// Decompress the payloads marked with the
+        /// [`content-encoding`](crate::compression::CONTENT_ENCODING) header before handing them
+        /// to the handler, the header is removed then. A payload which cannot be decompressed is
+        /// handed over as is, with its header. Only the map stream, batch map, source transformer,
+        /// reduce and accumulator requests carry headers. It is disabled by default.
+        #[cfg(feature = "compression")]
+        pub fn with_decompression(mut self, enabled: bool) -> Self {
+            self.config.decompression = enabled;
+            self
+        }
+
+        /// Compress the gRPC messages with the encoding, e.g., for the reduce requests and
+        /// responses of tens of MB, and accept the messages numaflow compresses with it. A
+        /// response is only compressed if numaflow accepts the encoding, as per its
+        /// `grpc-accept-encoding` header. Only gzip is supported by the gRPC transport. Unlike the
+        /// compression of the payloads, the messages are compressed on the wire only. They are
+        /// not compressed by default.
+        #[cfg(feature = "compression")]
+        pub fn with_grpc_compression(
+            mut self,
+            encoding: $crate::compression::CompressionEncoding,
+        ) -> Self {
+            self.config.grpc_compression = Some(encoding);
+            self
+        }
+
+        /// Get the encoding the gRPC messages are compressed with.
+        #[cfg(feature = "compression")]
+        pub fn grpc_compression(&self) -> Option<$crate::compression::CompressionEncoding> {
+            self.config.grpc_compression
+        }
+
+        /// Serve the standard gRPC health checking service (`grpc.health.v1.Health`) next to the
+        /// UDF service, for the probes of Kubernetes. The server is reported as serving as long
+        /// as it runs, unless a [`HealthCheck`](crate::health::HealthCheck) is set with
+        /// [`with_health_check`](Self::with_health_check). It is disabled by default.
+        #[cfg(feature = "health")]
+        pub fn with_health_service(mut self, enabled: bool) -> Self {
+            self.config.health_service = enabled;
+            self
+        }
+
+        /// Get whether the gRPC health checking service is served.
+        #[cfg(feature = "health")]
+        pub fn health_service(&self) -> bool {
+            self.config.health_service
+        }
+
+        /// Poll the [`HealthCheck`](crate::health::HealthCheck) of the handler and report the
+        /// server as not serving through the gRPC health checking service while it fails, it turns
+        /// the service on.
+        #[cfg(feature = "health")]
+        pub fn with_health_check(
+            mut self,
+            check: impl $crate::health::HealthCheck + 'static,
+        ) -> Self {
+            self.config.health_service = true;
+            self.config.health_check = Some(std::sync::Arc::new(check));
+            self
+        }
+
+        /// Set how often the health check is polled, and how long it is given to answer. Default
+        /// value is [`DEFAULT_CHECK_INTERVAL`](crate::health::DEFAULT_CHECK_INTERVAL).
+        #[cfg(feature = "health")]
+        pub fn with_health_check_interval(mut self, interval: std::time::Duration) -> Self {
+            self.config.health_check_interval = interval;
+            self
+        }
+
+        /// Get how often the health check is polled.
+        #[cfg(feature = "health")]
+        pub fn health_check_interval(&self) -> std::time::Duration {
+            self.config.health_check_interval
+        }
+    };
+}
+
+pub(crate) use server_config_methods;
+
+/// Applies the gRPC compression set with `with_grpc_compression` to the generated server of a UDF kind,
+/// e.g., `MapServer`.
+macro_rules! grpc_service {
+    ($config:expr, $server:expr) => {{
+        let server = $server;
+        #[cfg(feature = "compression")]
+        let server = match $config.grpc_compression {
+            Some(encoding) => server.accept_compressed(encoding).send_compressed(encoding),
+            None => server,
+        };
+        server
+    }};
+}
+
+pub(crate) use grpc_service;
+
+/// Returns the gRPC router serving the generated server of a UDF kind, with the compression
+/// applied and the health checking service next to it when they are on.
+macro_rules! router {
+    ($config:expr, $server:expr) => {{
+        let server = $crate::shared::grpc_service!($config, $server);
+        #[cfg(feature = "health")]
+        let health = $crate::health::service(&$config, &server);
+        let router = $config.transport().add_service(server);
+        #[cfg(feature = "health")]
+        let router = router.add_optional_service(health);
+        router
+    }};
+}
+
+pub(crate) use router;
@@ -0,0 +1,438 @@
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use arc_swap::ArcSwapOption;
+use futures_util::future::BoxFuture;
+use serde::de::DeserializeOwned;
+use tokio::sync::watch;
+use tonic::{async_trait, Request, Response, Status};
+use tracing::Instrument;
+
+use crate::shared::HandlerSettings;
+use crate::sideinput::sideinputer::side_input_server::{SideInput, SideInputServer};
+use crate::sideinput::sideinputer::{ReadyResponse, SideInputResponse};
+use crate::{error, shared};
+
+mod sideinputer {
+    tonic::include_proto!("sideinput.v1");
+}
+
+/// The gRPC messages, client and server of the side input protocol, generated from its proto.
+pub mod proto {
+    pub use super::sideinputer::*;
+}
+
+/// Directory in which numaflow mounts the values of the side inputs of a vertex, one file per
+/// side input named after it.
+pub const SIDE_INPUTS_DIR: &str = "/var/numaflow/side-inputs";
+
+// how often the file of a watched side input is read
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default unix domain socket file of the side input server, see [`Server::with_socket_file`]. It is
+/// relocated by the [`SOCKET_ENV`](crate::shared::SOCKET_ENV) and
+/// [`RUN_DIR_ENV`](crate::shared::RUN_DIR_ENV) environment variables.
+pub const DEFAULT_SOCK_ADDR: &str = "/var/run/numaflow/sideinput.sock";
+
+/// Version of the side input protocol, i.e., of the `sideinput.v1` proto package.
+pub const PROTOCOL_VERSION: &str = "v1";
+
+struct SideInputService<T> {
+    handler: T,
+    settings: HandlerSettings,
+}
+
+/// SideInputer trait for implementing the side input retriever of a side input generator vertex.
+#[async_trait]
+pub trait SideInputer {
+    /// retrieve_sideinput is invoked periodically by numaflow to fetch the latest value of the
+    /// side input. Returning `None` tells numaflow not to broadcast anything this time, e.g.,
+    /// because the value has not changed or could not be fetched. More about side inputs can be
+    /// read [here](https://numaflow.numaproj.io/specifications/side-inputs/).
+    ///
+    /// # Example
+    ///
+    /// Following is an example of a side input which broadcasts the current time on every other
+    /// invocation.
+    ///
+    /// ```no_run
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    ///
+    /// use numaflow::sideinput;
+    ///
+    /// struct Clock {
+    ///     skip: AtomicBool,
+    /// }
+    ///
+    /// #[tonic::async_trait]
+    /// impl sideinput::SideInputer for Clock {
+    ///     async fn retrieve_sideinput(&self) -> Option<Vec<u8>> {
+    ///         if self.skip.fetch_xor(true, Ordering::SeqCst) {
+    ///             return None;
+    ///         }
+    ///         Some(chrono::Utc::now().to_rfc3339().into_bytes())
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     let side_input_handler = Clock {
+    ///         skip: AtomicBool::new(false),
+    ///     };
+    ///
+    ///     sideinput::Server::new(side_input_handler).start().await
+    /// }
+    /// ```
+    async fn retrieve_sideinput(&self) -> Option<Vec<u8>>;
+}
+
+#[async_trait]
+impl<T> SideInput for SideInputService<T>
+where
+    T: SideInputer + Send + Sync + 'static,
+{
+    async fn retrieve_side_input(
+        &self,
+        _: Request<()>,
+    ) -> Result<Response<SideInputResponse>, Status> {
+        let retrieve_handle = self
+            .settings
+            .watch("sideinput", self.handler.retrieve_sideinput())
+            .instrument(self.settings.handler_span("sideinput", None));
+        let response = match retrieve_handle.await {
+            Some(value) => SideInputResponse {
+                value: value.into(),
+                no_broadcast: false,
+            },
+            // the "no broadcast" sentinel
+            None => SideInputResponse {
+                value: Default::default(),
+                no_broadcast: true,
+            },
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn is_ready(&self, _: Request<()>) -> Result<Response<ReadyResponse>, Status> {
+        Ok(Response::new(ReadyResponse { ready: true }))
+    }
+}
+
+/// FromFn is a [`SideInputer`] running a closure, see [`Server::from_fn`].
+pub struct FromFn<F>(F);
+
+#[async_trait]
+impl<F, Fut> SideInputer for FromFn<F>
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Option<Vec<u8>>> + Send,
+{
+    async fn retrieve_sideinput(&self) -> Option<Vec<u8>> {
+        (self.0)().await
+    }
+}
+
+/// gRPC server to start a side input service
+pub struct Server<T> {
+    config: shared::ServerConfig,
+    svc: T,
+}
+
+impl<T> Server<T> {
+    /// Create a new side input server with the given [`SideInputer`] handler.
+    pub fn new(side_input_svc: T) -> Self {
+        Self {
+            config: shared::ServerConfig::new(DEFAULT_SOCK_ADDR, PROTOCOL_VERSION),
+            svc: side_input_svc,
+        }
+    }
+
+    shared::server_config_methods!();
+
+    /// Starts the gRPC server. The server runs until it is stopped or errors out.
+    pub async fn start(self) -> Result<(), shared::BoxError>
+    where
+        T: SideInputer + Send + Sync + 'static,
+    {
+        self.start_with_shutdown(std::future::pending()).await
+    }
+
+    /// Starts the gRPC server, it shuts down once `shutdown` resolves. The server then stops
+    /// accepting traffic and returns once the in-flight requests are done.
+    pub async fn start_with_shutdown<F>(self, shutdown: F) -> Result<(), shared::BoxError>
+    where
+        T: SideInputer + Send + Sync + 'static,
+        F: Future<Output = ()>,
+    {
+        let mut config = self.config;
+        let incoming = config.prepare().await?;
+
+        let side_input_svc = SideInputService {
+            handler: self.svc,
+            settings: config.handler_settings(),
+        };
+
+        shared::router!(config, SideInputServer::new(side_input_svc))
+            .serve_with_incoming_shutdown(incoming, shutdown)
+            .await
+            .map_err(|e| error::Error::SideInputError(error::ErrorKind::connection(e)))?;
+
+        Ok(())
+    }
+}
+
+impl<T> crate::server::Service for Server<T>
+where
+    T: SideInputer + Send + Sync + 'static,
+{
+    fn serve(
+        self,
+        shutdown: BoxFuture<'static, ()>,
+    ) -> BoxFuture<'static, Result<(), shared::BoxError>> {
+        Box::pin(self.start_with_shutdown(shutdown))
+    }
+}
+
+impl<F> Server<FromFn<F>> {
+    /// Create a new side input server running the closure as the
+    /// [`SideInputer::retrieve_sideinput`] handler, for the handlers too small to be worth a type
+    /// of their own.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use numaflow::sideinput;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     sideinput::Server::from_fn(|| async { Some(chrono::Utc::now().to_rfc3339().into_bytes()) })
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn from_fn<Fut>(handler: F) -> Self
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Option<Vec<u8>>>,
+    {
+        Self::new(FromFn(handler))
+    }
+}
+
+type Validator<T> = Box<dyn Fn(&T) -> Result<(), shared::BoxError> + Send + Sync>;
+
+/// ConfigWatcher watches a side input holding the JSON configuration of the handlers, e.g., the
+/// thresholds of a filter, and decodes it into `T`. It is the consumption half of the side inputs,
+/// the value is broadcast by a [`SideInputer`] and mounted by numaflow in the UDF container.
+///
+/// # Example
+///
+/// ```no_run
+/// use numaflow::map::{self, Datum, Message};
+/// use numaflow::sideinput::{Config, ConfigWatcher};
+///
+/// #[derive(serde::Deserialize)]
+/// struct Thresholds {
+///     min_len: usize,
+/// }
+///
+/// struct Filter {
+///     thresholds: Config<Thresholds>,
+/// }
+///
+/// #[tonic::async_trait]
+/// impl map::Mapper for Filter {
+///     async fn map<T: Datum + Send + Sync + 'static>(&self, input: T) -> Vec<Message> {
+///         let min_len = self.thresholds.current().map_or(0, |t| t.min_len);
+///         if input.value().len() < min_len {
+///             return vec![];
+///         }
+///         vec![Message {
+///             keys: input.keys().clone(),
+///             value: input.value().clone(),
+///             tags: vec![],
+///         }]
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+///     let thresholds = ConfigWatcher::new("thresholds")
+///         .with_validator(|t: &Thresholds| match t.min_len {
+///             0..=1024 => Ok(()),
+///             _ => Err("min_len is beyond the largest payload"),
+///         })
+///         .start();
+///     map::Server::new(Filter { thresholds }).start().await
+/// }
+/// ```
+pub struct ConfigWatcher<T> {
+    name: String,
+    dir: PathBuf,
+    poll_interval: Duration,
+    validator: Option<Validator<T>>,
+}
+
+impl<T> ConfigWatcher<T>
+where
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    /// Create a watcher of the side input named `name`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            dir: SIDE_INPUTS_DIR.into(),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            validator: None,
+        }
+    }
+
+    /// Change the directory the side input is read from. Default value is [`SIDE_INPUTS_DIR`].
+    pub fn with_side_inputs_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    /// Change how often the side input is read. Default value is 1 second.
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Register a check of the decoded values, a value failing it is logged and ignored like one
+    /// which cannot be decoded, the handlers keep seeing the previous one.
+    pub fn with_validator<F, E>(mut self, validator: F) -> Self
+    where
+        F: Fn(&T) -> Result<(), E> + Send + Sync + 'static,
+        E: Into<shared::BoxError>,
+    {
+        self.validator = Some(Box::new(move |value| validator(value).map_err(Into::into)));
+        self
+    }
+
+    /// Reads the side input and starts watching it, it must be called within a tokio runtime.
+    /// The watch stops once every clone of the returned [`Config`] is dropped.
+    pub fn start(self) -> Config<T> {
+        let (changes, _) = watch::channel(None);
+        let inner = Arc::new(ConfigInner {
+            current: ArcSwapOption::empty(),
+            changes,
+        });
+
+        let mut poller = ConfigPoller {
+            path: self.dir.join(&self.name),
+            name: self.name,
+            validator: self.validator,
+            last: None,
+            config: Arc::downgrade(&inner),
+        };
+        poller.poll();
+        let poll_interval = self.poll_interval;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(poll_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // the first tick is immediate, the side input has just been read
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if !poller.poll() {
+                    break;
+                }
+            }
+        });
+
+        Config { inner }
+    }
+}
+
+struct ConfigInner<T> {
+    current: ArcSwapOption<T>,
+    changes: watch::Sender<Option<Arc<T>>>,
+}
+
+/// Config is the latest valid value of a side input watched by a [`ConfigWatcher`], it is cheap
+/// to clone and to read from the handlers.
+pub struct Config<T> {
+    inner: Arc<ConfigInner<T>>,
+}
+
+impl<T> Clone for Config<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> Config<T> {
+    /// Returns the latest valid value, None until the side input is broadcast with one.
+    pub fn current(&self) -> Option<Arc<T>> {
+        self.inner.current.load_full()
+    }
+
+    /// Returns a receiver notified of every new valid value, e.g., to rebuild a cache derived
+    /// from the configuration.
+    pub fn subscribe(&self) -> watch::Receiver<Option<Arc<T>>> {
+        self.inner.changes.subscribe()
+    }
+}
+
+struct ConfigPoller<T> {
+    name: String,
+    path: PathBuf,
+    validator: Option<Validator<T>>,
+    // the content of the file last read, a file which did not change is not decoded again
+    last: Option<Vec<u8>>,
+    config: Weak<ConfigInner<T>>,
+}
+
+impl<T: DeserializeOwned> ConfigPoller<T> {
+    // returns false once the config is dropped
+    fn poll(&mut self) -> bool {
+        let Some(config) = self.config.upgrade() else {
+            return false;
+        };
+
+        let content = match std::fs::read(&self.path) {
+            Ok(content) => content,
+            // the side input is not there until it is broadcast for the first time
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return true,
+            Err(e) => {
+                tracing::warn!(
+                    side_input = %self.name,
+                    error = %e,
+                    "failed to read the side input"
+                );
+                return true;
+            }
+        };
+        if self.last.as_ref() == Some(&content) {
+            return true;
+        }
+
+        let decoded = crate::json::from_slice::<T>(&content)
+            .map_err(shared::BoxError::from)
+            .and_then(|value| match &self.validator {
+                Some(validator) => validator(&value).map(|_| value),
+                None => Ok(value),
+            });
+        self.last = Some(content);
+        match decoded {
+            Ok(value) => {
+                let value = Arc::new(value);
+                config.current.store(Some(Arc::clone(&value)));
+                config.changes.send_replace(Some(value));
+            }
+            Err(e) => tracing::warn!(
+                side_input = %self.name,
+                error = %e,
+                "ignoring the new value of the side input, keeping the previous one"
+            ),
+        }
+        true
+    }
+}
@@ -1,20 +1,35 @@
+use std::collections::HashSet;
+use std::future::Future;
+use std::time::Instant;
+
+use bytes::Bytes;
 use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use futures_util::{Stream, StreamExt};
 use tokio::sync::mpsc;
-use tonic::transport::Server;
 use tonic::{Request, Status, Streaming};
+use tracing::Instrument;
 
 use sinker_grpc::sink_server::SinkServer;
 use sinker_grpc::{ReadyResponse, SinkRequest, SinkResponse};
 
-use crate::shared;
+use crate::shared::HandlerSettings;
 use crate::sink::sinker_grpc::sink_server::Sink;
+use crate::timestamp::TimestampCache;
+use crate::{error, metrics, shared, tasks};
 
 mod sinker_grpc {
     tonic::include_proto!("sink.v1");
 }
 
+/// The gRPC messages, client and server of the sink protocol, generated from its proto.
+pub mod proto {
+    pub use super::sinker_grpc::*;
+}
+
 struct SinkService<T: Sinker> {
     pub handler: T,
+    settings: HandlerSettings,
 }
 
 /// Sinker trait implements the user defined sink handle.
@@ -28,7 +43,7 @@ pub trait Sinker {
     ///
     /// A simple log sink.
     ///
-    /// ```rust
+    /// ```no_run
     /// use numaflow::sink;
     /// use numaflow::sink::{Datum, Response};
     /// use tonic::async_trait;
@@ -58,17 +73,11 @@ pub trait Sinker {
     ///                 Ok(v) => {
     ///                     println!("{}", v);
     ///                     // record the response
-    ///                     Response {
-    ///                         id: datum.id().to_string(),
-    ///                         success: true,
-    ///                         err: "".to_string(),
-    ///                     }
+    ///                     Response::ok(datum.id().to_string())
     ///                 }
-    ///                 Err(e) => Response {
-    ///                     id: datum.id().to_string(),
-    ///                     success: true, // there is no point setting success to false as retrying is not going to help
-    ///                     err: format!("Invalid UTF-8 sequence: {}", e),
-    ///                 },
+    ///                 // there is no point in retrying as it is not going to help, hence route
+    ///                 // the message to the fallback sink.
+    ///                 Err(_) => Response::fallback(datum.id().to_string()),
     ///             };
     ///
     ///             // return the responses
@@ -96,15 +105,112 @@ pub trait Sinker {
     ) -> Vec<Response>;
 }
 
-/// Response is the result returned from the [`Sinker::handle`].
-pub struct Response {
-    /// id is the unique ID of the message.
-    pub id: String,
-    /// success indicates whether the write to the sink was successful. If set to `false`, it will be
-    /// retried, hence it is better to try till it is successful.
-    pub success: bool,
-    /// err string is used to describe the error if [`Response::success`]  was `false`.
-    pub err: String,
+/// Response is the result returned from the [`Sinker::sink`] for a message, it tells numaflow
+/// whether to move on, to retry the message or to write it to the fallback sink. New outcomes may
+/// be added, hence a `match` on it needs a wildcard arm.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::sink::Response;
+///
+/// let responses = vec![
+///     Response::ok("1".to_string()),
+///     Response::failure("2".to_string(), "connection reset".to_string()),
+///     Response::fallback("3".to_string()),
+/// ];
+///
+/// let retried: Vec<&str> = responses
+///     .iter()
+///     .filter_map(|response| match response {
+///         Response::Failure { id, .. } => Some(id.as_str()),
+///         _ => None,
+///     })
+///     .collect();
+/// assert_eq!(retried, ["2"]);
+/// assert_eq!(responses[1].err(), Some("connection reset"));
+/// assert_eq!(responses[2].id(), "3");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Response {
+    /// The message of the id was written to the sink. It is also how a sink drops a message, the
+    /// protocol has no other way to.
+    Ok(String),
+    /// The message of the id could not be written to the sink for the reason `err`, numaflow
+    /// retries it, hence it is better to try till it is successful.
+    Failure { id: String, err: String },
+    /// The message of the id is to be written to the
+    /// [fallback sink](https://numaflow.numaproj.io/user-guide/sinks/fallback/) instead.
+    Fallback(String),
+}
+
+impl Response {
+    /// ok creates a response for a message which was successfully written to the sink.
+    pub fn ok(id: String) -> Self {
+        Self::Ok(id)
+    }
+
+    /// failure creates a response for a message which could not be written to the sink, the
+    /// message will be retried.
+    pub fn failure(id: String, err: String) -> Self {
+        Self::Failure { id, err }
+    }
+
+    /// fallback creates a response for a message which should be written to the fallback sink.
+    /// It is sent with `success` set as well, so that a platform without the fallback sink does
+    /// not retry the message.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use numaflow::sink::proto::{SinkRequest, Status};
+    /// use numaflow::sink::{Datum, Response, Sinker};
+    /// use numaflow::testing::sink::run_stream;
+    /// use tokio::sync::mpsc::Receiver;
+    ///
+    /// struct Fallback;
+    ///
+    /// #[tonic::async_trait]
+    /// impl Sinker for Fallback {
+    ///     async fn sink<T: Datum + Send + Sync + 'static>(&self, mut input: Receiver<T>) -> Vec<Response> {
+    ///         let mut responses = vec![];
+    ///         while let Some(datum) = input.recv().await {
+    ///             responses.push(Response::fallback(datum.id().to_string()));
+    ///         }
+    ///         responses
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() {
+    ///     let request = Ok(SinkRequest {
+    ///         id: "1".to_string(),
+    ///         ..Default::default()
+    ///     });
+    ///     let response = run_stream(&Fallback, tokio_stream::iter([request])).await.unwrap();
+    ///     assert!(response.results[0].success);
+    ///     assert_eq!(response.results[0].status, Status::Fallback as i32);
+    /// }
+    /// ```
+    pub fn fallback(id: String) -> Self {
+        Self::Fallback(id)
+    }
+
+    /// Returns the id of the message of the response.
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Ok(id) | Self::Failure { id, .. } | Self::Fallback(id) => id,
+        }
+    }
+
+    /// Returns why the message could not be written, for a [`Response::Failure`].
+    pub fn err(&self) -> Option<&str> {
+        match self {
+            Self::Failure { err, .. } => Some(err),
+            _ => None,
+        }
+    }
 }
 
 /// Datum trait represents an incoming element into the [`Sinker::handle`].
@@ -112,7 +218,7 @@ pub trait Datum {
     /// keys are the keys in the (key, value) terminology of map/reduce paradigm.
     fn keys(&self) -> &Vec<String>;
     /// value is the value in (key, value) terminology of map/reduce paradigm.
-    fn value(&self) -> &Vec<u8>;
+    fn value(&self) -> &Bytes;
     /// [watermark](https://numaflow.numaproj.io/core-concepts/watermarks/) represented by time is a guarantee that we will not see an element older than this
     /// time.
     fn watermark(&self) -> DateTime<Utc>;
@@ -125,18 +231,18 @@ pub trait Datum {
 /// Owned copy of SinkRequest from tonic.
 struct OwnedSinkRequest {
     keys: Vec<String>,
-    value: Vec<u8>,
+    value: Bytes,
     watermark: DateTime<Utc>,
     eventtime: DateTime<Utc>,
     id: String,
 }
 
 impl OwnedSinkRequest {
-    fn new(sr: SinkRequest) -> Self {
+    fn new(sr: SinkRequest, watermarks: &mut TimestampCache) -> Self {
         Self {
             keys: sr.keys,
             value: sr.value,
-            watermark: shared::utc_from_timestamp(sr.watermark),
+            watermark: watermarks.convert(sr.watermark.as_ref()),
             eventtime: shared::utc_from_timestamp(sr.event_time),
             id: sr.id,
         }
@@ -148,7 +254,7 @@ impl Datum for OwnedSinkRequest {
         &self.keys
     }
 
-    fn value(&self) -> &Vec<u8> {
+    fn value(&self) -> &Bytes {
         &self.value
     }
 
@@ -174,74 +280,248 @@ where
         &self,
         request: Request<Streaming<SinkRequest>>,
     ) -> Result<tonic::Response<SinkResponse>, Status> {
-        let mut stream = request.into_inner();
-
-        // TODO: what should be the idle buffer size?
-        let (tx, rx) = mpsc::channel::<OwnedSinkRequest>(1);
-
-        // call the user's sink handle
-        let sink_handle = self.handler.sink(rx);
-
-        // write to the user-defined channel
-        tokio::spawn(async move {
-            while let Some(next_message) = stream
-                .message()
-                .await
-                .expect("expected next message from stream")
-            {
-                let owned_next_message = OwnedSinkRequest::new(next_message);
-                // panic is good i think!
-                tx.send(owned_next_message)
-                    .await
-                    .expect("send be successfully received!");
+        sink_stream(&self.handler, request.into_inner(), self.settings)
+            .await
+            .map(tonic::Response::new)
+    }
+
+    async fn is_ready(&self, _: Request<()>) -> Result<tonic::Response<ReadyResponse>, Status> {
+        Ok(tonic::Response::new(ReadyResponse { ready: true }))
+    }
+}
+
+/// Runs the handle over the requests of a sink stream and returns its responses. The stream fails
+/// instead when it could not be read fully or when the handle did not answer every element it was
+/// given, as numaflow would otherwise never retry the elements left out.
+pub(crate) async fn sink_stream<T, S>(
+    handler: &T,
+    stream: S,
+    settings: HandlerSettings,
+) -> Result<SinkResponse, Status>
+where
+    T: Sinker,
+    S: Stream<Item = Result<SinkRequest, Status>> + Send + 'static,
+{
+    // TODO: what should be the idle buffer size?
+    let (tx, rx) = mpsc::channel::<OwnedSinkRequest>(1);
+
+    // call the user's sink handle
+    let start = Instant::now();
+    let sink_handle = settings
+        .watch("sink", handler.sink(rx))
+        .instrument(settings.handler_span("sink", None));
+
+    // write to the user-defined channel, tx is dropped at the end of the stream which closes
+    // the user's rx.
+    let reader = tasks::spawn("sink:stream-reader", async move {
+        tokio::pin!(stream);
+        let mut watermarks = TimestampCache::default();
+        // the ids handed to the handle, each of them is expected to be answered
+        let mut ids = HashSet::new();
+        while let Some(next_message) = stream.next().await.transpose()? {
+            metrics::messages_received("sink", 1);
+            let owned_next_message = OwnedSinkRequest::new(next_message, &mut watermarks);
+            ids.insert(owned_next_message.id.clone());
+            if tx.send(owned_next_message).await.is_err() {
+                return Err(settings.to_status(error::Error::SinkError(
+                    error::ErrorKind::InternalError(
+                        "sink handle returned before reading the whole stream".to_string(),
+                    ),
+                )));
             }
-        });
-
-        // wait for the sink handle to respond
-        let responses = sink_handle.await;
-
-        // build the result
-        let mut sink_responses: Vec<sinker_grpc::sink_response::Result> = Vec::new();
-        for response in responses {
-            sink_responses.push(sinker_grpc::sink_response::Result {
-                id: response.id,
-                success: response.success,
-                err_msg: response.err.to_string(),
-            })
         }
+        Ok::<_, Status>(ids)
+    });
 
-        Ok(tonic::Response::new(SinkResponse {
-            results: sink_responses,
-        }))
+    // wait for the sink handle to respond
+    let responses = sink_handle.await;
+    metrics::handler_latency("sink", start.elapsed());
+
+    // the responses of a stream which could not be read fully do not cover all its elements,
+    // the stream is failed instead so that numaflow retries them.
+    let ids = reader.await.map_err(|e| {
+        settings.to_status(error::Error::SinkError(error::ErrorKind::InternalError(
+            format!("sink reader failed: {}", e),
+        )))
+    })??;
+    // an element the handle has read but not answered, e.g., one left in the channel when it
+    // returned, would never be retried
+    let answered: HashSet<&str> = responses.iter().map(Response::id).collect();
+    let unanswered = ids
+        .iter()
+        .filter(|id| !answered.contains(id.as_str()))
+        .count();
+    if unanswered > 0 {
+        return Err(
+            settings.to_status(error::Error::SinkError(error::ErrorKind::InternalError(
+                format!(
+                    "sink handle returned no response for {} of the {} elements of the stream",
+                    unanswered,
+                    ids.len()
+                ),
+            ))),
+        );
     }
 
-    async fn is_ready(&self, _: Request<()>) -> Result<tonic::Response<ReadyResponse>, Status> {
-        Ok(tonic::Response::new(ReadyResponse { ready: true }))
+    // build the result
+    let mut sink_responses: Vec<sinker_grpc::sink_response::Result> = Vec::new();
+    for response in responses {
+        let (id, status, err_msg) = match response {
+            Response::Ok(id) => (id, sinker_grpc::Status::Success, String::new()),
+            Response::Failure { id, err } => (id, sinker_grpc::Status::Failure, err),
+            Response::Fallback(id) => (id, sinker_grpc::Status::Fallback, String::new()),
+        };
+        // a fallback is not to be retried, it is sent as a success to the platforms which only read
+        // `success`
+        sink_responses.push(sinker_grpc::sink_response::Result {
+            id,
+            success: status != sinker_grpc::Status::Failure,
+            err_msg,
+            status: status as i32,
+        })
     }
+
+    Ok(SinkResponse {
+        results: sink_responses,
+    })
 }
 
-/// start_uds_server starts a gRPC server over an UDS (unix-domain-socket) endpoint.
-pub async fn start_uds_server<T>(m: T) -> Result<(), Box<dyn std::error::Error>>
+/// Default unix domain socket file of the sink server, see [`Server::with_socket_file`]. It is
+/// relocated by the [`SOCKET_ENV`](crate::shared::SOCKET_ENV) and
+/// [`RUN_DIR_ENV`](crate::shared::RUN_DIR_ENV) environment variables.
+pub const DEFAULT_SOCK_ADDR: &str = "/var/run/numaflow/sink.sock";
+
+/// Default unix domain socket file of the sink run as the
+/// [fallback sink](https://numaflow.numaproj.io/user-guide/sinks/fallback/) of the vertex.
+pub const FALLBACK_SOCK_ADDR: &str = "/var/run/numaflow/fb-sink.sock";
+
+/// Version of the sink protocol, i.e., of the `sink.v1` proto package.
+pub const PROTOCOL_VERSION: &str = "v1";
+
+/// FromFn is a [`Sinker`] running a closure, see [`Server::from_fn`].
+pub struct FromFn<F>(F);
+
+#[tonic::async_trait]
+impl<F, Fut> Sinker for FromFn<F>
 where
-    T: Sinker + Send + Sync + 'static,
+    F: Fn(mpsc::Receiver<Box<dyn Datum + Send + Sync>>) -> Fut + Send + Sync,
+    Fut: Future<Output = Vec<Response>> + Send,
 {
-    shared::write_info_file();
+    async fn sink<T: Datum + Send + Sync + 'static>(
+        &self,
+        input: mpsc::Receiver<T>,
+    ) -> Vec<Response> {
+        shared::forward_input(
+            input,
+            |datum| Box::new(datum) as Box<dyn Datum + Send + Sync>,
+            |input| (self.0)(input),
+        )
+        .await
+    }
+}
+
+/// gRPC server to start a sink service
+pub struct Server<T> {
+    config: shared::ServerConfig,
+    svc: T,
+}
+
+impl<T> Server<T> {
+    /// Create a new sink server with the given [`Sinker`] handler.
+    pub fn new(sink_svc: T) -> Self {
+        Self {
+            config: shared::ServerConfig::new(DEFAULT_SOCK_ADDR, PROTOCOL_VERSION),
+            svc: sink_svc,
+        }
+    }
+
+    shared::server_config_methods!();
+
+    /// Starts the gRPC server. The server runs until it is stopped or errors out.
+    pub async fn start(self) -> Result<(), shared::BoxError>
+    where
+        T: Sinker + Send + Sync + 'static,
+    {
+        self.start_with_shutdown(std::future::pending()).await
+    }
 
-    let path = "/var/run/numaflow/sink.sock";
-    fs::create_dir_all(std::path::Path::new(path).parent().unwrap())?;
-    use std::fs;
-    use tokio::net::UnixListener;
-    use tokio_stream::wrappers::UnixListenerStream;
+    /// Starts the gRPC server, it shuts down once `shutdown` resolves. The server then stops
+    /// accepting traffic and returns once the in-flight requests are done.
+    pub async fn start_with_shutdown<F>(self, shutdown: F) -> Result<(), shared::BoxError>
+    where
+        T: Sinker + Send + Sync + 'static,
+        F: Future<Output = ()>,
+    {
+        let mut config = self.config;
+        let incoming = config.prepare().await?;
 
-    let uds = UnixListener::bind(path)?;
-    let _uds_stream = UnixListenerStream::new(uds);
+        let sink_svc = SinkService {
+            handler: self.svc,
+            settings: config.handler_settings(),
+        };
 
-    let sink_service = SinkService { handler: m };
+        shared::router!(config, SinkServer::new(sink_svc))
+            .serve_with_incoming_shutdown(incoming, shutdown)
+            .await
+            .map_err(|e| error::Error::SinkError(error::ErrorKind::connection(e)))?;
 
-    Server::builder()
-        .add_service(SinkServer::new(sink_service))
-        .serve_with_incoming(_uds_stream)
-        .await?;
+        Ok(())
+    }
+}
 
-    Ok(())
+impl<T> crate::server::Service for Server<T>
+where
+    T: Sinker + Send + Sync + 'static,
+{
+    fn serve(
+        self,
+        shutdown: BoxFuture<'static, ()>,
+    ) -> BoxFuture<'static, Result<(), shared::BoxError>> {
+        Box::pin(self.start_with_shutdown(shutdown))
+    }
+}
+
+impl<F> Server<FromFn<F>> {
+    /// Create a new sink server running the closure as the [`Sinker::sink`] handler, for the
+    /// handlers too small to be worth a type of their own. The elements are passed to the closure
+    /// as [`Datum`] trait objects.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use numaflow::sink::{self, Response};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     sink::Server::from_fn(|mut input| async move {
+    ///         let mut responses = vec![];
+    ///         while let Some(datum) = input.recv().await {
+    ///             println!("{}", String::from_utf8_lossy(datum.value()));
+    ///             responses.push(Response::ok(datum.id().to_string()));
+    ///         }
+    ///         responses
+    ///     })
+    ///     .start()
+    ///     .await
+    /// }
+    /// ```
+    pub fn from_fn<Fut>(handler: F) -> Self
+    where
+        F: Fn(mpsc::Receiver<Box<dyn Datum + Send + Sync>>) -> Fut,
+        Fut: Future<Output = Vec<Response>>,
+    {
+        Self::new(FromFn(handler))
+    }
+}
+
+/// start_uds_server starts a sink gRPC server over an UDS (unix-domain-socket) endpoint with the
+/// default settings. Use [`Server`] to customize the server.
+pub async fn start_uds_server<T>(m: T) -> Result<(), Box<dyn std::error::Error>>
+where
+    T: Sinker + Send + Sync + 'static,
+{
+    Server::new(m)
+        .start()
+        .await
+        .map_err(|e| e as Box<dyn std::error::Error>)
 }
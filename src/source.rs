@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{async_trait, Request, Response, Status};
+use tracing::Instrument;
+
+#[cfg(feature = "compression")]
+use crate::compression::{self, Encoding};
+use crate::error::{Error, ErrorKind};
+use crate::shared::HandlerSettings;
+use crate::source::sourcer::source_server::{Source, SourceServer};
+use crate::source::sourcer::{
+    ack_response, partitions_response, pending_response, read_response, AckRequest, AckResponse,
+    PartitionsResponse, PendingResponse, ReadRequest, ReadResponse, ReadyResponse,
+};
+use crate::{metrics, shared, tasks};
+
+mod sourcer {
+    tonic::include_proto!("source.v1");
+}
+
+/// The gRPC messages, client and server of the source protocol, generated from its proto.
+pub mod proto {
+    pub use super::sourcer::*;
+}
+
+/// Default unix domain socket file of the source server, see [`Server::with_socket_file`]. It is
+/// relocated by the [`SOCKET_ENV`](crate::shared::SOCKET_ENV) and
+/// [`RUN_DIR_ENV`](crate::shared::RUN_DIR_ENV) environment variables.
+pub const DEFAULT_SOCK_ADDR: &str = "/var/run/numaflow/source.sock";
+
+/// Version of the source protocol, i.e., of the `source.v1` proto package.
+pub const PROTOCOL_VERSION: &str = "v1";
+
+struct SourceService<T> {
+    handler: Arc<T>,
+    // buffer size of the channels between the gRPC streams and the user's handle
+    channel_size: usize,
+    settings: HandlerSettings,
+    #[cfg(feature = "compression")]
+    compression: Option<Encoding>,
+}
+
+/// Sourcer trait implements the user defined source.
+///
+/// Types implementing this trait can be passed as user-defined source handle. More about user
+/// defined sources can be read [here](https://numaflow.numaproj.io/user-guide/sources/user-defined-sources/).
+#[async_trait]
+pub trait Sourcer {
+    /// read reads the next batch of messages from the source and writes them to the `transmitter`.
+    /// It should write at most [`SourceReadRequest::count`] messages and return once the count is
+    /// reached or [`SourceReadRequest::timeout`] has elapsed, whichever happens first. The read can
+    /// be cut short once [`is_output_alive`](crate::shared::OutputAlive::is_output_alive) on the
+    /// `transmitter` returns false, i.e., numaflow has stopped reading.
+    ///
+    /// # Example
+    ///
+    /// A source generating a monotonically increasing counter.
+    ///
+    /// ```no_run
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// use chrono::Utc;
+    /// use numaflow::source::{self, Message, Offset, SourceReadRequest};
+    /// use tokio::sync::mpsc::Sender;
+    /// use tonic::async_trait;
+    ///
+    /// struct Counter {
+    ///     next: AtomicUsize,
+    /// }
+    ///
+    /// #[async_trait]
+    /// impl source::Sourcer for Counter {
+    ///     async fn read(&self, request: SourceReadRequest, transmitter: Sender<Message>) {
+    ///         for _ in 0..request.count {
+    ///             let n = self.next.fetch_add(1, Ordering::SeqCst);
+    ///             let message = Message {
+    ///                 value: n.to_string().into_bytes().into(),
+    ///                 offset: Offset {
+    ///                     offset: n.to_be_bytes().to_vec(),
+    ///                     partition_id: 0,
+    ///                 },
+    ///                 event_time: Utc::now(),
+    ///                 keys: vec![],
+    ///                 headers: Default::default(),
+    ///             };
+    ///             if transmitter.send(message).await.is_err() {
+    ///                 return;
+    ///             }
+    ///         }
+    ///     }
+    ///
+    ///     async fn ack(&self, _offsets: Vec<Offset>) {}
+    ///
+    ///     async fn pending(&self) -> Option<usize> {
+    ///         None
+    ///     }
+    ///
+    ///     async fn partitions(&self) -> Option<Vec<i32>> {
+    ///         None
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     let source_handler = Counter {
+    ///         next: AtomicUsize::new(0),
+    ///     };
+    ///
+    ///     source::Server::new(source_handler).start().await
+    /// }
+    /// ```
+    async fn read(&self, request: SourceReadRequest, transmitter: mpsc::Sender<Message>);
+    /// ack acknowledges the given offsets, they are in the same order as the messages were read.
+    async fn ack(&self, offsets: Vec<Offset>);
+    /// pending returns the number of messages yet to be read from the source, `None` if the
+    /// information is not available.
+    async fn pending(&self) -> Option<usize>;
+    /// partitions returns the partitions of the source the current replica reads from. If `None`,
+    /// the replica index (`NUMAFLOW_REPLICA`) is used as the only partition.
+    async fn partitions(&self) -> Option<Vec<i32>>;
+}
+
+/// SourceReadRequest is the request passed into [`Sourcer::read`].
+pub struct SourceReadRequest {
+    /// count is the maximum number of messages to be read.
+    pub count: usize,
+    /// timeout is the maximum time to be spent on a single read.
+    pub timeout: Duration,
+}
+
+/// Offset uniquely identifies a message within the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offset {
+    /// offset is the offset of the message in the source, its interpretation is left to the source.
+    pub offset: Vec<u8>,
+    /// partition_id is the partition of the source the message belongs to.
+    pub partition_id: i32,
+}
+
+/// Message is the element read by [`Sourcer::read`].
+pub struct Message {
+    /// Value is the value passed to the next vertex.
+    pub value: Bytes,
+    /// Offset of the message, it is handed back to [`Sourcer::ack`] once processed.
+    pub offset: Offset,
+    /// event_time is the time associated with the message, it is used for watermarking.
+    pub event_time: DateTime<Utc>,
+    /// Keys are a collection of strings which will be passed on to the next vertex as is. It can
+    /// be an empty collection.
+    pub keys: Vec<String>,
+    /// headers are the user defined headers passed on to the next vertex as is.
+    pub headers: HashMap<String, String>,
+}
+
+impl From<Offset> for sourcer::Offset {
+    fn from(offset: Offset) -> Self {
+        Self {
+            offset: offset.offset.into(),
+            partition_id: offset.partition_id,
+        }
+    }
+}
+
+impl From<sourcer::Offset> for Offset {
+    fn from(offset: sourcer::Offset) -> Self {
+        Self {
+            offset: offset.offset.into(),
+            partition_id: offset.partition_id,
+        }
+    }
+}
+
+impl From<Message> for ReadResponse {
+    fn from(message: Message) -> Self {
+        Self {
+            result: Some(read_response::Result {
+                payload: message.value,
+                offset: Some(message.offset.into()),
+                event_time: Some(shared::prost_timestamp_from_utc(message.event_time)),
+                keys: message.keys,
+                headers: message.headers,
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl<T> Source for SourceService<T>
+where
+    T: Sourcer + Send + Sync + 'static,
+{
+    type ReadFnStream = ReceiverStream<Result<ReadResponse, Status>>;
+
+    async fn read_fn(
+        &self,
+        request: Request<ReadRequest>,
+    ) -> Result<Response<Self::ReadFnStream>, Status> {
+        let sr = request.into_inner().request.ok_or_else(|| {
+            self.settings
+                .to_status(Error::SourceError(ErrorKind::ProtocolViolation(
+                    "read request is empty".to_string(),
+                )))
+        })?;
+
+        // channel the user's read handle writes into
+        let (tx, mut rx) = mpsc::channel::<Message>(self.channel_size);
+        // channel to respond to numaflow main car as it expects streaming results.
+        let (resp_tx, resp_rx) = mpsc::channel::<Result<ReadResponse, Status>>(self.channel_size);
+
+        // call the user's read handle, tx is dropped once the read is done which ends the stream
+        let handler = Arc::clone(&self.handler);
+        let settings = self.settings;
+        tasks::spawn("source:task", async move {
+            let start = Instant::now();
+            let read_handle = handler.read(
+                SourceReadRequest {
+                    count: sr.num_records as usize,
+                    timeout: Duration::from_millis(sr.timeout_in_ms as u64),
+                },
+                tx,
+            );
+            settings
+                .watch("source", read_handle)
+                .instrument(settings.handler_span("source", None))
+                .await;
+            metrics::handler_latency("source", start.elapsed());
+        });
+
+        // stream the messages out to the client
+        #[cfg(feature = "compression")]
+        let compression = self.compression;
+        tasks::spawn("source:response-writer", async move {
+            // rx is dropped as soon as the client is gone which closes the output of the handle
+            while let Some(message) = tokio::select! {
+                message = rx.recv() => message,
+                _ = resp_tx.closed() => None,
+            } {
+                #[cfg(feature = "compression")]
+                let message = match compression {
+                    Some(encoding) => {
+                        let mut message = message;
+                        compression::compress_payload(
+                            encoding,
+                            &mut message.headers,
+                            &mut message.value,
+                        );
+                        message
+                    }
+                    None => message,
+                };
+                metrics::messages_emitted("source", 1);
+                metrics::channel_saturation("source", &resp_tx);
+                if resp_tx.send(Ok(message.into())).await.is_err() {
+                    // client is gone, nothing more to do
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(resp_rx)))
+    }
+
+    async fn ack_fn(&self, request: Request<AckRequest>) -> Result<Response<AckResponse>, Status> {
+        let ar = request.into_inner().request.ok_or_else(|| {
+            self.settings
+                .to_status(Error::SourceError(ErrorKind::ProtocolViolation(
+                    "ack request is empty".to_string(),
+                )))
+        })?;
+
+        self.handler
+            .ack(ar.offsets.into_iter().map(Offset::from).collect())
+            .await;
+
+        Ok(Response::new(AckResponse {
+            result: Some(ack_response::Result { success: Some(()) }),
+        }))
+    }
+
+    async fn pending_fn(&self, _: Request<()>) -> Result<Response<PendingResponse>, Status> {
+        // a negative count means the pending information is not available
+        let count = match self.handler.pending().await {
+            Some(count) => count as i64,
+            None => -1,
+        };
+
+        Ok(Response::new(PendingResponse {
+            result: Some(pending_response::Result { count }),
+        }))
+    }
+
+    async fn partitions_fn(&self, _: Request<()>) -> Result<Response<PartitionsResponse>, Status> {
+        let partitions = match self.handler.partitions().await {
+            Some(partitions) => partitions,
+            None => vec![default_partition()],
+        };
+
+        Ok(Response::new(PartitionsResponse {
+            result: Some(partitions_response::Result { partitions }),
+        }))
+    }
+
+    async fn is_ready(&self, _: Request<()>) -> Result<Response<ReadyResponse>, Status> {
+        Ok(Response::new(ReadyResponse { ready: true }))
+    }
+}
+
+// the replica index is the default partition of a source
+fn default_partition() -> i32 {
+    std::env::var("NUMAFLOW_REPLICA")
+        .ok()
+        .and_then(|replica| replica.parse().ok())
+        .unwrap_or_default()
+}
+
+/// gRPC server to start a source service
+pub struct Server<T> {
+    config: shared::ServerConfig,
+    svc: T,
+    #[cfg(feature = "compression")]
+    compression: Option<Encoding>,
+}
+
+impl<T> Server<T> {
+    /// Create a new source server with the given [`Sourcer`] handler.
+    pub fn new(source_svc: T) -> Self {
+        Self {
+            config: shared::ServerConfig::new(DEFAULT_SOCK_ADDR, PROTOCOL_VERSION),
+            svc: source_svc,
+            #[cfg(feature = "compression")]
+            compression: None,
+        }
+    }
+
+    shared::server_config_methods!();
+
+    /// Compress the payloads of the messages read with the encoding and mark them with the
+    /// [`content-encoding`](compression::CONTENT_ENCODING) header, the messages the handler has
+    /// already marked are left as is. The vertices downstream have to understand the header,
+    /// e.g., by running with `with_decompression`. It is disabled by default.
+    #[cfg(feature = "compression")]
+    pub fn with_compression(mut self, encoding: Encoding) -> Self {
+        self.compression = Some(encoding);
+        self
+    }
+
+    /// Get the encoding the payloads of the messages read are compressed with.
+    #[cfg(feature = "compression")]
+    pub fn compression(&self) -> Option<Encoding> {
+        self.compression
+    }
+
+    /// Starts the gRPC server. The server runs until it is stopped or errors out.
+    pub async fn start(self) -> Result<(), shared::BoxError>
+    where
+        T: Sourcer + Send + Sync + 'static,
+    {
+        self.start_with_shutdown(std::future::pending()).await
+    }
+
+    /// Starts the gRPC server, it shuts down once `shutdown` resolves. The server then stops
+    /// accepting traffic and returns once the in-flight requests are done.
+    pub async fn start_with_shutdown<F>(self, shutdown: F) -> Result<(), shared::BoxError>
+    where
+        T: Sourcer + Send + Sync + 'static,
+        F: Future<Output = ()>,
+    {
+        let mut config = self.config;
+        let incoming = config.prepare().await?;
+
+        let source_svc = SourceService {
+            handler: Arc::new(self.svc),
+            channel_size: config.tuning.channel_size,
+            settings: config.handler_settings(),
+            #[cfg(feature = "compression")]
+            compression: self.compression,
+        };
+
+        shared::router!(config, SourceServer::new(source_svc))
+            .serve_with_incoming_shutdown(incoming, shutdown)
+            .await
+            .map_err(|e| Error::SourceError(ErrorKind::connection(e)))?;
+
+        Ok(())
+    }
+}
+
+impl<T> crate::server::Service for Server<T>
+where
+    T: Sourcer + Send + Sync + 'static,
+{
+    fn serve(
+        self,
+        shutdown: BoxFuture<'static, ()>,
+    ) -> BoxFuture<'static, Result<(), shared::BoxError>> {
+        Box::pin(self.start_with_shutdown(shutdown))
+    }
+}
@@ -0,0 +1,338 @@
+use std::future::Future;
+use std::time::Instant;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use tonic::{async_trait, Request, Response, Status};
+use tracing::Instrument;
+
+use crate::headers::Headers;
+use crate::message::{self, MessageBuilder};
+use crate::shared::HandlerSettings;
+use crate::sourcetransform::transformer::{
+    source_transform_response, source_transform_server, ReadyResponse, SourceTransformRequest,
+    SourceTransformResponse,
+};
+use crate::{error, metrics, shared};
+
+mod transformer {
+    tonic::include_proto!("sourcetransformer.v1");
+}
+
+/// The gRPC messages, client and server of the source transformer protocol, generated from its proto.
+pub mod proto {
+    pub use super::transformer::*;
+}
+
+/// Default unix domain socket file of the source transformer server, see [`Server::with_socket_file`]. It is
+/// relocated by the [`SOCKET_ENV`](crate::shared::SOCKET_ENV) and
+/// [`RUN_DIR_ENV`](crate::shared::RUN_DIR_ENV) environment variables.
+pub const DEFAULT_SOCK_ADDR: &str = "/var/run/numaflow/sourcetransform.sock";
+
+/// Version of the source transformer protocol, i.e., of the `sourcetransformer.v1` proto package.
+pub const PROTOCOL_VERSION: &str = "v1";
+
+struct SourceTransformerService<T> {
+    handler: T,
+    settings: HandlerSettings,
+}
+
+/// SourceTransformer trait for implementing the source data transformer.
+#[async_trait]
+pub trait SourceTransformer {
+    /// transform takes in an input element and can produce 0, 1, or more results. It is similar
+    /// to [`map`](crate::map::Mapper::map) but runs at the source vertex and can additionally
+    /// assign a new event time to the results, which is used for watermarking. More about source
+    /// data transformers can be read
+    /// [here](https://numaflow.numaproj.io/user-guide/sources/transformer/overview/).
+    ///
+    /// # Example
+    ///
+    /// Following is an example of a transformer which assigns the current time as the event time.
+    ///
+    /// ```no_run
+    /// use chrono::Utc;
+    /// use numaflow::sourcetransform::{self, Datum, Message};
+    ///
+    /// struct NowAssigner {}
+    ///
+    /// #[tonic::async_trait]
+    /// impl sourcetransform::SourceTransformer for NowAssigner {
+    ///     async fn transform<T>(&self, input: T) -> Vec<Message>
+    ///     where
+    ///         T: Datum + Send + Sync + 'static,
+    ///     {
+    ///         vec![Message {
+    ///             keys: input.keys().clone(),
+    ///             value: input.value().clone(),
+    ///             event_time: Utc::now(),
+    ///             tags: vec![],
+    ///         }]
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     sourcetransform::Server::new(NowAssigner {}).start().await
+    /// }
+    /// ```
+    async fn transform<T: Datum + Send + Sync + 'static>(&self, input: T) -> Vec<Message>;
+}
+
+#[async_trait]
+impl<T> source_transform_server::SourceTransform for SourceTransformerService<T>
+where
+    T: SourceTransformer + Send + Sync + 'static,
+{
+    async fn source_transform_fn(
+        &self,
+        request: Request<SourceTransformRequest>,
+    ) -> Result<Response<SourceTransformResponse>, Status> {
+        let request = request.into_inner();
+        metrics::messages_received("sourcetransform", 1);
+        let start = Instant::now();
+
+        // call the transform handle
+        let span = self
+            .settings
+            .handler_span("sourcetransform", Some(&request.headers));
+        let request = OwnedSourceTransformRequest::new(request, self.settings);
+        let result = self
+            .settings
+            .watch("sourcetransform", self.handler.transform(request))
+            .instrument(span)
+            .await;
+        metrics::handler_latency("sourcetransform", start.elapsed());
+        metrics::messages_emitted("sourcetransform", result.len());
+
+        let mut response_list = vec![];
+        // build the response struct
+        for message in result {
+            let datum_response = source_transform_response::Result {
+                keys: message.keys,
+                value: message.value,
+                event_time: Some(shared::prost_timestamp_from_utc(message.event_time)),
+                tags: message.tags,
+            };
+            response_list.push(datum_response);
+        }
+
+        // return the result
+        Ok(Response::new(SourceTransformResponse {
+            results: response_list,
+        }))
+    }
+
+    async fn is_ready(&self, _: Request<()>) -> Result<Response<ReadyResponse>, Status> {
+        Ok(Response::new(ReadyResponse { ready: true }))
+    }
+}
+
+/// Message is the response struct from the [`SourceTransformer::transform`].
+pub struct Message {
+    /// Keys are a collection of strings which will be passed on to the next vertex as is. It can
+    /// be an empty collection.
+    pub keys: Vec<String>,
+    /// Value is the value passed to the next vertex.
+    pub value: Bytes,
+    /// event_time is the time assigned to the message, it is used for watermarking.
+    pub event_time: DateTime<Utc>,
+    /// Tags are used for [conditional forwarding](https://numaflow.numaproj.io/user-guide/reference/conditional-forwarding/).
+    pub tags: Vec<String>,
+}
+
+impl Message {
+    /// Returns a builder of the message, the event time is required.
+    pub fn builder() -> MessageBuilder<Self> {
+        MessageBuilder::new()
+    }
+
+    /// Returns a message numaflow drops. The event time is the one of the input, so that the
+    /// watermark still moves on when the inputs are filtered out.
+    pub fn dropped(event_time: DateTime<Utc>) -> Self {
+        Self {
+            keys: vec![],
+            value: Bytes::new(),
+            event_time,
+            tags: vec![message::DROP.to_string()],
+        }
+    }
+
+    /// Returns whether numaflow drops the message, see [`DROP`](message::DROP).
+    pub fn is_dropped(&self) -> bool {
+        self.tags.iter().any(|tag| tag == message::DROP)
+    }
+}
+
+/// Datum trait represents an incoming element into the [`SourceTransformer::transform`].
+pub trait Datum {
+    /// keys are the keys in the (key, value) terminology of map/reduce paradigm.
+    fn keys(&self) -> &Vec<String>;
+    /// value is the value in (key, value) terminology of map/reduce paradigm.
+    fn value(&self) -> &Bytes;
+    /// [watermark](https://numaflow.numaproj.io/core-concepts/watermarks/) represented by time is a guarantee that we will not see an element older than this
+    /// time.
+    fn watermark(&self) -> DateTime<Utc>;
+    /// event_time is the time of the element as seen at source.
+    fn event_time(&self) -> DateTime<Utc>;
+    /// headers are the user defined headers set at the source.
+    fn headers(&self) -> &Headers;
+}
+
+/// Owned copy of SourceTransformRequest from Datum.
+struct OwnedSourceTransformRequest {
+    keys: Vec<String>,
+    value: Bytes,
+    watermark: DateTime<Utc>,
+    eventtime: DateTime<Utc>,
+    headers: Headers,
+}
+
+impl OwnedSourceTransformRequest {
+    fn new(mut sr: SourceTransformRequest, settings: HandlerSettings) -> Self {
+        let mut headers = Headers::from(sr.headers);
+        settings.decompress_payload(&mut headers, &mut sr.value);
+        Self {
+            keys: sr.keys,
+            value: sr.value,
+            watermark: shared::utc_from_timestamp(sr.watermark),
+            eventtime: shared::utc_from_timestamp(sr.event_time),
+            headers,
+        }
+    }
+}
+
+impl Datum for OwnedSourceTransformRequest {
+    fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    fn watermark(&self) -> DateTime<Utc> {
+        self.watermark
+    }
+
+    fn event_time(&self) -> DateTime<Utc> {
+        self.eventtime
+    }
+
+    fn headers(&self) -> &Headers {
+        &self.headers
+    }
+}
+
+/// FromFn is a [`SourceTransformer`] running a closure, see [`Server::from_fn`].
+pub struct FromFn<F>(F);
+
+#[async_trait]
+impl<F, Fut> SourceTransformer for FromFn<F>
+where
+    F: Fn(Box<dyn Datum + Send + Sync>) -> Fut + Send + Sync,
+    Fut: Future<Output = Vec<Message>> + Send,
+{
+    async fn transform<T: Datum + Send + Sync + 'static>(&self, input: T) -> Vec<Message> {
+        (self.0)(Box::new(input)).await
+    }
+}
+
+/// gRPC server to start a source transformer service
+pub struct Server<T> {
+    config: shared::ServerConfig,
+    svc: T,
+}
+
+impl<T> Server<T> {
+    /// Create a new source transformer server with the given [`SourceTransformer`] handler.
+    pub fn new(transformer_svc: T) -> Self {
+        Self {
+            config: shared::ServerConfig::new(DEFAULT_SOCK_ADDR, PROTOCOL_VERSION),
+            svc: transformer_svc,
+        }
+    }
+
+    shared::server_config_methods!();
+
+    /// Starts the gRPC server. The server runs until it is stopped or errors out.
+    pub async fn start(self) -> Result<(), shared::BoxError>
+    where
+        T: SourceTransformer + Send + Sync + 'static,
+    {
+        self.start_with_shutdown(std::future::pending()).await
+    }
+
+    /// Starts the gRPC server, it shuts down once `shutdown` resolves. The server then stops
+    /// accepting traffic and returns once the in-flight requests are done.
+    pub async fn start_with_shutdown<F>(self, shutdown: F) -> Result<(), shared::BoxError>
+    where
+        T: SourceTransformer + Send + Sync + 'static,
+        F: Future<Output = ()>,
+    {
+        let mut config = self.config;
+        let incoming = config.prepare().await?;
+
+        let transformer_svc = SourceTransformerService {
+            handler: self.svc,
+            settings: config.handler_settings(),
+        };
+
+        shared::router!(
+            config,
+            source_transform_server::SourceTransformServer::new(transformer_svc)
+        )
+        .serve_with_incoming_shutdown(incoming, shutdown)
+        .await
+        .map_err(|e| error::Error::SourceTransformerError(error::ErrorKind::connection(e)))?;
+
+        Ok(())
+    }
+}
+
+impl<T> crate::server::Service for Server<T>
+where
+    T: SourceTransformer + Send + Sync + 'static,
+{
+    fn serve(
+        self,
+        shutdown: BoxFuture<'static, ()>,
+    ) -> BoxFuture<'static, Result<(), shared::BoxError>> {
+        Box::pin(self.start_with_shutdown(shutdown))
+    }
+}
+
+impl<F> Server<FromFn<F>> {
+    /// Create a new source transformer server running the closure as the
+    /// [`SourceTransformer::transform`] handler, for the handlers too small to be worth a type of
+    /// their own. The input is passed to the closure as a [`Datum`] trait object.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use chrono::Utc;
+    /// use numaflow::sourcetransform::{self, Message};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     sourcetransform::Server::from_fn(|input| async move {
+    ///         vec![Message {
+    ///             keys: input.keys().clone(),
+    ///             value: input.value().clone(),
+    ///             event_time: Utc::now(),
+    ///             tags: vec![],
+    ///         }]
+    ///     })
+    ///     .start()
+    ///     .await
+    /// }
+    /// ```
+    pub fn from_fn<Fut>(handler: F) -> Self
+    where
+        F: Fn(Box<dyn Datum + Send + Sync>) -> Fut,
+        Fut: Future<Output = Vec<Message>>,
+    {
+        Self::new(FromFn(handler))
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use thiserror::Error;
+use tonic::async_trait;
+
+use crate::shared::BoxError;
+
+/// StateError is the error of a [`StateStore`] operation.
+#[derive(Error, Debug)]
+pub enum StateError {
+    /// The store failed to read or write the disk.
+    #[error("state store I/O failed: {0}")]
+    Io(#[from] io::Error),
+    /// Any other failure of the store, e.g., of a remote database backing it.
+    #[error("state store failed: {0}")]
+    Backend(BoxError),
+}
+
+/// StateStore keeps values by key beyond the lifetime of a window, e.g., the running total of the
+/// keys of a reduce across its windows, or the checkpoints of the windows. The store is passed to
+/// the reduce handler with the `unstable` feature, see `reduce::Server::with_state_store`.
+///
+/// The windows of the same keys may be reduced at the same time, e.g., the sliding ones, hence a
+/// read-modify-write of a key is not atomic across windows. A store backing a reducer running in
+/// several replicas must be shared by them, unlike the [`MemoryStore`] and the [`FileStore`].
+///
+/// # Example
+///
+/// ```
+/// use numaflow::state::{MemoryStore, StateStore};
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let store = MemoryStore::new();
+///     store.put("a", "1".into()).await.unwrap();
+///     assert_eq!(store.clone().get("a").await.unwrap(), Some("1".into()));
+///
+///     store.delete("a").await.unwrap();
+///     assert_eq!(store.get("a").await.unwrap(), None);
+/// }
+/// ```
+#[async_trait]
+pub trait StateStore: Send + Sync {
+    /// Returns the value of the key, None if the key has no value.
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, StateError>;
+
+    /// Sets the value of the key, replacing the previous one.
+    async fn put(&self, key: &str, value: Bytes) -> Result<(), StateError>;
+
+    /// Removes the value of the key, it is a no-op if the key has no value.
+    async fn delete(&self, key: &str) -> Result<(), StateError>;
+}
+
+/// MemoryStore is a [`StateStore`] in the memory of the process, its values are lost on restart.
+/// The clones share the same values.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    values: Arc<Mutex<HashMap<String, Bytes>>>,
+}
+
+impl MemoryStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl StateStore for MemoryStore {
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, StateError> {
+        let values = self.values.lock().expect("state store lock is poisoned");
+        Ok(values.get(key).cloned())
+    }
+
+    async fn put(&self, key: &str, value: Bytes) -> Result<(), StateError> {
+        let mut values = self.values.lock().expect("state store lock is poisoned");
+        values.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StateError> {
+        let mut values = self.values.lock().expect("state store lock is poisoned");
+        values.remove(key);
+        Ok(())
+    }
+}
+
+// tells apart the temporary files of the puts in flight
+static TMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// FileStore is a [`StateStore`] keeping a file per key in a directory, e.g., on a persistent
+/// volume so that the values outlive the restarts of the pod. A value is replaced atomically, a
+/// crash in the middle of a put leaves the previous value.
+#[derive(Clone, Debug)]
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    /// Create a store in the directory, it is created if missing.
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Returns the directory of the store.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // the keys are hex encoded, they may hold any character a file name cannot
+    fn path(&self, key: &str) -> PathBuf {
+        let mut name = String::with_capacity(2 * key.len());
+        for b in key.bytes() {
+            let _ = write!(name, "{:02x}", b);
+        }
+        self.dir.join(name)
+    }
+}
+
+// runs the blocking file operation off the executor threads
+async fn blocking<T, F>(f: F) -> Result<T, StateError>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| StateError::Backend(e.into()))?
+        .map_err(StateError::Io)
+}
+
+#[async_trait]
+impl StateStore for FileStore {
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, StateError> {
+        let path = self.path(key);
+        blocking(move || match std::fs::read(path) {
+            Ok(value) => Ok(Some(value.into())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        })
+        .await
+    }
+
+    async fn put(&self, key: &str, value: Bytes) -> Result<(), StateError> {
+        let path = self.path(key);
+        let tmp = self.dir.join(format!(
+            ".tmp-{}-{}",
+            std::process::id(),
+            TMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        blocking(move || {
+            std::fs::write(&tmp, &value)?;
+            std::fs::rename(&tmp, path).inspect_err(|_| {
+                let _ = std::fs::remove_file(&tmp);
+            })
+        })
+        .await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StateError> {
+        let path = self.path(key);
+        blocking(move || match std::fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        })
+        .await
+    }
+}
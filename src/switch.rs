@@ -0,0 +1,205 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+use tonic::async_trait;
+
+use crate::{map, reduce};
+
+pub use crate::sideinput::SIDE_INPUTS_DIR;
+
+// how long a value read from the side input file is used before the file is read again
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Variant is one of the two handlers of a [`Switch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variant {
+    /// The handler running until the side input says otherwise.
+    #[default]
+    Blue,
+    /// The other handler.
+    Green,
+}
+
+impl Variant {
+    fn from_value(value: &[u8]) -> Option<Self> {
+        match std::str::from_utf8(value).ok()?.trim() {
+            v if v.eq_ignore_ascii_case("blue") => Some(Variant::Blue),
+            v if v.eq_ignore_ascii_case("green") => Some(Variant::Green),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Variant::Blue => write!(f, "blue"),
+            Variant::Green => write!(f, "green"),
+        }
+    }
+}
+
+struct State {
+    variant: Variant,
+    // when the side input file was last read
+    read_at: Option<Instant>,
+}
+
+/// Switch runs one of two handlers, the blue or the green one, as per the value of a side input,
+/// so that a new version of the logic is rolled out, and rolled back, by broadcasting `green` or
+/// `blue` instead of redeploying the pipeline. Any other value, or no value yet, keeps the handler
+/// running.
+///
+/// The handler is picked when an invocation starts and runs it to the end, for a reduce it is when
+/// a window of a set of keys is opened. Hence a window is reduced by one handler only, the windows
+/// opened after the switch are reduced by the other one.
+///
+/// # Example
+///
+/// ```no_run
+/// use numaflow::map::{self, Datum, Message};
+/// use numaflow::switch::Switch;
+///
+/// struct Cat;
+///
+/// #[tonic::async_trait]
+/// impl map::Mapper for Cat {
+///     async fn map<T: Datum + Send + Sync + 'static>(&self, input: T) -> Vec<Message> {
+///         vec![Message {
+///             keys: input.keys().clone(),
+///             value: input.value().clone(),
+///             tags: vec![],
+///         }]
+///     }
+/// }
+///
+/// struct Upper;
+///
+/// #[tonic::async_trait]
+/// impl map::Mapper for Upper {
+///     async fn map<T: Datum + Send + Sync + 'static>(&self, input: T) -> Vec<Message> {
+///         vec![Message {
+///             keys: input.keys().clone(),
+///             value: input.value().to_ascii_uppercase().into(),
+///             tags: vec![],
+///         }]
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+///     map::Server::new(Switch::new("rollout", Cat, Upper)).start().await
+/// }
+/// ```
+pub struct Switch<B, G> {
+    blue: B,
+    green: G,
+    side_input: String,
+    dir: PathBuf,
+    refresh_interval: Duration,
+    state: Mutex<State>,
+}
+
+impl<B, G> Switch<B, G> {
+    /// Create a switch between the `blue` and the `green` handlers driven by the side input named
+    /// `side_input`, running the blue one until the side input says otherwise.
+    pub fn new(side_input: impl Into<String>, blue: B, green: G) -> Self {
+        Self {
+            blue,
+            green,
+            side_input: side_input.into(),
+            dir: SIDE_INPUTS_DIR.into(),
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            state: Mutex::new(State {
+                variant: Variant::default(),
+                read_at: None,
+            }),
+        }
+    }
+
+    /// Change the directory the side input is read from. Default value is [`SIDE_INPUTS_DIR`].
+    pub fn with_side_inputs_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    /// Change how often the side input is read again. Default value is 1 second.
+    pub fn with_refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh_interval = interval;
+        self
+    }
+
+    /// Returns the handler run by the invocations starting now.
+    pub fn current(&self) -> Variant {
+        let mut state = self.state.lock().expect("switch state lock is poisoned");
+        if state
+            .read_at
+            .is_some_and(|read_at| read_at.elapsed() < self.refresh_interval)
+        {
+            return state.variant;
+        }
+        state.read_at = Some(Instant::now());
+
+        // the side input is not there until it is broadcast for the first time
+        let Ok(value) = std::fs::read(self.dir.join(&self.side_input)) else {
+            return state.variant;
+        };
+        match Variant::from_value(&value) {
+            Some(variant) if variant != state.variant => {
+                tracing::info!(
+                    side_input = %self.side_input,
+                    from = %state.variant,
+                    to = %variant,
+                    "side input switched the handler"
+                );
+                state.variant = variant;
+            }
+            Some(_) => {}
+            None => tracing::warn!(
+                side_input = %self.side_input,
+                handler = %state.variant,
+                "side input is neither blue nor green, keeping the handler"
+            ),
+        }
+        state.variant
+    }
+}
+
+#[async_trait]
+impl<B, G> map::Mapper for Switch<B, G>
+where
+    B: map::Mapper + Send + Sync,
+    G: map::Mapper + Send + Sync,
+{
+    async fn map<T: map::Datum + Send + Sync + 'static>(&self, input: T) -> Vec<map::Message> {
+        match self.current() {
+            Variant::Blue => self.blue.map(input).await,
+            Variant::Green => self.green.map(input).await,
+        }
+    }
+}
+
+#[async_trait]
+impl<B, G> reduce::Reducer for Switch<B, G>
+where
+    B: reduce::Reducer + Send + Sync,
+    G: reduce::Reducer + Send + Sync,
+{
+    async fn reduce<
+        T: reduce::Datum + Send + Sync + 'static,
+        U: reduce::Metadata + Send + Sync + 'static,
+    >(
+        &self,
+        keys: Vec<String>,
+        input: mpsc::Receiver<T>,
+        md: &U,
+    ) -> Vec<reduce::Message> {
+        match self.current() {
+            Variant::Blue => self.blue.reduce(keys, input, md).await,
+            Variant::Green => self.green.reduce(keys, input, md).await,
+        }
+    }
+}
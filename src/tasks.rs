@@ -0,0 +1,152 @@
+// The internal tasks of the SDK are spawned with a stable name telling what they do, e.g.,
+// `reduce:task:{keys}` for the handle of a set of keys. The name is carried by a `numaflow.task`
+// span around the task and, with the `tokio-console` feature and `--cfg tokio_unstable`, by the
+// tokio task name shown in tokio-console. With the `task-dump` feature the running tasks are also
+// tracked and can be dumped on demand through the `tasks` command of the control socket, to find
+// out what a hanging server is stuck on.
+#![cfg_attr(not(feature = "task-dump"), allow(unused_variables))]
+
+use std::future::Future;
+
+use tokio::task::{AbortHandle, JoinHandle, JoinSet};
+use tracing::Instrument;
+
+/// Spawns the future as a task with the given name.
+pub(crate) fn spawn<F>(name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let future = named(name, future).instrument(task_span(name));
+    #[cfg(all(tokio_unstable, feature = "tokio-console"))]
+    return tokio::task::Builder::new()
+        .name(name)
+        .spawn(future)
+        .expect("tasks are spawned within the runtime");
+    #[cfg(not(all(tokio_unstable, feature = "tokio-console")))]
+    tokio::spawn(future)
+}
+
+/// Spawns the future as a task with the given name on the set.
+pub(crate) fn spawn_on<T, F>(set: &mut JoinSet<T>, name: &str, future: F) -> AbortHandle
+where
+    T: Send + 'static,
+    F: Future<Output = T> + Send + 'static,
+{
+    let future = named(name, future).instrument(task_span(name));
+    #[cfg(all(tokio_unstable, feature = "tokio-console"))]
+    return set
+        .build_task()
+        .name(name)
+        .spawn(future)
+        .expect("tasks are spawned within the runtime");
+    #[cfg(not(all(tokio_unstable, feature = "tokio-console")))]
+    set.spawn(future)
+}
+
+fn task_span(name: &str) -> tracing::Span {
+    tracing::debug_span!("numaflow.task", task = name)
+}
+
+#[cfg(not(feature = "task-dump"))]
+fn named<F: Future>(name: &str, future: F) -> F {
+    future
+}
+
+#[cfg(feature = "task-dump")]
+pub(crate) use registry::dump;
+#[cfg(feature = "task-dump")]
+use registry::named;
+
+#[cfg(feature = "task-dump")]
+mod registry {
+    use std::collections::BTreeMap;
+    use std::future::Future;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Mutex, MutexGuard, OnceLock};
+    use std::time::Instant;
+
+    struct TaskInfo {
+        name: String,
+        spawned_at: Instant,
+        polls: u64,
+        // set while the task is being polled
+        polled_since: Option<Instant>,
+        last_polled_at: Option<Instant>,
+    }
+
+    fn tasks() -> MutexGuard<'static, BTreeMap<u64, TaskInfo>> {
+        static TASKS: OnceLock<Mutex<BTreeMap<u64, TaskInfo>>> = OnceLock::new();
+        TASKS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // removes the task from the registry once it is done or aborted
+    struct Registered(u64);
+
+    impl Drop for Registered {
+        fn drop(&mut self) {
+            tasks().remove(&self.0);
+        }
+    }
+
+    /// Tracks the future of a task until it completes or is dropped.
+    pub(super) fn named<F: Future>(name: &str, future: F) -> impl Future<Output = F::Output> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        tasks().insert(
+            id,
+            TaskInfo {
+                name: name.to_string(),
+                spawned_at: Instant::now(),
+                polls: 0,
+                polled_since: None,
+                last_polled_at: None,
+            },
+        );
+        let registered = Registered(id);
+
+        async move {
+            let _registered = registered;
+            let mut future = std::pin::pin!(future);
+            std::future::poll_fn(|cx| {
+                if let Some(task) = tasks().get_mut(&id) {
+                    task.polls += 1;
+                    task.polled_since = Some(Instant::now());
+                }
+                let poll = future.as_mut().poll(cx);
+                if let Some(task) = tasks().get_mut(&id) {
+                    task.polled_since = None;
+                    task.last_polled_at = Some(Instant::now());
+                }
+                poll
+            })
+            .await
+        }
+    }
+
+    /// Returns one line per running task, the oldest first.
+    pub(crate) fn dump() -> Vec<String> {
+        let now = Instant::now();
+        tasks()
+            .iter()
+            .map(|(id, task)| {
+                let state = match (task.polled_since, task.last_polled_at) {
+                    (Some(since), _) => format!("running for {:?}", now - since),
+                    (None, Some(at)) => format!("idle for {:?}", now - at),
+                    (None, None) => "never polled".to_string(),
+                };
+                format!(
+                    "#{} {}: age {:?}, {} polls, {}",
+                    id,
+                    task.name,
+                    now - task.spawned_at,
+                    task.polls,
+                    state
+                )
+            })
+            .collect()
+    }
+}
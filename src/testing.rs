@@ -0,0 +1,628 @@
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::FutureExt;
+use tokio::sync::oneshot;
+use tonic::transport::Channel;
+
+use crate::local;
+use crate::shared::{self, BoxError};
+
+// Implements Ephemeral for the `Server` of a UDF kind and defines the `client_for` of its module,
+// e.g., `impl_ephemeral!("map", Mapper, MapClient<Channel>)`. The doc comments passed first are
+// appended to the one of `client_for`.
+macro_rules! impl_ephemeral {
+    ($(#[$doc:meta])* $kind:literal, $handler:path, $client:ty) => {
+        #[doc = concat!(
+            "Starts the ", $kind, " server on a unix domain socket in a temporary directory and ",
+            "returns a client connected to it. The socket and the server info files set on the ",
+            "server are replaced by files of the temporary directory."
+        )]
+        $(#[$doc])*
+        pub async fn client_for<T>(
+            server: Server<T>,
+        ) -> Result<$crate::testing::TestClient<$client>, $crate::shared::BoxError>
+        where
+            T: $handler + Send + Sync + 'static,
+        {
+            $crate::testing::ephemeral_server(server).await
+        }
+
+        impl<T> $crate::testing::Ephemeral for Server<T>
+        where
+            T: $handler + Send + Sync + 'static,
+        {
+            type Client = $client;
+
+            fn with_test_socket(
+                self,
+                sock: &::std::path::Path,
+                server_info: &::std::path::Path,
+            ) -> Self {
+                self.with_socket_file(sock)
+                    .with_server_info_file(server_info)
+            }
+
+            fn client(channel: ::tonic::transport::Channel) -> Self::Client {
+                <$client>::new(channel)
+            }
+        }
+    };
+}
+
+/// accumulator connects clients to the accumulator servers.
+pub mod accumulator;
+
+/// batchmap connects clients to the batch map servers and runs their streams without the
+/// transport.
+pub mod batchmap;
+
+/// faults injects the failures of the handlers and of the streams, to assert that the servers fail
+/// with a status.
+pub mod faults;
+
+/// map connects clients to the map servers.
+pub mod map;
+
+/// mapstream connects clients to the map stream servers.
+pub mod mapstream;
+
+/// reduce runs the reduce handlers in memory, without the gRPC server, connects clients to the
+/// reduce servers and runs their streams without the transport.
+pub mod reduce;
+
+/// sandbox runs the handlers without access to the files and the network, it is Linux only.
+#[cfg(all(feature = "sandbox", target_os = "linux"))]
+pub mod sandbox;
+
+/// sideinput connects clients to the side input servers.
+pub mod sideinput;
+
+/// sink connects clients to the sink servers.
+pub mod sink;
+
+/// source connects clients to the source servers.
+pub mod source;
+
+/// sourcetransform connects clients to the source transformer servers.
+pub mod sourcetransform;
+
+/// Output is a result returned by a handler, it is implemented by the `Message` of every UDF kind
+/// and by the [`local::Element`] returned by the local pipeline.
+pub trait Output {
+    /// keys of the result.
+    fn keys(&self) -> &[String];
+    /// value of the result.
+    fn value(&self) -> &[u8];
+    /// tags of the result, empty for the kinds without conditional forwarding.
+    fn tags(&self) -> &[String] {
+        &[]
+    }
+    /// event time of the result, `None` for the kinds which do not assign one.
+    fn event_time(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+}
+
+macro_rules! impl_output {
+    ($($message:ty),* $(,)?) => {
+        $(impl Output for $message {
+            fn keys(&self) -> &[String] {
+                &self.keys
+            }
+
+            fn value(&self) -> &[u8] {
+                &self.value
+            }
+
+            fn tags(&self) -> &[String] {
+                &self.tags
+            }
+        })*
+    };
+}
+
+impl_output!(
+    crate::map::Message,
+    crate::mapstream::Message,
+    crate::batchmap::Message,
+);
+
+impl Output for crate::reduce::Message {
+    fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn event_time(&self) -> Option<DateTime<Utc>> {
+        crate::reduce::Message::event_time(self)
+    }
+}
+
+impl Output for crate::sourcetransform::Message {
+    fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn event_time(&self) -> Option<DateTime<Utc>> {
+        Some(self.event_time)
+    }
+}
+
+impl Output for crate::accumulator::Message {
+    fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn event_time(&self) -> Option<DateTime<Utc>> {
+        Some(self.event_time)
+    }
+}
+
+impl Output for crate::source::Message {
+    fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    fn event_time(&self) -> Option<DateTime<Utc>> {
+        Some(self.event_time)
+    }
+}
+
+impl Output for local::Element {
+    fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    fn event_time(&self) -> Option<DateTime<Utc>> {
+        Some(self.event_time)
+    }
+}
+
+/// Starts the assertions on the results returned by a handler. Every assertion panics with a
+/// message telling the expected and the actual results, so it can be used as is in a `#[test]`.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::map::Message;
+/// use numaflow::testing::assert_messages;
+/// use serde_json::json;
+///
+/// let out = vec![
+///     Message {
+///         keys: vec!["k".to_string()],
+///         value: br#"{"count": 2}"#.to_vec().into(),
+///         tags: vec![],
+///     },
+///     Message {
+///         keys: vec![],
+///         value: b"done".to_vec().into(),
+///         tags: vec!["even".to_string()],
+///     },
+/// ];
+///
+/// assert_messages(&out)
+///     .has_len(2)
+///     .message(0)
+///     .keys(["k"])
+///     .value_json(json!({"count": 2}))
+///     .message(1)
+///     .value_str("done")
+///     .tags(["even"]);
+/// ```
+pub fn assert_messages<M: Output>(messages: &[M]) -> MessagesAssert<'_, M> {
+    MessagesAssert { messages }
+}
+
+/// Assertions on all the results of a handler, see [`assert_messages`].
+pub struct MessagesAssert<'a, M> {
+    messages: &'a [M],
+}
+
+impl<'a, M: Output> MessagesAssert<'a, M> {
+    /// Asserts the number of results.
+    #[track_caller]
+    pub fn has_len(self, len: usize) -> Self {
+        assert_eq!(
+            self.messages.len(),
+            len,
+            "expected {} messages, got {}",
+            len,
+            self.messages.len()
+        );
+        self
+    }
+
+    /// Asserts that every result satisfies `check`, which is given the position of the result.
+    #[track_caller]
+    pub fn all(self, check: impl Fn(usize, MessageAssert<'a, M>)) -> Self {
+        for index in 0..self.messages.len() {
+            let messages = MessagesAssert {
+                messages: self.messages,
+            };
+            check(index, messages.message(index));
+        }
+        self
+    }
+
+    /// Moves on to the assertions on the result at `index`.
+    #[track_caller]
+    pub fn message(self, index: usize) -> MessageAssert<'a, M> {
+        let Some(message) = self.messages.get(index) else {
+            panic!(
+                "expected a message at index {}, got {} messages",
+                index,
+                self.messages.len()
+            );
+        };
+        MessageAssert {
+            messages: self.messages,
+            index,
+            message,
+        }
+    }
+}
+
+/// Assertions on a single result of a handler, see [`assert_messages`].
+pub struct MessageAssert<'a, M> {
+    messages: &'a [M],
+    index: usize,
+    message: &'a M,
+}
+
+impl<'a, M: Output> MessageAssert<'a, M> {
+    /// Asserts the keys of the result.
+    #[track_caller]
+    pub fn keys<I, S>(self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let keys: Vec<String> = keys.into_iter().map(Into::into).collect();
+        assert_eq!(
+            self.message.keys(),
+            keys.as_slice(),
+            "keys of message {} do not match",
+            self.index
+        );
+        self
+    }
+
+    /// Asserts the value of the result.
+    #[track_caller]
+    pub fn value(self, value: impl AsRef<[u8]>) -> Self {
+        assert_eq!(
+            self.message.value(),
+            value.as_ref(),
+            "value of message {} does not match",
+            self.index
+        );
+        self
+    }
+
+    /// Asserts that the value of the result is the given UTF-8 string.
+    #[track_caller]
+    pub fn value_str(self, value: &str) -> Self {
+        let actual = match std::str::from_utf8(self.message.value()) {
+            Ok(actual) => actual,
+            Err(e) => panic!("value of message {} is not UTF-8: {}", self.index, e),
+        };
+        assert_eq!(
+            actual, value,
+            "value of message {} does not match",
+            self.index
+        );
+        self
+    }
+
+    /// Asserts that the value of the result is a JSON document equal to `value`, irrespective of
+    /// the formatting and the order of the fields.
+    #[track_caller]
+    pub fn value_json(self, value: serde_json::Value) -> Self {
+        let actual: serde_json::Value = match serde_json::from_slice(self.message.value()) {
+            Ok(actual) => actual,
+            Err(e) => panic!("value of message {} is not JSON: {}", self.index, e),
+        };
+        assert_eq!(
+            actual, value,
+            "value of message {} does not match",
+            self.index
+        );
+        self
+    }
+
+    /// Asserts the tags of the result.
+    #[track_caller]
+    pub fn tags<I, S>(self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let tags: Vec<String> = tags.into_iter().map(Into::into).collect();
+        assert_eq!(
+            self.message.tags(),
+            tags.as_slice(),
+            "tags of message {} do not match",
+            self.index
+        );
+        self
+    }
+
+    /// Asserts that the result has the tag, among others.
+    #[track_caller]
+    pub fn has_tag(self, tag: &str) -> Self {
+        assert!(
+            self.message.tags().iter().any(|t| t == tag),
+            "message {} does not have the tag {:?}, its tags are {:?}",
+            self.index,
+            tag,
+            self.message.tags()
+        );
+        self
+    }
+
+    /// Asserts the event time of the result, it fails for the kinds which do not assign one.
+    #[track_caller]
+    pub fn event_time(self, event_time: DateTime<Utc>) -> Self {
+        assert_eq!(
+            self.message.event_time(),
+            Some(event_time),
+            "event time of message {} does not match",
+            self.index
+        );
+        self
+    }
+
+    /// Asserts the result with a custom check.
+    #[track_caller]
+    pub fn satisfies(self, check: impl FnOnce(&M) -> bool) -> Self {
+        assert!(
+            check(self.message),
+            "message {} does not satisfy the check",
+            self.index
+        );
+        self
+    }
+
+    /// Moves on to the assertions on another result.
+    #[track_caller]
+    pub fn message(self, index: usize) -> MessageAssert<'a, M> {
+        self.and().message(index)
+    }
+
+    /// Goes back to the assertions on all the results.
+    pub fn and(self) -> MessagesAssert<'a, M> {
+        MessagesAssert {
+            messages: self.messages,
+        }
+    }
+}
+
+// how long a test server is waited for to accept connections
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+static TEST_SOCKETS: AtomicU64 = AtomicU64::new(0);
+
+/// TestClient is a gRPC client connected to a server started by [`ephemeral_server`] or the
+/// `client_for` of a UDF kind, e.g., [`map::client_for`]. The server listens on a unix domain
+/// socket in a temporary directory, it is stopped and the directory removed once the client is
+/// dropped.
+pub struct TestClient<C> {
+    client: C,
+    server: TestServer,
+}
+
+impl<C> TestClient<C> {
+    /// Get the unix domain socket file the server listens on, e.g., to connect another client.
+    pub fn socket_file(&self) -> &Path {
+        &self.server.sock
+    }
+}
+
+impl<C> Deref for TestClient<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.client
+    }
+}
+
+impl<C> DerefMut for TestClient<C> {
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.client
+    }
+}
+
+/// The socket and the server info file of a test server, in a directory of their own.
+struct TestSocket {
+    dir: PathBuf,
+    sock: PathBuf,
+    server_info: PathBuf,
+}
+
+impl TestSocket {
+    fn new() -> io::Result<Self> {
+        let dir = std::env::temp_dir().join(format!(
+            "numaflow-test-{}-{}",
+            std::process::id(),
+            TEST_SOCKETS.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            sock: dir.join("udf.sock"),
+            server_info: dir.join("server-info"),
+            dir,
+        })
+    }
+}
+
+// stops the server and removes its directory once dropped
+struct TestServer {
+    shutdown: Option<oneshot::Sender<()>>,
+    dir: PathBuf,
+    sock: PathBuf,
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Ephemeral is a server which [`ephemeral_server`] starts, it is implemented by the `Server` of
+/// every UDF kind.
+pub trait Ephemeral: crate::server::Service + Sized {
+    /// The gRPC client of the UDF kind, e.g., a `MapClient`.
+    type Client;
+
+    #[doc(hidden)]
+    fn with_test_socket(self, sock: &Path, server_info: &Path) -> Self;
+
+    #[doc(hidden)]
+    fn client(channel: Channel) -> Self::Client;
+}
+
+/// Starts the server of any UDF kind on a unix domain socket in a temporary directory and returns
+/// a client connected to it, in a single call. The returned [`TestClient`] is the guard of the
+/// server: once it is dropped the server is stopped and the directory removed. The socket and the
+/// server info files set on the server are replaced by files of the temporary directory, the
+/// other settings are kept.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::sourcetransform::proto::SourceTransformRequest;
+/// use numaflow::sourcetransform::{self, Datum, Message};
+/// use numaflow::testing::ephemeral_server;
+///
+/// struct Stamp;
+///
+/// #[tonic::async_trait]
+/// impl sourcetransform::SourceTransformer for Stamp {
+///     async fn transform<T: Datum + Send + Sync + 'static>(&self, input: T) -> Vec<Message> {
+///         vec![Message {
+///             keys: input.keys().clone(),
+///             value: input.value().clone(),
+///             event_time: input.event_time(),
+///             tags: vec![],
+///         }]
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+///     let mut client = ephemeral_server(sourcetransform::Server::new(Stamp)).await?;
+///     let socket = client.socket_file().to_path_buf();
+///
+///     let response = client
+///         .source_transform_fn(SourceTransformRequest {
+///             keys: vec!["k".to_string()],
+///             value: "hello".into(),
+///             ..Default::default()
+///         })
+///         .await?
+///         .into_inner();
+///     assert_eq!(response.results[0].value, "hello");
+///
+///     drop(client);
+///     assert!(!socket.exists());
+///     Ok(())
+/// }
+/// ```
+pub async fn ephemeral_server<S: Ephemeral>(server: S) -> Result<TestClient<S::Client>, BoxError> {
+    let socket = TestSocket::new()?;
+    let server = server.with_test_socket(&socket.sock, &socket.server_info);
+    client_for(server, socket, S::client).await
+}
+
+/// Starts the server, which listens on the socket, and returns a client connected to it.
+async fn client_for<S, C>(
+    server: S,
+    socket: TestSocket,
+    client: impl FnOnce(Channel) -> C,
+) -> Result<TestClient<C>, BoxError>
+where
+    S: crate::server::Service,
+{
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    let guard = TestServer {
+        shutdown: Some(shutdown_tx),
+        dir: socket.dir,
+        sock: socket.sock.clone(),
+    };
+    let mut serving = tokio::spawn(
+        server.serve(
+            async move {
+                let _ = shutdown_rx.await;
+            }
+            .boxed(),
+        ),
+    );
+
+    let connect = async {
+        loop {
+            match shared::connect_unix(&socket.sock).await {
+                Ok(channel) => return channel,
+                // the socket is not bound yet
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        }
+    };
+
+    let channel = tokio::select! {
+        result = tokio::time::timeout(CONNECT_TIMEOUT, connect) => result.map_err(|_| {
+            format!("test server did not accept connections within {:?}", CONNECT_TIMEOUT)
+        })?,
+        result = &mut serving => {
+            return Err(match result {
+                Ok(Ok(())) => "test server stopped before accepting connections".into(),
+                Ok(Err(e)) => e,
+                Err(e) => e.into(),
+            })
+        }
+    };
+
+    Ok(TestClient {
+        client: client(channel),
+        server: guard,
+    })
+}
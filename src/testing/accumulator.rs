@@ -0,0 +1,83 @@
+use tonic::transport::Channel;
+
+use crate::accumulator::proto::accumulator_client::AccumulatorClient;
+use crate::accumulator::{Accumulator, Server};
+
+impl_ephemeral!(
+    ///
+    /// # Example
+    ///
+    /// The OPEN of the keys is resent, e.g., after a transient failure of numaflow, the element it
+    /// carries is accumulated once.
+    ///
+    /// ```
+    /// use numaflow::accumulator::proto::accumulator_request::window_operation::Event;
+    /// use numaflow::accumulator::proto::accumulator_request::WindowOperation;
+    /// use numaflow::accumulator::proto::{AccumulatorRequest, KeyedWindow, Payload};
+    /// use numaflow::accumulator::{self, Datum, Message};
+    /// use tokio::sync::mpsc::{Receiver, Sender};
+    ///
+    /// struct Echo;
+    ///
+    /// #[tonic::async_trait]
+    /// impl accumulator::Accumulator for Echo {
+    ///     async fn accumulate<T>(&self, mut input: Receiver<T>, output: Sender<Message>)
+    ///     where
+    ///         T: Datum + Send + Sync + 'static,
+    ///     {
+    ///         while let Some(datum) = input.recv().await {
+    ///             let _ = output.send(Message::from_datum(&datum)).await;
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// fn request(event: Event, id: &str) -> AccumulatorRequest {
+    ///     let keys = vec!["k".to_string()];
+    ///     AccumulatorRequest {
+    ///         payload: (event != Event::Close).then(|| Payload {
+    ///             keys: keys.clone(),
+    ///             value: id.to_string().into(),
+    ///             id: id.to_string(),
+    ///             ..Default::default()
+    ///         }),
+    ///         operation: Some(WindowOperation {
+    ///             event: event as i32,
+    ///             keyed_window: Some(KeyedWindow {
+    ///                 keys,
+    ///                 ..Default::default()
+    ///             }),
+    ///         }),
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     let server = accumulator::Server::new(Echo);
+    ///     let mut client = numaflow::testing::accumulator::client_for(server).await?;
+    ///
+    ///     let requests = [
+    ///         request(Event::Open, "a"),
+    ///         request(Event::Open, "a"),
+    ///         request(Event::Append, "b"),
+    ///         request(Event::Close, ""),
+    ///     ];
+    ///     let mut responses = client
+    ///         .accumulate_fn(tokio_stream::iter(requests))
+    ///         .await?
+    ///         .into_inner();
+    ///
+    ///     let mut ids = vec![];
+    ///     while let Some(response) = responses.message().await? {
+    ///         if response.eof {
+    ///             break;
+    ///         }
+    ///         ids.extend(response.payload.map(|payload| payload.id));
+    ///     }
+    ///     assert_eq!(ids, ["a", "b"]);
+    ///     Ok(())
+    /// }
+    /// ```
+    "accumulator",
+    Accumulator,
+    AccumulatorClient<Channel>
+);
@@ -0,0 +1,91 @@
+use futures_util::{Stream, StreamExt};
+use tonic::transport::Channel;
+use tonic::Status;
+
+use crate::batchmap::proto::batch_map_client::BatchMapClient;
+use crate::batchmap::proto::{BatchMapRequest, BatchMapResponse};
+use crate::batchmap::{BatchMapper, Server};
+
+impl_ephemeral!("batch map", BatchMapper, BatchMapClient<Channel>);
+
+/// Runs the batch map server over the requests the way the server runs a stream, without the gRPC
+/// transport, so that the failures of a stream can be injected: an `Err` item fails the read of
+/// the stream at that point. It returns the responses of the stream, or the status the stream is
+/// failed with.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::batchmap::proto::BatchMapRequest;
+/// use numaflow::batchmap::{self, BatchResponse, Datum};
+/// use numaflow::testing::batchmap::run_stream;
+/// use numaflow::testing::faults::{fail_after, Fault, Faulty};
+/// use tokio::sync::mpsc::Receiver;
+/// use tonic::{Code, Status};
+///
+/// // acknowledges every element without results
+/// struct Acker;
+///
+/// #[tonic::async_trait]
+/// impl batchmap::BatchMapper for Acker {
+///     async fn batch<T>(&self, mut input: Receiver<T>) -> Vec<BatchResponse>
+///     where
+///         T: Datum + Send + Sync + 'static,
+///     {
+///         let mut responses = vec![];
+///         while let Some(datum) = input.recv().await {
+///             responses.push(BatchResponse {
+///                 id: datum.id().to_string(),
+///                 ..Default::default()
+///             });
+///         }
+///         responses
+///     }
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let requests = || {
+///         tokio_stream::iter(["1", "2", "3"].map(|id| {
+///             Ok(BatchMapRequest {
+///                 id: id.to_string(),
+///                 ..Default::default()
+///             })
+///         }))
+///     };
+///
+///     let responses = run_stream(batchmap::Server::new(Acker), requests()).await.unwrap();
+///     assert_eq!(responses.len(), 3);
+///
+///     // the read of the stream fails after the first element
+///     let failing = fail_after(requests(), 1, Status::data_loss("connection reset"));
+///     let status = run_stream(batchmap::Server::new(Acker), failing).await.unwrap_err();
+///     assert_eq!(status.code(), Code::DataLoss);
+///
+///     // the handler stops reading after the first element, the others have no response
+///     let server = batchmap::Server::new(Faulty::new(Acker, Fault::Stop, 1));
+///     let status = run_stream(server, requests()).await.unwrap_err();
+///     assert_eq!(status.code(), Code::InvalidArgument);
+///
+///     // the handler panics on the second element
+///     let server = batchmap::Server::new(Faulty::new(Acker, Fault::Panic, 1));
+///     let status = run_stream(server, requests()).await.unwrap_err();
+///     assert_eq!(status.code(), Code::Internal);
+/// }
+/// ```
+pub async fn run_stream<T, S>(
+    server: Server<T>,
+    requests: S,
+) -> Result<Vec<BatchMapResponse>, Status>
+where
+    T: BatchMapper + Send + Sync + 'static,
+    S: Stream<Item = Result<BatchMapRequest, Status>> + Send + 'static,
+{
+    let (_, service) = server.into_parts();
+    let mut stream = service.batch_map_stream(Box::pin(requests));
+    let mut responses = vec![];
+    while let Some(response) = stream.next().await {
+        responses.push(response?);
+    }
+    Ok(responses)
+}
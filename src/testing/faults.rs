@@ -0,0 +1,186 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures_util::{stream, Stream, StreamExt};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tonic::{async_trait, Status};
+
+use crate::batchmap::{self, BatchMapper, BatchResponse};
+use crate::map::{self, Mapper};
+use crate::reduce::{self, Metadata, Reducer};
+
+/// Fault is the failure injected by a [`Faulty`] handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The handler panics.
+    Panic,
+    /// The handler never returns. The server answers with a status once the handler times out,
+    /// see [`map::Server::with_map_timeout`] and [`reduce::Server::with_handler_timeout`].
+    Hang,
+    /// The handler stops reading its input and returns the results of the elements it has read.
+    /// A map handler, which has a single element, returns no message.
+    Stop,
+}
+
+/// Faulty wraps a map, batch map or reduce handler and injects a [`Fault`] once the handler has
+/// been given `after` elements, so that the tests assert how the server fails: with a status
+/// rather than a hang or a connection torn down. The elements are counted across the invocations
+/// of a map handler, and per invocation, i.e., per batch or per keys, of the other handlers.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use numaflow::map::proto::MapRequest;
+/// use numaflow::map::{self, Message};
+/// use numaflow::testing::faults::{Fault, Faulty};
+/// use tonic::Code;
+///
+/// struct Cat;
+///
+/// #[tonic::async_trait]
+/// impl map::Mapper for Cat {
+///     async fn map<T: map::Datum + Send + Sync + 'static>(&self, input: T) -> Vec<Message> {
+///         vec![Message {
+///             keys: input.keys().clone(),
+///             value: input.value().clone(),
+///             tags: vec![],
+///         }]
+///     }
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let request = || MapRequest {
+///         keys: vec!["k".to_string()],
+///         value: "hello".into(),
+///         ..Default::default()
+///     };
+///
+///     // the first element goes through, the second one panics
+///     let server = map::Server::new(Faulty::new(Cat, Fault::Panic, 1));
+///     let mut client = numaflow::testing::map::client_for(server).await.unwrap();
+///     assert!(client.map_fn(request()).await.is_ok());
+///     let status = client.map_fn(request()).await.unwrap_err();
+///     assert_eq!(status.code(), Code::Internal);
+///
+///     // a hanging handler is timed out
+///     let server = map::Server::new(Faulty::new(Cat, Fault::Hang, 0))
+///         .with_map_timeout(Duration::from_millis(100));
+///     let mut client = numaflow::testing::map::client_for(server).await.unwrap();
+///     let status = client.map_fn(request()).await.unwrap_err();
+///     assert_eq!(status.code(), Code::DeadlineExceeded);
+/// }
+/// ```
+pub struct Faulty<H> {
+    handler: H,
+    fault: Fault,
+    after: usize,
+    // the invocations of a map handler
+    calls: AtomicUsize,
+}
+
+impl<H> Faulty<H> {
+    /// Create a handler injecting the fault into `handler` once it has been given `after`
+    /// elements.
+    pub fn new(handler: H, fault: Fault, after: usize) -> Self {
+        Self {
+            handler,
+            fault,
+            after,
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    async fn inject(&self) {
+        match self.fault {
+            Fault::Panic => panic!("injected fault"),
+            Fault::Hang => std::future::pending().await,
+            Fault::Stop => {}
+        }
+    }
+
+    // passes the first `after` elements of the input on to the handler, the fault is injected
+    // once there is another one. On a stop, the input and the channel of the handler are closed.
+    async fn feed<T>(&self, mut input: Receiver<T>, tx: Sender<T>) {
+        for _ in 0..self.after {
+            let Some(datum) = input.recv().await else {
+                return;
+            };
+            if tx.send(datum).await.is_err() {
+                return;
+            }
+        }
+        if input.recv().await.is_some() {
+            self.inject().await;
+        }
+    }
+}
+
+#[async_trait]
+impl<H> Mapper for Faulty<H>
+where
+    H: Mapper + Send + Sync,
+{
+    async fn map<T: map::Datum + Send + Sync + 'static>(&self, input: T) -> Vec<map::Message> {
+        if self.calls.fetch_add(1, Ordering::Relaxed) < self.after {
+            return self.handler.map(input).await;
+        }
+        self.inject().await;
+        vec![]
+    }
+}
+
+#[async_trait]
+impl<H> BatchMapper for Faulty<H>
+where
+    H: BatchMapper + Send + Sync,
+{
+    async fn batch<T: batchmap::Datum + Send + Sync + 'static>(
+        &self,
+        input: Receiver<T>,
+    ) -> Vec<BatchResponse> {
+        let (tx, rx) = mpsc::channel(1);
+        let (responses, ()) = tokio::join!(self.handler.batch(rx), self.feed(input, tx));
+        responses
+    }
+}
+
+#[async_trait]
+impl<H> Reducer for Faulty<H>
+where
+    H: Reducer + Send + Sync,
+{
+    async fn reduce<
+        T: reduce::Datum + Send + Sync + 'static,
+        U: Metadata + Send + Sync + 'static,
+    >(
+        &self,
+        keys: Vec<String>,
+        input: Receiver<T>,
+        md: &U,
+    ) -> Vec<reduce::Message> {
+        let (tx, rx) = mpsc::channel(1);
+        let (messages, ()) = tokio::join!(self.handler.reduce(keys, rx, md), self.feed(input, tx));
+        messages
+    }
+}
+
+/// Returns the first `n` items of the requests followed by `status`, i.e., the read of the
+/// stream fails after `n` elements, for the `run_stream` of the
+/// [batch map](crate::testing::batchmap::run_stream),
+/// [reduce](crate::testing::reduce::run_stream) and [sink](crate::testing::sink::run_stream)
+/// servers.
+pub fn fail_after<S, T>(
+    requests: S,
+    n: usize,
+    status: Status,
+) -> impl Stream<Item = Result<T, Status>> + Send
+where
+    S: Stream<Item = Result<T, Status>> + Send,
+    T: Send,
+{
+    requests
+        .take(n)
+        .chain(stream::once(async move { Err(status) }))
+}
@@ -0,0 +1,40 @@
+use tonic::transport::Channel;
+
+use crate::map::proto::map_client::MapClient;
+use crate::map::{Mapper, Server};
+
+impl_ephemeral!(
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use numaflow::map::proto::MapRequest;
+    /// use numaflow::map::{self, Message};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ///     let server = map::Server::from_fn(|input| async move {
+    ///         vec![Message {
+    ///             keys: input.keys().clone(),
+    ///             value: input.value().clone(),
+    ///             tags: vec![],
+    ///         }]
+    ///     });
+    ///     let mut client = numaflow::testing::map::client_for(server).await?;
+    ///
+    ///     let response = client
+    ///         .map_fn(MapRequest {
+    ///             keys: vec!["k".to_string()],
+    ///             value: "hello".into(),
+    ///             ..Default::default()
+    ///         })
+    ///         .await?
+    ///         .into_inner();
+    ///     assert_eq!(response.results[0].value, "hello");
+    ///     Ok(())
+    /// }
+    /// ```
+    "map",
+    Mapper,
+    MapClient<Channel>
+);
@@ -0,0 +1,6 @@
+use tonic::transport::Channel;
+
+use crate::mapstream::proto::map_stream_client::MapStreamClient;
+use crate::mapstream::{MapStreamer, Server};
+
+impl_ephemeral!("map stream", MapStreamer, MapStreamClient<Channel>);
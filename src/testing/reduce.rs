@@ -0,0 +1,332 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use chrono::{DateTime, Duration, Utc};
+use futures_util::Stream;
+use tokio::sync::{mpsc, watch};
+use tonic::transport::Channel;
+use tonic::Status;
+
+use crate::keys;
+use crate::local::Element;
+use crate::reduce::proto::reduce_client::ReduceClient;
+use crate::reduce::proto::{ReduceRequest, ReduceResponse};
+use crate::reduce::{AbortSignal, Checkpoint, IntervalWindow, Message, Reducer, Server, Watermark};
+use crate::state::StateStore;
+
+/// TestDriver runs a [`Reducer`] over a window in memory, without the gRPC server, so that a
+/// reduce handler is unit tested like a plain function. The elements are grouped by keys and the
+/// handler is called once per group, in the order of the first element of the group, like numaflow
+/// does for the keys of a window.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::reduce::{Datum, Message, Metadata, Reducer};
+/// use numaflow::testing::assert_messages;
+/// use numaflow::testing::reduce::TestDriver;
+/// use tokio::sync::mpsc::Receiver;
+///
+/// struct Counter;
+///
+/// #[tonic::async_trait]
+/// impl Reducer for Counter {
+///     async fn reduce<T: Datum + Send + Sync + 'static, U: Metadata + Send + Sync + 'static>(
+///         &self,
+///         keys: Vec<String>,
+///         mut input: Receiver<T>,
+///         _md: &U,
+///     ) -> Vec<Message> {
+///         let mut count = 0;
+///         while input.recv().await.is_some() {
+///             count += 1;
+///         }
+///         vec![Message::new(keys, count.to_string(), vec![])]
+///     }
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let out = TestDriver::new(Counter)
+///         .with_input(["a"], "1")
+///         .with_input(["b"], "2")
+///         .with_input(["a"], "3")
+///         .run()
+///         .await;
+///
+///     assert_messages(&out)
+///         .has_len(2)
+///         .message(0)
+///         .keys(["a"])
+///         .value_str("2")
+///         .message(1)
+///         .keys(["b"])
+///         .value_str("1");
+/// }
+/// ```
+pub struct TestDriver<R> {
+    reducer: R,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    slot: String,
+    elements: Vec<Element>,
+    #[cfg(feature = "unstable")]
+    state_store: Option<Arc<dyn StateStore>>,
+    checkpoints: Option<(Arc<dyn StateStore>, std::time::Duration)>,
+    #[cfg(all(feature = "sandbox", target_os = "linux"))]
+    sandbox: bool,
+}
+
+impl<R> TestDriver<R>
+where
+    R: Reducer + Send + Sync,
+{
+    /// Create a driver of the reducer over the window of the first minute after the epoch.
+    pub fn new(reducer: R) -> Self {
+        let start = DateTime::UNIX_EPOCH;
+        Self {
+            reducer,
+            start,
+            end: start + Duration::minutes(1),
+            // numaflow puts the fixed windows in the first slot
+            slot: "slot-0".to_string(),
+            elements: vec![],
+            #[cfg(feature = "unstable")]
+            state_store: None,
+            checkpoints: None,
+            #[cfg(all(feature = "sandbox", target_os = "linux"))]
+            sandbox: false,
+        }
+    }
+
+    /// Change the boundaries of the window passed to the reducer in the
+    /// [`Metadata`](crate::reduce::Metadata).
+    pub fn with_window(mut self, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        self.start = start;
+        self.end = end;
+        self
+    }
+
+    /// Change the slot of the window. Default value is `slot-0`.
+    pub fn with_slot(mut self, slot: impl Into<String>) -> Self {
+        self.slot = slot.into();
+        self
+    }
+
+    /// Set the [`StateStore`] passed to the reducer, e.g., a
+    /// [`MemoryStore`](crate::state::MemoryStore) shared by the runs of consecutive windows.
+    #[cfg(feature = "unstable")]
+    pub fn with_state_store(mut self, store: impl StateStore + 'static) -> Self {
+        self.state_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Turn the [checkpoints](crate::reduce::Checkpoint) on, kept in the store and due every
+    /// `interval`. The snapshot of a window is removed once its results are out, like the server
+    /// does.
+    pub fn with_checkpoints(
+        mut self,
+        store: impl StateStore + 'static,
+        interval: std::time::Duration,
+    ) -> Self {
+        self.checkpoints = Some((Arc::new(store), interval));
+        self
+    }
+
+    /// Run the reducer in the [sandbox](crate::testing::sandbox::run), the run panics if the
+    /// reducer opens a file or a socket. The handler is run on a thread of its own, blocking the
+    /// caller's one. Default value is false.
+    #[cfg(all(feature = "sandbox", target_os = "linux"))]
+    pub fn with_sandbox(mut self, enabled: bool) -> Self {
+        self.sandbox = enabled;
+        self
+    }
+
+    /// Add an element with the given keys and value, its event time and watermark are the start of
+    /// the window.
+    pub fn with_input<I, S>(self, keys: I, value: impl Into<Bytes>) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let start = self.start;
+        self.with_element(
+            Element::new(value, start).with_keys(keys.into_iter().map(Into::into).collect()),
+        )
+    }
+
+    /// Add an element, e.g., one with headers or with an event time of its own.
+    pub fn with_element(mut self, element: Element) -> Self {
+        self.elements.push(element);
+        self
+    }
+
+    /// Runs the reducer over the elements and returns the results of all the groups. The
+    /// [watermark](crate::reduce::Metadata::watermark) of the window is the highest watermark of
+    /// the elements, it does not move.
+    pub async fn run(mut self) -> Vec<Message> {
+        // group by keys, in the order of the first element of the group
+        let mut groups: Vec<(Vec<String>, Vec<Element>)> = vec![];
+        for element in std::mem::take(&mut self.elements) {
+            match groups.iter_mut().find(|(keys, _)| *keys == element.keys) {
+                Some((_, group)) => group.push(element),
+                None => groups.push((element.keys.clone(), vec![element])),
+            }
+        }
+
+        // the sender is never fired, the windows of a test run are not aborted
+        let (_abort_tx, abort_rx) = watch::channel(None);
+
+        // the watermark of a stream which has received all the elements
+        let watermark = groups
+            .iter()
+            .flat_map(|(_, elements)| elements.iter().map(|element| element.watermark))
+            .max()
+            .unwrap_or(self.start);
+
+        let mut results = vec![];
+        for (group_keys, elements) in groups {
+            let window_id = crate::reduce::window_id(&group_keys, self.start, self.end, &self.slot);
+            let checkpoint = match &self.checkpoints {
+                Some((store, interval)) => Checkpoint::new(store.clone(), *interval, &window_id),
+                None => Checkpoint::disabled(),
+            };
+            let md = IntervalWindow::new(
+                self.start,
+                self.end,
+                self.slot.clone(),
+                crate::reduce::task_id(
+                    &keys::join(&group_keys, keys::DEFAULT_KEY_JOIN_DELIMITER),
+                    self.start,
+                    self.end,
+                    &self.slot,
+                ),
+                window_id,
+                AbortSignal::new(abort_rx.clone()),
+                Watermark::fixed(watermark),
+            )
+            .with_checkpoint(checkpoint.clone());
+            #[cfg(feature = "unstable")]
+            let md = md.with_state_store(self.state_store.clone());
+
+            // the channel holds the whole group so that it can be filled up front
+            let (tx, rx) = mpsc::channel::<Element>(elements.len());
+            for element in elements {
+                let _ = tx.send(element).await;
+            }
+            drop(tx);
+
+            results.extend(self.reduce(group_keys, rx, &md).await);
+            checkpoint.clear().await;
+        }
+        results
+    }
+
+    async fn reduce(
+        &self,
+        keys: Vec<String>,
+        rx: mpsc::Receiver<Element>,
+        md: &IntervalWindow,
+    ) -> Vec<Message> {
+        #[cfg(all(feature = "sandbox", target_os = "linux"))]
+        if self.sandbox {
+            let group_keys = keys.clone();
+            return match crate::testing::sandbox::run(self.reducer.reduce(keys, rx, md)) {
+                Ok(messages) => messages,
+                Err(e) => panic!(
+                    "reducer of the keys {:?} failed in the sandbox: {}",
+                    group_keys, e
+                ),
+            };
+        }
+        self.reducer.reduce(keys, rx, md).await
+    }
+}
+
+impl_ephemeral!("reduce", Reducer, ReduceClient<Channel>);
+
+/// Runs the reduce server over the requests of a stream of the window `start..end` the way the
+/// server runs a stream, without the gRPC transport, so that the failures of a stream can be
+/// injected: an `Err` item fails the read of the stream at that point. It returns the responses of
+/// the windows of the stream, or the status the stream is failed with.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use chrono::DateTime;
+/// use numaflow::reduce::proto::ReduceRequest;
+/// use numaflow::reduce::{self, Datum, Message, Metadata, Reducer};
+/// use numaflow::testing::faults::{fail_after, Fault, Faulty};
+/// use numaflow::testing::reduce::run_stream;
+/// use tokio::sync::mpsc::Receiver;
+/// use tonic::{Code, Status};
+///
+/// struct Counter;
+///
+/// #[tonic::async_trait]
+/// impl Reducer for Counter {
+///     async fn reduce<T: Datum + Send + Sync + 'static, U: Metadata + Send + Sync + 'static>(
+///         &self,
+///         keys: Vec<String>,
+///         mut input: Receiver<T>,
+///         _md: &U,
+///     ) -> Vec<Message> {
+///         let mut count = 0;
+///         while input.recv().await.is_some() {
+///             count += 1;
+///         }
+///         vec![Message::new(keys, count.to_string(), vec![])]
+///     }
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let (start, end) = (DateTime::UNIX_EPOCH, DateTime::UNIX_EPOCH + Duration::from_secs(60));
+///     let requests = || {
+///         tokio_stream::iter((0..3).map(|_| {
+///             Ok(ReduceRequest {
+///                 keys: vec!["a".to_string()],
+///                 value: "1".into(),
+///                 ..Default::default()
+///             })
+///         }))
+///     };
+///
+///     let responses = run_stream(reduce::Server::new(Counter), start, end, requests())
+///         .await
+///         .unwrap();
+///     assert_eq!(responses[0].results[0].value, "3");
+///
+///     // the read of the stream fails after the first element
+///     let failing = fail_after(requests(), 1, Status::data_loss("connection reset"));
+///     let status = run_stream(reduce::Server::new(Counter), start, end, failing)
+///         .await
+///         .unwrap_err();
+///     assert_eq!(status.code(), Code::DataLoss);
+///
+///     // the handler panics on the second element
+///     let server = reduce::Server::new(Faulty::new(Counter, Fault::Panic, 1));
+///     let status = run_stream(server, start, end, requests()).await.unwrap_err();
+///     assert_eq!(status.code(), Code::Internal);
+///
+///     // the handler hangs and is timed out once the input of the window is complete
+///     let server = reduce::Server::new(Faulty::new(Counter, Fault::Hang, 1))
+///         .with_handler_timeout(Duration::from_millis(100));
+///     let status = run_stream(server, start, end, requests()).await.unwrap_err();
+///     assert_eq!(status.code(), Code::DeadlineExceeded);
+/// }
+/// ```
+pub async fn run_stream<T, S>(
+    server: Server<T>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    requests: S,
+) -> Result<Vec<ReduceResponse>, Status>
+where
+    T: Reducer + Send + Sync + 'static,
+    S: Stream<Item = Result<ReduceRequest, Status>> + Send + 'static,
+{
+    server.run_stream(start, end, requests).await
+}
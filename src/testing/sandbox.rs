@@ -0,0 +1,301 @@
+use std::future::Future;
+use std::io;
+use std::panic;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering};
+use std::sync::OnceLock;
+
+use libc::{c_int, c_void, siginfo_t, sock_filter, sock_fprog};
+use thiserror::Error;
+
+// the system calls reading or writing files, opening sockets and running programs, the handler
+// is trapped on
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xc000_003e;
+#[cfg(target_arch = "x86_64")]
+const FORBIDDEN: &[(i32, &str)] = &[
+    (2, "open"),
+    (85, "creat"),
+    (257, "openat"),
+    (437, "openat2"),
+    (87, "unlink"),
+    (263, "unlinkat"),
+    (82, "rename"),
+    (264, "renameat"),
+    (316, "renameat2"),
+    (83, "mkdir"),
+    (258, "mkdirat"),
+    (84, "rmdir"),
+    (76, "truncate"),
+    (41, "socket"),
+    (42, "connect"),
+    (49, "bind"),
+    (59, "execve"),
+    (322, "execveat"),
+];
+
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xc000_00b7;
+#[cfg(target_arch = "aarch64")]
+const FORBIDDEN: &[(i32, &str)] = &[
+    (56, "openat"),
+    (437, "openat2"),
+    (35, "unlinkat"),
+    (38, "renameat"),
+    (276, "renameat2"),
+    (34, "mkdirat"),
+    (45, "truncate"),
+    (198, "socket"),
+    (203, "connect"),
+    (200, "bind"),
+    (221, "execve"),
+    (281, "execveat"),
+];
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH: u32 = 0;
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const FORBIDDEN: &[(i32, &str)] = &[];
+
+// offset of the number of the system call in the `_sigsys` member of the siginfo of a SIGSYS
+const SIGSYS_SYSCALL_OFFSET: usize = 24;
+
+/// SandboxError is the error of running a future in the [sandbox](run).
+#[derive(Error, Debug)]
+pub enum SandboxError {
+    /// The future made system calls it is not allowed to, they failed with a permission error.
+    #[error("the handler made {calls} forbidden system call(s), the first one being {first}")]
+    Forbidden { calls: u32, first: String },
+    /// The sandbox is not implemented for the architecture.
+    #[error("the sandbox is not supported on {}", std::env::consts::ARCH)]
+    Unsupported,
+    /// Too many sandboxes are running at once.
+    #[error("too many sandboxes are running at once")]
+    Busy,
+    /// The sandbox could not be set up, e.g., seccomp is disabled in the kernel.
+    #[error("failed to set up the sandbox: {0}")]
+    Setup(#[from] io::Error),
+}
+
+// the record of the forbidden calls of a sandbox, it is picked by the filter of the sandbox thread
+struct Slot {
+    in_use: AtomicBool,
+    calls: AtomicU32,
+    first: AtomicI32,
+}
+
+const SLOT_COUNT: usize = 64;
+
+static SLOTS: [Slot; SLOT_COUNT] = [const {
+    Slot {
+        in_use: AtomicBool::new(false),
+        calls: AtomicU32::new(0),
+        first: AtomicI32::new(0),
+    }
+}; SLOT_COUNT];
+
+// releases the slot once the sandbox is done
+struct SlotGuard(usize);
+
+impl SlotGuard {
+    fn acquire() -> Option<Self> {
+        SLOTS.iter().enumerate().find_map(|(i, slot)| {
+            slot.in_use
+                .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+                .then(|| {
+                    slot.calls.store(0, Ordering::Relaxed);
+                    SlotGuard(i)
+                })
+        })
+    }
+
+    fn slot(&self) -> &'static Slot {
+        &SLOTS[self.0]
+    }
+}
+
+impl Drop for SlotGuard {
+    fn drop(&mut self) {
+        self.slot().in_use.store(false, Ordering::Release);
+    }
+}
+
+// records the trapped call in the slot passed by the filter and fails it with EPERM, it only
+// touches atomics as it runs in a signal handler
+extern "C" fn on_sigsys(_: c_int, info: *mut siginfo_t, context: *mut c_void) {
+    // SAFETY: the kernel passes a SIGSYS siginfo and the ucontext of the trapped thread
+    unsafe {
+        let nr = *(info as *const u8).add(SIGSYS_SYSCALL_OFFSET).cast::<i32>();
+        if let Some(slot) = SLOTS.get((*info).si_errno as usize) {
+            if slot.calls.fetch_add(1, Ordering::Relaxed) == 0 {
+                slot.first.store(nr, Ordering::Relaxed);
+            }
+        }
+
+        let context = context.cast::<libc::ucontext_t>();
+        #[cfg(target_arch = "x86_64")]
+        {
+            (*context).uc_mcontext.gregs[libc::REG_RAX as usize] = -i64::from(libc::EPERM);
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            (*context).uc_mcontext.regs[0] = -i64::from(libc::EPERM) as u64;
+        }
+    }
+}
+
+// the SIGSYS handler is shared by the sandboxes, it is installed by the first one
+fn install_handler() -> io::Result<()> {
+    static INSTALLED: OnceLock<Result<(), i32>> = OnceLock::new();
+    let installed = INSTALLED.get_or_init(|| {
+        // SAFETY: the action is fully initialized and the handler is async-signal-safe
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sigsys as *const () as usize;
+            action.sa_flags = libc::SA_SIGINFO;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(libc::SIGSYS, &action, std::ptr::null_mut()) != 0 {
+                return Err(io::Error::last_os_error()
+                    .raw_os_error()
+                    .unwrap_or_default());
+            }
+        }
+        Ok(())
+    });
+    installed.map_err(io::Error::from_raw_os_error)
+}
+
+fn statement(code: u32, k: u32) -> sock_filter {
+    sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jump(code: u32, k: u32, jt: u8, jf: u8) -> sock_filter {
+    sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+// traps the forbidden calls of the current thread and of the threads it spawns, for good
+fn forbid(slot: usize) -> io::Result<()> {
+    use libc::{BPF_ABS, BPF_JEQ, BPF_JMP, BPF_K, BPF_LD, BPF_RET, BPF_W};
+
+    // the arch is at offset 4 of the seccomp data and the number of the call at offset 0
+    let mut filter = vec![
+        statement(BPF_LD | BPF_W | BPF_ABS, 4),
+        jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH, 1, 0),
+        statement(BPF_RET | BPF_K, libc::SECCOMP_RET_ALLOW),
+        statement(BPF_LD | BPF_W | BPF_ABS, 0),
+    ];
+    let count = FORBIDDEN.len();
+    for (i, (nr, _)) in FORBIDDEN.iter().enumerate() {
+        // a match jumps over the remaining checks and the allow to the trap
+        filter.push(jump(
+            BPF_JMP | BPF_JEQ | BPF_K,
+            *nr as u32,
+            (count - i) as u8,
+            0,
+        ));
+    }
+    filter.push(statement(BPF_RET | BPF_K, libc::SECCOMP_RET_ALLOW));
+    filter.push(statement(
+        BPF_RET | BPF_K,
+        libc::SECCOMP_RET_TRAP | (slot as u32 & libc::SECCOMP_RET_DATA),
+    ));
+
+    let program = sock_fprog {
+        len: filter.len() as u16,
+        filter: filter.as_mut_ptr(),
+    };
+    // SAFETY: the program outlives the calls, the kernel copies it
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::prctl(
+            libc::PR_SET_SECCOMP,
+            libc::SECCOMP_MODE_FILTER,
+            &program as *const sock_fprog,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Runs the future to completion on a thread of its own which is not allowed to open, create,
+/// rename or remove files, to open sockets or to run programs, so that a test catches a handler
+/// meant to be a pure transformation doing I/O, e.g., after a refactor. The forbidden calls fail
+/// with a permission error and the run fails with [`SandboxError::Forbidden`] once the future is
+/// done, even if the handler swallowed the errors. The threads spawned by the future, e.g., by
+/// `tokio::task::spawn_blocking`, are sandboxed as well.
+///
+/// The future runs on a current thread tokio runtime of its own, the calling thread is blocked
+/// until it is done. Hence it must not depend on the tasks of the caller's runtime, e.g., on a
+/// channel they feed. A panic of the future is resumed on the calling thread.
+///
+/// It is a seccomp filter trapping the calls, available on x86_64 and aarch64 Linux. The trap is
+/// a `SIGSYS` handler installed for the process, replacing any other. It is a guard against
+/// mistakes, not a security boundary, e.g., reading the metadata of a path or writing to an
+/// already open file are allowed.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::testing::sandbox::{self, SandboxError};
+///
+/// let pure = sandbox::run(async { "hello".to_uppercase() });
+/// assert_eq!(pure.unwrap(), "HELLO");
+///
+/// let impure = sandbox::run(async { std::fs::read("/etc/hostname").is_ok() });
+/// assert!(matches!(impure, Err(SandboxError::Forbidden { .. })));
+/// ```
+pub fn run<F>(future: F) -> Result<F::Output, SandboxError>
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    if FORBIDDEN.is_empty() {
+        return Err(SandboxError::Unsupported);
+    }
+    install_handler()?;
+    let guard = SlotGuard::acquire().ok_or(SandboxError::Busy)?;
+
+    let output = std::thread::scope(|scope| {
+        scope
+            .spawn(|| -> io::Result<F::Output> {
+                // the runtime opens what it needs before the filter is installed
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                forbid(guard.0)?;
+                Ok(runtime.block_on(future))
+            })
+            .join()
+    });
+    let output = match output {
+        Ok(output) => output?,
+        Err(panic) => panic::resume_unwind(panic),
+    };
+
+    let slot = guard.slot();
+    match slot.calls.load(Ordering::Relaxed) {
+        0 => Ok(output),
+        calls => {
+            let nr = slot.first.load(Ordering::Relaxed);
+            let first = FORBIDDEN
+                .iter()
+                .find(|(forbidden, _)| *forbidden == nr)
+                .map_or_else(|| format!("#{}", nr), |(_, name)| name.to_string());
+            Err(SandboxError::Forbidden { calls, first })
+        }
+    }
+}
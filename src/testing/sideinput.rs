@@ -0,0 +1,6 @@
+use tonic::transport::Channel;
+
+use crate::sideinput::proto::side_input_client::SideInputClient;
+use crate::sideinput::{Server, SideInputer};
+
+impl_ephemeral!("side input", SideInputer, SideInputClient<Channel>);
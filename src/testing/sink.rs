@@ -0,0 +1,74 @@
+use futures_util::Stream;
+use tonic::transport::Channel;
+use tonic::Status;
+
+use crate::sink::proto::sink_client::SinkClient;
+use crate::sink::proto::{SinkRequest, SinkResponse};
+use crate::sink::{Server, Sinker};
+
+impl_ephemeral!("sink", Sinker, SinkClient<Channel>);
+
+/// Runs the sink handler over the requests the way the server runs a stream, without the gRPC
+/// transport, so that the failures of a stream can be injected: an `Err` item fails the read of
+/// the stream at that point. It returns the response of the stream, or the status the stream is
+/// failed with, with the default status mapping.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::sink::proto::SinkRequest;
+/// use numaflow::sink::{Datum, Response, Sinker};
+/// use numaflow::testing::sink::run_stream;
+/// use tokio::sync::mpsc::Receiver;
+/// use tonic::{Code, Status};
+///
+/// // acknowledges the first `limit` elements and returns
+/// struct Acker {
+///     limit: usize,
+/// }
+///
+/// #[tonic::async_trait]
+/// impl Sinker for Acker {
+///     async fn sink<T: Datum + Send + Sync + 'static>(&self, mut input: Receiver<T>) -> Vec<Response> {
+///         let mut responses = vec![];
+///         while responses.len() < self.limit {
+///             let Some(datum) = input.recv().await else { break };
+///             responses.push(Response::ok(datum.id().to_string()));
+///         }
+///         responses
+///     }
+/// }
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let request = |id: &str| Ok(SinkRequest {
+///         id: id.to_string(),
+///         ..Default::default()
+///     });
+///     let sinker = Acker { limit: usize::MAX };
+///
+///     let response = run_stream(&sinker, tokio_stream::iter([request("1"), request("2")]))
+///         .await
+///         .unwrap();
+///     assert_eq!(response.results.len(), 2);
+///
+///     // the read of the stream fails after the first element
+///     let requests = [request("1"), Err(Status::data_loss("connection reset"))];
+///     let status = run_stream(&sinker, tokio_stream::iter(requests)).await.unwrap_err();
+///     assert_eq!(status.code(), Code::DataLoss);
+///
+///     // the handler returns without answering the second element
+///     let requests = [request("1"), request("2")];
+///     let status = run_stream(&Acker { limit: 1 }, tokio_stream::iter(requests))
+///         .await
+///         .unwrap_err();
+///     assert_eq!(status.code(), Code::Internal);
+/// }
+/// ```
+pub async fn run_stream<T, S>(sinker: &T, requests: S) -> Result<SinkResponse, Status>
+where
+    T: Sinker,
+    S: Stream<Item = Result<SinkRequest, Status>> + Send + 'static,
+{
+    crate::sink::sink_stream(sinker, requests, Default::default()).await
+}
@@ -0,0 +1,6 @@
+use tonic::transport::Channel;
+
+use crate::source::proto::source_client::SourceClient;
+use crate::source::{Server, Sourcer};
+
+impl_ephemeral!("source", Sourcer, SourceClient<Channel>);
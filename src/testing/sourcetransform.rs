@@ -0,0 +1,10 @@
+use tonic::transport::Channel;
+
+use crate::sourcetransform::proto::source_transform_client::SourceTransformClient;
+use crate::sourcetransform::{Server, SourceTransformer};
+
+impl_ephemeral!(
+    "source transformer",
+    SourceTransformer,
+    SourceTransformClient<Channel>
+);
@@ -0,0 +1,86 @@
+use chrono::{DateTime, TimeZone, Utc};
+use prost_types::Timestamp;
+
+const NANOS_PER_SECOND: i32 = 1_000_000_000;
+
+/// Converts a protobuf timestamp, an unset one is the time just before the epoch and one beyond
+/// the range of chrono is clamped to it.
+pub(crate) fn utc_from_timestamp(t: Option<Timestamp>) -> DateTime<Utc> {
+    match t {
+        Some(t) => checked_utc_from_parts(t.seconds, t.nanos).unwrap_or_else(|| clamp(t.seconds)),
+        None => unset(),
+    }
+}
+
+pub(crate) fn prost_timestamp_from_utc(t: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: t.timestamp(),
+        nanos: t.timestamp_subsec_nanos() as i32,
+    }
+}
+
+fn unset() -> DateTime<Utc> {
+    Utc.timestamp_nanos(-1)
+}
+
+fn clamp(seconds: i64) -> DateTime<Utc> {
+    if seconds < 0 {
+        DateTime::<Utc>::MIN_UTC
+    } else {
+        DateTime::<Utc>::MAX_UTC
+    }
+}
+
+// None if the time is beyond the range of chrono
+fn checked_utc_from_parts(seconds: i64, nanos: i32) -> Option<DateTime<Utc>> {
+    // a normalized timestamp, which is what numaflow sends, maps to the chrono time as is
+    if (0..NANOS_PER_SECOND).contains(&nanos) {
+        return DateTime::from_timestamp(seconds, nanos as u32);
+    }
+    let seconds = seconds.checked_add(i64::from(nanos.div_euclid(NANOS_PER_SECOND)))?;
+    DateTime::from_timestamp(seconds, nanos.rem_euclid(NANOS_PER_SECOND) as u32)
+}
+
+// timestamps remembered by a cache
+const CACHED_TIMESTAMPS: usize = 8;
+
+// the seconds and the nanoseconds of a timestamp and its conversion
+type Entry = (i64, i32, Option<DateTime<Utc>>);
+
+/// Remembers the last timestamps converted for a stream. The watermark of the elements of a stream
+/// only moves once in a while, hence most of its conversions are hits.
+#[derive(Default)]
+pub(crate) struct TimestampCache {
+    entries: [Option<Entry>; CACHED_TIMESTAMPS],
+    // the entry replaced by the next miss
+    next: usize,
+}
+
+impl TimestampCache {
+    /// Converts the timestamp like [`utc_from_timestamp`].
+    pub(crate) fn convert(&mut self, t: Option<&Timestamp>) -> DateTime<Utc> {
+        match t {
+            Some(t) => self.checked(t).unwrap_or_else(|| clamp(t.seconds)),
+            None => unset(),
+        }
+    }
+
+    /// Converts the timestamp, None if it is beyond the range of chrono.
+    pub(crate) fn checked(&mut self, t: &Timestamp) -> Option<DateTime<Utc>> {
+        let hit = self
+            .entries
+            .iter()
+            .flatten()
+            .find_map(|&(seconds, nanos, converted)| {
+                (seconds == t.seconds && nanos == t.nanos).then_some(converted)
+            });
+        if let Some(converted) = hit {
+            return converted;
+        }
+
+        let converted = checked_utc_from_parts(t.seconds, t.nanos);
+        self.entries[self.next] = Some((t.seconds, t.nanos, converted));
+        self.next = (self.next + 1) % CACHED_TIMESTAMPS;
+        converted
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use tonic::codegen::http;
+use tracing::field::{display, Empty};
+use tracing::Span;
+
+use crate::headers;
+use crate::shared::ConnectionInfo;
+
+// ids of the gRPC streams served by the process
+static NEXT_STREAM_ID: AtomicU64 = AtomicU64::new(1);
+
+/// W3C trace context header carried by the messages, see <https://www.w3.org/TR/trace-context/>.
+const TRACEPARENT: &str = "traceparent";
+
+/// The trace context of the upstream span which produced the message.
+struct TraceParent<'a> {
+    trace_id: &'a str,
+    parent_id: &'a str,
+    sampled: bool,
+}
+
+/// Parses a `traceparent` header, `{version}-{trace-id}-{parent-id}-{trace-flags}`. Invalid headers
+/// are ignored as the spec asks for.
+fn parse_traceparent(value: &str) -> Option<TraceParent<'_>> {
+    let is_hex = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit());
+    let is_zero = |s: &str| s.bytes().all(|b| b == b'0');
+
+    let mut parts = value.trim().split('-');
+    let (version, trace_id, parent_id, flags) =
+        (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+    // versions after 00 may append fields, 00 must not
+    if !is_hex(version, 2) || version == "ff" || (version == "00" && parts.next().is_some()) {
+        return None;
+    }
+    if !is_hex(trace_id, 32) || is_zero(trace_id) || !is_hex(parent_id, 16) || is_zero(parent_id) {
+        return None;
+    }
+    let flags = u8::from_str_radix(flags, 16)
+        .ok()
+        .filter(|_| flags.len() == 2)?;
+
+    Some(TraceParent {
+        trace_id,
+        parent_id,
+        sampled: flags & 0x01 == 0x01,
+    })
+}
+
+/// Returns the span to run an invocation of the handler in, the trace context of the upstream
+/// vertex is taken from the `traceparent` header of the message, if any, and recorded as the
+/// `trace_id` and `parent_span_id` fields of the span. It is a disabled span when the tracing is
+/// off, i.e., unless `enabled`.
+pub(crate) fn handler_span(
+    enabled: bool,
+    handler: &str,
+    headers: Option<&HashMap<String, String>>,
+) -> Span {
+    if !enabled {
+        return Span::none();
+    }
+
+    let span = tracing::info_span!(
+        "numaflow.handler",
+        handler,
+        trace_id = Empty,
+        parent_span_id = Empty,
+        sampled = Empty,
+    );
+
+    let traceparent = headers.and_then(|headers| headers::find(headers, TRACEPARENT));
+    if let Some(parent) = traceparent.and_then(|(_, value)| parse_traceparent(value)) {
+        span.record("trace_id", parent.trace_id);
+        span.record("parent_span_id", parent.parent_id);
+        span.record("sampled", parent.sampled);
+    }
+
+    span
+}
+
+/// Returns the span tagging the events of a reduce task with its keys and window, the events the
+/// handler logs through `tracing` carry the `keys`, `window_start`, `window_end`, `slot` and
+/// `window_id` fields.
+/// It is a child of the handler span so that it follows the same trace, it is enabled whether the
+/// tracing is on or not.
+pub(crate) fn reduce_task_span(
+    parent: &Span,
+    keys: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    slot: &str,
+    window_id: &str,
+) -> Span {
+    tracing::info_span!(
+        parent: parent,
+        "numaflow.reduce.task",
+        keys,
+        window_start = %start.to_rfc3339_opts(SecondsFormat::Millis, true),
+        window_end = %end.to_rfc3339_opts(SecondsFormat::Millis, true),
+        slot,
+        window_id,
+    )
+}
+
+/// Returns the span of a gRPC stream opened by numaflow, it is entered by tonic while the call is
+/// served, hence the tasks spawned and the handler spans created by the call are within it. The
+/// vertex and the pod are those of the numa container, which shares the pod of the UDF.
+pub(crate) fn stream_span(request: &http::Request<()>) -> Span {
+    let span = tracing::info_span!(
+        "numaflow.stream",
+        stream_id = NEXT_STREAM_ID.fetch_add(1, Ordering::Relaxed),
+        method = request.uri().path(),
+        peer = Empty,
+        vertex = Empty,
+        pod = Empty,
+    );
+
+    let peer = request
+        .extensions()
+        .get::<ConnectionInfo>()
+        .and_then(|info| info.peer);
+    if let Some(peer) = peer {
+        span.record("peer", display(peer));
+    }
+    for (field, var) in [("vertex", "NUMAFLOW_VERTEX_NAME"), ("pod", "NUMAFLOW_POD")] {
+        if let Ok(value) = env::var(var) {
+            span.record(field, value.as_str());
+        }
+    }
+
+    span
+}
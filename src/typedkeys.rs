@@ -0,0 +1,110 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+/// TypedKeys is a struct standing for the keys of the elements, e.g.,
+/// `struct Keys { region: String, device: String }`, in place of the positional `Vec<String>` the
+/// elements carry. Each field is one key, in the order of the fields, so the handlers name the
+/// keys instead of indexing them and the vertices writing and reading the keys share the struct.
+///
+/// The conversion checks the number of keys and parses each of them with [`FromStr`], the fields
+/// are written back with [`Display`]. With the `macros` feature, `#[derive(numaflow::TypedKeys)]`
+/// implements it for a struct of named fields.
+///
+/// # Example
+///
+/// ```
+/// use numaflow::typedkeys::{KeyError, TypedKeys};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Keys {
+///     region: String,
+///     device: u32,
+/// }
+///
+/// impl TypedKeys for Keys {
+///     const NAMES: &'static [&'static str] = &["region", "device"];
+///
+///     fn from_keys(keys: &[String]) -> Result<Self, KeyError> {
+///         Self::check_len(keys)?;
+///         Ok(Keys {
+///             region: numaflow::typedkeys::parse(keys, 0, "region")?,
+///             device: numaflow::typedkeys::parse(keys, 1, "device")?,
+///         })
+///     }
+///
+///     fn into_keys(self) -> Vec<String> {
+///         vec![self.region, self.device.to_string()]
+///     }
+/// }
+///
+/// let keys = vec!["eu-west".to_string(), "42".to_string()];
+/// let typed = Keys::from_keys(&keys).unwrap();
+/// assert_eq!(typed, Keys { region: "eu-west".to_string(), device: 42 });
+/// assert_eq!(typed.into_keys(), keys);
+///
+/// let swapped = vec!["42".to_string(), "eu-west".to_string()];
+/// assert_eq!(
+///     Keys::from_keys(&swapped).unwrap_err().to_string(),
+///     "key device (#1) \"eu-west\" is invalid: invalid digit found in string"
+/// );
+/// assert!(matches!(
+///     Keys::from_keys(&keys[..1]),
+///     Err(KeyError::Count { expected: 2, got: 1 })
+/// ));
+/// ```
+pub trait TypedKeys: Sized {
+    /// Names of the keys, in the order of the positional keys.
+    const NAMES: &'static [&'static str];
+
+    /// Converts the positional keys of an element.
+    fn from_keys(keys: &[String]) -> Result<Self, KeyError>;
+
+    /// Converts back to the positional keys, e.g., for the keys of a result.
+    fn into_keys(self) -> Vec<String>;
+
+    /// Returns an error unless there is one key per name.
+    fn check_len(keys: &[String]) -> Result<(), KeyError> {
+        if keys.len() != Self::NAMES.len() {
+            return Err(KeyError::Count {
+                expected: Self::NAMES.len(),
+                got: keys.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// KeyError is returned when the keys of an element do not convert to a [`TypedKeys`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KeyError {
+    /// The element does not have as many keys as the struct has fields.
+    #[error("expected {expected} keys, got {got}")]
+    Count { expected: usize, got: usize },
+    /// A key does not parse into the type of its field.
+    #[error("key {name} (#{index}) {value:?} is invalid: {reason}")]
+    Invalid {
+        name: &'static str,
+        index: usize,
+        value: String,
+        reason: String,
+    },
+}
+
+/// Parses the key at the index, named `name` in the errors, with [`FromStr`]. The number of keys
+/// is expected to be checked beforehand, e.g., with [`TypedKeys::check_len`].
+pub fn parse<T>(keys: &[String], index: usize, name: &'static str) -> Result<T, KeyError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = keys.get(index).map(String::as_str).unwrap_or_default();
+    value.parse().map_err(|e: T::Err| KeyError::Invalid {
+        name,
+        index,
+        value: value.to_string(),
+        reason: e.to_string(),
+    })
+}
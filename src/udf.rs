@@ -0,0 +1,238 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::shared;
+use crate::{
+    accumulator, batchmap, map, mapstream, reduce, sideinput, sink, source, sourcetransform,
+};
+
+/// Environment variable numaflow sets to the type of the container running the UDF.
+const CONTAINER_TYPE_ENV: &str = "NUMAFLOW_UD_CONTAINER_TYPE";
+
+/// Container type of the sink run as the fallback sink of the vertex.
+const FALLBACK_SINK_CONTAINER: &str = "fb-udsink";
+
+/// UdfKind is the kind of the UDF run by a container, it picks the socket and the server info
+/// file numaflow looks for in the container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UdfKind {
+    Map,
+    MapStream,
+    BatchMap,
+    Reduce,
+    Accumulator,
+    Sink,
+    /// The sink of the [fallback](https://numaflow.numaproj.io/user-guide/sinks/fallback/)
+    /// container of the vertex.
+    FallbackSink,
+    Source,
+    SourceTransform,
+    SideInput,
+}
+
+impl UdfKind {
+    /// Returns the default unix domain socket file numaflow connects to for the kind.
+    pub fn socket_file(self) -> &'static str {
+        match self {
+            UdfKind::Map => map::DEFAULT_SOCK_ADDR,
+            UdfKind::MapStream => mapstream::DEFAULT_SOCK_ADDR,
+            UdfKind::BatchMap => batchmap::DEFAULT_SOCK_ADDR,
+            UdfKind::Reduce => reduce::DEFAULT_SOCK_ADDR,
+            UdfKind::Accumulator => accumulator::DEFAULT_SOCK_ADDR,
+            UdfKind::Sink => sink::DEFAULT_SOCK_ADDR,
+            UdfKind::FallbackSink => sink::FALLBACK_SOCK_ADDR,
+            UdfKind::Source => source::DEFAULT_SOCK_ADDR,
+            UdfKind::SourceTransform => sourcetransform::DEFAULT_SOCK_ADDR,
+            UdfKind::SideInput => sideinput::DEFAULT_SOCK_ADDR,
+        }
+    }
+
+    /// Returns the name of the server info file numaflow reads for the kind. The map kinds share
+    /// theirs, the mode of the map is told by the server info.
+    pub fn server_info_name(self) -> &'static str {
+        match self {
+            UdfKind::Map | UdfKind::MapStream | UdfKind::BatchMap => "mapper-server-info",
+            UdfKind::Reduce => "reducer-server-info",
+            UdfKind::Accumulator => "accumulator-server-info",
+            UdfKind::Sink => "sinker-server-info",
+            UdfKind::FallbackSink => "fb-sinker-server-info",
+            UdfKind::Source => "sourcer-server-info",
+            UdfKind::SourceTransform => "sourcetransformer-server-info",
+            UdfKind::SideInput => "sideinput-server-info",
+        }
+    }
+
+    /// Returns the kind as per the container it runs in, i.e., a [`Sink`](UdfKind::Sink) is the
+    /// [`FallbackSink`](UdfKind::FallbackSink) in the fallback container of the vertex.
+    fn resolve(self) -> Self {
+        let container = std::env::var(CONTAINER_TYPE_ENV).unwrap_or_default();
+        match self {
+            UdfKind::Sink if container == FALLBACK_SINK_CONTAINER => UdfKind::FallbackSink,
+            kind => kind,
+        }
+    }
+}
+
+impl fmt::Display for UdfKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            UdfKind::Map => "map",
+            UdfKind::MapStream => "mapstream",
+            UdfKind::BatchMap => "batchmap",
+            UdfKind::Reduce => "reduce",
+            UdfKind::Accumulator => "accumulator",
+            UdfKind::Sink => "sink",
+            UdfKind::FallbackSink => "fallback sink",
+            UdfKind::Source => "source",
+            UdfKind::SourceTransform => "sourcetransform",
+            UdfKind::SideInput => "sideinput",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Returns the builder of the server of the kind, preconfigured with what the numaflow sidecar
+/// expects of the container, see [`Builder`].
+pub fn builder(kind: UdfKind) -> Builder {
+    let kind = kind.resolve();
+    Builder {
+        kind,
+        socket_file: shared::socket_file(kind.socket_file()),
+        server_info_file: shared::server_info_file(kind.server_info_name()),
+    }
+}
+
+/// Builder creates the server of a UDF kind with the settings the numaflow sidecar expects of the
+/// container, so that they are not copied from one module to the next:
+///
+/// - the socket file of the kind, e.g., `/var/run/numaflow/reduce.sock`,
+/// - the server info file of the kind, e.g., `/var/run/numaflow/reducer-server-info` when running
+///   inside a numaflow pod (`NUMAFLOW_POD` is set) and `/tmp/numaflow.reducer-server-info`
+///   otherwise,
+/// - the fallback sink socket and server info file when a sink runs in the fallback container,
+///   as per `NUMAFLOW_UD_CONTAINER_TYPE`,
+/// - the paths relocated by the environment, see [`RUN_DIR_ENV`](crate::shared::RUN_DIR_ENV),
+/// - the [version check](crate::map::Server::with_version_check) against `NUMAFLOW_VERSION`.
+///
+/// The server is created by the method of the kind, e.g., [`reduce`](Builder::reduce), and the
+/// settings can still be changed on it.
+///
+/// # Example
+///
+/// ```no_run
+/// use numaflow::udf::{self, UdfKind};
+/// # use numaflow::reduce::{Datum, Message, Metadata, Reducer};
+/// # struct Counter;
+/// # #[tonic::async_trait]
+/// # impl Reducer for Counter {
+/// #     async fn reduce<T: Datum + Send + Sync + 'static, U: Metadata + Send + Sync + 'static>(
+/// #         &self,
+/// #         _: Vec<String>,
+/// #         _: tokio::sync::mpsc::Receiver<T>,
+/// #         _: &U,
+/// #     ) -> Vec<Message> {
+/// #         vec![]
+/// #     }
+/// # }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+///     let server = udf::builder(UdfKind::Reduce).reduce(Counter);
+///     assert_eq!(server.socket_file().to_str(), Some("/var/run/numaflow/reduce.sock"));
+///     server.start().await
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Builder {
+    kind: UdfKind,
+    socket_file: PathBuf,
+    server_info_file: PathBuf,
+}
+
+/// The method creating the server of a UDF kind out of the [`Builder`].
+macro_rules! server_of_kind {
+    ($(#[$doc:meta])* $method:ident, $module:ident, $($kind:pat_param)|+) => {
+        $(#[$doc])*
+        ///
+        /// # Panics
+        ///
+        /// Panics if the builder is of another kind, it is a bug of the UDF.
+        pub fn $method<T>(self, handler: T) -> $module::Server<T> {
+            assert!(
+                matches!(self.kind, $($kind)|+),
+                "the builder of a {} UDF cannot create a {} server",
+                self.kind,
+                stringify!($method),
+            );
+            $module::Server::new(handler)
+                .with_socket_file(self.socket_file)
+                .with_server_info_file(self.server_info_file)
+                .with_version_check(true)
+        }
+    };
+}
+
+impl Builder {
+    /// Get the kind of the UDF, the [`FallbackSink`](UdfKind::FallbackSink) for a
+    /// [`Sink`](UdfKind::Sink) in the fallback container.
+    pub fn kind(&self) -> UdfKind {
+        self.kind
+    }
+
+    /// Get the unix domain socket file the server listens on.
+    pub fn socket_file(&self) -> &Path {
+        &self.socket_file
+    }
+
+    /// Get the path of the server info file the server writes on start up.
+    pub fn server_info_file(&self) -> &Path {
+        &self.server_info_file
+    }
+
+    server_of_kind!(
+        /// Create the map server of the [`Mapper`](map::Mapper).
+        map, map, UdfKind::Map
+    );
+
+    server_of_kind!(
+        /// Create the map stream server of the [`MapStreamer`](mapstream::MapStreamer).
+        mapstream, mapstream, UdfKind::MapStream
+    );
+
+    server_of_kind!(
+        /// Create the batch map server of the [`BatchMapper`](batchmap::BatchMapper).
+        batchmap, batchmap, UdfKind::BatchMap
+    );
+
+    server_of_kind!(
+        /// Create the reduce server of the [`Reducer`](reduce::Reducer).
+        reduce, reduce, UdfKind::Reduce
+    );
+
+    server_of_kind!(
+        /// Create the accumulator server of the [`Accumulator`](accumulator::Accumulator).
+        accumulator, accumulator, UdfKind::Accumulator
+    );
+
+    server_of_kind!(
+        /// Create the sink server of the [`Sinker`](sink::Sinker), it serves as the fallback sink
+        /// in the fallback container.
+        sink, sink, UdfKind::Sink | UdfKind::FallbackSink
+    );
+
+    server_of_kind!(
+        /// Create the source server of the [`Sourcer`](source::Sourcer).
+        source, source, UdfKind::Source
+    );
+
+    server_of_kind!(
+        /// Create the source transformer server of the
+        /// [`SourceTransformer`](sourcetransform::SourceTransformer).
+        sourcetransform, sourcetransform, UdfKind::SourceTransform
+    );
+
+    server_of_kind!(
+        /// Create the side input server of the [`SideInputer`](sideinput::SideInputer).
+        sideinput, sideinput, UdfKind::SideInput
+    );
+}
@@ -0,0 +1,34 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// Runs the future of a handler, logging every poll which takes longer than the threshold. A
+/// poll is expected to return quickly, a long one means the handler did blocking work, e.g.,
+/// blocking I/O or heavy computation, on the executor thread and stalled the other tasks on it.
+/// The future is run as is without a threshold.
+pub(crate) async fn watch<F: Future>(
+    threshold: Option<Duration>,
+    handler: &str,
+    future: F,
+) -> F::Output {
+    let Some(threshold) = threshold else {
+        return future.await;
+    };
+
+    let mut future = std::pin::pin!(future);
+    std::future::poll_fn(|cx| {
+        let start = Instant::now();
+        let poll = future.as_mut().poll(cx);
+        let elapsed = start.elapsed();
+        if elapsed > threshold {
+            tracing::warn!(
+                handler,
+                ?elapsed,
+                ?threshold,
+                "handler blocked the async runtime, run the blocking work with \
+                 tokio::task::spawn_blocking or tokio::task::block_in_place instead"
+            );
+        }
+        poll
+    })
+    .await
+}